@@ -0,0 +1,428 @@
+//! command-line administration of Parklife Cyberduck, working on the database directly
+mod output;
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use cyberduck_backend::admin_auth::{Admin, Role};
+use cyberduck_backend::configuration::{Configuration, CONFIG_FILE_ENV};
+use cyberduck_backend::coordinates::CoordinateSystem;
+use cyberduck_backend::db_api::archive::{Archive, ImportMode};
+use cyberduck_backend::db_api::dangerous::DangerousOp;
+use cyberduck_backend::db_api::ducks::{NewDuckData, UpdateDuckData};
+use cyberduck_backend::db_api::exhibits::{NewExhibitData, UpdateExhibitData};
+use cyberduck_backend::db_api::locations::{NewLocationData, UpdateLocationData};
+use cyberduck_backend::db_api::DB;
+use output::{Column, Output};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+const DUCK_COLUMNS: &[Column] = &[
+    ("ID", "/id"),
+    ("TITLE", "/title/en"),
+    ("LOCATION", "/location/description/en"),
+    ("HIDDEN", "/isHidden"),
+    ("NEXT STORY", "/nextDuckStory/id"),
+];
+const EXHIBIT_COLUMNS: &[Column] = &[
+    ("ID", "/id"),
+    ("TITLE", "/title/en"),
+    ("LOCATION", "/location/en"),
+    ("DUCK", "/relatedDuckId"),
+];
+const LOCATION_COLUMNS: &[Column] = &[
+    ("ID", "/id"),
+    ("DESCRIPTION", "/description/en"),
+    ("COORDINATE", "/coordinate"),
+    ("DUCK", "/duckId"),
+    ("GEOFENCE", "/geofenceMode"),
+];
+const RANKING_COLUMNS: &[Column] = &[
+    ("RANKING", "/ranking"),
+    ("PLAYER", "/userWechatOpenId"),
+    ("SINCE", "/createdAt"),
+];
+const HISTORY_COLUMNS: &[Column] = &[("ID", "/id"), ("DUCK", "/duckId"), ("FOUND", "/createdAt")];
+
+#[derive(Parser)]
+#[command(name = "cyberduck-admin", version, about)]
+struct Cli {
+    /// configuration file of the server, defaults to `config.yaml`
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// admin name recorded in the audit log
+    #[arg(long, global = true, default_value = "cli")]
+    admin: String,
+    /// print JSON instead of tables
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// manage ducks
+    #[command(subcommand)]
+    Duck(ContentCommand),
+    /// manage exhibits
+    #[command(subcommand)]
+    Exhibit(ContentCommand),
+    /// manage locations
+    #[command(subcommand)]
+    Location(ContentCommand),
+    /// manage rankings
+    #[command(subcommand)]
+    Ranking(RankingCommand),
+    /// manage the ducks found by a player
+    #[command(subcommand)]
+    History(HistoryCommand),
+    /// write every document to a versioned JSON archive
+    Export {
+        /// output file, defaults to stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// import a JSON archive under new ids
+    Import {
+        file: PathBuf,
+        #[arg(long, value_enum)]
+        mode: Mode,
+    },
+    /// turn the untyped coordinates of older versions into typed ones
+    MigrateCoordinates {
+        /// system of the untyped numbers
+        #[arg(long, value_enum, default_value = "wgs84")]
+        system: System,
+        /// write the changes, otherwise only list them
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ContentCommand {
+    /// list documents
+    List {
+        /// list the trash instead
+        #[arg(long)]
+        trash: bool,
+    },
+    /// show one document
+    Get { id: String },
+    /// create one document, or a list of documents, from a YAML or JSON file
+    Create { file: PathBuf },
+    /// update a document from a YAML or JSON file
+    Update { id: String, file: PathBuf },
+    /// move a document to the trash
+    Delete { id: String },
+    /// bring a document back from the trash
+    Restore { id: String },
+}
+
+#[derive(Subcommand)]
+enum RankingCommand {
+    /// list rankings
+    List,
+    /// renumber rankings to 1..=n, repairing duplicated ranks
+    Renumber,
+    /// delete every ranking
+    Clear {
+        /// really delete
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum HistoryCommand {
+    /// list the ducks found by a player (user id or wechat openid)
+    List { user: String },
+    /// delete the history of a player, after saving a snapshot
+    Clear {
+        user: String,
+        /// really delete, otherwise only count the records
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Mode {
+    Replace,
+    Merge,
+    Content,
+}
+
+impl From<Mode> for ImportMode {
+    fn from(mode: Mode) -> Self {
+        match mode {
+            Mode::Replace => ImportMode::Replace,
+            Mode::Merge => ImportMode::Merge,
+            Mode::Content => ImportMode::Content,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum System {
+    Wgs84,
+    Gcj02,
+    FloorPlan,
+}
+
+impl From<System> for CoordinateSystem {
+    fn from(system: System) -> Self {
+        match system {
+            System::Wgs84 => CoordinateSystem::Wgs84,
+            System::Gcj02 => CoordinateSystem::Gcj02,
+            System::FloorPlan => CoordinateSystem::FloorPlan,
+        }
+    }
+}
+
+/// a file holding either one document or a list of them
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany<T> {
+    Many(Vec<T>),
+    One(T),
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    if let Some(config) = &cli.config {
+        std::env::set_var(CONFIG_FILE_ENV, config);
+    }
+    let config = Configuration::load().context("error loading configuration")?;
+    let db = DB::new(&config.db_url, &config.redis_session.redis_url).await?;
+    let out = Output { json: cli.json };
+    let admin = |route: &str| Admin {
+        name: cli.admin.clone(),
+        role: Role::Operator,
+        route: format!("cli {}", route),
+    };
+
+    match cli.command {
+        Command::Duck(command) => match command {
+            ContentCommand::List { trash: false } => {
+                out.list(&db.get_all_ducks().await?, DUCK_COLUMNS)?
+            }
+            ContentCommand::List { trash: true } => {
+                out.list(&db.get_trashed_ducks().await?, DUCK_COLUMNS)?
+            }
+            ContentCommand::Get { id } => match db.get_duck(id.clone()).await? {
+                Some(duck) => out.one(&duck, DUCK_COLUMNS)?,
+                None => bail!("duck {} not found", id),
+            },
+            ContentCommand::Create { file } => {
+                let admin = admin("duck create");
+                let db = db.audited(&admin);
+                match read_file::<OneOrMany<NewDuckData>>(&file)? {
+                    OneOrMany::One(data) => out.one(&db.create_duck(data).await?, DUCK_COLUMNS)?,
+                    OneOrMany::Many(data) => out.summary(json!({
+                        "number_of_ducks_created": db.create_many_ducks(data).await?,
+                    }))?,
+                }
+            }
+            ContentCommand::Update { id, file } => {
+                let data: UpdateDuckData = read_file(&file)?;
+                let duck = db
+                    .audited(&admin("duck update"))
+                    .update_duck(id, data)
+                    .await?;
+                out.one(&duck, DUCK_COLUMNS)?
+            }
+            ContentCommand::Delete { id } => {
+                let duck = db.audited(&admin("duck delete")).delete_duck(id).await?;
+                out.one(&duck, DUCK_COLUMNS)?
+            }
+            ContentCommand::Restore { id } => {
+                let duck = db.audited(&admin("duck restore")).restore_duck(id).await?;
+                out.one(&duck, DUCK_COLUMNS)?
+            }
+        },
+        Command::Exhibit(command) => match command {
+            ContentCommand::List { trash: false } => {
+                out.list(&db.get_all_exhibits().await?, EXHIBIT_COLUMNS)?
+            }
+            ContentCommand::List { trash: true } => {
+                out.list(&db.get_trashed_exhibits().await?, EXHIBIT_COLUMNS)?
+            }
+            ContentCommand::Get { id } => match db.get_exhibit(id.clone()).await? {
+                Some(exhibit) => out.one(&exhibit, EXHIBIT_COLUMNS)?,
+                None => bail!("exhibit {} not found", id),
+            },
+            ContentCommand::Create { file } => {
+                let admin = admin("exhibit create");
+                let db = db.audited(&admin);
+                match read_file::<OneOrMany<NewExhibitData>>(&file)? {
+                    OneOrMany::One(data) => {
+                        out.one(&db.create_exhibit(data).await?, EXHIBIT_COLUMNS)?
+                    }
+                    OneOrMany::Many(data) => out.summary(json!({
+                        "number_of_exhibits_created": db.create_many_exhibits(data).await?,
+                    }))?,
+                }
+            }
+            ContentCommand::Update { id, file } => {
+                let data: UpdateExhibitData = read_file(&file)?;
+                let exhibit = db
+                    .audited(&admin("exhibit update"))
+                    .update_exhibit(id, data)
+                    .await?;
+                out.one(&exhibit, EXHIBIT_COLUMNS)?
+            }
+            ContentCommand::Delete { id } => {
+                let exhibit = db
+                    .audited(&admin("exhibit delete"))
+                    .delete_exhibit(id)
+                    .await?;
+                out.one(&exhibit, EXHIBIT_COLUMNS)?
+            }
+            ContentCommand::Restore { id } => {
+                let exhibit = db
+                    .audited(&admin("exhibit restore"))
+                    .restore_exhibit(id)
+                    .await?;
+                out.one(&exhibit, EXHIBIT_COLUMNS)?
+            }
+        },
+        Command::Location(command) => match command {
+            ContentCommand::List { trash: false } => {
+                out.list(&db.get_all_locations().await?, LOCATION_COLUMNS)?
+            }
+            ContentCommand::List { trash: true } => {
+                out.list(&db.get_trashed_locations().await?, LOCATION_COLUMNS)?
+            }
+            ContentCommand::Get { id } => match db.get_location(id.clone()).await? {
+                Some(location) => out.one(&location, LOCATION_COLUMNS)?,
+                None => bail!("location {} not found", id),
+            },
+            ContentCommand::Create { file } => {
+                let admin = admin("location create");
+                let db = db.audited(&admin);
+                match read_file::<OneOrMany<NewLocationData>>(&file)? {
+                    OneOrMany::One(data) => {
+                        out.one(&db.create_location(data).await?, LOCATION_COLUMNS)?
+                    }
+                    OneOrMany::Many(data) => out.summary(json!({
+                        "number_of_locations_created": db.create_many_locations(data).await?,
+                    }))?,
+                }
+            }
+            ContentCommand::Update { id, file } => {
+                let data: UpdateLocationData = read_file(&file)?;
+                let location = db
+                    .audited(&admin("location update"))
+                    .update_location(id, data)
+                    .await?;
+                out.one(&location, LOCATION_COLUMNS)?
+            }
+            ContentCommand::Delete { id } => {
+                let location = db
+                    .audited(&admin("location delete"))
+                    .delete_location(id)
+                    .await?;
+                out.one(&location, LOCATION_COLUMNS)?
+            }
+            ContentCommand::Restore { id } => {
+                let location = db
+                    .audited(&admin("location restore"))
+                    .restore_location(id)
+                    .await?;
+                out.one(&location, LOCATION_COLUMNS)?
+            }
+        },
+        Command::Ranking(command) => match command {
+            RankingCommand::List => out.list(&db.get_all_rankings().await?, RANKING_COLUMNS)?,
+            RankingCommand::Renumber => {
+                let n = db
+                    .audited(&admin("ranking renumber"))
+                    .renumber_rankings()
+                    .await?;
+                out.summary(json!({ "number_of_rankings_changed": n }))?
+            }
+            RankingCommand::Clear { yes: false } => {
+                bail!("deleting every ranking cannot be undone, repeat with --yes")
+            }
+            RankingCommand::Clear { yes: true } => {
+                let n = db
+                    .audited(&admin("ranking clear"))
+                    .delete_all_rankings()
+                    .await?;
+                out.summary(json!({ "number_of_rankings_deleted": n }))?
+            }
+        },
+        Command::History(command) => match command {
+            HistoryCommand::List { user } => {
+                let op = DangerousOp::DeleteDuckHistory { user_id: user };
+                let history = db.collect_snapshot(&op).await?.duck_history;
+                out.list(&history, HISTORY_COLUMNS)?
+            }
+            HistoryCommand::Clear { user, yes } => {
+                let op = DangerousOp::DeleteDuckHistory {
+                    user_id: user.clone(),
+                };
+                let data = db.collect_snapshot(&op).await?;
+                if !yes {
+                    return out.summary(json!({ "dry_run": data.report() }));
+                }
+                let admin = admin("history clear");
+                let snapshot = db
+                    .save_snapshot(admin.name.clone(), admin.route.clone(), data)
+                    .await?;
+                let n = db.audited(&admin).delete_duck_history(user).await?;
+                out.summary(json!({
+                    "number_of_duck_view_records_deleted": n,
+                    "snapshot_id": snapshot.id,
+                }))?
+            }
+        },
+        Command::Export { output } => {
+            let archive = serde_json::to_string_pretty(&db.export_archive().await?)?;
+            match output {
+                Some(path) => std::fs::write(&path, archive)
+                    .with_context(|| format!("error writing {}", path.display()))?,
+                None => println!("{}", archive),
+            }
+        }
+        Command::Import { file, mode } => {
+            let archive: Archive = read_file(&file)?;
+            archive.check()?;
+            let report = db
+                .audited(&admin("import"))
+                .import_archive(archive, mode.into())
+                .await?;
+            out.summary(json!({ "imported": report }))?
+        }
+        Command::MigrateCoordinates { system, yes } => {
+            let plan = db.plan_coordinate_migration(system.into()).await?;
+            if !yes {
+                return out.summary(json!({ "dry_run": plan }));
+            }
+            let n = db
+                .audited(&admin("migrate-coordinates"))
+                .migrate_coordinates(&plan)
+                .await?;
+            out.summary(json!({
+                "number_of_locations_migrated": n,
+                "invalid": plan.invalid,
+            }))?
+        }
+    }
+    Ok(())
+}
+
+/// Read a `.json` file as JSON, and any other file as YAML.
+fn read_file<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("error reading {}", path.display()))?;
+    let data = match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => serde_json::from_str(&content)?,
+        _ => serde_yaml::from_str(&content)?,
+    };
+    Ok(data)
+}
@@ -0,0 +1,79 @@
+//! printing results as aligned tables or as JSON
+use serde::Serialize;
+use serde_json::Value;
+
+/// column header, and JSON pointer to the cell of each row
+pub type Column = (&'static str, &'static str);
+
+pub struct Output {
+    pub json: bool,
+}
+
+impl Output {
+    /// Print a list, as a table of the given columns, or as a JSON array.
+    pub fn list<T: Serialize>(&self, items: &[T], columns: &[Column]) -> anyhow::Result<()> {
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(items)?);
+            return Ok(());
+        }
+        let mut rows = vec![columns.iter().map(|(h, _)| h.to_string()).collect()];
+        for item in items {
+            let item = serde_json::to_value(item)?;
+            rows.push(
+                columns
+                    .iter()
+                    .map(|(_, pointer)| cell(item.pointer(pointer)))
+                    .collect::<Vec<_>>(),
+            );
+        }
+        print_table(&rows);
+        Ok(())
+    }
+
+    /// Print a single document, as a one-row table or as JSON.
+    pub fn one<T: Serialize>(&self, item: &T, columns: &[Column]) -> anyhow::Result<()> {
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(item)?);
+            return Ok(());
+        }
+        self.list(std::slice::from_ref(item), columns)
+    }
+
+    /// Print a summary, such as numbers of documents changed.
+    pub fn summary(&self, summary: Value) -> anyhow::Result<()> {
+        match summary {
+            Value::Object(map) if !self.json => {
+                for (key, value) in map {
+                    println!("{}: {}", key, cell(Some(&value)));
+                }
+            }
+            summary => println!("{}", serde_json::to_string_pretty(&summary)?),
+        }
+        Ok(())
+    }
+}
+
+fn cell(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(value) => value.to_string(),
+    }
+}
+
+fn print_table(rows: &[Vec<String>]) {
+    let mut widths = vec![0; rows[0].len()];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in rows {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    }
+}
@@ -0,0 +1,163 @@
+//! named admin accounts with roles
+use crate::api_error::{ApiError, ResultExt};
+use crate::db_api::DB;
+use crate::SERVER_CONFIG;
+use axum::async_trait;
+use axum::extract::{FromRef, FromRequestParts, MatchedPath};
+use axum::http::request::Parts;
+use axum_auth::AuthBearer;
+use rand::distributions::{Alphanumeric, Distribution};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::marker::PhantomData;
+use std::str::FromStr;
+use subtle::ConstantTimeEq;
+
+pub static ROOT_ADMIN_NAME: &str = "root";
+pub static TOKEN_LENGTH: usize = 48;
+
+/// Admin roles, each role is granted everything the lower roles can do.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// read content, rankings and accounts
+    Viewer,
+    /// create, update and delete single ducks, exhibits and locations
+    Editor,
+    /// bulk deletes, player histories, rankings and admin accounts
+    Operator,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Operator => "operator",
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "viewer" => Ok(Role::Viewer),
+            "editor" => Ok(Role::Editor),
+            "operator" => Ok(Role::Operator),
+            _ => Err(anyhow::anyhow!("unknown admin role: {}", s)),
+        }
+    }
+}
+
+/// role markers for [`RequireAdmin`]
+pub mod roles {
+    use super::Role;
+
+    pub trait RequiredRole {
+        const ROLE: Role;
+    }
+
+    pub struct Viewer;
+    pub struct Editor;
+    pub struct Operator;
+
+    impl RequiredRole for Viewer {
+        const ROLE: Role = Role::Viewer;
+    }
+
+    impl RequiredRole for Editor {
+        const ROLE: Role = Role::Editor;
+    }
+
+    impl RequiredRole for Operator {
+        const ROLE: Role = Role::Operator;
+    }
+}
+
+/// an authenticated admin
+pub struct Admin {
+    pub name: String,
+    pub role: Role,
+    /// method and matched path of the request, e.g. `PATCH /admin/duck/:id`
+    pub route: String,
+}
+
+/// Extractor admitting admins whose role grants `R`,
+/// e.g. `RequireAdmin<roles::Editor>`.
+pub struct RequireAdmin<R>(pub Admin, pub PhantomData<R>);
+
+#[async_trait]
+impl<S, R> FromRequestParts<S> for RequireAdmin<R>
+where
+    DB: FromRef<S>,
+    S: Send + Sync,
+    R: roles::RequiredRole,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let AuthBearer(token) = AuthBearer::from_request_parts(parts, state)
+            .await
+            .map_err(|_| ApiError::Unauthorized("provide admin token"))?;
+        let path = match parts.extensions.get::<MatchedPath>() {
+            Some(matched) => matched.as_str(),
+            None => parts.uri.path(),
+        };
+        let route = format!("{} {}", parts.method, path);
+        let admin = authorize(&DB::from_ref(state), &token, R::ROLE, route).await?;
+        Ok(RequireAdmin(admin, PhantomData))
+    }
+}
+
+/// Authenticate a bearer token and check that it grants `role`.
+///
+/// The `admin_token` of the configuration, if set, acts as the `root` operator.
+pub async fn authorize(db: &DB, token: &str, role: Role, route: String) -> Result<Admin, ApiError> {
+    let is_root = match &SERVER_CONFIG.admin_token {
+        Some(root_token) => bool::from(root_token.as_bytes().ct_eq(token.as_bytes())),
+        None => false,
+    };
+    let admin = if is_root {
+        Admin {
+            name: ROOT_ADMIN_NAME.to_string(),
+            role: Role::Operator,
+            route,
+        }
+    } else {
+        let account = db
+            .find_admin_by_token_hash(hash_token(token))
+            .await
+            .or_api(ApiError::Internal("error finding admin account"))?
+            .ok_or(ApiError::Unauthorized("provide admin token"))?;
+        Admin {
+            role: account
+                .role
+                .parse()
+                .or_api(ApiError::Internal("invalid admin account"))?,
+            name: account.name,
+            route,
+        }
+    };
+    if admin.role >= role {
+        Ok(admin)
+    } else {
+        Err(ApiError::Forbidden("admin role not permitted"))
+    }
+}
+
+pub fn hash_token(token: &str) -> String {
+    base64::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[inline]
+pub fn gen_token() -> String {
+    String::from_iter(
+        Alphanumeric
+            .sample_iter(OsRng::default())
+            .take(TOKEN_LENGTH)
+            .map(|u| u as char),
+    )
+}
@@ -0,0 +1,139 @@
+//! error responses shared by all handlers
+use axum::http::{HeaderValue, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use rand::distributions::{Alphanumeric, Distribution};
+use rand::rngs::OsRng;
+use serde_json::json;
+use std::fmt::Display;
+use tracing::error;
+
+pub static REQUEST_ID_HEADER: &str = "x-request-id";
+pub static REQUEST_ID_LENGTH: usize = 16;
+
+tokio::task_local! {
+    /// id of the request being handled, set by [`request_id`]
+    static REQUEST_ID: String;
+}
+
+/// Error answered as `{"code": ..., "message": ..., "requestId": ...}`.
+///
+/// Messages are sent to clients, so causes are only logged (see [`ResultExt`]).
+#[derive(Debug)]
+pub enum ApiError {
+    BadRequest(&'static str),
+    Unauthorized(&'static str),
+    Forbidden(&'static str),
+    NotFound(&'static str),
+    Conflict(&'static str),
+    TooManyRequests(&'static str),
+    Internal(&'static str),
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::TooManyRequests(_) => "too_many_requests",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(message)
+            | ApiError::Unauthorized(message)
+            | ApiError::Forbidden(message)
+            | ApiError::NotFound(message)
+            | ApiError::Conflict(message)
+            | ApiError::TooManyRequests(message)
+            | ApiError::Internal(message) => message,
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = json!({
+            "code": self.code(),
+            "message": self.message(),
+            "requestId": current_request_id(),
+        });
+        (self.status(), Json(body)).into_response()
+    }
+}
+
+pub trait ResultExt<T> {
+    /// log the error with the request id, and answer with `err` instead
+    fn or_api(self, err: ApiError) -> Result<T, ApiError>;
+}
+
+impl<T, E: Display> ResultExt<T> for Result<T, E> {
+    fn or_api(self, err: ApiError) -> Result<T, ApiError> {
+        self.map_err(|e| {
+            error!(
+                "{}: {} (request_id={})",
+                err.message(),
+                e,
+                current_request_id().unwrap_or_default()
+            );
+            err
+        })
+    }
+}
+
+/// Middleware tagging each request with an id, taken from the `x-request-id`
+/// header when it looks valid, and echoed in the response headers.
+pub async fn request_id<B>(req: Request<B>, next: Next<B>) -> Response {
+    let id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| is_valid_request_id(v))
+        .map(str::to_string)
+        .unwrap_or_else(gen_request_id);
+    let mut rsp = REQUEST_ID.scope(id.clone(), next.run(req)).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        rsp.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    rsp
+}
+
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 64
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[inline]
+fn gen_request_id() -> String {
+    String::from_iter(
+        Alphanumeric
+            .sample_iter(OsRng::default())
+            .take(REQUEST_ID_LENGTH)
+            .map(|u| u as char),
+    )
+}
@@ -1,3 +1,4 @@
+use crate::posters::PosterConfig;
 use crate::redis_session_layer::RedisSessionConfig;
 use crate::wechat_login::WechatLogin;
 use config::{Config, ConfigError};
@@ -5,17 +6,28 @@ use serde::Deserialize;
 use std::path::PathBuf;
 
 pub const CONFIG_FILE_NAME: &str = "config.yaml";
+/// environment variable to load the configuration from another file
+pub const CONFIG_FILE_ENV: &str = "CYBERDUCK_CONFIG";
+/// prefix of environment variables overriding single entries,
+/// e.g. `CYBERDUCK_WECHAT__TOKEN_URL` overrides `wechat.token_url`
+pub const CONFIG_ENV_PREFIX: &str = "CYBERDUCK";
 
 #[derive(Deserialize)]
 pub struct Configuration {
     pub redis_session: RedisSessionConfig,
     pub server_binding: String,
-    pub admin_token: String,
+    /// bootstrap token of the `root` operator, omit once named accounts exist
+    pub admin_token: Option<String>,
+    /// base64 of 32 random bytes encrypting the secrets of the QR keys in the database,
+    /// which are stored in plain text without it
+    pub qr_secret_key: Option<String>,
     pub log_file: PathBuf,
     pub wechat: WechatLogin,
     pub server_tls: Option<TlsConfig>,
     pub db_url: String,
     pub allow_origin: String,
+    /// QR codes and poster sheets, whose routes answer 404 without it
+    pub posters: Option<PosterConfig>,
 }
 
 #[derive(Deserialize)]
@@ -26,8 +38,15 @@ pub struct TlsConfig {
 
 impl Configuration {
     pub fn load() -> Result<Self, ConfigError> {
+        let file_name =
+            std::env::var(CONFIG_FILE_ENV).unwrap_or_else(|_| CONFIG_FILE_NAME.to_string());
         let config = Config::builder()
-            .add_source(config::File::with_name(CONFIG_FILE_NAME))
+            .add_source(config::File::with_name(&file_name))
+            .add_source(
+                config::Environment::with_prefix(CONFIG_ENV_PREFIX)
+                    .prefix_separator("_")
+                    .separator("__"),
+            )
             .build()?;
         config.try_deserialize()
     }
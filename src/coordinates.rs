@@ -0,0 +1,173 @@
+//! coordinates of locations, and conversion between coordinate systems
+use anyhow::{anyhow, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::f64::consts::PI;
+
+/// mean radius of the earth, in metres
+const EARTH_RADIUS: f64 = 6_371_000.0;
+/// semi-major axis and eccentricity squared of the Krasovsky ellipsoid used by GCJ-02
+const KRASOVSKY_A: f64 = 6_378_245.0;
+const KRASOVSKY_EE: f64 = 0.006_693_421_622_965_943;
+/// GCJ-02 to WGS84 is solved by iteration, to well below a centimetre
+const MAX_ITERATIONS: usize = 10;
+const PRECISION: f64 = 1e-9;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CoordinateSystem {
+    /// GPS longitude and latitude, in degrees
+    #[default]
+    Wgs84,
+    /// longitude and latitude of Chinese maps (WeChat, Amap, Tencent), in degrees
+    Gcj02,
+    /// pixels of the venue floor plan, from its top left corner
+    FloorPlan,
+}
+
+impl CoordinateSystem {
+    pub fn is_geographic(&self) -> bool {
+        !matches!(self, CoordinateSystem::FloorPlan)
+    }
+}
+
+/// A validated point: `x` is the longitude and `y` the latitude in geographic systems.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(try_from = "RawCoordinate")]
+pub struct Coordinate {
+    system: CoordinateSystem,
+    x: f64,
+    y: f64,
+}
+
+#[derive(Deserialize)]
+struct RawCoordinate {
+    system: CoordinateSystem,
+    x: f64,
+    y: f64,
+}
+
+impl TryFrom<RawCoordinate> for Coordinate {
+    type Error = anyhow::Error;
+
+    fn try_from(raw: RawCoordinate) -> Result<Self, Self::Error> {
+        Coordinate::new(raw.system, raw.x, raw.y)
+    }
+}
+
+impl Coordinate {
+    pub fn new(system: CoordinateSystem, x: f64, y: f64) -> anyhow::Result<Self> {
+        if !x.is_finite() || !y.is_finite() {
+            bail!("coordinates must be finite numbers");
+        }
+        if system.is_geographic() {
+            if !(-90.0..=90.0).contains(&y) {
+                if (-90.0..=90.0).contains(&x) {
+                    bail!("latitude {} out of range, are x and y swapped?", y);
+                }
+                bail!("latitude {} out of range", y);
+            }
+            if !(-180.0..=180.0).contains(&x) {
+                bail!("longitude {} out of range", x);
+            }
+        } else if x < 0.0 || y < 0.0 {
+            bail!("floor plan coordinates must not be negative");
+        }
+        Ok(Coordinate { system, x, y })
+    }
+
+    /// Parse a stored `Location.coordinate`.
+    pub fn from_value(value: &Value) -> anyhow::Result<Self> {
+        Ok(serde_json::from_value(value.clone())?)
+    }
+
+    pub fn system(&self) -> CoordinateSystem {
+        self.system
+    }
+
+    pub fn x(&self) -> f64 {
+        self.x
+    }
+
+    pub fn y(&self) -> f64 {
+        self.y
+    }
+
+    /// The same point in another system, only possible between geographic systems.
+    pub fn to_system(&self, system: CoordinateSystem) -> anyhow::Result<Self> {
+        let (x, y) = match (self.system, system) {
+            (from, to) if from == to => (self.x, self.y),
+            (CoordinateSystem::Wgs84, CoordinateSystem::Gcj02) => wgs84_to_gcj02(self.x, self.y),
+            (CoordinateSystem::Gcj02, CoordinateSystem::Wgs84) => gcj02_to_wgs84(self.x, self.y),
+            (from, to) => return Err(anyhow!("cannot convert {:?} coordinates to {:?}", from, to)),
+        };
+        Ok(Coordinate { system, x, y })
+    }
+
+    /// Great-circle distance in metres, none unless both points are geographic.
+    pub fn distance(&self, other: &Coordinate) -> Option<f64> {
+        let a = self.to_system(CoordinateSystem::Wgs84).ok()?;
+        let b = other.to_system(CoordinateSystem::Wgs84).ok()?;
+        let (lat1, lat2) = (a.y.to_radians(), b.y.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = (b.x - a.x).to_radians();
+        let h = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        Some(2.0 * EARTH_RADIUS * h.sqrt().asin())
+    }
+}
+
+/// GCJ-02 only offsets points in mainland China
+fn out_of_china(lng: f64, lat: f64) -> bool {
+    !(72.004..=137.8347).contains(&lng) || !(0.8293..=55.8271).contains(&lat)
+}
+
+fn transform_lat(x: f64, y: f64) -> f64 {
+    let mut ret = -100.0 + 2.0 * x + 3.0 * y + 0.2 * y * y + 0.1 * x * y + 0.2 * x.abs().sqrt();
+    ret += (20.0 * (6.0 * x * PI).sin() + 20.0 * (2.0 * x * PI).sin()) * 2.0 / 3.0;
+    ret += (20.0 * (y * PI).sin() + 40.0 * (y / 3.0 * PI).sin()) * 2.0 / 3.0;
+    ret += (160.0 * (y / 12.0 * PI).sin() + 320.0 * (y * PI / 30.0).sin()) * 2.0 / 3.0;
+    ret
+}
+
+fn transform_lng(x: f64, y: f64) -> f64 {
+    let mut ret = 300.0 + x + 2.0 * y + 0.1 * x * x + 0.1 * x * y + 0.1 * x.abs().sqrt();
+    ret += (20.0 * (6.0 * x * PI).sin() + 20.0 * (2.0 * x * PI).sin()) * 2.0 / 3.0;
+    ret += (20.0 * (x * PI).sin() + 40.0 * (x / 3.0 * PI).sin()) * 2.0 / 3.0;
+    ret += (150.0 * (x / 12.0 * PI).sin() + 300.0 * (x / 30.0 * PI).sin()) * 2.0 / 3.0;
+    ret
+}
+
+/// offset added by GCJ-02 to a WGS84 point, in degrees
+fn gcj02_offset(lng: f64, lat: f64) -> (f64, f64) {
+    if out_of_china(lng, lat) {
+        return (0.0, 0.0);
+    }
+    let d_lat = transform_lat(lng - 105.0, lat - 35.0);
+    let d_lng = transform_lng(lng - 105.0, lat - 35.0);
+    let rad_lat = lat.to_radians();
+    let magic = 1.0 - KRASOVSKY_EE * rad_lat.sin().powi(2);
+    let sqrt_magic = magic.sqrt();
+    (
+        d_lng * 180.0 / (KRASOVSKY_A / sqrt_magic * rad_lat.cos() * PI),
+        d_lat * 180.0 / ((KRASOVSKY_A * (1.0 - KRASOVSKY_EE)) / (magic * sqrt_magic) * PI),
+    )
+}
+
+fn wgs84_to_gcj02(lng: f64, lat: f64) -> (f64, f64) {
+    let (d_lng, d_lat) = gcj02_offset(lng, lat);
+    (lng + d_lng, lat + d_lat)
+}
+
+fn gcj02_to_wgs84(lng: f64, lat: f64) -> (f64, f64) {
+    let (mut x, mut y) = (lng, lat);
+    for _ in 0..MAX_ITERATIONS {
+        let (gx, gy) = wgs84_to_gcj02(x, y);
+        let (dx, dy) = (lng - gx, lat - gy);
+        x += dx;
+        y += dy;
+        if dx.abs() < PRECISION && dy.abs() < PRECISION {
+            break;
+        }
+    }
+    (x, y)
+}
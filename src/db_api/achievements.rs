@@ -0,0 +1,402 @@
+//! achievements defined by admins, earned by players as they find ducks
+use crate::db_api::completion::{chain_of, completion_duck, has_topic};
+use crate::db_api::events::GameEvent;
+use crate::db_api::public::user_info;
+use crate::db_api::story::StoryGraph;
+use crate::db_api::{Bilingual, PrismaDB, DB};
+use crate::prisma::{achievement, duck_history, user, user_achievement};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use prisma_client_rust::chrono::{DateTime, Duration, FixedOffset, TimeZone, Utc};
+use prisma_client_rust::Direction;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// largest offset of the days of `first_of_day`, in minutes
+const MAX_UTC_OFFSET_MINUTES: i32 = 14 * 60;
+
+// Achievements count every duck a player found, whatever its season,
+// and are kept once earned, even if their rule changes afterwards.
+
+/// rule of an achievement, met once
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AchievementRule {
+    /// every duck with a topic, named in English or Chinese
+    Topic { topic: String },
+    /// the duck of an exhibit
+    Exhibit {
+        #[serde(rename = "exhibitId")]
+        exhibit_id: String,
+    },
+    /// a duck, with every duck it needs in the story
+    StoryChain {
+        #[serde(rename = "duckId")]
+        duck_id: String,
+    },
+    /// the first find of a day, among every player,
+    /// days starting at midnight `utcOffsetMinutes` ahead of UTC
+    FirstOfDay {
+        #[serde(default, rename = "utcOffsetMinutes")]
+        utc_offset_minutes: i32,
+    },
+    /// `count` ducks found within `minutes`
+    DucksWithin { count: usize, minutes: i64 },
+}
+
+impl AchievementRule {
+    /// reason for rejecting the rule, if any
+    pub fn check(&self) -> Result<(), &'static str> {
+        match self {
+            AchievementRule::Topic { topic } if topic.is_empty() => Err("empty topic"),
+            AchievementRule::Exhibit { exhibit_id } if exhibit_id.is_empty() => {
+                Err("empty exhibit id")
+            }
+            AchievementRule::StoryChain { duck_id } if duck_id.is_empty() => Err("empty duck id"),
+            AchievementRule::FirstOfDay { utc_offset_minutes }
+                if utc_offset_minutes.abs() > MAX_UTC_OFFSET_MINUTES =>
+            {
+                Err("invalid utc offset")
+            }
+            AchievementRule::DucksWithin { count: 0, .. } => Err("duck count must be positive"),
+            AchievementRule::DucksWithin { minutes, .. } if *minutes <= 0 => {
+                Err("minutes must be positive")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// query struct for POST request
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewAchievementData {
+    pub(crate) title: Bilingual,
+    pub(crate) description: Bilingual,
+    pub(crate) icon_url: Option<String>,
+    pub(crate) rule: AchievementRule,
+}
+
+/// query struct for PATCH request
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateAchievementData {
+    pub(crate) title: Option<Bilingual>,
+    pub(crate) description: Option<Bilingual>,
+    pub(crate) icon_url: Option<String>,
+    pub(crate) rule: Option<AchievementRule>,
+}
+
+impl NewAchievementData {
+    fn into_db_data(
+        self,
+    ) -> anyhow::Result<(
+        serde_json::Value,
+        serde_json::Value,
+        serde_json::Value,
+        Vec<achievement::SetParam>,
+    )> {
+        Ok((
+            serde_json::to_value(self.title)?,
+            serde_json::to_value(self.description)?,
+            serde_json::to_value(self.rule)?,
+            vec![achievement::SetParam::SetIconUrl(self.icon_url)],
+        ))
+    }
+}
+
+impl UpdateAchievementData {
+    fn into_db_data(self) -> anyhow::Result<Vec<achievement::SetParam>> {
+        let mut params = vec![];
+        if let Some(title) = self.title {
+            params.push(achievement::SetParam::SetTitle(serde_json::to_value(
+                title,
+            )?));
+        }
+        if let Some(description) = self.description {
+            params.push(achievement::SetParam::SetDescription(serde_json::to_value(
+                description,
+            )?));
+        }
+        if let Some(icon_url) = self.icon_url {
+            params.push(achievement::SetParam::SetIconUrl(Some(icon_url)));
+        }
+        if let Some(rule) = self.rule {
+            params.push(achievement::SetParam::SetRule(serde_json::to_value(rule)?));
+        }
+        Ok(params)
+    }
+}
+
+/// read the rule of a stored achievement
+pub(crate) fn parse_achievement_rule(data: &achievement::Data) -> anyhow::Result<AchievementRule> {
+    serde_json::from_value(data.rule.clone())
+        .map_err(|e| anyhow!("invalid achievement rule {}: {}", data.id, e))
+}
+
+#[async_trait]
+pub trait AchievementStore {
+    // C
+    async fn create_achievement(
+        &self,
+        data: NewAchievementData,
+    ) -> anyhow::Result<achievement::Data>;
+    // R
+    async fn get_achievement(&self, id: String) -> anyhow::Result<Option<achievement::Data>>;
+    /// oldest first
+    async fn get_all_achievements(&self) -> anyhow::Result<Vec<achievement::Data>>;
+    /// earliest find made since a time, by any player
+    async fn first_discovery_since(
+        &self,
+        since: DateTime<FixedOffset>,
+    ) -> anyhow::Result<Option<duck_history::Data>>;
+    // U
+    async fn update_achievement(
+        &self,
+        id: String,
+        data: UpdateAchievementData,
+    ) -> anyhow::Result<achievement::Data>;
+    // C/U
+    /// Give an achievement to a user, or return the one already given.
+    async fn award_achievement(
+        &self,
+        achievement_id: String,
+        wechat_id: String,
+    ) -> anyhow::Result<user_achievement::Data>;
+    // D
+    /// delete an achievement, taking it back from every player
+    async fn delete_achievement(&self, id: String) -> anyhow::Result<achievement::Data>;
+}
+
+#[async_trait]
+impl AchievementStore for PrismaDB {
+    // C
+
+    async fn create_achievement(
+        &self,
+        data: NewAchievementData,
+    ) -> anyhow::Result<achievement::Data> {
+        let (title, description, rule, params) = data.into_db_data()?;
+        let data = self
+            .0
+            .achievement()
+            .create(title, description, rule, params)
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    // R
+
+    async fn get_achievement(&self, id: String) -> anyhow::Result<Option<achievement::Data>> {
+        let data = self
+            .0
+            .achievement()
+            .find_unique(achievement::UniqueWhereParam::IdEquals(id))
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    async fn get_all_achievements(&self) -> anyhow::Result<Vec<achievement::Data>> {
+        let data = self
+            .0
+            .achievement()
+            .find_many(vec![])
+            .order_by(achievement::id::order(Direction::Asc))
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    async fn first_discovery_since(
+        &self,
+        since: DateTime<FixedOffset>,
+    ) -> anyhow::Result<Option<duck_history::Data>> {
+        let data = self
+            .0
+            .duck_history()
+            .find_first(vec![duck_history::created_at::gte(since)])
+            .order_by(duck_history::created_at::order(Direction::Asc))
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    // U
+
+    async fn update_achievement(
+        &self,
+        id: String,
+        data: UpdateAchievementData,
+    ) -> anyhow::Result<achievement::Data> {
+        let data = self
+            .0
+            .achievement()
+            .update(
+                achievement::UniqueWhereParam::IdEquals(id),
+                data.into_db_data()?,
+            )
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    // C/U
+
+    async fn award_achievement(
+        &self,
+        achievement_id: String,
+        wechat_id: String,
+    ) -> anyhow::Result<user_achievement::Data> {
+        // empty update: keep the time it was first earned
+        let data = self
+            .0
+            .user_achievement()
+            .upsert(
+                user_achievement::UniqueWhereParam::AchievementIdUserWechatOpenIdEquals(
+                    achievement_id.clone(),
+                    wechat_id.clone(),
+                ),
+                (
+                    achievement::UniqueWhereParam::IdEquals(achievement_id),
+                    user::UniqueWhereParam::WechatOpenIdEquals(wechat_id),
+                    vec![],
+                ),
+                vec![],
+            )
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    // D
+
+    async fn delete_achievement(&self, id: String) -> anyhow::Result<achievement::Data> {
+        // children before parents, like the cascade would
+        self.0
+            .user_achievement()
+            .delete_many(vec![user_achievement::achievement_id::equals(id.clone())])
+            .exec()
+            .await?;
+        let data = self
+            .0
+            .achievement()
+            .delete(achievement::UniqueWhereParam::IdEquals(id))
+            .exec()
+            .await?;
+        Ok(data)
+    }
+}
+
+/// midnight starting the current day, `utc_offset_minutes` ahead of UTC
+fn start_of_day(utc_offset_minutes: i32) -> anyhow::Result<DateTime<FixedOffset>> {
+    let offset = FixedOffset::east_opt(utc_offset_minutes * 60)
+        .ok_or_else(|| anyhow!("invalid utc offset {}", utc_offset_minutes))?;
+    let midnight = Utc::now()
+        .with_timezone(&offset)
+        .date_naive()
+        .and_hms_opt(0, 0, 0)
+        .unwrap();
+    offset
+        .from_local_datetime(&midnight)
+        .single()
+        .ok_or_else(|| anyhow!("invalid midnight"))
+}
+
+impl DB {
+    /// Award a player every achievement whose rule they now meet.
+    ///
+    /// `user` is not scoped to a season, and is returned with the achievements earned.
+    /// Rules about ducks which do not exist, such as a topic no duck has, are never met.
+    pub async fn award_achievements(
+        &self,
+        user: user_info::Data,
+    ) -> anyhow::Result<user_info::Data> {
+        let earned: HashSet<&str> = user
+            .achievements
+            .iter()
+            .map(|a| a.achievement.id.as_str())
+            .collect();
+        let pending: Vec<achievement::Data> = self
+            .get_all_achievements()
+            .await?
+            .into_iter()
+            .filter(|a| !earned.contains(a.id.as_str()))
+            .collect();
+        if pending.is_empty() {
+            return Ok(user);
+        }
+        let mut awarded = vec![];
+        for achievement in pending {
+            let rule = parse_achievement_rule(&achievement)?;
+            if self.meets(&user, rule).await? {
+                awarded.push(achievement.id);
+            }
+        }
+        if awarded.is_empty() {
+            return Ok(user);
+        }
+        let wechat_id = user.wechat_open_id;
+        for achievement_id in awarded {
+            self.award_achievement(achievement_id.clone(), wechat_id.clone())
+                .await?;
+            self.notify(GameEvent::Achieved {
+                wechat_openid: wechat_id.clone(),
+                achievement_id,
+            })
+            .await;
+        }
+        self.upsert_user_info(wechat_id).await
+    }
+
+    /// whether a player meets the rule of an achievement
+    async fn meets(&self, user: &user_info::Data, rule: AchievementRule) -> anyhow::Result<bool> {
+        let found: HashSet<&str> = user
+            .duck_history
+            .iter()
+            .map(|h| h.duck.id.as_str())
+            .collect();
+        // all of the ducks, and at least one
+        let all_found =
+            |ducks: Vec<&str>| !ducks.is_empty() && ducks.iter().all(|id| found.contains(id));
+        let met = match rule {
+            AchievementRule::Topic { topic } => {
+                let ducks: Vec<completion_duck::Data> = self
+                    .get_completion_ducks()
+                    .await?
+                    .into_iter()
+                    .filter(|d| d.deleted_at.is_none() && has_topic(d, &topic))
+                    .collect();
+                all_found(ducks.iter().map(|d| d.id.as_str()).collect())
+            }
+            AchievementRule::Exhibit { exhibit_id } => self
+                .get_exhibit(exhibit_id)
+                .await?
+                .and_then(|e| e.related_duck_id)
+                .map_or(false, |duck_id| found.contains(duck_id.as_str())),
+            AchievementRule::StoryChain { duck_id } => {
+                if self.get_duck(duck_id.clone()).await?.is_none() {
+                    false
+                } else {
+                    let graph = StoryGraph::new(self.get_story_nodes().await?);
+                    all_found(chain_of(&graph, &duck_id).into_iter().collect())
+                }
+            }
+            AchievementRule::FirstOfDay { utc_offset_minutes } => self
+                .first_discovery_since(start_of_day(utc_offset_minutes)?)
+                .await?
+                .map_or(false, |h| h.user_id == user.id),
+            AchievementRule::DucksWithin { count, minutes } => {
+                let mut times: Vec<DateTime<FixedOffset>> =
+                    user.duck_history.iter().map(|h| h.created_at).collect();
+                times.sort();
+                count > 0
+                    && times.windows(count).any(|w| {
+                        w[count - 1].signed_duration_since(w[0]) <= Duration::minutes(minutes)
+                    })
+            }
+        };
+        Ok(met)
+    }
+}
@@ -0,0 +1,133 @@
+//! admin api to manage admin accounts
+use crate::db_api::PrismaDB;
+use crate::prisma::admin_account;
+use async_trait::async_trait;
+use prisma_client_rust::chrono::Utc;
+
+// response struct for listing accounts, without token hashes
+admin_account::select! { admin_account_info {
+    name
+    role
+    created_at
+    revoked_at
+}}
+
+impl admin_account::Data {
+    pub fn info(&self) -> admin_account_info::Data {
+        admin_account_info::Data {
+            name: self.name.clone(),
+            role: self.role.clone(),
+            created_at: self.created_at,
+            revoked_at: self.revoked_at,
+        }
+    }
+}
+
+#[async_trait]
+pub trait AdminStore {
+    // C
+    async fn create_admin(
+        &self,
+        name: String,
+        role: String,
+        token_hash: String,
+    ) -> anyhow::Result<admin_account::Data>;
+    // R
+    async fn get_all_admins(&self) -> anyhow::Result<Vec<admin_account_info::Data>>;
+    /// revoked accounts are not returned
+    async fn find_admin_by_token_hash(
+        &self,
+        token_hash: String,
+    ) -> anyhow::Result<Option<admin_account::Data>>;
+    // U
+    /// replace the token of an account, which also lifts a revocation
+    async fn reset_admin_token(
+        &self,
+        name: String,
+        token_hash: String,
+    ) -> anyhow::Result<admin_account::Data>;
+    async fn revoke_admin(&self, name: String) -> anyhow::Result<admin_account::Data>;
+}
+
+#[async_trait]
+impl AdminStore for PrismaDB {
+    // C
+
+    async fn create_admin(
+        &self,
+        name: String,
+        role: String,
+        token_hash: String,
+    ) -> anyhow::Result<admin_account::Data> {
+        let data = self
+            .0
+            .admin_account()
+            .create(name, role, token_hash, vec![])
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    // R
+
+    async fn get_all_admins(&self) -> anyhow::Result<Vec<admin_account_info::Data>> {
+        let data = self
+            .0
+            .admin_account()
+            .find_many(vec![])
+            .select(admin_account_info::select())
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    async fn find_admin_by_token_hash(
+        &self,
+        token_hash: String,
+    ) -> anyhow::Result<Option<admin_account::Data>> {
+        let data = self
+            .0
+            .admin_account()
+            .find_unique(admin_account::UniqueWhereParam::TokenHashEquals(token_hash))
+            .exec()
+            .await?;
+        Ok(data.filter(|a| a.revoked_at.is_none()))
+    }
+
+    // U
+
+    async fn reset_admin_token(
+        &self,
+        name: String,
+        token_hash: String,
+    ) -> anyhow::Result<admin_account::Data> {
+        let data = self
+            .0
+            .admin_account()
+            .update(
+                admin_account::UniqueWhereParam::NameEquals(name),
+                vec![
+                    admin_account::SetParam::SetTokenHash(token_hash),
+                    admin_account::SetParam::SetRevokedAt(None),
+                ],
+            )
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    async fn revoke_admin(&self, name: String) -> anyhow::Result<admin_account::Data> {
+        let data = self
+            .0
+            .admin_account()
+            .update(
+                admin_account::UniqueWhereParam::NameEquals(name),
+                vec![admin_account::SetParam::SetRevokedAt(Some(
+                    Utc::now().into(),
+                ))],
+            )
+            .exec()
+            .await?;
+        Ok(data)
+    }
+}
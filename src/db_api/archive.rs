@@ -0,0 +1,712 @@
+//! export and import of the whole database as a versioned archive
+use crate::db_api::achievements::{parse_achievement_rule, AchievementRule};
+use crate::db_api::completion::{parse_rule, CompletionRule, CompletionStore};
+use crate::db_api::teams::TeamStore;
+use crate::db_api::PrismaDB;
+use crate::prisma::{
+    achievement, completion_rule, duck, duck_history, exhibit, location, ranking, season,
+    season_ranking, team, team_ranking, user, user_achievement,
+};
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use prisma_client_rust::chrono::{DateTime, FixedOffset, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::hash::Hash;
+
+/// Version of the archive format,
+/// to be increased whenever the archived models change.
+pub const ARCHIVE_VERSION: u32 = 5;
+
+/// every document of the game, with the ids of the exporting database
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Archive {
+    pub version: u32,
+    pub exported_at: DateTime<FixedOffset>,
+    pub ducks: Vec<duck::Data>,
+    pub locations: Vec<location::Data>,
+    pub exhibits: Vec<exhibit::Data>,
+    pub users: Vec<user::Data>,
+    pub duck_history: Vec<duck_history::Data>,
+    pub rankings: Vec<ranking::Data>,
+    pub seasons: Vec<season::Data>,
+    pub season_rankings: Vec<season_ranking::Data>,
+    pub completion_rules: Vec<completion_rule::Data>,
+    pub achievements: Vec<achievement::Data>,
+    pub user_achievements: Vec<user_achievement::Data>,
+    pub teams: Vec<team::Data>,
+    pub team_rankings: Vec<team_ranking::Data>,
+}
+
+/// how an archive is combined with the documents already stored
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// delete every document first, then import everything
+    Replace,
+    /// import everything next to the stored documents,
+    /// merging players by wechat openid and teams by join code,
+    /// imported players and teams being ranked after those already ranked
+    Merge,
+    /// import seasons, ducks, locations, exhibits, completion rules
+    /// and achievements only, leaving players and teams untouched
+    Content,
+}
+
+/// number of documents created by an import
+#[derive(Serialize, Default)]
+pub struct ImportReport {
+    pub ducks: usize,
+    pub locations: usize,
+    pub exhibits: usize,
+    pub users: usize,
+    pub duck_history: usize,
+    pub rankings: usize,
+    pub seasons: usize,
+    pub season_rankings: usize,
+    pub completion_rules: usize,
+    pub achievements: usize,
+    pub user_achievements: usize,
+    pub teams: usize,
+    pub team_rankings: usize,
+}
+
+/// Fails on the first key found twice in `keys`, which must be unique.
+fn check_unique<K: Eq + Hash + Debug>(
+    what: &str,
+    keys: impl IntoIterator<Item = K>,
+) -> anyhow::Result<()> {
+    let mut seen = HashSet::new();
+    for key in keys {
+        if seen.contains(&key) {
+            bail!("archive repeats {} {:?}", what, key);
+        }
+        seen.insert(key);
+    }
+    Ok(())
+}
+
+impl Archive {
+    /// Check the version, that every relation points into the archive,
+    /// that every rule can be read, and that no unique field is repeated,
+    /// so that an import fails before writing, or deleting, anything.
+    pub fn check(&self) -> anyhow::Result<()> {
+        if self.version != ARCHIVE_VERSION {
+            bail!(
+                "unsupported archive version {} (expected {})",
+                self.version,
+                ARCHIVE_VERSION
+            );
+        }
+        let ducks: HashSet<&str> = self.ducks.iter().map(|d| d.id.as_str()).collect();
+        let seasons: HashSet<&str> = self.seasons.iter().map(|s| s.id.as_str()).collect();
+        let users: HashSet<&str> = self.users.iter().map(|u| u.id.as_str()).collect();
+        let openids: HashSet<&str> = self
+            .users
+            .iter()
+            .map(|u| u.wechat_open_id.as_str())
+            .collect();
+        let achievements: HashSet<&str> = self.achievements.iter().map(|a| a.id.as_str()).collect();
+        let teams: HashSet<&str> = self.teams.iter().map(|t| t.id.as_str()).collect();
+        let duck_refs = self
+            .ducks
+            .iter()
+            .filter_map(|d| d.prev_duck_story_id.as_deref())
+            .chain(self.locations.iter().filter_map(|l| l.duck_id.as_deref()))
+            .chain(
+                self.exhibits
+                    .iter()
+                    .filter_map(|e| e.related_duck_id.as_deref()),
+            )
+            .chain(self.duck_history.iter().map(|h| h.duck_id.as_str()));
+        for duck_id in duck_refs {
+            if !ducks.contains(duck_id) {
+                bail!("archive refers to missing duck {}", duck_id);
+            }
+        }
+        for h in &self.duck_history {
+            if !users.contains(h.user_id.as_str()) {
+                bail!("archive refers to missing user {}", h.user_id);
+            }
+        }
+        let openid_refs = self
+            .rankings
+            .iter()
+            .map(|r| r.user_wechat_open_id.as_str())
+            .chain(
+                self.season_rankings
+                    .iter()
+                    .map(|r| r.user_wechat_open_id.as_str()),
+            )
+            .chain(
+                self.user_achievements
+                    .iter()
+                    .map(|a| a.user_wechat_open_id.as_str()),
+            );
+        for openid in openid_refs {
+            if !openids.contains(openid) {
+                bail!("archive refers to missing user {}", openid);
+            }
+        }
+        let season_refs = self
+            .ducks
+            .iter()
+            .filter_map(|d| d.season_id.as_deref())
+            .chain(self.season_rankings.iter().map(|r| r.season_id.as_str()))
+            .chain(
+                self.completion_rules
+                    .iter()
+                    .filter_map(|r| r.season_id.as_deref()),
+            )
+            .chain(
+                self.team_rankings
+                    .iter()
+                    .filter_map(|r| r.season_id.as_deref()),
+            );
+        for season_id in season_refs {
+            if !seasons.contains(season_id) {
+                bail!("archive refers to missing season {}", season_id);
+            }
+        }
+        for a in &self.user_achievements {
+            if !achievements.contains(a.achievement_id.as_str()) {
+                bail!("archive refers to missing achievement {}", a.achievement_id);
+            }
+        }
+        let team_refs = self
+            .users
+            .iter()
+            .filter_map(|u| u.team_id.as_deref())
+            .chain(self.team_rankings.iter().map(|r| r.team_id.as_str()));
+        for team_id in team_refs {
+            if !teams.contains(team_id) {
+                bail!("archive refers to missing team {}", team_id);
+            }
+        }
+        for r in &self.completion_rules {
+            parse_rule(r)?;
+        }
+        for a in &self.achievements {
+            parse_achievement_rule(a)?;
+        }
+
+        check_unique("duck id", self.ducks.iter().map(|d| &d.id))?;
+        check_unique("season id", self.seasons.iter().map(|s| &s.id))?;
+        check_unique("user id", self.users.iter().map(|u| &u.id))?;
+        check_unique("achievement id", self.achievements.iter().map(|a| &a.id))?;
+        check_unique("team id", self.teams.iter().map(|t| &t.id))?;
+        check_unique(
+            "previous story",
+            self.ducks
+                .iter()
+                .filter_map(|d| d.prev_duck_story_id.as_ref()),
+        )?;
+        check_unique(
+            "duck of location",
+            self.locations.iter().filter_map(|l| l.duck_id.as_ref()),
+        )?;
+        check_unique(
+            "duck of exhibit",
+            self.exhibits
+                .iter()
+                .filter_map(|e| e.related_duck_id.as_ref()),
+        )?;
+        check_unique(
+            "wechat openid",
+            self.users.iter().map(|u| &u.wechat_open_id),
+        )?;
+        check_unique(
+            "history",
+            self.duck_history.iter().map(|h| (&h.user_id, &h.duck_id)),
+        )?;
+        check_unique(
+            "ranking of",
+            self.rankings.iter().map(|r| &r.user_wechat_open_id),
+        )?;
+        check_unique(
+            "season ranking of",
+            self.season_rankings
+                .iter()
+                .map(|r| (&r.season_id, &r.user_wechat_open_id)),
+        )?;
+        check_unique(
+            "achievement of",
+            self.user_achievements
+                .iter()
+                .map(|a| (&a.achievement_id, &a.user_wechat_open_id)),
+        )?;
+        check_unique("join code", self.teams.iter().map(|t| &t.join_code))?;
+        check_unique(
+            "team ranking of",
+            self.team_rankings
+                .iter()
+                .map(|r| (&r.team_id, &r.season_id)),
+        )?;
+        Ok(())
+    }
+}
+
+/// New ids of the prerequisites of an archived duck.
+///
+/// Prerequisites are plain ids which may dangle, those are dropped.
+pub(crate) fn prerequisites_of(
+    duck: &duck::Data,
+    duck_ids: &HashMap<String, String>,
+) -> Vec<String> {
+    duck.prerequisite_ids
+        .iter()
+        .filter_map(|id| duck_ids.get(id).cloned())
+        .collect()
+}
+
+/// new id of a duck or exhibit named by a rule, a dangling id is kept
+fn remapped(id: String, ids: &HashMap<String, String>) -> String {
+    ids.get(&id).cloned().unwrap_or(id)
+}
+
+/// Rule of an archived completion rule, naming the new id of its duck.
+pub(crate) fn completion_rule_of(
+    rule: &completion_rule::Data,
+    duck_ids: &HashMap<String, String>,
+) -> anyhow::Result<Value> {
+    let rule = match parse_rule(rule)? {
+        CompletionRule::StoryChain { duck_id } => CompletionRule::StoryChain {
+            duck_id: remapped(duck_id, duck_ids),
+        },
+        rule => rule,
+    };
+    Ok(serde_json::to_value(rule)?)
+}
+
+/// Rule of an archived achievement, naming the new id of its duck or exhibit.
+pub(crate) fn achievement_rule_of(
+    achievement: &achievement::Data,
+    duck_ids: &HashMap<String, String>,
+    exhibit_ids: &HashMap<String, String>,
+) -> anyhow::Result<Value> {
+    let rule = match parse_achievement_rule(achievement)? {
+        AchievementRule::Exhibit { exhibit_id } => AchievementRule::Exhibit {
+            exhibit_id: remapped(exhibit_id, exhibit_ids),
+        },
+        AchievementRule::StoryChain { duck_id } => AchievementRule::StoryChain {
+            duck_id: remapped(duck_id, duck_ids),
+        },
+        rule => rule,
+    };
+    Ok(serde_json::to_value(rule)?)
+}
+
+#[async_trait]
+pub trait ArchiveStore {
+    // R
+    async fn export_archive(&self) -> anyhow::Result<Archive>;
+    // C
+    /// Import the documents of a checked archive under new ids,
+    /// remapping the relations between them.
+    async fn import_archive(
+        &self,
+        archive: Archive,
+        mode: ImportMode,
+    ) -> anyhow::Result<ImportReport>;
+}
+
+#[async_trait]
+impl ArchiveStore for PrismaDB {
+    // R
+
+    async fn export_archive(&self) -> anyhow::Result<Archive> {
+        Ok(Archive {
+            version: ARCHIVE_VERSION,
+            exported_at: Utc::now().into(),
+            ducks: self.0.duck().find_many(vec![]).exec().await?,
+            locations: self.0.location().find_many(vec![]).exec().await?,
+            exhibits: self.0.exhibit().find_many(vec![]).exec().await?,
+            users: self.0.user().find_many(vec![]).exec().await?,
+            duck_history: self.0.duck_history().find_many(vec![]).exec().await?,
+            rankings: self.0.ranking().find_many(vec![]).exec().await?,
+            seasons: self.0.season().find_many(vec![]).exec().await?,
+            season_rankings: self.0.season_ranking().find_many(vec![]).exec().await?,
+            completion_rules: self.0.completion_rule().find_many(vec![]).exec().await?,
+            achievements: self.0.achievement().find_many(vec![]).exec().await?,
+            user_achievements: self.0.user_achievement().find_many(vec![]).exec().await?,
+            teams: self.0.team().find_many(vec![]).exec().await?,
+            team_rankings: self.0.team_ranking().find_many(vec![]).exec().await?,
+        })
+    }
+
+    // C
+
+    async fn import_archive(
+        &self,
+        archive: Archive,
+        mode: ImportMode,
+    ) -> anyhow::Result<ImportReport> {
+        // every lookup of a new id below succeeds on a checked archive,
+        // so a replace never deletes the documents of an archive it cannot import
+        archive.check()?;
+        let mut report = ImportReport::default();
+        if mode == ImportMode::Replace {
+            // children before parents, like the cascades would
+            self.0.duck_history().delete_many(vec![]).exec().await?;
+            self.0.ranking().delete_many(vec![]).exec().await?;
+            self.0.season_ranking().delete_many(vec![]).exec().await?;
+            self.0.user_achievement().delete_many(vec![]).exec().await?;
+            self.0.achievement().delete_many(vec![]).exec().await?;
+            self.0.hint_reveal().delete_many(vec![]).exec().await?;
+            self.0
+                .pending_discovery()
+                .delete_many(vec![])
+                .exec()
+                .await?;
+            self.0
+                .challenge_attempt()
+                .delete_many(vec![])
+                .exec()
+                .await?;
+            self.0.discovery_flag().delete_many(vec![]).exec().await?;
+            self.0.duck_code().delete_many(vec![]).exec().await?;
+            self.0.completion_rule().delete_many(vec![]).exec().await?;
+            self.0.team_ranking().delete_many(vec![]).exec().await?;
+            self.0.user().delete_many(vec![]).exec().await?;
+            self.0.team().delete_many(vec![]).exec().await?;
+            self.0.location().delete_many(vec![]).exec().await?;
+            self.0.exhibit().delete_many(vec![]).exec().await?;
+            self.0.duck().delete_many(vec![]).exec().await?;
+            self.0.season().delete_many(vec![]).exec().await?;
+        }
+
+        // old id -> new id
+        let mut season_ids = HashMap::new();
+        for s in &archive.seasons {
+            let data = self
+                .0
+                .season()
+                .create(
+                    s.title.clone(),
+                    s.starts_at,
+                    vec![
+                        season::SetParam::SetCreatedAt(s.created_at),
+                        season::SetParam::SetEndsAt(s.ends_at),
+                        season::SetParam::SetArchivedAt(s.archived_at),
+                    ],
+                )
+                .exec()
+                .await?;
+            season_ids.insert(s.id.clone(), data.id);
+            report.seasons += 1;
+        }
+        let mut duck_ids = HashMap::new();
+        for d in &archive.ducks {
+            let mut params = vec![
+                duck::SetParam::SetCreatedAt(d.created_at),
+                duck::SetParam::SetIsHidden(d.is_hidden),
+                duck::SetParam::SetChallenge(d.challenge.clone()),
+                duck::SetParam::SetHints(d.hints.clone()),
+                duck::SetParam::SetDeletedAt(d.deleted_at),
+            ];
+            if let Some(season_id) = &d.season_id {
+                params.push(duck::SetParam::ConnectSeason(
+                    season::UniqueWhereParam::IdEquals(season_ids[season_id].clone()),
+                ));
+            }
+            let data = self
+                .0
+                .duck()
+                .create(
+                    d.title.clone(),
+                    d.story.clone(),
+                    d.topics.clone(),
+                    d.duck_icon_url.clone(),
+                    params,
+                )
+                .exec()
+                .await?;
+            duck_ids.insert(d.id.clone(), data.id);
+            report.ducks += 1;
+        }
+        for d in &archive.ducks {
+            let mut params = vec![duck::SetParam::SetPrerequisiteIds(prerequisites_of(
+                d, &duck_ids,
+            ))];
+            if let Some(prev_duck_story_id) = &d.prev_duck_story_id {
+                params.push(duck::SetParam::ConnectPrevDuckStory(
+                    duck::UniqueWhereParam::IdEquals(duck_ids[prev_duck_story_id].clone()),
+                ));
+            }
+            self.0
+                .duck()
+                .update(
+                    duck::UniqueWhereParam::IdEquals(duck_ids[&d.id].clone()),
+                    params,
+                )
+                .exec()
+                .await?;
+        }
+        for l in archive.locations {
+            let mut params = vec![
+                location::SetParam::SetDeletedAt(l.deleted_at),
+                location::SetParam::SetGeofenceRadius(l.geofence_radius),
+                location::SetParam::SetGeofenceMode(l.geofence_mode),
+            ];
+            if let Some(duck_id) = l.duck_id {
+                params.push(location::SetParam::ConnectDuck(
+                    duck::UniqueWhereParam::IdEquals(duck_ids[&duck_id].clone()),
+                ));
+            }
+            self.0
+                .location()
+                .create(l.description, l.coordinate, params)
+                .exec()
+                .await?;
+            report.locations += 1;
+        }
+        self.reindex_locations()
+            .await
+            .context("error indexing locations")?;
+        let mut exhibit_ids = HashMap::new();
+        for e in archive.exhibits {
+            let mut params = vec![
+                exhibit::SetParam::SetCreatedAt(e.created_at),
+                exhibit::SetParam::SetDeletedAt(e.deleted_at),
+            ];
+            if let Some(related_duck_id) = e.related_duck_id {
+                params.push(exhibit::SetParam::ConnectRelatedDuck(
+                    duck::UniqueWhereParam::IdEquals(duck_ids[&related_duck_id].clone()),
+                ));
+            }
+            let data = self
+                .0
+                .exhibit()
+                .create(e.location, e.title, e.sign, e.artists, params)
+                .exec()
+                .await?;
+            exhibit_ids.insert(e.id, data.id);
+            report.exhibits += 1;
+        }
+        // rules of the main game already set are kept, like the ranking of a merged player
+        let mut kept_main_rules = HashSet::new();
+        for team in [false, true] {
+            if !self.get_completion_rules(None, team).await?.is_empty() {
+                kept_main_rules.insert(team);
+            }
+        }
+        for r in archive.completion_rules {
+            if r.season_id.is_none() && kept_main_rules.contains(&r.team.unwrap_or(false)) {
+                continue;
+            }
+            let season_id = r.season_id.as_ref().map(|s| season_ids[s].clone());
+            self.0
+                .completion_rule()
+                .create(
+                    completion_rule_of(&r, &duck_ids)?,
+                    vec![
+                        completion_rule::SetParam::SetCreatedAt(r.created_at),
+                        completion_rule::SetParam::SetSeasonId(season_id),
+                        completion_rule::SetParam::SetTeam(r.team),
+                    ],
+                )
+                .exec()
+                .await?;
+            report.completion_rules += 1;
+        }
+        let mut achievement_ids = HashMap::new();
+        for a in archive.achievements {
+            let data = self
+                .0
+                .achievement()
+                .create(
+                    a.title.clone(),
+                    a.description.clone(),
+                    achievement_rule_of(&a, &duck_ids, &exhibit_ids)?,
+                    vec![
+                        achievement::SetParam::SetCreatedAt(a.created_at),
+                        achievement::SetParam::SetIconUrl(a.icon_url),
+                    ],
+                )
+                .exec()
+                .await?;
+            achievement_ids.insert(a.id, data.id);
+            report.achievements += 1;
+        }
+        if mode == ImportMode::Content {
+            return Ok(report);
+        }
+
+        let mut team_ids = HashMap::new();
+        for t in archive.teams {
+            let existing = self
+                .0
+                .team()
+                .find_unique(team::UniqueWhereParam::JoinCodeEquals(t.join_code.clone()))
+                .exec()
+                .await?;
+            let id = match existing {
+                Some(existing) => existing.id,
+                None => {
+                    report.teams += 1;
+                    self.0
+                        .team()
+                        .create(
+                            t.name,
+                            t.join_code,
+                            vec![team::SetParam::SetCreatedAt(t.created_at)],
+                        )
+                        .exec()
+                        .await?
+                        .id
+                }
+            };
+            team_ids.insert(t.id, id);
+        }
+        let mut user_ids = HashMap::new();
+        for u in archive.users {
+            let existing = self
+                .0
+                .user()
+                .find_unique(user::UniqueWhereParam::WechatOpenIdEquals(
+                    u.wechat_open_id.clone(),
+                ))
+                .exec()
+                .await?;
+            let id = match existing {
+                Some(existing) => existing.id,
+                None => {
+                    report.users += 1;
+                    let mut params = vec![
+                        user::SetParam::SetCreatedAt(u.created_at),
+                        user::SetParam::SetNickname(u.nickname),
+                    ];
+                    if let Some(team_id) = u.team_id {
+                        params.push(user::SetParam::ConnectTeam(
+                            team::UniqueWhereParam::IdEquals(team_ids[&team_id].clone()),
+                        ));
+                    }
+                    self.0
+                        .user()
+                        .create(u.wechat_open_id, params)
+                        .exec()
+                        .await?
+                        .id
+                }
+            };
+            user_ids.insert(u.id, id);
+        }
+        for h in archive.duck_history {
+            self.0
+                .duck_history()
+                .create(
+                    user::UniqueWhereParam::IdEquals(user_ids[&h.user_id].clone()),
+                    duck::UniqueWhereParam::IdEquals(duck_ids[&h.duck_id].clone()),
+                    vec![duck_history::SetParam::SetCreatedAt(h.created_at)],
+                )
+                .exec()
+                .await?;
+            report.duck_history += 1;
+        }
+        for a in archive.user_achievements {
+            self.0
+                .user_achievement()
+                .create(
+                    achievement::UniqueWhereParam::IdEquals(
+                        achievement_ids[&a.achievement_id].clone(),
+                    ),
+                    user::UniqueWhereParam::WechatOpenIdEquals(a.user_wechat_open_id),
+                    vec![user_achievement::SetParam::SetCreatedAt(a.created_at)],
+                )
+                .exec()
+                .await?;
+            report.user_achievements += 1;
+        }
+        let mut rankings = archive.rankings;
+        rankings.sort_by(|a, b| (a.ranking, a.created_at).cmp(&(b.ranking, b.created_at)));
+        for r in rankings {
+            let params = vec![ranking::SetParam::SetCreatedAt(r.created_at)];
+            if mode == ImportMode::Replace {
+                self.0
+                    .ranking()
+                    .create(
+                        user::UniqueWhereParam::WechatOpenIdEquals(r.user_wechat_open_id),
+                        r.ranking,
+                        params,
+                    )
+                    .exec()
+                    .await?;
+                report.rankings += 1;
+                continue;
+            }
+            // a merged player keeps the ranking already earned here,
+            // the others are ranked after every player ranked here, in their order
+            let existing = self
+                .0
+                .ranking()
+                .find_unique(ranking::UniqueWhereParam::UserWechatOpenIdEquals(
+                    r.user_wechat_open_id.clone(),
+                ))
+                .exec()
+                .await?;
+            if existing.is_some() {
+                continue;
+            }
+            self.rank_player(r.user_wechat_open_id, params).await?;
+            report.rankings += 1;
+        }
+        for r in archive.season_rankings {
+            let season_id = season_ids[&r.season_id].clone();
+            // seasons are imported anew, their counters are seeded on the next ranking
+            self.0
+                .season_ranking()
+                .create(
+                    season::UniqueWhereParam::IdEquals(season_id),
+                    r.user_wechat_open_id,
+                    r.ranking,
+                    vec![season_ranking::SetParam::SetCreatedAt(r.created_at)],
+                )
+                .exec()
+                .await?;
+            report.season_rankings += 1;
+        }
+        let mut team_rankings = archive.team_rankings;
+        team_rankings.sort_by(|a, b| (a.ranking, a.created_at).cmp(&(b.ranking, b.created_at)));
+        for r in team_rankings {
+            let team_id = team_ids[&r.team_id].clone();
+            let mut params = vec![team_ranking::SetParam::SetCreatedAt(r.created_at)];
+            match r.season_id {
+                // like the rankings of players
+                None if mode == ImportMode::Merge => {
+                    let ranked = self
+                        .get_team_rankings(None)
+                        .await?
+                        .iter()
+                        .any(|ranked| ranked.team_id == team_id);
+                    if ranked {
+                        continue;
+                    }
+                    self.rank_team(team_id, None, params).await?;
+                }
+                season_id => {
+                    params.push(team_ranking::SetParam::SetSeasonId(
+                        season_id.map(|s| season_ids[&s].clone()),
+                    ));
+                    self.0
+                        .team_ranking()
+                        .create(team::UniqueWhereParam::IdEquals(team_id), r.ranking, params)
+                        .exec()
+                        .await?;
+                }
+            }
+            report.team_rankings += 1;
+        }
+        if mode == ImportMode::Replace {
+            self.reseed_ranking_counter()
+                .await
+                .context("error reseeding ranking counter")?;
+            self.reseed_team_ranking_counter()
+                .await
+                .context("error reseeding team ranking counter")?;
+        }
+        Ok(report)
+    }
+}
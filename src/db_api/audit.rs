@@ -0,0 +1,726 @@
+//! audit log of admin mutations
+use crate::admin_auth::Admin;
+use crate::db_api::achievements::{NewAchievementData, UpdateAchievementData};
+use crate::db_api::admins::admin_account_info;
+use crate::db_api::archive::{Archive, ImportMode, ImportReport};
+use crate::db_api::challenges::Challenge;
+use crate::db_api::completion::CompletionRule;
+use crate::db_api::ducks::{NewDuckData, UpdateDuckData};
+use crate::db_api::exhibits::{NewExhibitData, UpdateExhibitData};
+use crate::db_api::hints::Hint;
+use crate::db_api::locations::{CoordinateMigration, NewLocationData, UpdateLocationData};
+use crate::db_api::seasons::{NewSeasonData, UpdateSeasonData};
+use crate::db_api::{PrismaDB, DB};
+use crate::prisma::{
+    achievement, admin_account, audit_log, duck, exhibit, location, qr_key, season,
+};
+use async_trait::async_trait;
+use prisma_client_rust::chrono::{DateTime, FixedOffset};
+use prisma_client_rust::Direction;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::error;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 500;
+
+/// kinds of entities touched by admin mutations
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityType {
+    Duck,
+    Exhibit,
+    Location,
+    Ranking,
+    DuckHistory,
+    Snapshot,
+    Archive,
+    QrKey,
+    DuckCode,
+    CompletionRule,
+    Season,
+    Achievement,
+    AdminAccount,
+}
+
+impl EntityType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EntityType::Duck => "duck",
+            EntityType::Exhibit => "exhibit",
+            EntityType::Location => "location",
+            EntityType::Ranking => "ranking",
+            EntityType::DuckHistory => "duck_history",
+            EntityType::Snapshot => "snapshot",
+            EntityType::Archive => "archive",
+            EntityType::QrKey => "qr_key",
+            EntityType::DuckCode => "duck_code",
+            EntityType::CompletionRule => "completion_rule",
+            EntityType::Season => "season",
+            EntityType::Achievement => "achievement",
+            EntityType::AdminAccount => "admin_account",
+        }
+    }
+}
+
+pub struct NewAuditEntry {
+    pub admin: String,
+    pub route: String,
+    pub entity_type: EntityType,
+    /// `None` for bulk operations
+    pub entity_id: Option<String>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+/// query struct for GET request
+#[derive(Deserialize)]
+pub struct AuditLogQuery {
+    pub(crate) entity_type: Option<EntityType>,
+    /// inclusive lower bound of `createdAt`
+    pub(crate) from: Option<DateTime<FixedOffset>>,
+    /// inclusive upper bound of `createdAt`
+    pub(crate) to: Option<DateTime<FixedOffset>>,
+    #[serde(default)]
+    pub(crate) page: i64,
+    #[serde(default = "default_page_size")]
+    pub(crate) page_size: i64,
+}
+
+fn default_page_size() -> i64 {
+    DEFAULT_PAGE_SIZE
+}
+
+impl AuditLogQuery {
+    /// (skip, take) of the requested page
+    pub(crate) fn range(&self) -> (i64, i64) {
+        let page_size = self.page_size.clamp(1, MAX_PAGE_SIZE);
+        (self.page.max(0) * page_size, page_size)
+    }
+
+    fn into_db_filters(self) -> Vec<audit_log::WhereParam> {
+        let mut filters = vec![];
+        if let Some(entity_type) = self.entity_type {
+            filters.push(audit_log::entity_type::equals(
+                entity_type.as_str().to_string(),
+            ));
+        }
+        if let Some(from) = self.from {
+            filters.push(audit_log::created_at::gte(from));
+        }
+        if let Some(to) = self.to {
+            filters.push(audit_log::created_at::lte(to));
+        }
+        filters
+    }
+}
+
+/// response struct for GET request, newest entries first
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditLogPage {
+    pub items: Vec<audit_log::Data>,
+    pub page: i64,
+    pub page_size: i64,
+    pub total: i64,
+}
+
+#[async_trait]
+pub trait AuditStore {
+    // C
+    async fn record_audit(&self, entry: NewAuditEntry) -> anyhow::Result<audit_log::Data>;
+    // R
+    async fn get_audit_log(&self, query: AuditLogQuery) -> anyhow::Result<AuditLogPage>;
+}
+
+#[async_trait]
+impl AuditStore for PrismaDB {
+    // C
+
+    async fn record_audit(&self, entry: NewAuditEntry) -> anyhow::Result<audit_log::Data> {
+        let data = self
+            .0
+            .audit_log()
+            .create(
+                entry.admin,
+                entry.route,
+                entry.entity_type.as_str().to_string(),
+                vec![
+                    audit_log::SetParam::SetEntityId(entry.entity_id),
+                    audit_log::SetParam::SetBefore(entry.before),
+                    audit_log::SetParam::SetAfter(entry.after),
+                ],
+            )
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    // R
+
+    async fn get_audit_log(&self, query: AuditLogQuery) -> anyhow::Result<AuditLogPage> {
+        let (skip, take) = query.range();
+        let page = query.page.max(0);
+        let filters = query.into_db_filters();
+        let total = self.0.audit_log().count(filters.clone()).exec().await?;
+        let items = self
+            .0
+            .audit_log()
+            .find_many(filters)
+            .order_by(audit_log::created_at::order(Direction::Desc))
+            .skip(skip)
+            .take(take)
+            .exec()
+            .await?;
+        Ok(AuditLogPage {
+            items,
+            page,
+            page_size: take,
+            total,
+        })
+    }
+}
+
+impl DB {
+    /// Storage operations of an admin request which are written to the audit log.
+    pub fn audited<'a>(&'a self, admin: &'a Admin) -> Audited<'a> {
+        Audited { db: self, admin }
+    }
+}
+
+/// Mirrors the mutations of the storage traits, recording for each one
+/// the entity before and after it is changed.
+/// Entities in the trash are recorded as missing.
+///
+/// A failure to write the audit log is logged, but does not fail the mutation,
+/// which has already been applied.
+///
+/// Changes to archived seasons, their ducks and the locations of those ducks
+/// fail with `ArchivedSeason`, whichever admin tool asks for them.
+pub struct Audited<'a> {
+    db: &'a DB,
+    admin: &'a Admin,
+}
+
+impl<'a> Audited<'a> {
+    async fn record(
+        &self,
+        entity_type: EntityType,
+        entity_id: Option<String>,
+        before: Option<Value>,
+        after: Option<Value>,
+    ) {
+        let entry = NewAuditEntry {
+            admin: self.admin.name.clone(),
+            route: self.admin.route.clone(),
+            entity_type,
+            entity_id,
+            before,
+            after,
+        };
+        if let Err(e) = self.db.record_audit(entry).await {
+            error!(
+                "error writing audit log (admin: {}, route: {}): {}",
+                self.admin.name, self.admin.route, e
+            );
+        }
+    }
+
+    // ducks
+
+    pub async fn create_duck(&self, data: NewDuckData) -> anyhow::Result<duck::Data> {
+        self.db.require_writable(data.season_id.as_deref()).await?;
+        let rsp = self.db.create_duck(data).await?;
+        let after = self.db.get_duck(rsp.id.clone()).await?;
+        self.record(EntityType::Duck, Some(rsp.id.clone()), None, to_json(after))
+            .await;
+        Ok(rsp)
+    }
+
+    pub async fn create_many_ducks(&self, data: Vec<NewDuckData>) -> anyhow::Result<i64> {
+        for duck in &data {
+            self.db.require_writable(duck.season_id.as_deref()).await?;
+        }
+        let after = to_json(&data);
+        let rsp = self.db.create_many_ducks(data).await?;
+        self.record(EntityType::Duck, None, None, after).await;
+        Ok(rsp)
+    }
+
+    pub async fn update_duck(
+        &self,
+        id: String,
+        data: UpdateDuckData,
+    ) -> anyhow::Result<duck::Data> {
+        self.db.require_writable_duck(&id).await?;
+        if let Some(season_id) = &data.season_id {
+            self.db.require_writable(season_id.as_deref()).await?;
+        }
+        let before = self.db.get_duck(id.clone()).await?;
+        let rsp = self.db.update_duck(id.clone(), data).await?;
+        let after = self.db.get_duck(id.clone()).await?;
+        self.record(EntityType::Duck, Some(id), to_json(before), to_json(after))
+            .await;
+        Ok(rsp)
+    }
+
+    pub async fn restore_duck(&self, id: String) -> anyhow::Result<duck::Data> {
+        self.db.require_writable_duck(&id).await?;
+        let rsp = self.db.restore_duck(id.clone()).await?;
+        let after = self.db.get_duck(id.clone()).await?;
+        self.record(EntityType::Duck, Some(id), None, to_json(after))
+            .await;
+        Ok(rsp)
+    }
+
+    pub async fn delete_duck(&self, id: String) -> anyhow::Result<duck::Data> {
+        self.db.require_writable_duck(&id).await?;
+        let before = self.db.get_duck(id.clone()).await?;
+        let rsp = self.db.delete_duck(id.clone()).await?;
+        self.record(EntityType::Duck, Some(id), to_json(before), None)
+            .await;
+        Ok(rsp)
+    }
+
+    pub async fn set_duck_challenge(
+        &self,
+        id: String,
+        challenge: Option<Challenge>,
+    ) -> anyhow::Result<duck::Data> {
+        self.db.require_writable_duck(&id).await?;
+        let before = self.db.get_duck(id.clone()).await?;
+        let rsp = self.db.set_duck_challenge(id.clone(), challenge).await?;
+        let after = self.db.get_duck(id.clone()).await?;
+        self.record(EntityType::Duck, Some(id), to_json(before), to_json(after))
+            .await;
+        Ok(rsp)
+    }
+
+    pub async fn set_duck_hints(&self, id: String, hints: Vec<Hint>) -> anyhow::Result<duck::Data> {
+        self.db.require_writable_duck(&id).await?;
+        let before = self.db.get_duck(id.clone()).await?;
+        let rsp = self.db.set_duck_hints(id.clone(), hints).await?;
+        let after = self.db.get_duck(id.clone()).await?;
+        self.record(EntityType::Duck, Some(id), to_json(before), to_json(after))
+            .await;
+        Ok(rsp)
+    }
+
+    pub async fn delete_duck_history(&self, user_id: String) -> anyhow::Result<i64> {
+        let rsp = self.db.delete_duck_history(user_id.clone()).await?;
+        self.record(EntityType::DuckHistory, Some(user_id), None, None)
+            .await;
+        Ok(rsp)
+    }
+
+    pub async fn delete_all_ducks(&self) -> anyhow::Result<i64> {
+        let before = self.db.get_all_ducks().await?;
+        let rsp = self.db.delete_all_ducks().await?;
+        self.record(EntityType::Duck, None, to_json(before), None)
+            .await;
+        Ok(rsp)
+    }
+
+    // exhibits
+
+    pub async fn create_exhibit(&self, data: NewExhibitData) -> anyhow::Result<exhibit::Data> {
+        let rsp = self.db.create_exhibit(data).await?;
+        self.record(
+            EntityType::Exhibit,
+            Some(rsp.id.clone()),
+            None,
+            to_json(&rsp),
+        )
+        .await;
+        Ok(rsp)
+    }
+
+    pub async fn create_many_exhibits(&self, data: Vec<NewExhibitData>) -> anyhow::Result<i64> {
+        let after = to_json(&data);
+        let rsp = self.db.create_many_exhibits(data).await?;
+        self.record(EntityType::Exhibit, None, None, after).await;
+        Ok(rsp)
+    }
+
+    pub async fn update_exhibit(
+        &self,
+        id: String,
+        data: UpdateExhibitData,
+    ) -> anyhow::Result<exhibit::Data> {
+        let before = self.db.get_exhibit(id.clone()).await?;
+        let rsp = self.db.update_exhibit(id.clone(), data).await?;
+        self.record(
+            EntityType::Exhibit,
+            Some(id),
+            to_json(before),
+            to_json(&rsp),
+        )
+        .await;
+        Ok(rsp)
+    }
+
+    pub async fn restore_exhibit(&self, id: String) -> anyhow::Result<exhibit::Data> {
+        let rsp = self.db.restore_exhibit(id.clone()).await?;
+        self.record(EntityType::Exhibit, Some(id), None, to_json(&rsp))
+            .await;
+        Ok(rsp)
+    }
+
+    pub async fn delete_exhibit(&self, id: String) -> anyhow::Result<exhibit::Data> {
+        let before = self.db.get_exhibit(id.clone()).await?;
+        let rsp = self.db.delete_exhibit(id.clone()).await?;
+        self.record(EntityType::Exhibit, Some(id), to_json(before), None)
+            .await;
+        Ok(rsp)
+    }
+
+    pub async fn delete_all_exhibits(&self) -> anyhow::Result<i64> {
+        let before = self.db.get_all_exhibits().await?;
+        let rsp = self.db.delete_all_exhibits().await?;
+        self.record(EntityType::Exhibit, None, to_json(before), None)
+            .await;
+        Ok(rsp)
+    }
+
+    // locations
+
+    pub async fn create_location(&self, data: NewLocationData) -> anyhow::Result<location::Data> {
+        if let Some(duck_id) = &data.duck_id {
+            self.db.require_writable_duck(duck_id).await?;
+        }
+        let rsp = self.db.create_location(data).await?;
+        self.record(
+            EntityType::Location,
+            Some(rsp.id.clone()),
+            None,
+            to_json(&rsp),
+        )
+        .await;
+        Ok(rsp)
+    }
+
+    pub async fn create_many_locations(&self, data: Vec<NewLocationData>) -> anyhow::Result<i64> {
+        for duck_id in data.iter().filter_map(|l| l.duck_id.as_deref()) {
+            self.db.require_writable_duck(duck_id).await?;
+        }
+        let after = to_json(&data);
+        let rsp = self.db.create_many_locations(data).await?;
+        self.record(EntityType::Location, None, None, after).await;
+        Ok(rsp)
+    }
+
+    pub async fn update_location(
+        &self,
+        id: String,
+        data: UpdateLocationData,
+    ) -> anyhow::Result<location::Data> {
+        self.db.require_writable_location(&id).await?;
+        if let Some(duck_id) = &data.duck_id {
+            self.db.require_writable_duck(duck_id).await?;
+        }
+        let before = self.db.get_location(id.clone()).await?;
+        let rsp = self.db.update_location(id.clone(), data).await?;
+        self.record(
+            EntityType::Location,
+            Some(id),
+            to_json(before),
+            to_json(&rsp),
+        )
+        .await;
+        Ok(rsp)
+    }
+
+    pub async fn restore_location(&self, id: String) -> anyhow::Result<location::Data> {
+        self.db.require_writable_location(&id).await?;
+        let rsp = self.db.restore_location(id.clone()).await?;
+        self.record(EntityType::Location, Some(id), None, to_json(&rsp))
+            .await;
+        Ok(rsp)
+    }
+
+    pub async fn delete_location(&self, id: String) -> anyhow::Result<location::Data> {
+        self.db.require_writable_location(&id).await?;
+        let before = self.db.get_location(id.clone()).await?;
+        let rsp = self.db.delete_location(id.clone()).await?;
+        self.record(EntityType::Location, Some(id), to_json(before), None)
+            .await;
+        Ok(rsp)
+    }
+
+    pub async fn delete_all_locations(&self) -> anyhow::Result<i64> {
+        let before = self.db.get_all_locations().await?;
+        let rsp = self.db.delete_all_locations().await?;
+        self.record(EntityType::Location, None, to_json(before), None)
+            .await;
+        Ok(rsp)
+    }
+
+    /// Apply a plan of `DB::plan_coordinate_migration`, returning the number of locations changed.
+    pub async fn migrate_coordinates(&self, plan: &CoordinateMigration) -> anyhow::Result<usize> {
+        for change in &plan.changes {
+            let rsp = self
+                .db
+                .update_location(
+                    change.id.clone(),
+                    UpdateLocationData::coordinate(change.after),
+                )
+                .await?;
+            self.record(
+                EntityType::Location,
+                Some(change.id.clone()),
+                Some(change.before.clone()),
+                to_json(&rsp),
+            )
+            .await;
+        }
+        Ok(plan.changes.len())
+    }
+
+    // rankings
+
+    pub async fn renumber_rankings(&self) -> anyhow::Result<i64> {
+        let before = self.db.get_all_rankings().await?;
+        let rsp = self.db.renumber_rankings().await?;
+        self.db.clear_leaderboard_cache(None).await?;
+        let after = self.db.get_all_rankings().await?;
+        self.record(EntityType::Ranking, None, to_json(before), to_json(after))
+            .await;
+        Ok(rsp)
+    }
+
+    pub async fn delete_all_rankings(&self) -> anyhow::Result<i64> {
+        let before = self.db.get_all_rankings().await?;
+        let rsp = self.db.delete_all_rankings().await?;
+        self.db.clear_leaderboard_cache(None).await?;
+        self.record(EntityType::Ranking, None, to_json(before), None)
+            .await;
+        Ok(rsp)
+    }
+
+    // completion rules
+
+    /// Recorded under the id of the season, if any.
+    pub async fn set_completion_rules(
+        &self,
+        season_id: Option<String>,
+        team: bool,
+        rules: Vec<CompletionRule>,
+    ) -> anyhow::Result<Vec<CompletionRule>> {
+        self.db.require_writable(season_id.as_deref()).await?;
+        let before = self
+            .db
+            .get_completion_rules(season_id.clone(), team)
+            .await?;
+        let rsp = self
+            .db
+            .set_completion_rules(season_id.clone(), team, rules)
+            .await?;
+        self.record(
+            EntityType::CompletionRule,
+            season_id,
+            to_json(before),
+            to_json(&rsp),
+        )
+        .await;
+        Ok(rsp)
+    }
+
+    // seasons
+
+    pub async fn create_season(&self, data: NewSeasonData) -> anyhow::Result<season::Data> {
+        let rsp = self.db.create_season(data).await?;
+        self.record(
+            EntityType::Season,
+            Some(rsp.id.clone()),
+            None,
+            to_json(&rsp),
+        )
+        .await;
+        Ok(rsp)
+    }
+
+    pub async fn update_season(
+        &self,
+        id: String,
+        data: UpdateSeasonData,
+    ) -> anyhow::Result<season::Data> {
+        self.db.require_writable(Some(&id)).await?;
+        let before = self.db.get_season(id.clone()).await?;
+        let rsp = self.db.update_season(id.clone(), data).await?;
+        self.record(EntityType::Season, Some(id), to_json(before), to_json(&rsp))
+            .await;
+        Ok(rsp)
+    }
+
+    pub async fn archive_season(&self, id: String) -> anyhow::Result<season::Data> {
+        self.db.require_writable(Some(&id)).await?;
+        let before = self.db.get_season(id.clone()).await?;
+        let rsp = self.db.archive_season(id.clone()).await?;
+        self.record(EntityType::Season, Some(id), to_json(before), to_json(&rsp))
+            .await;
+        Ok(rsp)
+    }
+
+    // achievements
+
+    pub async fn create_achievement(
+        &self,
+        data: NewAchievementData,
+    ) -> anyhow::Result<achievement::Data> {
+        let rsp = self.db.create_achievement(data).await?;
+        self.record(
+            EntityType::Achievement,
+            Some(rsp.id.clone()),
+            None,
+            to_json(&rsp),
+        )
+        .await;
+        Ok(rsp)
+    }
+
+    pub async fn update_achievement(
+        &self,
+        id: String,
+        data: UpdateAchievementData,
+    ) -> anyhow::Result<achievement::Data> {
+        let before = self.db.get_achievement(id.clone()).await?;
+        let rsp = self.db.update_achievement(id.clone(), data).await?;
+        self.record(
+            EntityType::Achievement,
+            Some(id),
+            to_json(before),
+            to_json(&rsp),
+        )
+        .await;
+        Ok(rsp)
+    }
+
+    pub async fn delete_achievement(&self, id: String) -> anyhow::Result<achievement::Data> {
+        let before = self.db.get_achievement(id.clone()).await?;
+        let rsp = self.db.delete_achievement(id.clone()).await?;
+        self.record(EntityType::Achievement, Some(id), to_json(before), None)
+            .await;
+        Ok(rsp)
+    }
+
+    // admin accounts
+
+    /// Token hashes are not recorded.
+    pub async fn create_admin(
+        &self,
+        name: String,
+        role: String,
+        token_hash: String,
+    ) -> anyhow::Result<admin_account::Data> {
+        let rsp = self.db.create_admin(name, role, token_hash).await?;
+        self.record(
+            EntityType::AdminAccount,
+            Some(rsp.name.clone()),
+            None,
+            to_json(rsp.info()),
+        )
+        .await;
+        Ok(rsp)
+    }
+
+    pub async fn reset_admin_token(
+        &self,
+        name: String,
+        token_hash: String,
+    ) -> anyhow::Result<admin_account::Data> {
+        let before = self.admin_info(&name).await?;
+        let rsp = self.db.reset_admin_token(name.clone(), token_hash).await?;
+        self.record(
+            EntityType::AdminAccount,
+            Some(name),
+            to_json(before),
+            to_json(rsp.info()),
+        )
+        .await;
+        Ok(rsp)
+    }
+
+    pub async fn revoke_admin(&self, name: String) -> anyhow::Result<admin_account::Data> {
+        let before = self.admin_info(&name).await?;
+        let rsp = self.db.revoke_admin(name.clone()).await?;
+        self.record(
+            EntityType::AdminAccount,
+            Some(name),
+            to_json(before),
+            to_json(rsp.info()),
+        )
+        .await;
+        Ok(rsp)
+    }
+
+    async fn admin_info(&self, name: &str) -> anyhow::Result<Option<admin_account_info::Data>> {
+        Ok(self
+            .db
+            .get_all_admins()
+            .await?
+            .into_iter()
+            .find(|a| a.name == name))
+    }
+
+    // snapshots
+
+    pub async fn restore_snapshot(&self, id: String) -> anyhow::Result<i64> {
+        let rsp = self.db.restore_snapshot(id.clone()).await?;
+        self.record(EntityType::Snapshot, Some(id), None, None)
+            .await;
+        Ok(rsp)
+    }
+
+    // archives
+
+    /// The archive itself is not recorded, only the number of documents imported.
+    pub async fn import_archive(
+        &self,
+        archive: Archive,
+        mode: ImportMode,
+    ) -> anyhow::Result<ImportReport> {
+        let rsp = self.db.import_archive(archive, mode).await?;
+        self.record(EntityType::Archive, None, None, to_json(&rsp))
+            .await;
+        Ok(rsp)
+    }
+
+    // duck codes
+
+    /// Secrets are not recorded.
+    pub async fn new_qr_key(&self) -> anyhow::Result<qr_key::Data> {
+        let rsp = self.db.new_qr_key().await?;
+        self.record(
+            EntityType::QrKey,
+            Some(rsp.kid.clone()),
+            None,
+            to_json(rsp.info()),
+        )
+        .await;
+        Ok(rsp)
+    }
+
+    pub async fn retire_qr_key(&self, kid: String) -> anyhow::Result<qr_key::Data> {
+        let rsp = self.db.retire_qr_key(kid.clone()).await?;
+        self.record(EntityType::QrKey, Some(kid), None, to_json(rsp.info()))
+            .await;
+        Ok(rsp)
+    }
+
+    pub async fn bump_duck_code_epoch(&self, duck_id: String) -> anyhow::Result<i32> {
+        let before = self.db.get_duck_code_epoch(duck_id.clone()).await?;
+        let rsp = self.db.bump_duck_code_epoch(duck_id.clone()).await?;
+        self.record(
+            EntityType::DuckCode,
+            Some(duck_id),
+            to_json(before),
+            to_json(rsp),
+        )
+        .await;
+        Ok(rsp)
+    }
+}
+
+fn to_json<T: Serialize>(value: T) -> Option<Value> {
+    serde_json::to_value(value).ok()
+}
@@ -0,0 +1,414 @@
+//! challenges players answer before the find of a duck counts
+use crate::db_api::{Bilingual, PrismaDB, DB};
+use crate::prisma::{challenge_attempt, duck, pending_discovery};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use prisma_client_rust::chrono::{DateTime, Duration, FixedOffset, Utc};
+use prisma_client_rust::Direction;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// wrong answers allowed for one duck within `ATTEMPT_WINDOW_MINUTES`,
+/// fewer for multiple choices (see [`Challenge::max_wrong_attempts`])
+pub const MAX_WRONG_ATTEMPTS: usize = 3;
+const ATTEMPT_WINDOW_MINUTES: i64 = 10;
+/// a scan must be answered within this time, or scanned again
+const PENDING_DISCOVERY_MINUTES: i64 = 60;
+
+// Finding a duck with a challenge records a pending discovery. The find only
+// counts, and goes to the history of the player, once the answer is accepted.
+
+/// question of a duck, with its accepted answers
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Challenge {
+    /// `answer` is the index of the right choice
+    MultipleChoice {
+        question: Bilingual,
+        choices: Vec<Bilingual>,
+        answer: usize,
+    },
+    /// any of `answers`, in either language
+    FreeText {
+        question: Bilingual,
+        answers: Vec<FreeTextAnswer>,
+    },
+    /// a keyword of the sign of the related exhibit
+    SignKeyword {
+        question: Bilingual,
+        keyword: String,
+    },
+}
+
+/// accepted answer of a free-text challenge
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum FreeTextAnswer {
+    Bilingual(Bilingual),
+    /// one text for both languages, as older versions stored answers
+    Text(String),
+}
+
+impl FreeTextAnswer {
+    fn texts(&self) -> [&str; 2] {
+        match self {
+            FreeTextAnswer::Bilingual(answer) => [&answer.en, &answer.cn],
+            FreeTextAnswer::Text(text) => [text, text],
+        }
+    }
+}
+
+/// challenge as players see it, without the answers
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChallengeQuestion {
+    MultipleChoice {
+        question: Bilingual,
+        choices: Vec<Bilingual>,
+    },
+    FreeText {
+        question: Bilingual,
+    },
+    SignKeyword {
+        question: Bilingual,
+    },
+}
+
+/// answer of a player, the index of a choice or a text
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum Answer {
+    Choice(usize),
+    Text(String),
+}
+
+/// texts compared without case and extra whitespace
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+impl Challenge {
+    /// reason for rejecting the challenge, if any
+    pub fn check(&self) -> Result<(), &'static str> {
+        match self {
+            Challenge::MultipleChoice { choices, .. } if choices.len() < 2 => {
+                Err("at least two choices are needed")
+            }
+            Challenge::MultipleChoice {
+                choices, answer, ..
+            } if *answer >= choices.len() => Err("answer is not one of the choices"),
+            Challenge::FreeText { answers, .. }
+                if answers.is_empty()
+                    || answers
+                        .iter()
+                        .any(|a| a.texts().iter().any(|t| normalize(t).is_empty())) =>
+            {
+                Err("empty answer")
+            }
+            Challenge::SignKeyword { keyword, .. } if normalize(keyword).is_empty() => {
+                Err("empty keyword")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn question(&self) -> ChallengeQuestion {
+        match self {
+            Challenge::MultipleChoice {
+                question, choices, ..
+            } => ChallengeQuestion::MultipleChoice {
+                question: question.clone(),
+                choices: choices.clone(),
+            },
+            Challenge::FreeText { question, .. } => ChallengeQuestion::FreeText {
+                question: question.clone(),
+            },
+            Challenge::SignKeyword { question, .. } => ChallengeQuestion::SignKeyword {
+                question: question.clone(),
+            },
+        }
+    }
+
+    /// whether an answer is right
+    pub fn accepts(&self, answer: &Answer) -> bool {
+        match (self, answer) {
+            (Challenge::MultipleChoice { answer: right, .. }, Answer::Choice(choice)) => {
+                choice == right
+            }
+            (Challenge::FreeText { answers, .. }, Answer::Text(text)) => {
+                let text = normalize(text);
+                answers
+                    .iter()
+                    .any(|a| a.texts().iter().any(|t| normalize(t) == text))
+            }
+            (Challenge::SignKeyword { keyword, .. }, Answer::Text(text)) => {
+                normalize(keyword) == normalize(text)
+            }
+            _ => false,
+        }
+    }
+
+    /// Wrong answers allowed within `ATTEMPT_WINDOW_MINUTES`, at most half of the choices,
+    /// so that guessing through them takes longer than the window.
+    pub fn max_wrong_attempts(&self) -> usize {
+        match self {
+            Challenge::MultipleChoice { choices, .. } => {
+                (choices.len() / 2).clamp(1, MAX_WRONG_ATTEMPTS)
+            }
+            _ => MAX_WRONG_ATTEMPTS,
+        }
+    }
+}
+
+/// read the challenge of a duck
+pub(crate) fn parse_challenge(challenge: &Option<Value>) -> anyhow::Result<Option<Challenge>> {
+    challenge
+        .as_ref()
+        .map(|c| serde_json::from_value(c.clone()))
+        .transpose()
+        .map_err(|e| anyhow!("invalid challenge: {}", e))
+}
+
+/// response struct for the answers to the challenge of a duck
+#[derive(Serialize)]
+pub struct ChallengeResults {
+    pub attempts: usize,
+    pub correct: usize,
+    /// players who answered
+    pub players: usize,
+    /// players who answered right
+    pub solved: usize,
+}
+
+#[async_trait]
+pub trait ChallengeStore {
+    // C/U
+    /// Record the scan of a duck, or renew the one already recorded.
+    async fn upsert_pending_discovery(
+        &self,
+        wechat_openid: String,
+        duck_id: String,
+    ) -> anyhow::Result<pending_discovery::Data>;
+    // C
+    async fn record_challenge_attempt(
+        &self,
+        wechat_openid: String,
+        duck_id: String,
+        answer: Answer,
+        correct: bool,
+    ) -> anyhow::Result<challenge_attempt::Data>;
+    // R
+    async fn get_pending_discovery(
+        &self,
+        wechat_openid: String,
+        duck_id: String,
+    ) -> anyhow::Result<Option<pending_discovery::Data>>;
+    /// answers to the challenge of a duck, by one player or by all of them, oldest first
+    async fn get_challenge_attempts(
+        &self,
+        duck_id: String,
+        wechat_openid: Option<String>,
+    ) -> anyhow::Result<Vec<challenge_attempt::Data>>;
+    // U
+    /// `None` removes the challenge, pending discoveries are kept
+    async fn set_duck_challenge(
+        &self,
+        duck_id: String,
+        challenge: Option<Challenge>,
+    ) -> anyhow::Result<duck::Data>;
+    // D
+    async fn delete_pending_discovery(
+        &self,
+        wechat_openid: String,
+        duck_id: String,
+    ) -> anyhow::Result<()>;
+}
+
+#[async_trait]
+impl ChallengeStore for PrismaDB {
+    // C/U
+
+    async fn upsert_pending_discovery(
+        &self,
+        wechat_openid: String,
+        duck_id: String,
+    ) -> anyhow::Result<pending_discovery::Data> {
+        let data = self
+            .0
+            .pending_discovery()
+            .upsert(
+                pending_discovery::UniqueWhereParam::WechatOpenIdDuckIdEquals(
+                    wechat_openid.clone(),
+                    duck_id.clone(),
+                ),
+                (wechat_openid, duck_id, vec![]),
+                vec![pending_discovery::SetParam::SetCreatedAt(Utc::now().into())],
+            )
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    // C
+
+    async fn record_challenge_attempt(
+        &self,
+        wechat_openid: String,
+        duck_id: String,
+        answer: Answer,
+        correct: bool,
+    ) -> anyhow::Result<challenge_attempt::Data> {
+        let data = self
+            .0
+            .challenge_attempt()
+            .create(
+                wechat_openid,
+                duck_id,
+                serde_json::to_value(answer)?,
+                correct,
+                vec![],
+            )
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    // R
+
+    async fn get_pending_discovery(
+        &self,
+        wechat_openid: String,
+        duck_id: String,
+    ) -> anyhow::Result<Option<pending_discovery::Data>> {
+        let data = self
+            .0
+            .pending_discovery()
+            .find_unique(
+                pending_discovery::UniqueWhereParam::WechatOpenIdDuckIdEquals(
+                    wechat_openid,
+                    duck_id,
+                ),
+            )
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    async fn get_challenge_attempts(
+        &self,
+        duck_id: String,
+        wechat_openid: Option<String>,
+    ) -> anyhow::Result<Vec<challenge_attempt::Data>> {
+        let mut filters = vec![challenge_attempt::duck_id::equals(duck_id)];
+        if let Some(wechat_openid) = wechat_openid {
+            filters.push(challenge_attempt::wechat_open_id::equals(wechat_openid));
+        }
+        let data = self
+            .0
+            .challenge_attempt()
+            .find_many(filters)
+            .order_by(challenge_attempt::id::order(Direction::Asc))
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    // U
+
+    async fn set_duck_challenge(
+        &self,
+        duck_id: String,
+        challenge: Option<Challenge>,
+    ) -> anyhow::Result<duck::Data> {
+        let challenge = challenge.map(serde_json::to_value).transpose()?;
+        let data = self
+            .0
+            .duck()
+            .update(
+                duck::UniqueWhereParam::IdEquals(duck_id),
+                vec![duck::SetParam::SetChallenge(challenge)],
+            )
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    // D
+
+    async fn delete_pending_discovery(
+        &self,
+        wechat_openid: String,
+        duck_id: String,
+    ) -> anyhow::Result<()> {
+        self.0
+            .pending_discovery()
+            .delete_many(vec![
+                pending_discovery::wechat_open_id::equals(wechat_openid),
+                pending_discovery::duck_id::equals(duck_id),
+            ])
+            .exec()
+            .await?;
+        Ok(())
+    }
+}
+
+impl pending_discovery::Data {
+    /// whether the scan is too old to be answered
+    pub fn is_expired(&self) -> bool {
+        Utc::now() - Duration::minutes(PENDING_DISCOVERY_MINUTES) > self.created_at
+    }
+}
+
+impl DB {
+    /// Challenge of a duck, `None` if the duck has none.
+    ///
+    /// Fails if the duck is missing or in the trash.
+    pub async fn challenge_of(&self, duck_id: &str) -> anyhow::Result<Option<Challenge>> {
+        let duck = self
+            .get_duck(duck_id.to_string())
+            .await?
+            .ok_or_else(|| anyhow!("duck {} not found", duck_id))?;
+        parse_challenge(&duck.challenge)
+    }
+
+    /// Wrong answers a player may still give to the challenge of a duck.
+    pub async fn attempts_left(
+        &self,
+        wechat_openid: &str,
+        duck_id: &str,
+        challenge: &Challenge,
+    ) -> anyhow::Result<usize> {
+        let since: DateTime<FixedOffset> =
+            (Utc::now() - Duration::minutes(ATTEMPT_WINDOW_MINUTES)).into();
+        let wrong = self
+            .get_challenge_attempts(duck_id.to_string(), Some(wechat_openid.to_string()))
+            .await?
+            .iter()
+            .filter(|a| !a.correct && a.created_at > since)
+            .count();
+        Ok(challenge.max_wrong_attempts().saturating_sub(wrong))
+    }
+
+    /// Summary of the answers to the challenge of a duck.
+    pub async fn challenge_results(&self, duck_id: &str) -> anyhow::Result<ChallengeResults> {
+        let attempts = self
+            .get_challenge_attempts(duck_id.to_string(), None)
+            .await?;
+        let players: HashSet<&str> = attempts.iter().map(|a| a.wechat_open_id.as_str()).collect();
+        let solved: HashSet<&str> = attempts
+            .iter()
+            .filter(|a| a.correct)
+            .map(|a| a.wechat_open_id.as_str())
+            .collect();
+        Ok(ChallengeResults {
+            attempts: attempts.len(),
+            correct: attempts.iter().filter(|a| a.correct).count(),
+            players: players.len(),
+            solved: solved.len(),
+        })
+    }
+}
@@ -0,0 +1,298 @@
+//! signed duck codes, so that ducks are only found by scanning their posters
+use crate::admin_auth::gen_token;
+use crate::db_api::{PrismaDB, DB};
+use crate::prisma::{duck_code, qr_key};
+use crate::SERVER_CONFIG;
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{anyhow, bail};
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use prisma_client_rust::chrono::Utc;
+use prisma_client_rust::Direction;
+use rand::distributions::{Alphanumeric, Distribution};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::Sha256;
+
+const KID_LENGTH: usize = 8;
+/// kid of the key created at startup, on which starting instances agree
+pub(crate) const FIRST_KID: &str = "first";
+/// bytes of the HMAC kept in a code, short enough for a dense QR code
+const MAC_LENGTH: usize = 16;
+/// prefix of the secrets stored encrypted with the configured `qr_secret_key`
+const SEALED_PREFIX: &str = "aes256gcm:";
+const NONCE_LENGTH: usize = 12;
+
+// response struct for listing keys, without secrets
+qr_key::select! { qr_key_info {
+    kid
+    created_at
+    retired_at
+}}
+
+#[async_trait]
+pub trait CodeStore {
+    // C
+    async fn create_qr_key(&self, kid: String, secret: String) -> anyhow::Result<qr_key::Data>;
+    // C/U
+    /// Create a key, or return the key already stored under this kid.
+    async fn upsert_qr_key(&self, kid: String, secret: String) -> anyhow::Result<qr_key::Data>;
+    // R
+    /// newest first, retired keys included
+    async fn get_qr_keys(&self) -> anyhow::Result<Vec<qr_key::Data>>;
+    /// 0 until the codes of the duck are invalidated
+    async fn get_duck_code_epoch(&self, duck_id: String) -> anyhow::Result<i32>;
+    // U
+    /// Increase the epoch of a duck, returning the new epoch.
+    async fn bump_duck_code_epoch(&self, duck_id: String) -> anyhow::Result<i32>;
+    async fn retire_qr_key(&self, kid: String) -> anyhow::Result<qr_key::Data>;
+    /// Replace how the secret of a key is stored, the secret itself must not change.
+    async fn set_qr_key_secret(&self, kid: String, secret: String) -> anyhow::Result<qr_key::Data>;
+}
+
+#[async_trait]
+impl CodeStore for PrismaDB {
+    // C
+
+    async fn create_qr_key(&self, kid: String, secret: String) -> anyhow::Result<qr_key::Data> {
+        let data = self.0.qr_key().create(kid, secret, vec![]).exec().await?;
+        Ok(data)
+    }
+
+    // C/U
+
+    async fn upsert_qr_key(&self, kid: String, secret: String) -> anyhow::Result<qr_key::Data> {
+        // empty update: never replace the secret of a key signing codes
+        let data = self
+            .0
+            .qr_key()
+            .upsert(
+                qr_key::UniqueWhereParam::KidEquals(kid.clone()),
+                (kid, secret, vec![]),
+                vec![],
+            )
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    // R
+
+    async fn get_qr_keys(&self) -> anyhow::Result<Vec<qr_key::Data>> {
+        let data = self
+            .0
+            .qr_key()
+            .find_many(vec![])
+            .order_by(qr_key::created_at::order(Direction::Desc))
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    async fn get_duck_code_epoch(&self, duck_id: String) -> anyhow::Result<i32> {
+        let data = self
+            .0
+            .duck_code()
+            .find_unique(duck_code::UniqueWhereParam::DuckIdEquals(duck_id))
+            .exec()
+            .await?;
+        Ok(data.map(|d| d.epoch).unwrap_or(0))
+    }
+
+    // U
+
+    async fn bump_duck_code_epoch(&self, duck_id: String) -> anyhow::Result<i32> {
+        let data = self
+            .0
+            .duck_code()
+            .upsert(
+                duck_code::UniqueWhereParam::DuckIdEquals(duck_id.clone()),
+                (duck_id, 1, vec![]),
+                vec![duck_code::epoch::increment(1)],
+            )
+            .exec()
+            .await?;
+        Ok(data.epoch)
+    }
+
+    async fn retire_qr_key(&self, kid: String) -> anyhow::Result<qr_key::Data> {
+        let data = self
+            .0
+            .qr_key()
+            .update(
+                qr_key::UniqueWhereParam::KidEquals(kid),
+                vec![qr_key::SetParam::SetRetiredAt(Some(Utc::now().into()))],
+            )
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    async fn set_qr_key_secret(&self, kid: String, secret: String) -> anyhow::Result<qr_key::Data> {
+        let data = self
+            .0
+            .qr_key()
+            .update(
+                qr_key::UniqueWhereParam::KidEquals(kid),
+                vec![qr_key::SetParam::SetSecret(secret)],
+            )
+            .exec()
+            .await?;
+        Ok(data)
+    }
+}
+
+impl DB {
+    /// Create the first key at startup, unless keys already exist,
+    /// and encrypt the secrets stored before a `qr_secret_key` was configured.
+    pub async fn ensure_qr_key(&self) -> anyhow::Result<()> {
+        let keys = self.get_qr_keys().await?;
+        if keys.is_empty() {
+            self.upsert_qr_key(FIRST_KID.to_string(), seal(&gen_token())?)
+                .await?;
+        }
+        if secret_cipher()?.is_some() {
+            for key in keys {
+                if !key.secret.starts_with(SEALED_PREFIX) {
+                    self.set_qr_key_secret(key.kid, seal(&key.secret)?).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sign a code for a duck, as `KID.EPOCH.MAC`, with the newest key.
+    ///
+    /// Fails once every key is retired, until a new one is added.
+    pub async fn sign_duck_code(&self, duck_id: &str) -> anyhow::Result<String> {
+        let key = self
+            .get_qr_keys()
+            .await?
+            .into_iter()
+            .find(is_active)
+            .ok_or_else(|| anyhow!("no active QR key"))?;
+        let epoch = self.get_duck_code_epoch(duck_id.to_string()).await?;
+        let mac = mac(&open(&key.secret)?, duck_id, epoch)
+            .finalize()
+            .into_bytes();
+        Ok(format!(
+            "{}.{}.{}",
+            key.kid,
+            epoch,
+            base64::encode_config(&mac[..MAC_LENGTH], base64::URL_SAFE_NO_PAD)
+        ))
+    }
+
+    /// Check that a code was signed for this duck, by a key which is not retired,
+    /// and in the current epoch of the duck.
+    pub async fn verify_duck_code(&self, duck_id: &str, code: &str) -> anyhow::Result<bool> {
+        let mut parts = code.splitn(3, '.');
+        let (kid, epoch, tag) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(kid), Some(epoch), Some(tag)) => (kid, epoch, tag),
+            _ => return Ok(false),
+        };
+        let (epoch, tag) = match (
+            epoch.parse::<i32>(),
+            base64::decode_config(tag, base64::URL_SAFE_NO_PAD),
+        ) {
+            (Ok(epoch), Ok(tag)) if tag.len() == MAC_LENGTH => (epoch, tag),
+            _ => return Ok(false),
+        };
+        let key = match self
+            .get_qr_keys()
+            .await?
+            .into_iter()
+            .find(|k| k.kid == kid && is_active(k))
+        {
+            Some(key) => key,
+            None => return Ok(false),
+        };
+        if epoch != self.get_duck_code_epoch(duck_id.to_string()).await? {
+            return Ok(false);
+        }
+        // constant-time comparison
+        Ok(mac(&open(&key.secret)?, duck_id, epoch)
+            .verify_truncated_left(&tag)
+            .is_ok())
+    }
+
+    /// Add a key, which signs the codes from now on.
+    pub async fn new_qr_key(&self) -> anyhow::Result<qr_key::Data> {
+        let kid = String::from_iter(
+            Alphanumeric
+                .sample_iter(OsRng::default())
+                .take(KID_LENGTH)
+                .map(|u| u as char),
+        );
+        self.create_qr_key(kid, seal(&gen_token())?).await
+    }
+}
+
+impl qr_key::Data {
+    pub fn info(&self) -> qr_key_info::Data {
+        qr_key_info::Data {
+            kid: self.kid.clone(),
+            created_at: self.created_at,
+            retired_at: self.retired_at,
+        }
+    }
+}
+
+fn is_active(key: &qr_key::Data) -> bool {
+    key.retired_at.is_none()
+}
+
+/// cipher of the configured `qr_secret_key`, if any
+fn secret_cipher() -> anyhow::Result<Option<Aes256Gcm>> {
+    let key = match &SERVER_CONFIG.qr_secret_key {
+        Some(key) => base64::decode(key)?,
+        None => return Ok(None),
+    };
+    let cipher = Aes256Gcm::new_from_slice(&key)
+        .map_err(|_| anyhow!("qr_secret_key must be 32 bytes in base64"))?;
+    Ok(Some(cipher))
+}
+
+/// Encrypt a secret to be stored, unless no `qr_secret_key` is configured.
+fn seal(secret: &str) -> anyhow::Result<String> {
+    let cipher = match secret_cipher()? {
+        Some(cipher) => cipher,
+        None => return Ok(secret.to_string()),
+    };
+    let mut nonce = [0u8; NONCE_LENGTH];
+    OsRng.fill_bytes(&mut nonce);
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), secret.as_bytes())
+        .map_err(|_| anyhow!("error encrypting qr secret"))?;
+    Ok(format!(
+        "{}{}",
+        SEALED_PREFIX,
+        base64::encode([&nonce[..], &ciphertext].concat())
+    ))
+}
+
+/// Decrypt a stored secret, secrets stored without a `qr_secret_key` being plain text.
+fn open(stored: &str) -> anyhow::Result<String> {
+    let sealed = match stored.strip_prefix(SEALED_PREFIX) {
+        Some(sealed) => base64::decode(sealed)?,
+        None => return Ok(stored.to_string()),
+    };
+    let cipher = secret_cipher()?
+        .ok_or_else(|| anyhow!("qr secrets are encrypted but no qr_secret_key is configured"))?;
+    if sealed.len() < NONCE_LENGTH {
+        bail!("encrypted qr secret too short");
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LENGTH);
+    let secret = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow!("error decrypting qr secret, with another qr_secret_key?"))?;
+    Ok(String::from_utf8(secret)?)
+}
+
+fn mac(secret: &str, duck_id: &str, epoch: i32) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts any key length");
+    mac.update(format!("{}.{}", duck_id, epoch).as_bytes());
+    mac
+}
@@ -0,0 +1,335 @@
+//! rules a player must meet to finish the game and be ranked
+use crate::db_api::events::GameEvent;
+use crate::db_api::locks::with_lock;
+use crate::db_api::public::user_info;
+use crate::db_api::story::StoryGraph;
+use crate::db_api::{null_or_unset, PrismaDB, DB};
+use crate::prisma::{completion_rule, duck};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use prisma_client_rust::Direction;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// number of ducks to find while no rule is set
+pub const DEFAULT_DUCK_COUNT: usize = 10;
+/// lock held while replacing the rules of a scope, so that replacements never interleave
+const COMPLETION_RULES_LOCK: &str = "completion_rules";
+
+/// a rule players must meet, along with every other rule
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CompletionRule {
+    /// at least `count` ducks
+    DuckCount { count: usize },
+    /// every duck with a topic, named in English or Chinese
+    Topic { topic: String },
+    /// the duck of every exhibit
+    EveryExhibit,
+    /// a duck, with every duck it needs in the story
+    StoryChain {
+        #[serde(rename = "duckId")]
+        duck_id: String,
+    },
+}
+
+impl CompletionRule {
+    /// reason for rejecting the rule, if any
+    pub fn check(&self) -> Result<(), &'static str> {
+        match self {
+            CompletionRule::DuckCount { count: 0 } => Err("duck count must be positive"),
+            CompletionRule::Topic { topic } if topic.is_empty() => Err("empty topic"),
+            CompletionRule::StoryChain { duck_id } if duck_id.is_empty() => Err("empty duck id"),
+            _ => Ok(()),
+        }
+    }
+}
+
+// facts about ducks needed to evaluate the rules
+duck::select! { completion_duck {
+    id
+    topics
+    related_exhibit: select {
+        deleted_at
+    }
+    season_id
+    deleted_at
+}}
+
+#[async_trait]
+pub trait CompletionStore {
+    // R
+    /// rules of players, or of teams, of a season or of the main game, in the order they were set
+    async fn get_completion_rules(
+        &self,
+        season_id: Option<String>,
+        team: bool,
+    ) -> anyhow::Result<Vec<CompletionRule>>;
+    /// ducks in the trash are returned
+    async fn get_completion_ducks(&self) -> anyhow::Result<Vec<completion_duck::Data>>;
+    // U
+    /// Replace every rule of players, or of teams, of a season or of the main game,
+    /// returning the new rules.
+    async fn set_completion_rules(
+        &self,
+        season_id: Option<String>,
+        team: bool,
+        rules: Vec<CompletionRule>,
+    ) -> anyhow::Result<Vec<CompletionRule>>;
+}
+
+/// read a stored rule
+pub(crate) fn parse_rule(data: &completion_rule::Data) -> anyhow::Result<CompletionRule> {
+    serde_json::from_value(data.rule.clone())
+        .map_err(|e| anyhow!("invalid completion rule {}: {}", data.id, e))
+}
+
+/// filter of the stored rules of players, or of teams, of a season or of the main game
+fn rule_scope(season_id: Option<String>, team_rules: bool) -> Vec<completion_rule::WhereParam> {
+    let season = match season_id {
+        Some(season_id) => completion_rule::season_id::equals(Some(season_id)),
+        None => null_or_unset!(completion_rule::season_id),
+    };
+    let team = completion_rule::team::equals(Some(true));
+    let team = if team_rules {
+        team
+    } else {
+        completion_rule::WhereParam::Not(vec![team])
+    };
+    vec![season, team]
+}
+
+#[async_trait]
+impl CompletionStore for PrismaDB {
+    // R
+
+    async fn get_completion_rules(
+        &self,
+        season_id: Option<String>,
+        team: bool,
+    ) -> anyhow::Result<Vec<CompletionRule>> {
+        let data = self
+            .0
+            .completion_rule()
+            .find_many(rule_scope(season_id, team))
+            .order_by(completion_rule::id::order(Direction::Asc))
+            .exec()
+            .await?;
+        data.iter().map(parse_rule).collect()
+    }
+
+    async fn get_completion_ducks(&self) -> anyhow::Result<Vec<completion_duck::Data>> {
+        let data = self
+            .0
+            .duck()
+            .find_many(vec![])
+            .select(completion_duck::select())
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    // U
+
+    async fn set_completion_rules(
+        &self,
+        season_id: Option<String>,
+        team: bool,
+        rules: Vec<CompletionRule>,
+    ) -> anyhow::Result<Vec<CompletionRule>> {
+        let mut many_data = Vec::with_capacity(rules.len());
+        for rule in &rules {
+            many_data.push((
+                serde_json::to_value(rule)?,
+                vec![
+                    completion_rule::SetParam::SetSeasonId(season_id.clone()),
+                    completion_rule::SetParam::SetTeam(team.then_some(true)),
+                ],
+            ));
+        }
+        let lock = format!(
+            "{}:{}:{}",
+            COMPLETION_RULES_LOCK,
+            season_id.as_deref().unwrap_or("main"),
+            if team { "teams" } else { "players" }
+        );
+        with_lock(self, &lock, async {
+            let old_ids: Vec<String> = self
+                .0
+                .completion_rule()
+                .find_many(rule_scope(season_id, team))
+                .exec()
+                .await?
+                .into_iter()
+                .map(|r| r.id)
+                .collect();
+            // the new rules come first, so that players are never judged by the
+            // default duck count in between
+            self.0
+                .completion_rule()
+                .create_many(many_data)
+                .exec()
+                .await?;
+            self.0
+                .completion_rule()
+                .delete_many(vec![completion_rule::id::in_vec(old_ids)])
+                .exec()
+                .await?;
+            anyhow::Ok(())
+        })
+        .await??;
+        Ok(rules)
+    }
+}
+
+impl DB {
+    /// Rules of players, or of teams, in effect for a season or for the main game,
+    /// the default duck count while none is set.
+    pub async fn completion_rules(
+        &self,
+        season_id: Option<&str>,
+        team: bool,
+    ) -> anyhow::Result<Vec<CompletionRule>> {
+        let rules = self
+            .get_completion_rules(season_id.map(str::to_string), team)
+            .await?;
+        if rules.is_empty() {
+            return Ok(vec![CompletionRule::DuckCount {
+                count: DEFAULT_DUCK_COUNT,
+            }]);
+        }
+        Ok(rules)
+    }
+
+    /// Whether a player meets every completion rule of a season, or of the main game,
+    /// counting the ducks of that scope only.
+    pub async fn is_complete(
+        &self,
+        user: &user_info::Data,
+        season_id: Option<&str>,
+    ) -> anyhow::Result<bool> {
+        let found: HashSet<&str> = user
+            .duck_history
+            .iter()
+            .filter(|h| h.duck.season_id.as_deref() == season_id)
+            .map(|h| h.duck.id.as_str())
+            .collect();
+        self.meets_completion_rules(&found, season_id, false).await
+    }
+
+    /// Whether the ducks found, by a player or by the members of a team,
+    /// meet every rule of players, or of teams, of a season or of the main game.
+    ///
+    /// Rules about ducks which do not exist, such as a topic no duck has, are never met.
+    pub(crate) async fn meets_completion_rules(
+        &self,
+        found: &HashSet<&str>,
+        season_id: Option<&str>,
+        team: bool,
+    ) -> anyhow::Result<bool> {
+        let ducks: Vec<completion_duck::Data> = self
+            .get_completion_ducks()
+            .await?
+            .into_iter()
+            .filter(|d| d.deleted_at.is_none() && d.season_id.as_deref() == season_id)
+            .collect();
+        // all of the ducks, and at least one
+        let all_found =
+            |ducks: Vec<&str>| !ducks.is_empty() && ducks.iter().all(|id| found.contains(id));
+        for rule in self.completion_rules(season_id, team).await? {
+            let met = match rule {
+                CompletionRule::DuckCount { count } => found.len() >= count,
+                CompletionRule::Topic { topic } => all_found(
+                    ducks
+                        .iter()
+                        .filter(|d| has_topic(d, &topic))
+                        .map(|d| d.id.as_str())
+                        .collect(),
+                ),
+                CompletionRule::EveryExhibit => all_found(
+                    ducks
+                        .iter()
+                        .filter(|d| {
+                            d.related_exhibit
+                                .as_ref()
+                                .map_or(false, |e| e.deleted_at.is_none())
+                        })
+                        .map(|d| d.id.as_str())
+                        .collect(),
+                ),
+                CompletionRule::StoryChain { duck_id } => {
+                    if !ducks.iter().any(|d| d.id == duck_id) {
+                        false
+                    } else {
+                        let graph = StoryGraph::new(self.get_story_nodes().await?);
+                        chain_of(&graph, &duck_id)
+                            .iter()
+                            .all(|id| found.contains(id))
+                    }
+                }
+            };
+            if !met {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Rank a player meeting every completion rule of a season, or of the main game,
+    /// unless already ranked there or the season is not running.
+    ///
+    /// `user` is scoped with `DB::scoped_user_info` first, so its ranking is the one of the scope.
+    /// Called on every request of the player, so a ranking which failed is retried.
+    pub async fn rank_if_complete(
+        &self,
+        user: &mut user_info::Data,
+        season_id: Option<&str>,
+    ) -> anyhow::Result<()> {
+        if user.ranking.is_some() || !self.is_complete(user, season_id).await? {
+            return Ok(());
+        }
+        // the leaderboard of a season is closed once it ends
+        if !self.is_running_season(season_id).await? {
+            return Ok(());
+        }
+        let wechat_id = user.wechat_open_id.clone();
+        let ranking = match season_id {
+            Some(season_id) => {
+                self.upsert_season_ranking(season_id.to_string(), wechat_id.clone())
+                    .await?
+                    .ranking
+            }
+            None => self.upsert_ranking(wechat_id.clone()).await?.ranking,
+        };
+        user.ranking = Some(user_info::ranking::Data { ranking });
+        let season_id = season_id.map(str::to_string);
+        self.clear_leaderboard_cache(season_id.clone()).await?;
+        self.notify(GameEvent::Ranked {
+            wechat_openid: wechat_id,
+            season_id: season_id.clone(),
+            ranking,
+        })
+        .await;
+        self.notify(GameEvent::LeaderboardChanged { season_id })
+            .await;
+        Ok(())
+    }
+}
+
+pub(crate) fn has_topic(duck: &completion_duck::Data, topic: &str) -> bool {
+    duck.topics.as_array().map_or(false, |topics| {
+        topics.iter().any(|t| t["en"] == topic || t["cn"] == topic)
+    })
+}
+
+/// a duck with every duck it needs, directly or not
+pub(crate) fn chain_of<'a>(graph: &'a StoryGraph, duck_id: &'a str) -> HashSet<&'a str> {
+    let mut chain = HashSet::new();
+    let mut stack = vec![duck_id];
+    while let Some(id) = stack.pop() {
+        if chain.insert(id) {
+            stack.extend(graph.prerequisites(id).iter().map(|p| p.as_str()));
+        }
+    }
+    chain
+}
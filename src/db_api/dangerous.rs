@@ -0,0 +1,505 @@
+//! two-phase bulk deletes, with snapshots to undo them
+use crate::db_api::{PrismaDB, DB};
+use crate::prisma::{duck, duck_history, exhibit, location, ranking, snapshot, user};
+use async_trait::async_trait;
+use prisma_client_rust::chrono::Utc;
+use prisma_client_rust::Direction;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::time::Duration;
+
+/// prefix of the redis keys of confirmation tokens
+const CONFIRMATION_KEY_PREFIX: &str = "cyberduck:confirmation:";
+
+/// bulk deletes which need a confirmation
+pub enum DangerousOp {
+    DeleteAllDucks,
+    DeleteAllExhibits,
+    DeleteAllLocations,
+    /// `user_id` is either the user id or the wechat openid
+    DeleteDuckHistory {
+        user_id: String,
+    },
+}
+
+impl DangerousOp {
+    /// what a confirmation token is valid for
+    pub fn scope(&self) -> String {
+        match self {
+            DangerousOp::DeleteAllDucks => "many-ducks".to_string(),
+            DangerousOp::DeleteAllExhibits => "many-exhibits".to_string(),
+            DangerousOp::DeleteAllLocations => "many-locations".to_string(),
+            DangerousOp::DeleteDuckHistory { user_id } => format!("duck-history:{}", user_id),
+        }
+    }
+}
+
+/// documents removed by a bulk delete, or left without a relation by it
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotData {
+    pub ducks: Vec<duck::Data>,
+    pub duck_history: Vec<duck_history::Data>,
+    pub exhibits: Vec<exhibit::Data>,
+    pub locations: Vec<location::Data>,
+}
+
+impl SnapshotData {
+    /// Hash of the documents, whatever order they were read in,
+    /// to tell whether they changed between a dry run and its confirmation.
+    pub fn digest(&self) -> anyhow::Result<String> {
+        let mut lines = vec![];
+        lines.extend(document_lines("duck", &self.ducks)?);
+        lines.extend(document_lines("duck_history", &self.duck_history)?);
+        lines.extend(document_lines("exhibit", &self.exhibits)?);
+        lines.extend(document_lines("location", &self.locations)?);
+        lines.sort_unstable();
+        Ok(base64::encode(Sha256::digest(lines.join("\n").as_bytes())))
+    }
+}
+
+fn document_lines<T: Serialize>(collection: &str, documents: &[T]) -> anyhow::Result<Vec<String>> {
+    documents
+        .iter()
+        .map(|d| Ok(format!("{} {}", collection, serde_json::to_string(d)?)))
+        .collect()
+}
+
+/// number of documents a bulk delete would remove or disconnect
+#[derive(Serialize)]
+pub struct DryRunReport {
+    pub ducks: usize,
+    pub duck_history: usize,
+    pub exhibits: usize,
+    pub locations: usize,
+    /// rankings of the players losing duck history, who keep the ranking earned
+    pub rankings: usize,
+}
+
+// response struct for listing snapshots, without the documents
+snapshot::select! { snapshot_info {
+    id
+    created_at
+    admin
+    route
+    restored_at
+}}
+
+#[async_trait]
+pub trait DangerousStore {
+    // C
+    /// Collect the documents a bulk delete would remove or disconnect.
+    async fn collect_snapshot(&self, op: &DangerousOp) -> anyhow::Result<SnapshotData>;
+    async fn save_snapshot(
+        &self,
+        admin: String,
+        route: String,
+        data: SnapshotData,
+    ) -> anyhow::Result<snapshot_info::Data>;
+    /// Save a confirmation with the digest of the snapshot its dry run reported.
+    async fn save_confirmation(
+        &self,
+        key: String,
+        digest: String,
+        ttl: Duration,
+    ) -> anyhow::Result<()>;
+    // R
+    /// newest first
+    async fn get_snapshots(&self) -> anyhow::Result<Vec<snapshot_info::Data>>;
+    /// number of players, by user id, holding a ranking
+    async fn count_ranked_users(&self, user_ids: Vec<String>) -> anyhow::Result<usize>;
+    // U
+    /// Recreate the documents of a snapshot which are missing,
+    /// and reconnect the relations to their ducks.
+    ///
+    /// Returns the number of documents recreated or reconnected.
+    async fn restore_snapshot(&self, id: String) -> anyhow::Result<i64>;
+    // D
+    /// Remove a confirmation, returning its snapshot digest if it existed and had not expired.
+    async fn take_confirmation(&self, key: String) -> anyhow::Result<Option<String>>;
+}
+
+#[async_trait]
+impl DangerousStore for PrismaDB {
+    // C
+
+    async fn collect_snapshot(&self, op: &DangerousOp) -> anyhow::Result<SnapshotData> {
+        // the ducks of archived seasons, and their histories, are kept
+        let archived: HashSet<String> = self.archived_duck_ids().await?.into_iter().collect();
+        let deleted = |duck_id: &Option<String>| match duck_id {
+            Some(duck_id) => !archived.contains(duck_id),
+            None => false,
+        };
+        let data = match op {
+            DangerousOp::DeleteAllDucks => SnapshotData {
+                ducks: self
+                    .0
+                    .duck()
+                    .find_many(vec![])
+                    .exec()
+                    .await?
+                    .into_iter()
+                    .filter(|d| !archived.contains(&d.id))
+                    .collect(),
+                duck_history: self
+                    .0
+                    .duck_history()
+                    .find_many(vec![])
+                    .exec()
+                    .await?
+                    .into_iter()
+                    .filter(|h| !archived.contains(&h.duck_id))
+                    .collect(),
+                // relations to ducks are set null by the delete
+                exhibits: self
+                    .0
+                    .exhibit()
+                    .find_many(vec![])
+                    .exec()
+                    .await?
+                    .into_iter()
+                    .filter(|e| deleted(&e.related_duck_id))
+                    .collect(),
+                locations: self
+                    .0
+                    .location()
+                    .find_many(vec![])
+                    .exec()
+                    .await?
+                    .into_iter()
+                    .filter(|l| deleted(&l.duck_id))
+                    .collect(),
+            },
+            DangerousOp::DeleteAllExhibits => SnapshotData {
+                exhibits: self.0.exhibit().find_many(vec![]).exec().await?,
+                ..Default::default()
+            },
+            DangerousOp::DeleteAllLocations => SnapshotData {
+                locations: self.0.location().find_many(vec![]).exec().await?,
+                ..Default::default()
+            },
+            DangerousOp::DeleteDuckHistory { user_id } => {
+                let user_by_wechat = self
+                    .0
+                    .user()
+                    .find_unique(user::UniqueWhereParam::WechatOpenIdEquals(user_id.clone()))
+                    .exec()
+                    .await?;
+                let user_id = match user_by_wechat {
+                    None => user_id.clone(),
+                    Some(user) => user.id,
+                };
+                SnapshotData {
+                    duck_history: self
+                        .0
+                        .duck_history()
+                        .find_many(vec![duck_history::user_id::equals(user_id)])
+                        .exec()
+                        .await?
+                        .into_iter()
+                        .filter(|h| !archived.contains(&h.duck_id))
+                        .collect(),
+                    ..Default::default()
+                }
+            }
+        };
+        Ok(data)
+    }
+
+    async fn save_snapshot(
+        &self,
+        admin: String,
+        route: String,
+        data: SnapshotData,
+    ) -> anyhow::Result<snapshot_info::Data> {
+        let data = self
+            .0
+            .snapshot()
+            .create(admin, route, serde_json::to_value(data)?, vec![])
+            .select(snapshot_info::select())
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    async fn save_confirmation(
+        &self,
+        key: String,
+        digest: String,
+        ttl: Duration,
+    ) -> anyhow::Result<()> {
+        let mut con = self.1.get_async_connection().await?;
+        let key = format!("{}{}", CONFIRMATION_KEY_PREFIX, key);
+        let _: () = con.set_ex(key, digest, ttl.as_secs() as usize).await?;
+        Ok(())
+    }
+
+    // R
+
+    async fn get_snapshots(&self) -> anyhow::Result<Vec<snapshot_info::Data>> {
+        let data = self
+            .0
+            .snapshot()
+            .find_many(vec![])
+            .order_by(snapshot::created_at::order(Direction::Desc))
+            .select(snapshot_info::select())
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    async fn count_ranked_users(&self, user_ids: Vec<String>) -> anyhow::Result<usize> {
+        let openids = self
+            .0
+            .user()
+            .find_many(vec![user::id::in_vec(user_ids)])
+            .exec()
+            .await?
+            .into_iter()
+            .map(|u| u.wechat_open_id)
+            .collect();
+        let data = self
+            .0
+            .ranking()
+            .count(vec![ranking::user_wechat_open_id::in_vec(openids)])
+            .exec()
+            .await?;
+        Ok(data as usize)
+    }
+
+    // U
+
+    async fn restore_snapshot(&self, id: String) -> anyhow::Result<i64> {
+        let snapshot = self
+            .0
+            .snapshot()
+            .find_unique(snapshot::UniqueWhereParam::IdEquals(id.clone()))
+            .exec()
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("snapshot {} not found", id))?;
+        let data: SnapshotData = serde_json::from_value(snapshot.data)?;
+        let mut restored = 0;
+        for d in &data.ducks {
+            if self.exists_duck(&d.id).await? {
+                continue;
+            }
+            self.0
+                .duck()
+                .create(
+                    d.title.clone(),
+                    d.story.clone(),
+                    d.topics.clone(),
+                    d.duck_icon_url.clone(),
+                    vec![
+                        duck::SetParam::SetId(d.id.clone()),
+                        duck::SetParam::SetCreatedAt(d.created_at),
+                        duck::SetParam::SetIsHidden(d.is_hidden),
+                        duck::SetParam::SetPrerequisiteIds(d.prerequisite_ids.clone()),
+                        duck::SetParam::SetChallenge(d.challenge.clone()),
+                        duck::SetParam::SetHints(d.hints.clone()),
+                        // seasons are archived, never deleted
+                        duck::SetParam::SetSeasonId(d.season_id.clone()),
+                        duck::SetParam::SetDeletedAt(d.deleted_at),
+                    ],
+                )
+                .exec()
+                .await?;
+            restored += 1;
+        }
+        // story sequences, once every duck of the snapshot exists again
+        for d in &data.ducks {
+            if let Some(prev_duck_story_id) = &d.prev_duck_story_id {
+                if self.exists_duck(prev_duck_story_id).await? {
+                    self.0
+                        .duck()
+                        .update(
+                            duck::UniqueWhereParam::IdEquals(d.id.clone()),
+                            vec![duck::SetParam::ConnectPrevDuckStory(
+                                duck::UniqueWhereParam::IdEquals(prev_duck_story_id.clone()),
+                            )],
+                        )
+                        .exec()
+                        .await?;
+                }
+            }
+        }
+        for e in data.exhibits {
+            let mut params = vec![];
+            if let Some(related_duck_id) = e.related_duck_id {
+                if self.exists_duck(&related_duck_id).await? {
+                    params.push(exhibit::SetParam::ConnectRelatedDuck(
+                        duck::UniqueWhereParam::IdEquals(related_duck_id),
+                    ));
+                }
+            }
+            let existing = self
+                .0
+                .exhibit()
+                .find_unique(exhibit::UniqueWhereParam::IdEquals(e.id.clone()))
+                .exec()
+                .await?;
+            if existing.is_some() {
+                if params.is_empty() {
+                    continue;
+                }
+                self.0
+                    .exhibit()
+                    .update(exhibit::UniqueWhereParam::IdEquals(e.id), params)
+                    .exec()
+                    .await?;
+            } else {
+                params.push(exhibit::SetParam::SetId(e.id));
+                params.push(exhibit::SetParam::SetCreatedAt(e.created_at));
+                params.push(exhibit::SetParam::SetDeletedAt(e.deleted_at));
+                self.0
+                    .exhibit()
+                    .create(e.location, e.title, e.sign, e.artists, params)
+                    .exec()
+                    .await?;
+            }
+            restored += 1;
+        }
+        for l in data.locations {
+            let mut params = vec![];
+            if let Some(duck_id) = l.duck_id {
+                if self.exists_duck(&duck_id).await? {
+                    params.push(location::SetParam::ConnectDuck(
+                        duck::UniqueWhereParam::IdEquals(duck_id),
+                    ));
+                }
+            }
+            let existing = self
+                .0
+                .location()
+                .find_unique(location::UniqueWhereParam::IdEquals(l.id.clone()))
+                .exec()
+                .await?;
+            if existing.is_some() {
+                if params.is_empty() {
+                    continue;
+                }
+                self.0
+                    .location()
+                    .update(location::UniqueWhereParam::IdEquals(l.id), params)
+                    .exec()
+                    .await?;
+            } else {
+                params.push(location::SetParam::SetId(l.id));
+                params.push(location::SetParam::SetDeletedAt(l.deleted_at));
+                params.push(location::SetParam::SetGeofenceRadius(l.geofence_radius));
+                params.push(location::SetParam::SetGeofenceMode(l.geofence_mode));
+                self.0
+                    .location()
+                    .create(l.description, l.coordinate, params)
+                    .exec()
+                    .await?;
+            }
+            restored += 1;
+        }
+        self.reindex_locations().await?;
+        for h in data.duck_history {
+            let existing = self
+                .0
+                .duck_history()
+                .find_unique(duck_history::UniqueWhereParam::IdEquals(h.id.clone()))
+                .exec()
+                .await?;
+            // histories recorded again since, and those of removed players, are skipped
+            let recorded = self
+                .0
+                .duck_history()
+                .find_unique(duck_history::UniqueWhereParam::UserIdDuckIdEquals(
+                    h.user_id.clone(),
+                    h.duck_id.clone(),
+                ))
+                .exec()
+                .await?;
+            let user = self
+                .0
+                .user()
+                .find_unique(user::UniqueWhereParam::IdEquals(h.user_id.clone()))
+                .exec()
+                .await?;
+            if existing.is_some()
+                || recorded.is_some()
+                || user.is_none()
+                || !self.exists_duck(&h.duck_id).await?
+            {
+                continue;
+            }
+            self.0
+                .duck_history()
+                .create(
+                    user::UniqueWhereParam::IdEquals(h.user_id),
+                    duck::UniqueWhereParam::IdEquals(h.duck_id),
+                    vec![
+                        duck_history::SetParam::SetId(h.id),
+                        duck_history::SetParam::SetCreatedAt(h.created_at),
+                    ],
+                )
+                .exec()
+                .await?;
+            restored += 1;
+        }
+        // only once every document is written, so that a failed restore can be run again
+        self.0
+            .snapshot()
+            .update(
+                snapshot::UniqueWhereParam::IdEquals(id),
+                vec![snapshot::SetParam::SetRestoredAt(Some(Utc::now().into()))],
+            )
+            .exec()
+            .await?;
+        Ok(restored)
+    }
+
+    // D
+
+    async fn take_confirmation(&self, key: String) -> anyhow::Result<Option<String>> {
+        let mut con = self.1.get_async_connection().await?;
+        let key = format!("{}{}", CONFIRMATION_KEY_PREFIX, key);
+        // expired keys are already gone, and only one caller can delete a key
+        let (digest, deleted): (Option<String>, i64) = redis::pipe()
+            .atomic()
+            .get(&key)
+            .del(&key)
+            .query_async(&mut con)
+            .await?;
+        Ok(digest.filter(|_| deleted == 1))
+    }
+}
+
+impl PrismaDB {
+    async fn exists_duck(&self, id: &str) -> anyhow::Result<bool> {
+        let data = self
+            .0
+            .duck()
+            .find_unique(duck::UniqueWhereParam::IdEquals(id.to_string()))
+            .exec()
+            .await?;
+        Ok(data.is_some())
+    }
+}
+
+impl DB {
+    /// Counts of what a bulk delete would remove or disconnect.
+    pub async fn dry_run_report(&self, snapshot: &SnapshotData) -> anyhow::Result<DryRunReport> {
+        let user_ids: HashSet<&str> = snapshot
+            .duck_history
+            .iter()
+            .map(|h| h.user_id.as_str())
+            .collect();
+        let rankings = self
+            .count_ranked_users(user_ids.into_iter().map(str::to_string).collect())
+            .await?;
+        Ok(DryRunReport {
+            ducks: snapshot.ducks.len(),
+            duck_history: snapshot.duck_history.len(),
+            exhibits: snapshot.exhibits.len(),
+            locations: snapshot.locations.len(),
+            rankings,
+        })
+    }
+}
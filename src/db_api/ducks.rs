@@ -1,36 +1,53 @@
 //! admin api to manage ducks
-use crate::db_api::{Bilingual, DB};
+use crate::db_api::{null_or_unset, Bilingual, PrismaDB};
 use crate::prisma::read_filters::StringFilter;
-use crate::prisma::{duck, duck_history, exhibit, location, user};
-use serde::Deserialize;
+use crate::prisma::{duck, duck_history, exhibit, location, season, user};
+use async_trait::async_trait;
+use prisma_client_rust::chrono::Utc;
+use serde::{Deserialize, Deserializer, Serialize};
 
 /// query struct for POST request
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct NewDuckData {
-    title: Bilingual,
-    story: Bilingual,
-    topics: Vec<Bilingual>,
-    duck_icon_url: String,
+    pub(crate) title: Bilingual,
+    pub(crate) story: Bilingual,
+    pub(crate) topics: Vec<Bilingual>,
+    pub(crate) duck_icon_url: String,
+    #[serde(default)]
+    pub(crate) is_hidden: bool,
+    pub(crate) location_id: Option<String>,
+    pub(crate) related_exhibit_id: Option<String>,
+    pub(crate) prev_duck_story_id: Option<String>,
     #[serde(default)]
-    is_hidden: bool,
-    location_id: Option<String>,
-    related_exhibit_id: Option<String>,
-    prev_duck_story_id: Option<String>,
+    pub(crate) prerequisite_ids: Vec<String>,
+    /// missing for the main game
+    pub(crate) season_id: Option<String>,
 }
 
 /// query struct for PATCH request
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UpdateDuckData {
-    title: Option<Bilingual>,
-    story: Option<Bilingual>,
-    topics: Option<Vec<Bilingual>>,
-    duck_icon_url: Option<String>,
-    is_hidden: Option<bool>,
-    location_id: Option<String>,
-    related_exhibit_id: Option<String>,
-    prev_duck_story_id: Option<String>,
+    pub(crate) title: Option<Bilingual>,
+    pub(crate) story: Option<Bilingual>,
+    pub(crate) topics: Option<Vec<Bilingual>>,
+    pub(crate) duck_icon_url: Option<String>,
+    pub(crate) is_hidden: Option<bool>,
+    pub(crate) location_id: Option<String>,
+    pub(crate) related_exhibit_id: Option<String>,
+    pub(crate) prev_duck_story_id: Option<String>,
+    pub(crate) prerequisite_ids: Option<Vec<String>>,
+    /// `null` moves the duck back to the main game
+    #[serde(default, deserialize_with = "nullable")]
+    pub(crate) season_id: Option<Option<String>>,
+}
+
+/// `Some(None)` for an explicit `null`, told apart from a missing field
+pub(crate) fn nullable<'de, T: Deserialize<'de>, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Option<T>>, D::Error> {
+    Option::<T>::deserialize(deserializer).map(Some)
 }
 
 impl NewDuckData {
@@ -59,7 +76,13 @@ impl NewDuckData {
                 exhibit::UniqueWhereParam::IdEquals(related_exhibit_id),
             ));
         }
+        if let Some(season_id) = self.season_id {
+            params.push(duck::SetParam::ConnectSeason(
+                season::UniqueWhereParam::IdEquals(season_id),
+            ));
+        }
         params.push(duck::SetParam::SetIsHidden(self.is_hidden));
+        params.push(duck::SetParam::SetPrerequisiteIds(self.prerequisite_ids));
         Ok((
             serde_json::to_value(self.title)?,
             serde_json::to_value(self.story)?,
@@ -103,6 +126,16 @@ impl UpdateDuckData {
                 exhibit::UniqueWhereParam::IdEquals(related_exhibit_id),
             ));
         }
+        if let Some(prerequisite_ids) = self.prerequisite_ids {
+            params.push(duck::SetParam::SetPrerequisiteIds(prerequisite_ids));
+        }
+        match self.season_id {
+            Some(Some(season_id)) => params.push(duck::SetParam::ConnectSeason(
+                season::UniqueWhereParam::IdEquals(season_id),
+            )),
+            Some(None) => params.push(duck::SetParam::DisconnectSeason),
+            None => {}
+        }
         Ok(params)
     }
 }
@@ -132,12 +165,44 @@ duck::select! { duck_info {
         topics
         is_hidden
     }
+    prev_duck_story_id
+    prerequisite_ids
+    challenge
+    hints
+    season_id
+    deleted_at
 }}
 
-impl DB {
+#[async_trait]
+pub trait DuckStore {
+    // C
+    async fn create_duck(&self, data: NewDuckData) -> anyhow::Result<duck::Data>;
+    async fn create_many_ducks(&self, data: Vec<NewDuckData>) -> anyhow::Result<i64>;
+    // R
+    /// ducks in the trash are not returned
+    async fn get_duck(&self, id: String) -> anyhow::Result<Option<duck_info::Data>>;
+    /// ducks in the trash are not returned
+    async fn get_all_ducks(&self) -> anyhow::Result<Vec<duck_info::Data>>;
+    async fn get_trashed_ducks(&self) -> anyhow::Result<Vec<duck_info::Data>>;
+    // U
+    async fn update_duck(&self, id: String, data: UpdateDuckData) -> anyhow::Result<duck::Data>;
+    /// take a duck out of the trash
+    async fn restore_duck(&self, id: String) -> anyhow::Result<duck::Data>;
+    // D
+    /// Move a duck to the trash, keeping its relations and view history.
+    async fn delete_duck(&self, id: String) -> anyhow::Result<duck::Data>;
+    /// `user_id` is either the user id or the wechat openid,
+    /// histories of the ducks of archived seasons are kept
+    async fn delete_duck_history(&self, user_id: String) -> anyhow::Result<i64>;
+    /// ducks of archived seasons are kept
+    async fn delete_all_ducks(&self) -> anyhow::Result<i64>;
+}
+
+#[async_trait]
+impl DuckStore for PrismaDB {
     // C
 
-    pub async fn create_duck(&self, data: NewDuckData) -> anyhow::Result<duck::Data> {
+    async fn create_duck(&self, data: NewDuckData) -> anyhow::Result<duck::Data> {
         let (title, story, topics, duck_icon_url, params) = data.into_db_data()?;
         let data = self
             .0
@@ -148,7 +213,7 @@ impl DB {
         Ok(data)
     }
 
-    pub async fn create_many_ducks(&self, data: Vec<NewDuckData>) -> anyhow::Result<i64> {
+    async fn create_many_ducks(&self, data: Vec<NewDuckData>) -> anyhow::Result<i64> {
         let mut many_data = Vec::with_capacity(data.len());
         for duck in data {
             many_data.push(duck.into_db_data()?);
@@ -159,7 +224,7 @@ impl DB {
 
     // R
 
-    pub async fn get_duck(&self, id: String) -> anyhow::Result<Option<duck_info::Data>> {
+    async fn get_duck(&self, id: String) -> anyhow::Result<Option<duck_info::Data>> {
         let data = self
             .0
             .duck()
@@ -167,14 +232,28 @@ impl DB {
             .select(duck_info::select())
             .exec()
             .await?;
+        Ok(data.filter(|d| d.deleted_at.is_none()))
+    }
+
+    async fn get_all_ducks(&self) -> anyhow::Result<Vec<duck_info::Data>> {
+        let data = self
+            .0
+            .duck()
+            .find_many(vec![null_or_unset!(duck::deleted_at)])
+            .select(duck_info::select())
+            .exec()
+            .await?;
         Ok(data)
     }
 
-    pub async fn get_all_ducks(&self) -> anyhow::Result<Vec<duck_info::Data>> {
+    async fn get_trashed_ducks(&self) -> anyhow::Result<Vec<duck_info::Data>> {
         let data = self
             .0
             .duck()
-            .find_many(vec![])
+            .find_many(vec![
+                duck::deleted_at::is_set(true),
+                duck::deleted_at::not(None),
+            ])
             .select(duck_info::select())
             .exec()
             .await?;
@@ -183,11 +262,7 @@ impl DB {
 
     // U
 
-    pub async fn update_duck(
-        &self,
-        id: String,
-        data: UpdateDuckData,
-    ) -> anyhow::Result<duck::Data> {
+    async fn update_duck(&self, id: String, data: UpdateDuckData) -> anyhow::Result<duck::Data> {
         let data = self
             .0
             .duck()
@@ -197,19 +272,35 @@ impl DB {
         Ok(data)
     }
 
+    async fn restore_duck(&self, id: String) -> anyhow::Result<duck::Data> {
+        let data = self
+            .0
+            .duck()
+            .update(
+                duck::UniqueWhereParam::IdEquals(id),
+                vec![duck::SetParam::SetDeletedAt(None)],
+            )
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
     // D
 
-    pub async fn delete_duck(&self, id: String) -> anyhow::Result<duck::Data> {
+    async fn delete_duck(&self, id: String) -> anyhow::Result<duck::Data> {
         let data = self
             .0
             .duck()
-            .delete(duck::UniqueWhereParam::IdEquals(id))
+            .update(
+                duck::UniqueWhereParam::IdEquals(id),
+                vec![duck::SetParam::SetDeletedAt(Some(Utc::now().into()))],
+            )
             .exec()
             .await?;
         Ok(data)
     }
 
-    pub async fn delete_duck_history(&self, user_id: String) -> anyhow::Result<i64> {
+    async fn delete_duck_history(&self, user_id: String) -> anyhow::Result<i64> {
         // attempt to find user by wechat id
         let user_by_wechat = self
             .0
@@ -226,16 +317,45 @@ impl DB {
         let data = self
             .0
             .duck_history()
-            .delete_many(vec![duck_history::WhereParam::UserId(
-                StringFilter::Equals(user_id),
-            )])
+            .delete_many(vec![
+                duck_history::WhereParam::UserId(StringFilter::Equals(user_id)),
+                duck_history::duck_id::not_in_vec(self.archived_duck_ids().await?),
+            ])
             .exec()
             .await?;
         Ok(data)
     }
 
-    pub async fn delete_all_ducks(&self) -> anyhow::Result<i64> {
-        let data = self.0.duck().delete_many(vec![]).exec().await?;
+    async fn delete_all_ducks(&self) -> anyhow::Result<i64> {
+        let data = self
+            .0
+            .duck()
+            .delete_many(vec![duck::id::not_in_vec(self.archived_duck_ids().await?)])
+            .exec()
+            .await?;
         Ok(data)
     }
 }
+
+impl PrismaDB {
+    /// Ids of the ducks of archived seasons, which bulk deletes keep.
+    pub(crate) async fn archived_duck_ids(&self) -> anyhow::Result<Vec<String>> {
+        let seasons = self
+            .0
+            .season()
+            .find_many(vec![])
+            .exec()
+            .await?
+            .into_iter()
+            .filter(|s| s.archived_at.is_some())
+            .map(|s| s.id)
+            .collect();
+        let data = self
+            .0
+            .duck()
+            .find_many(vec![duck::season_id::in_vec(seasons)])
+            .exec()
+            .await?;
+        Ok(data.into_iter().map(|d| d.id).collect())
+    }
+}
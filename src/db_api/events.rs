@@ -0,0 +1,174 @@
+//! live events pushed to players, fanned out to every instance through redis pub/sub
+use crate::db_api::{PrismaDB, DB};
+use async_trait::async_trait;
+use futures::stream::{self, BoxStream, StreamExt};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{error, warn};
+
+/// redis channel of the events of every instance
+const EVENTS_CHANNEL: &str = "cyberduck:events";
+/// events kept for slow subscribers, older ones are skipped
+pub(crate) const EVENTS_CAPACITY: usize = 256;
+/// wait before subscribing again after losing the redis connection
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+// Each instance publishes its events to redis, and relays every event
+// of the channel to its local subscribers, so a player connected to one
+// instance hears about a find made through another.
+
+/// an event of the game, as published between instances
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GameEvent {
+    /// a player found a duck for the first time
+    Discovery {
+        wechat_openid: String,
+        duck_id: String,
+    },
+    /// a player was ranked in a season, or in the main game
+    Ranked {
+        wechat_openid: String,
+        season_id: Option<String>,
+        ranking: i32,
+    },
+    /// a player earned an achievement
+    Achieved {
+        wechat_openid: String,
+        achievement_id: String,
+    },
+    /// the leaderboard of a season, or of the main game, changed
+    LeaderboardChanged { season_id: Option<String> },
+}
+
+impl GameEvent {
+    /// Whether a player receives the event, only global events without `wechat_openid`.
+    pub fn is_for(&self, wechat_openid: Option<&str>) -> bool {
+        match self {
+            GameEvent::Discovery {
+                wechat_openid: to, ..
+            }
+            | GameEvent::Ranked {
+                wechat_openid: to, ..
+            }
+            | GameEvent::Achieved {
+                wechat_openid: to, ..
+            } => wechat_openid == Some(to.as_str()),
+            GameEvent::LeaderboardChanged { .. } => true,
+        }
+    }
+
+    /// Name and data of the event as pushed to players, without openids.
+    pub fn payload(&self) -> (&'static str, Value) {
+        match self {
+            GameEvent::Discovery { duck_id, .. } => ("discovery", json!({ "duckId": duck_id })),
+            GameEvent::Ranked {
+                season_id, ranking, ..
+            } => (
+                "ranked",
+                json!({ "seasonId": season_id, "ranking": ranking }),
+            ),
+            GameEvent::Achieved { achievement_id, .. } => {
+                ("achievement", json!({ "achievementId": achievement_id }))
+            }
+            GameEvent::LeaderboardChanged { season_id } => {
+                ("leaderboard_changed", json!({ "seasonId": season_id }))
+            }
+        }
+    }
+}
+
+#[async_trait]
+pub trait EventStore {
+    // C
+    /// send an event to the subscribers of every instance
+    async fn publish_event(&self, event: GameEvent) -> anyhow::Result<()>;
+    // R
+    /// events published from now on, by any instance
+    fn subscribe_events(&self) -> BoxStream<'static, GameEvent>;
+}
+
+/// Events of a local channel, skipping the ones missed by a slow receiver.
+pub(crate) fn receive_events(
+    receiver: broadcast::Receiver<GameEvent>,
+) -> BoxStream<'static, GameEvent> {
+    stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => return Some((event, receiver)),
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!("event subscriber lagging, {} events skipped", n);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    })
+    .boxed()
+}
+
+#[async_trait]
+impl EventStore for PrismaDB {
+    // C
+
+    async fn publish_event(&self, event: GameEvent) -> anyhow::Result<()> {
+        let mut con = self.1.get_async_connection().await?;
+        let _: () = con
+            .publish(EVENTS_CHANNEL, serde_json::to_string(&event)?)
+            .await?;
+        Ok(())
+    }
+
+    // R
+
+    fn subscribe_events(&self) -> BoxStream<'static, GameEvent> {
+        receive_events(self.2.subscribe())
+    }
+}
+
+/// Relay the events of the redis channel to the local subscribers,
+/// subscribing again whenever the connection is lost.
+pub(crate) async fn relay_events(client: redis::Client, sender: broadcast::Sender<GameEvent>) {
+    loop {
+        if let Err(e) = relay_events_once(&client, &sender).await {
+            error!("error relaying events from redis: {}", e);
+        }
+        tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+    }
+}
+
+async fn relay_events_once(
+    client: &redis::Client,
+    sender: &broadcast::Sender<GameEvent>,
+) -> anyhow::Result<()> {
+    let mut pubsub = client.get_async_connection().await?.into_pubsub();
+    pubsub.subscribe(EVENTS_CHANNEL).await?;
+    let mut messages = pubsub.into_on_message();
+    while let Some(message) = messages.next().await {
+        let event = message
+            .get_payload::<String>()
+            .map_err(anyhow::Error::from)
+            .and_then(|payload| Ok(serde_json::from_str::<GameEvent>(&payload)?));
+        match event {
+            Ok(event) => {
+                // no local subscriber is not an error
+                let _ = sender.send(event);
+            }
+            Err(e) => error!("invalid event from redis: {}", e),
+        }
+    }
+    anyhow::bail!("redis event subscription closed")
+}
+
+impl DB {
+    /// Publish an event of the game.
+    ///
+    /// A failure is logged, but does not fail the request, whose change is already applied.
+    pub async fn notify(&self, event: GameEvent) {
+        if let Err(e) = self.publish_event(event).await {
+            error!("error publishing event: {}", e);
+        }
+    }
+}
@@ -1,26 +1,28 @@
 //! admin api to manage exhibits
-use crate::db_api::{Bilingual, DB};
+use crate::db_api::{null_or_unset, Bilingual, PrismaDB};
 use crate::prisma::exhibit;
-use serde::Deserialize;
+use async_trait::async_trait;
+use prisma_client_rust::chrono::Utc;
+use serde::{Deserialize, Serialize};
 
 /// query struct for POST request
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct NewExhibitData {
-    location: Bilingual,
-    title: Bilingual,
-    sign: Bilingual,
-    artists: Vec<Bilingual>,
+    pub(crate) location: Bilingual,
+    pub(crate) title: Bilingual,
+    pub(crate) sign: Bilingual,
+    pub(crate) artists: Vec<Bilingual>,
 }
 
 /// query struct for PATCH request
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UpdateExhibitData {
-    location: Option<Bilingual>,
-    title: Option<Bilingual>,
-    sign: Option<Bilingual>,
-    artists: Option<Vec<Bilingual>>,
+    pub(crate) location: Option<Bilingual>,
+    pub(crate) title: Option<Bilingual>,
+    pub(crate) sign: Option<Bilingual>,
+    pub(crate) artists: Option<Vec<Bilingual>>,
 }
 
 impl NewExhibitData {
@@ -66,10 +68,36 @@ impl UpdateExhibitData {
     }
 }
 
-impl DB {
+#[async_trait]
+pub trait ExhibitStore {
+    // C
+    async fn create_exhibit(&self, data: NewExhibitData) -> anyhow::Result<exhibit::Data>;
+    async fn create_many_exhibits(&self, data: Vec<NewExhibitData>) -> anyhow::Result<i64>;
+    // R
+    /// exhibits in the trash are not returned
+    async fn get_exhibit(&self, id: String) -> anyhow::Result<Option<exhibit::Data>>;
+    /// exhibits in the trash are not returned
+    async fn get_all_exhibits(&self) -> anyhow::Result<Vec<exhibit::Data>>;
+    async fn get_trashed_exhibits(&self) -> anyhow::Result<Vec<exhibit::Data>>;
+    // U
+    async fn update_exhibit(
+        &self,
+        id: String,
+        data: UpdateExhibitData,
+    ) -> anyhow::Result<exhibit::Data>;
+    /// take an exhibit out of the trash
+    async fn restore_exhibit(&self, id: String) -> anyhow::Result<exhibit::Data>;
+    // D
+    /// move an exhibit to the trash
+    async fn delete_exhibit(&self, id: String) -> anyhow::Result<exhibit::Data>;
+    async fn delete_all_exhibits(&self) -> anyhow::Result<i64>;
+}
+
+#[async_trait]
+impl ExhibitStore for PrismaDB {
     // C
 
-    pub async fn create_exhibit(&self, data: NewExhibitData) -> anyhow::Result<exhibit::Data> {
+    async fn create_exhibit(&self, data: NewExhibitData) -> anyhow::Result<exhibit::Data> {
         let (location, title, sign, artists, _) = data.into_db_data()?;
         let data = self
             .0
@@ -80,7 +108,7 @@ impl DB {
         Ok(data)
     }
 
-    pub async fn create_many_exhibits(&self, data: Vec<NewExhibitData>) -> anyhow::Result<i64> {
+    async fn create_many_exhibits(&self, data: Vec<NewExhibitData>) -> anyhow::Result<i64> {
         let mut many_data = Vec::with_capacity(data.len());
         for d in data {
             many_data.push(d.into_db_data()?);
@@ -91,24 +119,42 @@ impl DB {
 
     // R
 
-    pub async fn get_exhibit(&self, id: String) -> anyhow::Result<Option<exhibit::Data>> {
+    async fn get_exhibit(&self, id: String) -> anyhow::Result<Option<exhibit::Data>> {
         let data = self
             .0
             .exhibit()
             .find_unique(exhibit::UniqueWhereParam::IdEquals(id))
             .exec()
             .await?;
+        Ok(data.filter(|d| d.deleted_at.is_none()))
+    }
+
+    async fn get_all_exhibits(&self) -> anyhow::Result<Vec<exhibit::Data>> {
+        let data = self
+            .0
+            .exhibit()
+            .find_many(vec![null_or_unset!(exhibit::deleted_at)])
+            .exec()
+            .await?;
         Ok(data)
     }
 
-    pub async fn get_all_exhibits(&self) -> anyhow::Result<Vec<exhibit::Data>> {
-        let data = self.0.exhibit().find_many(vec![]).exec().await?;
+    async fn get_trashed_exhibits(&self) -> anyhow::Result<Vec<exhibit::Data>> {
+        let data = self
+            .0
+            .exhibit()
+            .find_many(vec![
+                exhibit::deleted_at::is_set(true),
+                exhibit::deleted_at::not(None),
+            ])
+            .exec()
+            .await?;
         Ok(data)
     }
 
     // U
 
-    pub async fn update_exhibit(
+    async fn update_exhibit(
         &self,
         id: String,
         data: UpdateExhibitData,
@@ -125,19 +171,35 @@ impl DB {
         Ok(data)
     }
 
+    async fn restore_exhibit(&self, id: String) -> anyhow::Result<exhibit::Data> {
+        let data = self
+            .0
+            .exhibit()
+            .update(
+                exhibit::UniqueWhereParam::IdEquals(id),
+                vec![exhibit::SetParam::SetDeletedAt(None)],
+            )
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
     // D
 
-    pub async fn delete_exhibit(&self, id: String) -> anyhow::Result<exhibit::Data> {
+    async fn delete_exhibit(&self, id: String) -> anyhow::Result<exhibit::Data> {
         let data = self
             .0
             .exhibit()
-            .delete(exhibit::UniqueWhereParam::IdEquals(id))
+            .update(
+                exhibit::UniqueWhereParam::IdEquals(id),
+                vec![exhibit::SetParam::SetDeletedAt(Some(Utc::now().into()))],
+            )
             .exec()
             .await?;
         Ok(data)
     }
 
-    pub async fn delete_all_exhibits(&self) -> anyhow::Result<i64> {
+    async fn delete_all_exhibits(&self) -> anyhow::Result<i64> {
         let data = self.0.exhibit().delete_many(vec![]).exec().await?;
         Ok(data)
     }
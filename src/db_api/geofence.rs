@@ -0,0 +1,232 @@
+//! checks of where and how fast players find ducks
+use crate::coordinates::Coordinate;
+use crate::db_api::{PrismaDB, DB};
+use crate::prisma::{discovery_flag, location};
+use async_trait::async_trait;
+use prisma_client_rust::chrono::Utc;
+use prisma_client_rust::Direction;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::warn;
+
+/// faster than running through the park, in metres per second
+const MAX_TRAVEL_SPEED: f64 = 8.0;
+/// below this distance in metres, positions are too imprecise to compute speeds
+const MIN_TRAVEL_DISTANCE: f64 = 100.0;
+
+/// how the geofence of a location is applied
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum GeofenceMode {
+    #[default]
+    Off,
+    /// flag finds outside the geofence, but accept them
+    LogOnly,
+    /// refuse finds outside the geofence
+    Enforce,
+}
+
+impl GeofenceMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GeofenceMode::Off => "off",
+            GeofenceMode::LogOnly => "log_only",
+            GeofenceMode::Enforce => "enforce",
+        }
+    }
+
+    fn parse(mode: Option<&str>) -> Self {
+        match mode {
+            Some("log_only") => GeofenceMode::LogOnly,
+            Some("enforce") => GeofenceMode::Enforce,
+            _ => GeofenceMode::Off,
+        }
+    }
+}
+
+/// why a find was flagged
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlagReason {
+    OutsideGeofence,
+    MissingPosition,
+    ImpossibleTravel,
+}
+
+impl FlagReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FlagReason::OutsideGeofence => "outside_geofence",
+            FlagReason::MissingPosition => "missing_position",
+            FlagReason::ImpossibleTravel => "impossible_travel",
+        }
+    }
+}
+
+pub struct NewDiscoveryFlag {
+    pub wechat_open_id: String,
+    pub duck_id: String,
+    pub reason: FlagReason,
+    pub detail: Value,
+    pub rejected: bool,
+}
+
+#[async_trait]
+pub trait DiscoveryStore {
+    // C
+    async fn record_discovery_flag(&self, flag: NewDiscoveryFlag) -> anyhow::Result<()>;
+    // R
+    /// newest first
+    async fn get_discovery_flags(&self) -> anyhow::Result<Vec<discovery_flag::Data>>;
+    /// the location of a duck, even in the trash
+    async fn get_location_of_duck(&self, duck_id: String)
+        -> anyhow::Result<Option<location::Data>>;
+}
+
+#[async_trait]
+impl DiscoveryStore for PrismaDB {
+    // C
+
+    async fn record_discovery_flag(&self, flag: NewDiscoveryFlag) -> anyhow::Result<()> {
+        self.0
+            .discovery_flag()
+            .create(
+                flag.wechat_open_id,
+                flag.duck_id,
+                flag.reason.as_str().to_string(),
+                flag.detail,
+                flag.rejected,
+                vec![],
+            )
+            .exec()
+            .await?;
+        Ok(())
+    }
+
+    // R
+
+    async fn get_discovery_flags(&self) -> anyhow::Result<Vec<discovery_flag::Data>> {
+        let data = self
+            .0
+            .discovery_flag()
+            .find_many(vec![])
+            .order_by(discovery_flag::created_at::order(Direction::Desc))
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    async fn get_location_of_duck(
+        &self,
+        duck_id: String,
+    ) -> anyhow::Result<Option<location::Data>> {
+        let data = self
+            .0
+            .location()
+            .find_unique(location::UniqueWhereParam::DuckIdEquals(duck_id))
+            .exec()
+            .await?;
+        Ok(data)
+    }
+}
+
+impl DB {
+    /// Check the first find of a duck against the geofence of its location,
+    /// and against the previous find of the player, flagging anything suspicious.
+    ///
+    /// Returns whether the find is accepted.
+    pub async fn check_discovery(
+        &self,
+        wechat_openid: &str,
+        duck_id: &str,
+        position: Option<Coordinate>,
+    ) -> anyhow::Result<bool> {
+        let location = self
+            .get_location_of_duck(duck_id.to_string())
+            .await?
+            .filter(|l| l.deleted_at.is_none());
+        let target = location
+            .as_ref()
+            .and_then(|l| Coordinate::from_value(&l.coordinate).ok());
+        let mut flags = Vec::new();
+        let mut rejected = false;
+
+        if let Some(location) = &location {
+            let mode = GeofenceMode::parse(location.geofence_mode.as_deref());
+            if let (true, Some(radius)) = (mode != GeofenceMode::Off, location.geofence_radius) {
+                let flag = match (position, target) {
+                    (None, _) => Some((FlagReason::MissingPosition, json!({ "radius": radius }))),
+                    (Some(position), Some(target)) => match position.distance(&target) {
+                        Some(distance) => (distance > radius).then(|| {
+                            (
+                                FlagReason::OutsideGeofence,
+                                json!({
+                                    "position": position,
+                                    "target": target,
+                                    "distance": distance,
+                                    "radius": radius,
+                                }),
+                            )
+                        }),
+                        None => {
+                            warn!("geofence of location {} is not geographic", location.id);
+                            None
+                        }
+                    },
+                    (Some(_), None) => {
+                        warn!("location {} has an invalid coordinate", location.id);
+                        None
+                    }
+                };
+                if let Some(flag) = flag {
+                    rejected = mode == GeofenceMode::Enforce;
+                    flags.push(flag);
+                }
+            }
+        }
+
+        // speed from the previous find of the player
+        let user = self.upsert_user_info(wechat_openid.to_string()).await?;
+        let previous = user
+            .duck_history
+            .iter()
+            .filter(|h| h.duck.id != duck_id)
+            .max_by_key(|h| h.created_at);
+        if let (Some(previous), Some(target)) = (previous, target) {
+            let from = previous
+                .duck
+                .location
+                .as_ref()
+                .and_then(|l| Coordinate::from_value(&l.coordinate).ok());
+            if let Some(distance) = from.and_then(|from| from.distance(&target)) {
+                let seconds = (Utc::now() - previous.created_at.with_timezone(&Utc))
+                    .num_milliseconds()
+                    .max(1) as f64
+                    / 1000.0;
+                let speed = distance / seconds;
+                if distance > MIN_TRAVEL_DISTANCE && speed > MAX_TRAVEL_SPEED {
+                    flags.push((
+                        FlagReason::ImpossibleTravel,
+                        json!({
+                            "previousDuckId": previous.duck.id,
+                            "distance": distance,
+                            "seconds": seconds,
+                            "speed": speed,
+                        }),
+                    ));
+                }
+            }
+        }
+
+        for (reason, detail) in flags {
+            self.record_discovery_flag(NewDiscoveryFlag {
+                wechat_open_id: wechat_openid.to_string(),
+                duck_id: duck_id.to_string(),
+                reason,
+                detail,
+                rejected,
+            })
+            .await?;
+        }
+        Ok(!rejected)
+    }
+}
@@ -0,0 +1,252 @@
+//! hints revealed one after another to players looking for a duck
+use crate::db_api::{Bilingual, PrismaDB, DB};
+use crate::prisma::{duck, hint_reveal};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use prisma_client_rust::chrono::{DateTime, FixedOffset, Utc};
+use prisma_client_rust::Direction;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// points earned by finding a duck, spent on hints
+pub const POINTS_PER_DUCK: i64 = 10;
+
+/// a hint of a duck, revealed after the hints before it
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Hint {
+    pub text: Bilingual,
+    pub unlock: HintUnlock,
+}
+
+/// condition for revealing a hint
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HintUnlock {
+    /// minutes since the player first logged in
+    Elapsed { minutes: u32 },
+    /// ducks found by the player, in any season
+    DucksFound { count: usize },
+    /// points the player spends on the hint
+    Points { cost: u32 },
+}
+
+/// a hint as a player sees it, without its text until revealed
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HintView {
+    pub unlock: HintUnlock,
+    pub text: Option<Bilingual>,
+    pub revealed_at: Option<DateTime<FixedOffset>>,
+    /// whether the player may reveal it now
+    pub available: bool,
+}
+
+/// response struct for the hints of a duck
+#[derive(Serialize)]
+pub struct PlayerHints {
+    /// points the player has left
+    pub points: i64,
+    pub hints: Vec<HintView>,
+}
+
+/// response struct for the reveals of a hint
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HintStats {
+    pub hint: usize,
+    pub unlock: HintUnlock,
+    pub reveals: usize,
+    pub points_spent: i64,
+}
+
+/// read the hints of a duck
+pub(crate) fn parse_hints(hints: &Option<Value>) -> anyhow::Result<Vec<Hint>> {
+    match hints {
+        Some(hints) => {
+            serde_json::from_value(hints.clone()).map_err(|e| anyhow!("invalid hints: {}", e))
+        }
+        None => Ok(vec![]),
+    }
+}
+
+#[async_trait]
+pub trait HintStore {
+    // C
+    async fn record_hint_reveal(
+        &self,
+        wechat_openid: String,
+        duck_id: String,
+        hint: usize,
+        cost: u32,
+    ) -> anyhow::Result<hint_reveal::Data>;
+    // R
+    /// reveals to a player, of a duck, or both, oldest first
+    async fn get_hint_reveals(
+        &self,
+        wechat_openid: Option<String>,
+        duck_id: Option<String>,
+    ) -> anyhow::Result<Vec<hint_reveal::Data>>;
+    // U
+    /// Replace every hint of a duck, reveals are kept by position.
+    async fn set_duck_hints(&self, duck_id: String, hints: Vec<Hint>)
+        -> anyhow::Result<duck::Data>;
+}
+
+#[async_trait]
+impl HintStore for PrismaDB {
+    // C
+
+    async fn record_hint_reveal(
+        &self,
+        wechat_openid: String,
+        duck_id: String,
+        hint: usize,
+        cost: u32,
+    ) -> anyhow::Result<hint_reveal::Data> {
+        let data = self
+            .0
+            .hint_reveal()
+            .create(
+                wechat_openid,
+                duck_id,
+                i32::try_from(hint)?,
+                i32::try_from(cost)?,
+                vec![],
+            )
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    // R
+
+    async fn get_hint_reveals(
+        &self,
+        wechat_openid: Option<String>,
+        duck_id: Option<String>,
+    ) -> anyhow::Result<Vec<hint_reveal::Data>> {
+        let mut filters = vec![];
+        if let Some(wechat_openid) = wechat_openid {
+            filters.push(hint_reveal::wechat_open_id::equals(wechat_openid));
+        }
+        if let Some(duck_id) = duck_id {
+            filters.push(hint_reveal::duck_id::equals(duck_id));
+        }
+        let data = self
+            .0
+            .hint_reveal()
+            .find_many(filters)
+            .order_by(hint_reveal::id::order(Direction::Asc))
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    // U
+
+    async fn set_duck_hints(
+        &self,
+        duck_id: String,
+        hints: Vec<Hint>,
+    ) -> anyhow::Result<duck::Data> {
+        let data = self
+            .0
+            .duck()
+            .update(
+                duck::UniqueWhereParam::IdEquals(duck_id),
+                vec![duck::SetParam::SetHints(Some(serde_json::to_value(hints)?))],
+            )
+            .exec()
+            .await?;
+        Ok(data)
+    }
+}
+
+impl DB {
+    /// Hints of a duck, in the order they are revealed.
+    ///
+    /// Fails if the duck is missing or in the trash.
+    pub async fn hints_of(&self, duck_id: &str) -> anyhow::Result<Vec<Hint>> {
+        let duck = self
+            .get_duck(duck_id.to_string())
+            .await?
+            .ok_or_else(|| anyhow!("duck {} not found", duck_id))?;
+        parse_hints(&duck.hints)
+    }
+
+    /// Hints of a duck as a player sees them, with the points they have left.
+    ///
+    /// Each duck found earns `POINTS_PER_DUCK`, and every hint revealed with points spends them.
+    /// A hint is available once the hints before it are revealed and its condition is met.
+    pub async fn player_hints(
+        &self,
+        wechat_openid: &str,
+        duck_id: &str,
+    ) -> anyhow::Result<PlayerHints> {
+        let hints = self.hints_of(duck_id).await?;
+        let user = self.upsert_user_info(wechat_openid.to_string()).await?;
+        let reveals = self
+            .get_hint_reveals(Some(wechat_openid.to_string()), None)
+            .await?;
+        let found = user.duck_history.len();
+        let spent: i64 = reveals.iter().map(|r| i64::from(r.cost)).sum();
+        let points = found as i64 * POINTS_PER_DUCK - spent;
+        let minutes = Utc::now()
+            .signed_duration_since(user.created_at)
+            .num_minutes();
+        let revealed: HashMap<usize, DateTime<FixedOffset>> = reveals
+            .iter()
+            .filter(|r| r.duck_id == duck_id)
+            .filter_map(|r| Some((usize::try_from(r.hint).ok()?, r.created_at)))
+            .collect();
+
+        let mut previous_revealed = true;
+        let hints = hints
+            .into_iter()
+            .enumerate()
+            .map(|(i, hint)| {
+                let revealed_at = revealed.get(&i).copied();
+                let met = match hint.unlock {
+                    HintUnlock::Elapsed { minutes: needed } => minutes >= i64::from(needed),
+                    HintUnlock::DucksFound { count } => found >= count,
+                    HintUnlock::Points { cost } => points >= i64::from(cost),
+                };
+                let available = revealed_at.is_none() && previous_revealed && met;
+                previous_revealed = revealed_at.is_some();
+                HintView {
+                    text: revealed_at.map(|_| hint.text),
+                    unlock: hint.unlock,
+                    revealed_at,
+                    available,
+                }
+            })
+            .collect();
+        Ok(PlayerHints { points, hints })
+    }
+
+    /// How often each hint of a duck was revealed.
+    pub async fn hint_stats(&self, duck_id: &str) -> anyhow::Result<Vec<HintStats>> {
+        let hints = self.hints_of(duck_id).await?;
+        let reveals = self
+            .get_hint_reveals(None, Some(duck_id.to_string()))
+            .await?;
+        Ok(hints
+            .into_iter()
+            .enumerate()
+            .map(|(i, hint)| {
+                // a player reveals each hint once
+                let reveals: Vec<_> = reveals
+                    .iter()
+                    .filter(|r| usize::try_from(r.hint).ok() == Some(i))
+                    .collect();
+                HintStats {
+                    hint: i,
+                    unlock: hint.unlock,
+                    reveals: reveals.len(),
+                    points_spent: reveals.iter().map(|r| i64::from(r.cost)).sum(),
+                }
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,246 @@
+//! public leaderboard, without the wechat openids of players
+use crate::db_api::{PrismaDB, DB};
+use crate::prisma::{ranking, season_ranking, user};
+use async_trait::async_trait;
+use prisma_client_rust::chrono::{DateTime, FixedOffset};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// redis key prefix of the cached leaderboards, by season
+const LEADERBOARD_CACHE_KEY: &str = "cyberduck:leaderboard";
+/// seconds a leaderboard is cached
+const LEADERBOARD_CACHE_SECONDS: usize = 30;
+pub const DEFAULT_PAGE_SIZE: usize = 20;
+pub const MAX_PAGE_SIZE: usize = 100;
+/// entries shown on each side of the player by `DB::leaderboard_around`
+pub const DEFAULT_AROUND: usize = 5;
+pub const MAX_AROUND: usize = 50;
+
+/// a ranked player as anyone sees them
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderboardEntry {
+    pub rank: i32,
+    /// nickname of the player, or their anonymised handle
+    pub name: String,
+    pub completed_at: DateTime<FixedOffset>,
+    /// whether this is the player asking
+    #[serde(default)]
+    pub me: bool,
+}
+
+/// an entry of the leaderboard with the openid of its player, never sent as is
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RankedPlayer {
+    pub wechat_openid: String,
+    pub entry: LeaderboardEntry,
+}
+
+/// response struct for a page of the leaderboard, best first
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderboardPage {
+    pub items: Vec<LeaderboardEntry>,
+    pub page: usize,
+    pub page_size: usize,
+    pub total: usize,
+    /// entry of the player asking, if ranked
+    pub me: Option<LeaderboardEntry>,
+}
+
+/// Stable handle of a player without nickname, which does not reveal their openid.
+pub fn anonymised_handle(wechat_openid: &str) -> String {
+    let digest = Sha256::digest(wechat_openid.as_bytes());
+    let hex: String = digest[..4].iter().map(|b| format!("{:02x}", b)).collect();
+    format!("duck-{}", hex)
+}
+
+/// Entries of the leaderboard, best first, from
+/// `(ranking, completed at, wechat openid)` and the nicknames by openid.
+pub(crate) fn leaderboard_entries(
+    mut rankings: Vec<(i32, DateTime<FixedOffset>, String)>,
+    nicknames: &HashMap<String, String>,
+) -> Vec<RankedPlayer> {
+    rankings.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+    rankings
+        .into_iter()
+        .map(|(rank, completed_at, wechat_openid)| RankedPlayer {
+            entry: LeaderboardEntry {
+                rank,
+                name: nicknames
+                    .get(&wechat_openid)
+                    .cloned()
+                    .unwrap_or_else(|| anonymised_handle(&wechat_openid)),
+                completed_at,
+                me: false,
+            },
+            wechat_openid,
+        })
+        .collect()
+}
+
+#[async_trait]
+pub trait LeaderboardStore {
+    // R
+    /// Every entry of the leaderboard of a season, or of the main game, best first.
+    ///
+    /// May be cached for a few seconds.
+    async fn get_leaderboard(&self, season_id: Option<String>)
+        -> anyhow::Result<Vec<RankedPlayer>>;
+    // D
+    /// drop the cached leaderboard of a season, or of the main game
+    async fn clear_leaderboard_cache(&self, season_id: Option<String>) -> anyhow::Result<()>;
+}
+
+fn cache_key(season_id: &Option<String>) -> String {
+    format!(
+        "{}:{}",
+        LEADERBOARD_CACHE_KEY,
+        season_id.as_deref().unwrap_or("main")
+    )
+}
+
+#[async_trait]
+impl LeaderboardStore for PrismaDB {
+    // R
+
+    async fn get_leaderboard(
+        &self,
+        season_id: Option<String>,
+    ) -> anyhow::Result<Vec<RankedPlayer>> {
+        let key = cache_key(&season_id);
+        let mut con = self.1.get_async_connection().await?;
+        let cached: Option<String> = con.get(&key).await?;
+        if let Some(entries) = cached.and_then(|c| serde_json::from_str(&c).ok()) {
+            return Ok(entries);
+        }
+        let rankings: Vec<(i32, DateTime<FixedOffset>, String)> = match &season_id {
+            Some(season_id) => self
+                .0
+                .season_ranking()
+                .find_many(vec![season_ranking::season_id::equals(season_id.clone())])
+                .exec()
+                .await?
+                .into_iter()
+                .map(|r| (r.ranking, r.created_at, r.user_wechat_open_id))
+                .collect(),
+            None => self
+                .0
+                .ranking()
+                .find_many(vec![])
+                .exec()
+                .await?
+                .into_iter()
+                .map(|r: ranking::Data| (r.ranking, r.created_at, r.user_wechat_open_id))
+                .collect(),
+        };
+        let openids = rankings.iter().map(|r| r.2.clone()).collect();
+        let nicknames = self
+            .0
+            .user()
+            .find_many(vec![user::wechat_open_id::in_vec(openids)])
+            .exec()
+            .await?
+            .into_iter()
+            .filter_map(|u| Some((u.wechat_open_id, u.nickname?)))
+            .collect();
+        let entries = leaderboard_entries(rankings, &nicknames);
+        let _: () = con
+            .set_ex(
+                &key,
+                serde_json::to_string(&entries)?,
+                LEADERBOARD_CACHE_SECONDS,
+            )
+            .await?;
+        Ok(entries)
+    }
+
+    // D
+
+    async fn clear_leaderboard_cache(&self, season_id: Option<String>) -> anyhow::Result<()> {
+        let mut con = self.1.get_async_connection().await?;
+        let _: () = con.del(cache_key(&season_id)).await?;
+        Ok(())
+    }
+}
+
+impl DB {
+    /// A page of the leaderboard of a season, or of the main game,
+    /// with the entry of the player asking.
+    pub async fn leaderboard_page(
+        &self,
+        season_id: Option<&str>,
+        wechat_openid: Option<&str>,
+        page: usize,
+        page_size: usize,
+    ) -> anyhow::Result<LeaderboardPage> {
+        let page_size = page_size.clamp(1, MAX_PAGE_SIZE);
+        let (entries, me) = self.leaderboard_of(season_id, wechat_openid).await?;
+        let total = entries.len();
+        let items = entries
+            .into_iter()
+            .skip(page.saturating_mul(page_size))
+            .take(page_size)
+            .collect();
+        Ok(LeaderboardPage {
+            items,
+            page,
+            page_size,
+            total,
+            me: me.map(|(_, entry)| entry),
+        })
+    }
+
+    /// Entries within `around` places of the player, `None` if not ranked.
+    pub async fn leaderboard_around(
+        &self,
+        season_id: Option<&str>,
+        wechat_openid: &str,
+        around: usize,
+    ) -> anyhow::Result<Option<Vec<LeaderboardEntry>>> {
+        let around = around.min(MAX_AROUND);
+        let (entries, me) = self.leaderboard_of(season_id, Some(wechat_openid)).await?;
+        Ok(me.map(|(position, _)| {
+            entries
+                .into_iter()
+                .skip(position.saturating_sub(around))
+                .take(2 * around + 1)
+                .collect()
+        }))
+    }
+
+    /// Every entry, with the one of the player marked, and its position.
+    ///
+    /// The player is found by openid, as players may share a rank.
+    async fn leaderboard_of(
+        &self,
+        season_id: Option<&str>,
+        wechat_openid: Option<&str>,
+    ) -> anyhow::Result<(Vec<LeaderboardEntry>, Option<(usize, LeaderboardEntry)>)> {
+        let players = self.get_leaderboard(season_id.map(str::to_string)).await?;
+        let position = wechat_openid.and_then(|wechat_openid| {
+            players
+                .iter()
+                .position(|p| p.wechat_openid == wechat_openid)
+        });
+        let mut entries: Vec<LeaderboardEntry> = players.into_iter().map(|p| p.entry).collect();
+        let me = position.map(|position| {
+            entries[position].me = true;
+            (position, entries[position].clone())
+        });
+        Ok((entries, me))
+    }
+
+    /// Drop the cached leaderboards of the main game and of every season,
+    /// after a change shown on all of them.
+    pub async fn clear_leaderboard_caches(&self) -> anyhow::Result<()> {
+        self.clear_leaderboard_cache(None).await?;
+        for season in self.get_all_seasons().await? {
+            self.clear_leaderboard_cache(Some(season.id)).await?;
+        }
+        Ok(())
+    }
+}
@@ -1,30 +1,36 @@
 //! admin api to manage locations
-use crate::db_api::{Bilingual, DB};
+use crate::coordinates::{Coordinate, CoordinateSystem};
+use crate::db_api::geofence::GeofenceMode;
+use crate::db_api::{null_or_unset, Bilingual, PrismaDB, DB};
 use crate::prisma::{duck, location};
+use anyhow::bail;
+use async_trait::async_trait;
+use prisma_client_rust::chrono::Utc;
 use serde::{Deserialize, Serialize};
-
-#[derive(Deserialize, Serialize)]
-pub struct Coordinate {
-    x: String,
-    y: String,
-}
+use serde_json::Value;
 
 /// query struct for POST request
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct NewLocationData {
-    description: Bilingual,
-    coordinate: Coordinate,
-    duck_id: Option<String>,
+    pub(crate) description: Bilingual,
+    pub(crate) coordinate: Coordinate,
+    pub(crate) duck_id: Option<String>,
+    /// in metres
+    pub(crate) geofence_radius: Option<f64>,
+    pub(crate) geofence_mode: Option<GeofenceMode>,
 }
 
 /// query struct for PATCH request
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UpdateLocationData {
-    description: Option<Bilingual>,
-    coordinate: Option<Coordinate>,
-    duck_id: Option<String>,
+    pub(crate) description: Option<Bilingual>,
+    pub(crate) coordinate: Option<Coordinate>,
+    pub(crate) duck_id: Option<String>,
+    /// in metres
+    pub(crate) geofence_radius: Option<f64>,
+    pub(crate) geofence_mode: Option<GeofenceMode>,
 }
 
 impl NewLocationData {
@@ -35,12 +41,20 @@ impl NewLocationData {
         serde_json::Value,
         Vec<location::SetParam>,
     )> {
-        let mut params = Vec::with_capacity(1);
+        let mut params = Vec::with_capacity(3);
         if let Some(duck_id) = self.duck_id {
             params.push(location::SetParam::ConnectDuck(
                 duck::UniqueWhereParam::IdEquals(duck_id),
             ));
         }
+        if let Some(radius) = self.geofence_radius {
+            params.push(location::SetParam::SetGeofenceRadius(Some(radius)));
+        }
+        if let Some(mode) = self.geofence_mode {
+            params.push(location::SetParam::SetGeofenceMode(Some(
+                mode.as_str().to_string(),
+            )));
+        }
         Ok((
             serde_json::to_value(self.description)?,
             serde_json::to_value(self.coordinate)?,
@@ -51,7 +65,7 @@ impl NewLocationData {
 
 impl UpdateLocationData {
     fn into_db_data(self) -> anyhow::Result<Vec<location::SetParam>> {
-        let mut params = Vec::with_capacity(2);
+        let mut params = Vec::with_capacity(5);
         if let Some(description) = self.description {
             params.push(location::SetParam::SetDescription(serde_json::to_value(
                 description,
@@ -67,53 +81,107 @@ impl UpdateLocationData {
                 duck::UniqueWhereParam::IdEquals(duck_id),
             ));
         }
+        if let Some(radius) = self.geofence_radius {
+            params.push(location::SetParam::SetGeofenceRadius(Some(radius)));
+        }
+        if let Some(mode) = self.geofence_mode {
+            params.push(location::SetParam::SetGeofenceMode(Some(
+                mode.as_str().to_string(),
+            )));
+        }
         Ok(params)
     }
 }
 
-impl DB {
+#[async_trait]
+pub trait LocationStore {
+    // C
+    async fn create_location(&self, data: NewLocationData) -> anyhow::Result<location::Data>;
+    async fn create_many_locations(&self, data: Vec<NewLocationData>) -> anyhow::Result<i64>;
+    // R
+    /// locations in the trash are not returned
+    async fn get_location(&self, id: String) -> anyhow::Result<Option<location::Data>>;
+    /// locations in the trash are not returned
+    async fn get_all_locations(&self) -> anyhow::Result<Vec<location::Data>>;
+    async fn get_trashed_locations(&self) -> anyhow::Result<Vec<location::Data>>;
+    // U
+    async fn update_location(
+        &self,
+        id: String,
+        data: UpdateLocationData,
+    ) -> anyhow::Result<location::Data>;
+    /// take a location out of the trash
+    async fn restore_location(&self, id: String) -> anyhow::Result<location::Data>;
+    // D
+    /// move a location to the trash
+    async fn delete_location(&self, id: String) -> anyhow::Result<location::Data>;
+    async fn delete_all_locations(&self) -> anyhow::Result<i64>;
+}
+
+#[async_trait]
+impl LocationStore for PrismaDB {
     // C
 
-    pub async fn create_location(&self, data: NewLocationData) -> anyhow::Result<location::Data> {
-        let (location, coordinate, _) = data.into_db_data()?;
+    async fn create_location(&self, data: NewLocationData) -> anyhow::Result<location::Data> {
+        let (location, coordinate, params) = data.into_db_data()?;
         let data = self
             .0
             .location()
-            .create(location, coordinate, vec![])
+            .create(location, coordinate, params)
             .exec()
             .await?;
+        self.index_location(&data).await?;
         Ok(data)
     }
 
-    pub async fn create_many_locations(&self, data: Vec<NewLocationData>) -> anyhow::Result<i64> {
+    async fn create_many_locations(&self, data: Vec<NewLocationData>) -> anyhow::Result<i64> {
         let mut many_data = Vec::with_capacity(data.len());
         for d in data {
             many_data.push(d.into_db_data()?);
         }
         let data = self.0.location().create_many(many_data).exec().await?;
+        self.reindex_locations().await?;
         Ok(data)
     }
 
     // R
 
-    pub async fn get_location(&self, id: String) -> anyhow::Result<Option<location::Data>> {
+    async fn get_location(&self, id: String) -> anyhow::Result<Option<location::Data>> {
         let data = self
             .0
             .location()
             .find_unique(location::UniqueWhereParam::IdEquals(id))
             .exec()
             .await?;
+        Ok(data.filter(|d| d.deleted_at.is_none()))
+    }
+
+    async fn get_all_locations(&self) -> anyhow::Result<Vec<location::Data>> {
+        let data = self
+            .0
+            .location()
+            .find_many(vec![null_or_unset!(location::deleted_at)])
+            .exec()
+            .await?;
         Ok(data)
     }
 
-    pub async fn get_all_locations(&self) -> anyhow::Result<Vec<location::Data>> {
-        let data = self.0.location().find_many(vec![]).exec().await?;
+    async fn get_trashed_locations(&self) -> anyhow::Result<Vec<location::Data>> {
+        let data = self
+            .0
+            .location()
+            .find_many(vec![
+                location::deleted_at::is_set(true),
+                location::deleted_at::not(None),
+            ])
+            .exec()
+            .await?;
         Ok(data)
     }
 
     // U
 
-    pub async fn update_location(
+    async fn update_location(
         &self,
         id: String,
         data: UpdateLocationData,
@@ -127,23 +195,144 @@ impl DB {
             )
             .exec()
             .await?;
+        self.index_location(&data).await?;
+        Ok(data)
+    }
+
+    async fn restore_location(&self, id: String) -> anyhow::Result<location::Data> {
+        let data = self
+            .0
+            .location()
+            .update(
+                location::UniqueWhereParam::IdEquals(id),
+                vec![location::SetParam::SetDeletedAt(None)],
+            )
+            .exec()
+            .await?;
         Ok(data)
     }
 
     // D
 
-    pub async fn delete_location(&self, id: String) -> anyhow::Result<location::Data> {
+    async fn delete_location(&self, id: String) -> anyhow::Result<location::Data> {
         let data = self
             .0
             .location()
-            .delete(location::UniqueWhereParam::IdEquals(id))
+            .update(
+                location::UniqueWhereParam::IdEquals(id),
+                vec![location::SetParam::SetDeletedAt(Some(Utc::now().into()))],
+            )
             .exec()
             .await?;
         Ok(data)
     }
 
-    pub async fn delete_all_locations(&self) -> anyhow::Result<i64> {
+    async fn delete_all_locations(&self) -> anyhow::Result<i64> {
         let data = self.0.location().delete_many(vec![]).exec().await?;
+        self.reindex_locations().await?;
         Ok(data)
     }
 }
+
+/// coordinate stored by older versions, with no system and numbers often as strings
+#[derive(Deserialize)]
+struct LegacyCoordinate {
+    x: Value,
+    y: Value,
+}
+
+#[derive(Serialize)]
+pub struct CoordinateChange {
+    pub id: String,
+    pub before: Value,
+    pub after: Coordinate,
+    /// `x` held the latitude
+    pub swapped: bool,
+}
+
+#[derive(Serialize)]
+pub struct InvalidCoordinate {
+    pub id: String,
+    pub coordinate: Value,
+    pub error: String,
+}
+
+/// changes turning the stored coordinates into typed ones
+#[derive(Serialize, Default)]
+pub struct CoordinateMigration {
+    /// locations already holding a typed coordinate
+    pub unchanged: usize,
+    pub changes: Vec<CoordinateChange>,
+    /// coordinates left for an admin to fix
+    pub invalid: Vec<InvalidCoordinate>,
+}
+
+impl UpdateLocationData {
+    /// only set the coordinate
+    pub(crate) fn coordinate(coordinate: Coordinate) -> Self {
+        UpdateLocationData {
+            description: None,
+            coordinate: Some(coordinate),
+            duck_id: None,
+            geofence_radius: None,
+            geofence_mode: None,
+        }
+    }
+}
+
+impl DB {
+    /// Plan the migration of every location, trash included, whose coordinate
+    /// is not typed yet, reading its numbers in the given system.
+    /// Geographic coordinates with the latitude in `x` are swapped back.
+    pub async fn plan_coordinate_migration(
+        &self,
+        system: CoordinateSystem,
+    ) -> anyhow::Result<CoordinateMigration> {
+        let mut locations = self.get_all_locations().await?;
+        locations.extend(self.get_trashed_locations().await?);
+        let mut plan = CoordinateMigration::default();
+        for location in locations {
+            if Coordinate::from_value(&location.coordinate).is_ok() {
+                plan.unchanged += 1;
+                continue;
+            }
+            match legacy_coordinate(&location.coordinate, system) {
+                Ok((after, swapped)) => plan.changes.push(CoordinateChange {
+                    id: location.id,
+                    before: location.coordinate,
+                    after,
+                    swapped,
+                }),
+                Err(e) => plan.invalid.push(InvalidCoordinate {
+                    id: location.id,
+                    coordinate: location.coordinate,
+                    error: e.to_string(),
+                }),
+            }
+        }
+        Ok(plan)
+    }
+}
+
+fn legacy_coordinate(
+    value: &Value,
+    system: CoordinateSystem,
+) -> anyhow::Result<(Coordinate, bool)> {
+    let legacy: LegacyCoordinate = serde_json::from_value(value.clone())?;
+    let number = |v: &Value| match v {
+        Value::String(s) => s.trim().parse::<f64>().ok(),
+        v => v.as_f64(),
+    };
+    let (x, y) = match (number(&legacy.x), number(&legacy.y)) {
+        (Some(x), Some(y)) => (x, y),
+        _ => bail!("coordinates must be numbers"),
+    };
+    match Coordinate::new(system, x, y) {
+        Ok(coordinate) => Ok((coordinate, false)),
+        Err(e) if system.is_geographic() => match Coordinate::new(system, y, x) {
+            Ok(coordinate) => Ok((coordinate, true)),
+            Err(_) => Err(e),
+        },
+        Err(e) => Err(e),
+    }
+}
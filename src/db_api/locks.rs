@@ -0,0 +1,91 @@
+//! named locks shared by every instance of the backend, serializing
+//! read-then-write sequences that must not interleave
+use crate::db_api::{PrismaDB, DB};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use rand::distributions::{Alphanumeric, DistString};
+use rand::rngs::OsRng;
+use std::future::Future;
+use std::time::Duration;
+
+/// redis key prefix of the locks, by name
+const LOCK_KEY_PREFIX: &str = "cyberduck:lock";
+/// a lock expires when the instance holding it stops before releasing it
+const LOCK_EXPIRY_MILLISECONDS: u64 = 30_000;
+pub(crate) const LOCK_RETRY_MILLISECONDS: u64 = 20;
+/// retries before giving up on a busy lock
+pub(crate) const LOCK_ATTEMPTS: usize = 500;
+/// delete the lock only if it is still held with the token
+const UNLOCK_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("DEL", KEYS[1])
+end
+return 0
+"#;
+
+#[async_trait]
+pub trait LockStore {
+    /// Take a lock, waiting while another request holds it.
+    ///
+    /// Returns the token to release it with.
+    async fn lock(&self, name: String) -> anyhow::Result<String>;
+    /// Release a lock, unless it was taken by someone else since.
+    async fn unlock(&self, name: String, token: String) -> anyhow::Result<()>;
+}
+
+#[async_trait]
+impl LockStore for PrismaDB {
+    async fn lock(&self, name: String) -> anyhow::Result<String> {
+        let key = format!("{}:{}", LOCK_KEY_PREFIX, name);
+        let token = Alphanumeric.sample_string(&mut OsRng, 16);
+        let mut con = self.1.get_async_connection().await?;
+        for _ in 0..LOCK_ATTEMPTS {
+            let taken: Option<String> = redis::cmd("SET")
+                .arg(&key)
+                .arg(&token)
+                .arg("NX")
+                .arg("PX")
+                .arg(LOCK_EXPIRY_MILLISECONDS)
+                .query_async(&mut con)
+                .await?;
+            if taken.is_some() {
+                return Ok(token);
+            }
+            tokio::time::sleep(Duration::from_millis(LOCK_RETRY_MILLISECONDS)).await;
+        }
+        Err(anyhow!("lock {} is busy", name))
+    }
+
+    async fn unlock(&self, name: String, token: String) -> anyhow::Result<()> {
+        let key = format!("{}:{}", LOCK_KEY_PREFIX, name);
+        let mut con = self.1.get_async_connection().await?;
+        let _: i32 = redis::Script::new(UNLOCK_SCRIPT)
+            .key(key)
+            .arg(token)
+            .invoke_async(&mut con)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Run `f` while holding a lock, releasing it whatever `f` answers.
+pub(crate) async fn with_lock<S, F, T>(store: &S, name: &str, f: F) -> anyhow::Result<T>
+where
+    S: LockStore + ?Sized,
+    F: Future<Output = T> + Send,
+{
+    let token = store.lock(name.to_string()).await?;
+    let result = f.await;
+    store.unlock(name.to_string(), token).await?;
+    Ok(result)
+}
+
+impl DB {
+    /// Run `f` while holding a lock shared by every instance.
+    pub async fn locked<F, T>(&self, name: &str, f: F) -> anyhow::Result<T>
+    where
+        F: Future<Output = T> + Send,
+    {
+        with_lock(&**self, name, f).await
+    }
+}
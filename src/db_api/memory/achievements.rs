@@ -0,0 +1,140 @@
+use crate::db_api::achievements::{AchievementStore, NewAchievementData, UpdateAchievementData};
+use crate::db_api::memory::{now, MemoryDB, Tables};
+use crate::prisma::{achievement, duck_history, user_achievement};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use prisma_client_rust::chrono::{DateTime, FixedOffset};
+
+impl Tables {
+    fn achievement_mut(&mut self, id: &str) -> anyhow::Result<&mut achievement::Data> {
+        self.achievements
+            .get_mut(id)
+            .ok_or_else(|| anyhow!("achievement {} not found", id))
+    }
+}
+
+#[async_trait]
+impl AchievementStore for MemoryDB {
+    // C
+
+    async fn create_achievement(
+        &self,
+        data: NewAchievementData,
+    ) -> anyhow::Result<achievement::Data> {
+        let mut tables = self.0.lock().unwrap();
+        let id = tables.new_id();
+        let achievement = achievement::Data {
+            id: id.clone(),
+            created_at: now(),
+            updated_at: now(),
+            title: serde_json::to_value(data.title)?,
+            description: serde_json::to_value(data.description)?,
+            icon_url: data.icon_url,
+            rule: serde_json::to_value(data.rule)?,
+            earned_by: None,
+        };
+        tables.achievements.insert(id, achievement.clone());
+        Ok(achievement)
+    }
+
+    // R
+
+    async fn get_achievement(&self, id: String) -> anyhow::Result<Option<achievement::Data>> {
+        Ok(self.0.lock().unwrap().achievements.get(&id).cloned())
+    }
+
+    async fn get_all_achievements(&self) -> anyhow::Result<Vec<achievement::Data>> {
+        Ok(self
+            .0
+            .lock()
+            .unwrap()
+            .achievements
+            .values()
+            .cloned()
+            .collect())
+    }
+
+    async fn first_discovery_since(
+        &self,
+        since: DateTime<FixedOffset>,
+    ) -> anyhow::Result<Option<duck_history::Data>> {
+        let tables = self.0.lock().unwrap();
+        Ok(tables
+            .duck_history
+            .values()
+            .filter(|h| h.created_at >= since)
+            .min_by_key(|h| h.created_at)
+            .cloned())
+    }
+
+    // U
+
+    async fn update_achievement(
+        &self,
+        id: String,
+        data: UpdateAchievementData,
+    ) -> anyhow::Result<achievement::Data> {
+        let mut tables = self.0.lock().unwrap();
+        let achievement = tables.achievement_mut(&id)?;
+        if let Some(title) = data.title {
+            achievement.title = serde_json::to_value(title)?;
+        }
+        if let Some(description) = data.description {
+            achievement.description = serde_json::to_value(description)?;
+        }
+        if let Some(icon_url) = data.icon_url {
+            achievement.icon_url = Some(icon_url);
+        }
+        if let Some(rule) = data.rule {
+            achievement.rule = serde_json::to_value(rule)?;
+        }
+        achievement.updated_at = now();
+        Ok(achievement.clone())
+    }
+
+    // C/U
+
+    async fn award_achievement(
+        &self,
+        achievement_id: String,
+        wechat_id: String,
+    ) -> anyhow::Result<user_achievement::Data> {
+        let mut tables = self.0.lock().unwrap();
+        let existing = tables
+            .user_achievements
+            .values()
+            .find(|a| a.achievement_id == achievement_id && a.user_wechat_open_id == wechat_id);
+        if let Some(data) = existing {
+            return Ok(data.clone());
+        }
+        tables.achievement_mut(&achievement_id)?;
+        if tables.user_by_wechat(&wechat_id).is_none() {
+            return Err(anyhow!("user {} not found", wechat_id));
+        }
+        let id = tables.new_id();
+        let data = user_achievement::Data {
+            id: id.clone(),
+            created_at: now(),
+            achievement_id,
+            achievement: None,
+            user_wechat_open_id: wechat_id,
+            user: None,
+        };
+        tables.user_achievements.insert(id, data.clone());
+        Ok(data)
+    }
+
+    // D
+
+    async fn delete_achievement(&self, id: String) -> anyhow::Result<achievement::Data> {
+        let mut tables = self.0.lock().unwrap();
+        let achievement = tables
+            .achievements
+            .remove(&id)
+            .ok_or_else(|| anyhow!("achievement {} not found", id))?;
+        tables
+            .user_achievements
+            .retain(|_, a| a.achievement_id != id);
+        Ok(achievement)
+    }
+}
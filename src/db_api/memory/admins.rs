@@ -0,0 +1,94 @@
+use crate::db_api::admins::{admin_account_info, AdminStore};
+use crate::db_api::memory::{now, MemoryDB};
+use crate::prisma::admin_account;
+use anyhow::{anyhow, bail};
+use async_trait::async_trait;
+
+#[async_trait]
+impl AdminStore for MemoryDB {
+    // C
+
+    async fn create_admin(
+        &self,
+        name: String,
+        role: String,
+        token_hash: String,
+    ) -> anyhow::Result<admin_account::Data> {
+        let mut tables = self.0.lock().unwrap();
+        if tables
+            .admin_accounts
+            .values()
+            .any(|a| a.name == name || a.token_hash == token_hash)
+        {
+            bail!("unique constraint failed on name or tokenHash");
+        }
+        let id = tables.new_id();
+        let data = admin_account::Data {
+            id: id.clone(),
+            created_at: now(),
+            name,
+            role,
+            token_hash,
+            revoked_at: None,
+        };
+        tables.admin_accounts.insert(id, data.clone());
+        Ok(data)
+    }
+
+    // R
+
+    async fn get_all_admins(&self) -> anyhow::Result<Vec<admin_account_info::Data>> {
+        let tables = self.0.lock().unwrap();
+        Ok(tables
+            .admin_accounts
+            .values()
+            .map(|a| admin_account_info::Data {
+                name: a.name.clone(),
+                role: a.role.clone(),
+                created_at: a.created_at,
+                revoked_at: a.revoked_at,
+            })
+            .collect())
+    }
+
+    async fn find_admin_by_token_hash(
+        &self,
+        token_hash: String,
+    ) -> anyhow::Result<Option<admin_account::Data>> {
+        let tables = self.0.lock().unwrap();
+        Ok(tables
+            .admin_accounts
+            .values()
+            .find(|a| a.token_hash == token_hash && a.revoked_at.is_none())
+            .cloned())
+    }
+
+    // U
+
+    async fn reset_admin_token(
+        &self,
+        name: String,
+        token_hash: String,
+    ) -> anyhow::Result<admin_account::Data> {
+        let mut tables = self.0.lock().unwrap();
+        let account = tables
+            .admin_accounts
+            .values_mut()
+            .find(|a| a.name == name)
+            .ok_or_else(|| anyhow!("admin {} not found", name))?;
+        account.token_hash = token_hash;
+        account.revoked_at = None;
+        Ok(account.clone())
+    }
+
+    async fn revoke_admin(&self, name: String) -> anyhow::Result<admin_account::Data> {
+        let mut tables = self.0.lock().unwrap();
+        let account = tables
+            .admin_accounts
+            .values_mut()
+            .find(|a| a.name == name)
+            .ok_or_else(|| anyhow!("admin {} not found", name))?;
+        account.revoked_at = Some(now());
+        Ok(account.clone())
+    }
+}
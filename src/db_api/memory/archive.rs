@@ -0,0 +1,296 @@
+use crate::db_api::archive::{
+    achievement_rule_of, completion_rule_of, prerequisites_of, Archive, ArchiveStore, ImportMode,
+    ImportReport, ARCHIVE_VERSION,
+};
+use crate::db_api::memory::{now, MemoryDB};
+use crate::prisma::{
+    achievement, completion_rule, duck, duck_history, exhibit, location, ranking, season,
+    season_ranking, team, team_ranking, user, user_achievement,
+};
+use async_trait::async_trait;
+use std::collections::{HashMap, HashSet};
+
+#[async_trait]
+impl ArchiveStore for MemoryDB {
+    // R
+
+    async fn export_archive(&self) -> anyhow::Result<Archive> {
+        let tables = self.0.lock().unwrap();
+        Ok(Archive {
+            version: ARCHIVE_VERSION,
+            exported_at: now(),
+            ducks: tables.ducks.values().cloned().collect(),
+            locations: tables.locations.values().cloned().collect(),
+            exhibits: tables.exhibits.values().cloned().collect(),
+            users: tables.users.values().cloned().collect(),
+            duck_history: tables.duck_history.values().cloned().collect(),
+            rankings: tables.rankings.values().cloned().collect(),
+            seasons: tables.seasons.values().cloned().collect(),
+            season_rankings: tables.season_rankings.values().cloned().collect(),
+            completion_rules: tables.completion_rules.values().cloned().collect(),
+            achievements: tables.achievements.values().cloned().collect(),
+            user_achievements: tables.user_achievements.values().cloned().collect(),
+            teams: tables.teams.values().cloned().collect(),
+            team_rankings: tables.team_rankings.values().cloned().collect(),
+        })
+    }
+
+    // C
+
+    async fn import_archive(
+        &self,
+        archive: Archive,
+        mode: ImportMode,
+    ) -> anyhow::Result<ImportReport> {
+        archive.check()?;
+        let mut tables = self.0.lock().unwrap();
+        let mut report = ImportReport::default();
+        if mode == ImportMode::Replace {
+            tables.duck_history.clear();
+            tables.rankings.clear();
+            tables.season_rankings.clear();
+            tables.user_achievements.clear();
+            tables.achievements.clear();
+            tables.hint_reveals.clear();
+            tables.pending_discoveries.clear();
+            tables.challenge_attempts.clear();
+            tables.discovery_flags.clear();
+            tables.duck_codes.clear();
+            tables.completion_rules.clear();
+            tables.team_rankings.clear();
+            tables.teams.clear();
+            tables.users.clear();
+            tables.locations.clear();
+            tables.exhibits.clear();
+            tables.ducks.clear();
+            tables.seasons.clear();
+        }
+
+        // old id -> new id
+        let mut season_ids = HashMap::new();
+        for s in archive.seasons {
+            let id = tables.new_id();
+            season_ids.insert(s.id.clone(), id.clone());
+            tables.seasons.insert(id.clone(), season::Data { id, ..s });
+            report.seasons += 1;
+        }
+        let mut duck_ids = HashMap::new();
+        for d in &archive.ducks {
+            let id = tables.new_id();
+            duck_ids.insert(d.id.clone(), id);
+        }
+        for d in archive.ducks {
+            let id = duck_ids[&d.id].clone();
+            let duck = duck::Data {
+                id: id.clone(),
+                updated_at: now(),
+                prev_duck_story_id: d.prev_duck_story_id.clone().map(|p| duck_ids[&p].clone()),
+                prerequisite_ids: prerequisites_of(&d, &duck_ids),
+                season_id: d.season_id.clone().map(|s| season_ids[&s].clone()),
+                ..d
+            };
+            tables.ducks.insert(id, duck);
+            report.ducks += 1;
+        }
+        for l in archive.locations {
+            let id = tables.new_id();
+            let location = location::Data {
+                id: id.clone(),
+                duck_id: l.duck_id.map(|d| duck_ids[&d].clone()),
+                ..l
+            };
+            tables.locations.insert(id, location);
+            report.locations += 1;
+        }
+        let mut exhibit_ids = HashMap::new();
+        for e in archive.exhibits {
+            let id = tables.new_id();
+            exhibit_ids.insert(e.id.clone(), id.clone());
+            let exhibit = exhibit::Data {
+                id: id.clone(),
+                updated_at: now(),
+                related_duck_id: e.related_duck_id.map(|d| duck_ids[&d].clone()),
+                ..e
+            };
+            tables.exhibits.insert(id, exhibit);
+            report.exhibits += 1;
+        }
+        // rules of the main game already set are kept, like the ranking of a merged player
+        let kept_main_rules: HashSet<bool> = tables
+            .completion_rules
+            .values()
+            .filter(|r| r.season_id.is_none())
+            .map(|r| r.team.unwrap_or(false))
+            .collect();
+        for r in archive.completion_rules {
+            if r.season_id.is_none() && kept_main_rules.contains(&r.team.unwrap_or(false)) {
+                continue;
+            }
+            let id = tables.new_id();
+            let rule = completion_rule::Data {
+                id: id.clone(),
+                rule: completion_rule_of(&r, &duck_ids)?,
+                season_id: r.season_id.clone().map(|s| season_ids[&s].clone()),
+                ..r
+            };
+            tables.completion_rules.insert(id, rule);
+            report.completion_rules += 1;
+        }
+        let mut achievement_ids = HashMap::new();
+        for a in archive.achievements {
+            let id = tables.new_id();
+            achievement_ids.insert(a.id.clone(), id.clone());
+            let achievement = achievement::Data {
+                id: id.clone(),
+                updated_at: now(),
+                rule: achievement_rule_of(&a, &duck_ids, &exhibit_ids)?,
+                earned_by: None,
+                ..a
+            };
+            tables.achievements.insert(id, achievement);
+            report.achievements += 1;
+        }
+        if mode == ImportMode::Content {
+            return Ok(report);
+        }
+
+        let mut team_ids = HashMap::new();
+        for t in archive.teams {
+            let existing = tables
+                .teams
+                .values()
+                .find(|e| e.join_code == t.join_code)
+                .map(|e| e.id.clone());
+            let id = match existing {
+                Some(id) => id,
+                None => {
+                    let id = tables.new_id();
+                    let team = team::Data {
+                        id: id.clone(),
+                        members: None,
+                        rankings: None,
+                        ..t.clone()
+                    };
+                    tables.teams.insert(id.clone(), team);
+                    report.teams += 1;
+                    id
+                }
+            };
+            team_ids.insert(t.id, id);
+        }
+        let mut user_ids = HashMap::new();
+        for u in archive.users {
+            let existing = tables
+                .user_by_wechat(&u.wechat_open_id)
+                .map(|e| e.id.clone());
+            let id = match existing {
+                Some(id) => id,
+                None => {
+                    let id = tables.new_id();
+                    let user = user::Data {
+                        id: id.clone(),
+                        team_id: u.team_id.as_ref().map(|t| team_ids[t].clone()),
+                        ..u.clone()
+                    };
+                    tables.users.insert(id.clone(), user);
+                    report.users += 1;
+                    id
+                }
+            };
+            user_ids.insert(u.id, id);
+        }
+        for h in archive.duck_history {
+            let id = tables.new_id();
+            let history = duck_history::Data {
+                id: id.clone(),
+                user_id: user_ids[&h.user_id].clone(),
+                duck_id: duck_ids[&h.duck_id].clone(),
+                ..h
+            };
+            tables.duck_history.insert(id, history);
+            report.duck_history += 1;
+        }
+        for a in archive.user_achievements {
+            let id = tables.new_id();
+            let earned = user_achievement::Data {
+                id: id.clone(),
+                achievement_id: achievement_ids[&a.achievement_id].clone(),
+                ..a
+            };
+            tables.user_achievements.insert(id, earned);
+            report.user_achievements += 1;
+        }
+        let mut rankings = archive.rankings;
+        rankings.sort_by(|a, b| (a.ranking, a.created_at).cmp(&(b.ranking, b.created_at)));
+        for r in rankings {
+            // a merged player keeps the ranking already earned here,
+            // the others are ranked after every player ranked here, in their order
+            let ranking = match mode {
+                ImportMode::Replace => r.ranking,
+                _ => {
+                    if tables
+                        .rankings
+                        .values()
+                        .any(|existing| existing.user_wechat_open_id == r.user_wechat_open_id)
+                    {
+                        continue;
+                    }
+                    tables.ranking_counter += 1;
+                    tables.ranking_counter
+                }
+            };
+            let id = tables.new_id();
+            tables
+                .rankings
+                .insert(id.clone(), ranking::Data { id, ranking, ..r });
+            report.rankings += 1;
+        }
+        for r in archive.season_rankings {
+            let id = tables.new_id();
+            let ranking = season_ranking::Data {
+                id: id.clone(),
+                season_id: season_ids[&r.season_id].clone(),
+                ..r
+            };
+            tables.season_rankings.insert(id, ranking);
+            report.season_rankings += 1;
+        }
+        let mut team_rankings = archive.team_rankings;
+        team_rankings.sort_by(|a, b| (a.ranking, a.created_at).cmp(&(b.ranking, b.created_at)));
+        for r in team_rankings {
+            let team_id = team_ids[&r.team_id].clone();
+            let ranking = match r.season_id {
+                // like the rankings of players
+                None if mode == ImportMode::Merge => {
+                    let main: Vec<&team_ranking::Data> = tables
+                        .team_rankings
+                        .values()
+                        .filter(|ranked| ranked.season_id.is_none())
+                        .collect();
+                    if main.iter().any(|ranked| ranked.team_id == team_id) {
+                        continue;
+                    }
+                    main.iter().map(|ranked| ranked.ranking).max().unwrap_or(0) + 1
+                }
+                _ => r.ranking,
+            };
+            let id = tables.new_id();
+            let data = team_ranking::Data {
+                id: id.clone(),
+                team_id,
+                season_id: r.season_id.clone().map(|s| season_ids[&s].clone()),
+                ranking,
+                ..r
+            };
+            tables.team_rankings.insert(id, data);
+            report.team_rankings += 1;
+        }
+        tables.ranking_counter = tables
+            .rankings
+            .values()
+            .map(|r| r.ranking)
+            .max()
+            .unwrap_or(0);
+        Ok(report)
+    }
+}
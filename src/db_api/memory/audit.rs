@@ -0,0 +1,56 @@
+use crate::db_api::audit::{AuditLogPage, AuditLogQuery, AuditStore, NewAuditEntry};
+use crate::db_api::memory::{now, MemoryDB};
+use crate::prisma::audit_log;
+use async_trait::async_trait;
+
+#[async_trait]
+impl AuditStore for MemoryDB {
+    // C
+
+    async fn record_audit(&self, entry: NewAuditEntry) -> anyhow::Result<audit_log::Data> {
+        let mut tables = self.0.lock().unwrap();
+        let id = tables.new_id();
+        let data = audit_log::Data {
+            id: id.clone(),
+            created_at: now(),
+            admin: entry.admin,
+            route: entry.route,
+            entity_type: entry.entity_type.as_str().to_string(),
+            entity_id: entry.entity_id,
+            before: entry.before,
+            after: entry.after,
+        };
+        tables.audit_log.insert(id, data.clone());
+        Ok(data)
+    }
+
+    // R
+
+    async fn get_audit_log(&self, query: AuditLogQuery) -> anyhow::Result<AuditLogPage> {
+        let tables = self.0.lock().unwrap();
+        let (skip, take) = query.range();
+        // ids increase with insertion, so reversing them puts the newest first
+        let matched: Vec<&audit_log::Data> = tables
+            .audit_log
+            .values()
+            .rev()
+            .filter(|a| match query.entity_type {
+                Some(entity_type) => a.entity_type == entity_type.as_str(),
+                None => true,
+            })
+            .filter(|a| query.from.map_or(true, |from| a.created_at >= from))
+            .filter(|a| query.to.map_or(true, |to| a.created_at <= to))
+            .collect();
+        Ok(AuditLogPage {
+            total: matched.len() as i64,
+            items: matched
+                .into_iter()
+                .skip(skip as usize)
+                .take(take as usize)
+                .cloned()
+                .collect(),
+            page: query.page.max(0),
+            page_size: take,
+        })
+    }
+}
@@ -0,0 +1,123 @@
+use crate::db_api::challenges::{Answer, Challenge, ChallengeStore};
+use crate::db_api::memory::{now, MemoryDB};
+use crate::prisma::{challenge_attempt, duck, pending_discovery};
+use anyhow::anyhow;
+use async_trait::async_trait;
+
+#[async_trait]
+impl ChallengeStore for MemoryDB {
+    // C/U
+
+    async fn upsert_pending_discovery(
+        &self,
+        wechat_openid: String,
+        duck_id: String,
+    ) -> anyhow::Result<pending_discovery::Data> {
+        let mut tables = self.0.lock().unwrap();
+        let existing = tables
+            .pending_discoveries
+            .values_mut()
+            .find(|p| p.wechat_open_id == wechat_openid && p.duck_id == duck_id);
+        if let Some(pending) = existing {
+            pending.created_at = now();
+            return Ok(pending.clone());
+        }
+        let id = tables.new_id();
+        let pending = pending_discovery::Data {
+            id: id.clone(),
+            created_at: now(),
+            wechat_open_id: wechat_openid,
+            duck_id,
+        };
+        tables.pending_discoveries.insert(id, pending.clone());
+        Ok(pending)
+    }
+
+    // C
+
+    async fn record_challenge_attempt(
+        &self,
+        wechat_openid: String,
+        duck_id: String,
+        answer: Answer,
+        correct: bool,
+    ) -> anyhow::Result<challenge_attempt::Data> {
+        let mut tables = self.0.lock().unwrap();
+        let id = tables.new_id();
+        let attempt = challenge_attempt::Data {
+            id: id.clone(),
+            created_at: now(),
+            wechat_open_id: wechat_openid,
+            duck_id,
+            answer: serde_json::to_value(answer)?,
+            correct,
+        };
+        tables.challenge_attempts.insert(id, attempt.clone());
+        Ok(attempt)
+    }
+
+    // R
+
+    async fn get_pending_discovery(
+        &self,
+        wechat_openid: String,
+        duck_id: String,
+    ) -> anyhow::Result<Option<pending_discovery::Data>> {
+        let tables = self.0.lock().unwrap();
+        Ok(tables
+            .pending_discoveries
+            .values()
+            .find(|p| p.wechat_open_id == wechat_openid && p.duck_id == duck_id)
+            .cloned())
+    }
+
+    async fn get_challenge_attempts(
+        &self,
+        duck_id: String,
+        wechat_openid: Option<String>,
+    ) -> anyhow::Result<Vec<challenge_attempt::Data>> {
+        let tables = self.0.lock().unwrap();
+        Ok(tables
+            .challenge_attempts
+            .values()
+            .filter(|a| a.duck_id == duck_id)
+            .filter(|a| {
+                wechat_openid
+                    .as_ref()
+                    .map_or(true, |w| &a.wechat_open_id == w)
+            })
+            .cloned()
+            .collect())
+    }
+
+    // U
+
+    async fn set_duck_challenge(
+        &self,
+        duck_id: String,
+        challenge: Option<Challenge>,
+    ) -> anyhow::Result<duck::Data> {
+        let mut tables = self.0.lock().unwrap();
+        let duck = tables
+            .ducks
+            .get_mut(&duck_id)
+            .ok_or_else(|| anyhow!("duck {} not found", duck_id))?;
+        duck.challenge = challenge.map(serde_json::to_value).transpose()?;
+        duck.updated_at = now();
+        Ok(duck.clone())
+    }
+
+    // D
+
+    async fn delete_pending_discovery(
+        &self,
+        wechat_openid: String,
+        duck_id: String,
+    ) -> anyhow::Result<()> {
+        let mut tables = self.0.lock().unwrap();
+        tables
+            .pending_discoveries
+            .retain(|_, p| p.wechat_open_id != wechat_openid || p.duck_id != duck_id);
+        Ok(())
+    }
+}
@@ -0,0 +1,107 @@
+use crate::db_api::codes::CodeStore;
+use crate::db_api::memory::{now, MemoryDB, Tables};
+use crate::prisma::{duck_code, qr_key};
+use anyhow::{anyhow, bail};
+use async_trait::async_trait;
+
+impl Tables {
+    pub(super) fn insert_qr_key(&mut self, kid: String, secret: String) -> qr_key::Data {
+        let id = self.new_id();
+        let data = qr_key::Data {
+            id: id.clone(),
+            created_at: now(),
+            kid,
+            secret,
+            retired_at: None,
+        };
+        self.qr_keys.insert(id, data.clone());
+        data
+    }
+}
+
+#[async_trait]
+impl CodeStore for MemoryDB {
+    // C
+
+    async fn create_qr_key(&self, kid: String, secret: String) -> anyhow::Result<qr_key::Data> {
+        let mut tables = self.0.lock().unwrap();
+        if tables.qr_keys.values().any(|k| k.kid == kid) {
+            bail!("unique constraint failed on kid");
+        }
+        Ok(tables.insert_qr_key(kid, secret))
+    }
+
+    // C/U
+
+    async fn upsert_qr_key(&self, kid: String, secret: String) -> anyhow::Result<qr_key::Data> {
+        let mut tables = self.0.lock().unwrap();
+        if let Some(data) = tables.qr_keys.values().find(|k| k.kid == kid) {
+            return Ok(data.clone());
+        }
+        Ok(tables.insert_qr_key(kid, secret))
+    }
+
+    // R
+
+    async fn get_qr_keys(&self) -> anyhow::Result<Vec<qr_key::Data>> {
+        let tables = self.0.lock().unwrap();
+        // ids increase with insertion
+        Ok(tables.qr_keys.values().rev().cloned().collect())
+    }
+
+    async fn get_duck_code_epoch(&self, duck_id: String) -> anyhow::Result<i32> {
+        let tables = self.0.lock().unwrap();
+        Ok(tables
+            .duck_codes
+            .values()
+            .find(|c| c.duck_id == duck_id)
+            .map(|c| c.epoch)
+            .unwrap_or(0))
+    }
+
+    // U
+
+    async fn bump_duck_code_epoch(&self, duck_id: String) -> anyhow::Result<i32> {
+        let mut tables = self.0.lock().unwrap();
+        if let Some(code) = tables
+            .duck_codes
+            .values_mut()
+            .find(|c| c.duck_id == duck_id)
+        {
+            code.epoch += 1;
+            return Ok(code.epoch);
+        }
+        let id = tables.new_id();
+        tables.duck_codes.insert(
+            id.clone(),
+            duck_code::Data {
+                id,
+                duck_id,
+                epoch: 1,
+            },
+        );
+        Ok(1)
+    }
+
+    async fn retire_qr_key(&self, kid: String) -> anyhow::Result<qr_key::Data> {
+        let mut tables = self.0.lock().unwrap();
+        let key = tables
+            .qr_keys
+            .values_mut()
+            .find(|k| k.kid == kid)
+            .ok_or_else(|| anyhow!("qr key {} not found", kid))?;
+        key.retired_at = Some(now());
+        Ok(key.clone())
+    }
+
+    async fn set_qr_key_secret(&self, kid: String, secret: String) -> anyhow::Result<qr_key::Data> {
+        let mut tables = self.0.lock().unwrap();
+        let key = tables
+            .qr_keys
+            .values_mut()
+            .find(|k| k.kid == kid)
+            .ok_or_else(|| anyhow!("qr key {} not found", kid))?;
+        key.secret = secret;
+        Ok(key.clone())
+    }
+}
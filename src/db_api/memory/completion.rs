@@ -0,0 +1,68 @@
+use crate::db_api::completion::{completion_duck, parse_rule, CompletionRule, CompletionStore};
+use crate::db_api::memory::{now, MemoryDB};
+use crate::prisma::completion_rule;
+use async_trait::async_trait;
+
+#[async_trait]
+impl CompletionStore for MemoryDB {
+    // R
+
+    async fn get_completion_rules(
+        &self,
+        season_id: Option<String>,
+        team: bool,
+    ) -> anyhow::Result<Vec<CompletionRule>> {
+        let tables = self.0.lock().unwrap();
+        tables
+            .completion_rules
+            .values()
+            .filter(|r| r.season_id == season_id && r.team.unwrap_or(false) == team)
+            .map(parse_rule)
+            .collect()
+    }
+
+    async fn get_completion_ducks(&self) -> anyhow::Result<Vec<completion_duck::Data>> {
+        let tables = self.0.lock().unwrap();
+        Ok(tables
+            .ducks
+            .values()
+            .map(|d| completion_duck::Data {
+                id: d.id.clone(),
+                topics: d.topics.clone(),
+                related_exhibit: tables.exhibit_of(&d.id).map(|e| {
+                    completion_duck::related_exhibit::Data {
+                        deleted_at: e.deleted_at,
+                    }
+                }),
+                season_id: d.season_id.clone(),
+                deleted_at: d.deleted_at,
+            })
+            .collect())
+    }
+
+    // U
+
+    async fn set_completion_rules(
+        &self,
+        season_id: Option<String>,
+        team: bool,
+        rules: Vec<CompletionRule>,
+    ) -> anyhow::Result<Vec<CompletionRule>> {
+        let mut tables = self.0.lock().unwrap();
+        tables
+            .completion_rules
+            .retain(|_, r| r.season_id != season_id || r.team.unwrap_or(false) != team);
+        for rule in &rules {
+            let id = tables.new_id();
+            let data = completion_rule::Data {
+                id: id.clone(),
+                created_at: now(),
+                rule: serde_json::to_value(rule)?,
+                season_id: season_id.clone(),
+                team: team.then_some(true),
+            };
+            tables.completion_rules.insert(id, data);
+        }
+        Ok(rules)
+    }
+}
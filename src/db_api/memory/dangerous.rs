@@ -0,0 +1,261 @@
+use crate::db_api::dangerous::{snapshot_info, DangerousOp, DangerousStore, SnapshotData};
+use crate::db_api::memory::{now, MemoryDB};
+use crate::prisma::{duck_history, exhibit, location, snapshot};
+use anyhow::{anyhow, bail};
+use async_trait::async_trait;
+use prisma_client_rust::chrono;
+use std::time::Duration;
+
+fn snapshot_info(s: &snapshot::Data) -> snapshot_info::Data {
+    snapshot_info::Data {
+        id: s.id.clone(),
+        created_at: s.created_at,
+        admin: s.admin.clone(),
+        route: s.route.clone(),
+        restored_at: s.restored_at,
+    }
+}
+
+#[async_trait]
+impl DangerousStore for MemoryDB {
+    // C
+
+    async fn collect_snapshot(&self, op: &DangerousOp) -> anyhow::Result<SnapshotData> {
+        let tables = self.0.lock().unwrap();
+        let archived = tables.archived_duck_ids();
+        let deleted = |duck_id: &Option<String>| match duck_id {
+            Some(duck_id) => !archived.contains(duck_id),
+            None => false,
+        };
+        let data = match op {
+            DangerousOp::DeleteAllDucks => SnapshotData {
+                ducks: tables
+                    .ducks
+                    .values()
+                    .filter(|d| !archived.contains(&d.id))
+                    .cloned()
+                    .collect(),
+                duck_history: tables
+                    .duck_history
+                    .values()
+                    .filter(|h| !archived.contains(&h.duck_id))
+                    .cloned()
+                    .collect(),
+                exhibits: tables
+                    .exhibits
+                    .values()
+                    .filter(|e| deleted(&e.related_duck_id))
+                    .cloned()
+                    .collect(),
+                locations: tables
+                    .locations
+                    .values()
+                    .filter(|l| deleted(&l.duck_id))
+                    .cloned()
+                    .collect(),
+            },
+            DangerousOp::DeleteAllExhibits => SnapshotData {
+                exhibits: tables.exhibits.values().cloned().collect(),
+                ..Default::default()
+            },
+            DangerousOp::DeleteAllLocations => SnapshotData {
+                locations: tables.locations.values().cloned().collect(),
+                ..Default::default()
+            },
+            DangerousOp::DeleteDuckHistory { user_id } => {
+                let user_id = match tables.user_by_wechat(user_id) {
+                    None => user_id.clone(),
+                    Some(user) => user.id.clone(),
+                };
+                SnapshotData {
+                    duck_history: tables
+                        .duck_history
+                        .values()
+                        .filter(|h| h.user_id == user_id && !archived.contains(&h.duck_id))
+                        .cloned()
+                        .collect(),
+                    ..Default::default()
+                }
+            }
+        };
+        Ok(data)
+    }
+
+    async fn save_snapshot(
+        &self,
+        admin: String,
+        route: String,
+        data: SnapshotData,
+    ) -> anyhow::Result<snapshot_info::Data> {
+        let mut tables = self.0.lock().unwrap();
+        let id = tables.new_id();
+        let data = snapshot::Data {
+            id: id.clone(),
+            created_at: now(),
+            admin,
+            route,
+            data: serde_json::to_value(data)?,
+            restored_at: None,
+        };
+        let info = snapshot_info(&data);
+        tables.snapshots.insert(id, data);
+        Ok(info)
+    }
+
+    async fn save_confirmation(
+        &self,
+        key: String,
+        digest: String,
+        ttl: Duration,
+    ) -> anyhow::Result<()> {
+        let expires_at = now() + chrono::Duration::from_std(ttl)?;
+        let mut tables = self.0.lock().unwrap();
+        tables.confirmations.insert(key, (expires_at, digest));
+        Ok(())
+    }
+
+    // R
+
+    async fn get_snapshots(&self) -> anyhow::Result<Vec<snapshot_info::Data>> {
+        let tables = self.0.lock().unwrap();
+        Ok(tables.snapshots.values().rev().map(snapshot_info).collect())
+    }
+
+    async fn count_ranked_users(&self, user_ids: Vec<String>) -> anyhow::Result<usize> {
+        let tables = self.0.lock().unwrap();
+        Ok(user_ids
+            .iter()
+            .filter_map(|id| tables.users.get(id))
+            .filter(|u| {
+                tables
+                    .rankings
+                    .values()
+                    .any(|r| r.user_wechat_open_id == u.wechat_open_id)
+            })
+            .count())
+    }
+
+    // U
+
+    async fn restore_snapshot(&self, id: String) -> anyhow::Result<i64> {
+        let mut tables = self.0.lock().unwrap();
+        let snapshot = tables
+            .snapshots
+            .get(&id)
+            .ok_or_else(|| anyhow!("snapshot {} not found", id))?;
+        let data: SnapshotData = serde_json::from_value(snapshot.data.clone())?;
+        let mut restored = 0;
+        for d in &data.ducks {
+            if !tables.ducks.contains_key(&d.id) {
+                let mut duck = d.clone();
+                duck.prev_duck_story_id = None;
+                tables.ducks.insert(d.id.clone(), duck);
+                restored += 1;
+            }
+        }
+        // story sequences, once every duck of the snapshot exists again
+        for d in &data.ducks {
+            if let Some(prev_duck_story_id) = &d.prev_duck_story_id {
+                if tables.ducks.contains_key(prev_duck_story_id) {
+                    tables.check_duck_connections(
+                        Some(&d.id),
+                        &None,
+                        &None,
+                        &Some(prev_duck_story_id.clone()),
+                        &None,
+                    )?;
+                    tables.ducks.get_mut(&d.id).unwrap().prev_duck_story_id =
+                        Some(prev_duck_story_id.clone());
+                }
+            }
+        }
+        for e in data.exhibits {
+            let related_duck_id = e
+                .related_duck_id
+                .clone()
+                .filter(|duck_id| tables.ducks.contains_key(duck_id));
+            if related_duck_id.is_some()
+                && tables
+                    .exhibits
+                    .values()
+                    .any(|other| other.id != e.id && other.related_duck_id == related_duck_id)
+            {
+                bail!("unique constraint failed on relatedDuckId");
+            }
+            match tables.exhibits.get_mut(&e.id) {
+                Some(existing) => {
+                    if related_duck_id.is_none() {
+                        continue;
+                    }
+                    existing.related_duck_id = related_duck_id;
+                }
+                None => {
+                    let id = e.id.clone();
+                    tables.exhibits.insert(
+                        id,
+                        exhibit::Data {
+                            related_duck_id,
+                            ..e
+                        },
+                    );
+                }
+            }
+            restored += 1;
+        }
+        for l in data.locations {
+            let duck_id = l
+                .duck_id
+                .clone()
+                .filter(|duck_id| tables.ducks.contains_key(duck_id));
+            tables.check_location_duck(Some(&l.id), &duck_id)?;
+            match tables.locations.get_mut(&l.id) {
+                Some(existing) => {
+                    if duck_id.is_none() {
+                        continue;
+                    }
+                    existing.duck_id = duck_id;
+                }
+                None => {
+                    let id = l.id.clone();
+                    tables.locations.insert(id, location::Data { duck_id, ..l });
+                }
+            }
+            restored += 1;
+        }
+        for h in data.duck_history {
+            // histories recorded again since, and those of removed players, are skipped
+            if tables.duck_history.contains_key(&h.id)
+                || tables
+                    .duck_history
+                    .values()
+                    .any(|other| other.user_id == h.user_id && other.duck_id == h.duck_id)
+                || !tables.ducks.contains_key(&h.duck_id)
+                || !tables.users.contains_key(&h.user_id)
+            {
+                continue;
+            }
+            tables.duck_history.insert(
+                h.id.clone(),
+                duck_history::Data {
+                    user: None,
+                    duck: None,
+                    ..h
+                },
+            );
+            restored += 1;
+        }
+        // only once every document is written, so that a failed restore can be run again
+        tables.snapshots.get_mut(&id).unwrap().restored_at = Some(now());
+        Ok(restored)
+    }
+
+    // D
+
+    async fn take_confirmation(&self, key: String) -> anyhow::Result<Option<String>> {
+        let mut tables = self.0.lock().unwrap();
+        Ok(match tables.confirmations.remove(&key) {
+            Some((expires_at, digest)) if expires_at > now() => Some(digest),
+            _ => None,
+        })
+    }
+}
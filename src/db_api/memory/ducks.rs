@@ -0,0 +1,332 @@
+use crate::db_api::ducks::{duck_info, DuckStore, NewDuckData, UpdateDuckData};
+use crate::db_api::memory::{now, MemoryDB, Tables};
+use crate::prisma::duck;
+use anyhow::{anyhow, bail};
+use async_trait::async_trait;
+use prisma_client_rust::chrono::{DateTime, FixedOffset};
+use std::collections::HashSet;
+
+impl Tables {
+    /// ids of the ducks of archived seasons, which bulk deletes keep
+    pub(super) fn archived_duck_ids(&self) -> HashSet<String> {
+        self.ducks
+            .values()
+            .filter(|d| {
+                d.season_id
+                    .as_ref()
+                    .and_then(|s| self.seasons.get(s))
+                    .map_or(false, |s| s.archived_at.is_some())
+            })
+            .map(|d| d.id.clone())
+            .collect()
+    }
+
+    fn insert_duck(&mut self, data: NewDuckData) -> anyhow::Result<duck::Data> {
+        self.check_duck_connections(
+            None,
+            &data.location_id,
+            &data.related_exhibit_id,
+            &data.prev_duck_story_id,
+            &data.season_id,
+        )?;
+        let id = self.new_id();
+        let created_at = now();
+        let duck = duck::Data {
+            id: id.clone(),
+            created_at,
+            updated_at: created_at,
+            title: serde_json::to_value(data.title)?,
+            story: serde_json::to_value(data.story)?,
+            location: None,
+            topics: serde_json::to_value(data.topics)?,
+            duck_icon_url: data.duck_icon_url,
+            is_hidden: data.is_hidden,
+            related_exhibit: None,
+            view_history: None,
+            next_duck_story: None,
+            prev_duck_story: None,
+            prev_duck_story_id: None,
+            prerequisite_ids: data.prerequisite_ids,
+            challenge: None,
+            hints: None,
+            season: None,
+            season_id: data.season_id,
+            deleted_at: None,
+        };
+        self.ducks.insert(id.clone(), duck);
+        self.connect_duck(
+            &id,
+            data.location_id,
+            data.related_exhibit_id,
+            data.prev_duck_story_id,
+        );
+        Ok(self.ducks[&id].clone())
+    }
+
+    /// fail like prisma does when a connected record is missing or already taken
+    pub(super) fn check_duck_connections(
+        &self,
+        duck_id: Option<&str>,
+        location_id: &Option<String>,
+        related_exhibit_id: &Option<String>,
+        prev_duck_story_id: &Option<String>,
+        season_id: &Option<String>,
+    ) -> anyhow::Result<()> {
+        if let Some(location_id) = location_id {
+            if !self.locations.contains_key(location_id) {
+                bail!("location {} not found", location_id);
+            }
+        }
+        if let Some(related_exhibit_id) = related_exhibit_id {
+            if !self.exhibits.contains_key(related_exhibit_id) {
+                bail!("exhibit {} not found", related_exhibit_id);
+            }
+        }
+        if let Some(prev_duck_story_id) = prev_duck_story_id {
+            if !self.ducks.contains_key(prev_duck_story_id) {
+                bail!("duck {} not found", prev_duck_story_id);
+            }
+            if let Some(next) = self.next_duck_of(prev_duck_story_id) {
+                if Some(next.id.as_str()) != duck_id {
+                    bail!("unique constraint failed on prevDuckStoryId");
+                }
+            }
+        }
+        if let Some(season_id) = season_id {
+            if !self.seasons.contains_key(season_id) {
+                bail!("season {} not found", season_id);
+            }
+        }
+        Ok(())
+    }
+
+    fn connect_duck(
+        &mut self,
+        duck_id: &str,
+        location_id: Option<String>,
+        related_exhibit_id: Option<String>,
+        prev_duck_story_id: Option<String>,
+    ) {
+        if let Some(location_id) = location_id {
+            if let Some(location) = self.locations.get_mut(&location_id) {
+                location.duck_id = Some(duck_id.to_string());
+            }
+        }
+        if let Some(related_exhibit_id) = related_exhibit_id {
+            if let Some(exhibit) = self.exhibits.get_mut(&related_exhibit_id) {
+                exhibit.related_duck_id = Some(duck_id.to_string());
+            }
+        }
+        if let Some(prev_duck_story_id) = prev_duck_story_id {
+            if let Some(duck) = self.ducks.get_mut(duck_id) {
+                duck.prev_duck_story_id = Some(prev_duck_story_id);
+            }
+        }
+    }
+
+    /// remove a duck and apply the referential actions of the schema
+    fn remove_duck(&mut self, id: &str) -> Option<duck::Data> {
+        let duck = self.ducks.remove(id)?;
+        self.duck_history.retain(|_, h| h.duck_id != id);
+        for location in self.locations.values_mut() {
+            if location.duck_id.as_deref() == Some(id) {
+                location.duck_id = None;
+            }
+        }
+        for exhibit in self.exhibits.values_mut() {
+            if exhibit.related_duck_id.as_deref() == Some(id) {
+                exhibit.related_duck_id = None;
+            }
+        }
+        Some(duck)
+    }
+
+    pub(super) fn duck_info(&self, duck: &duck::Data) -> duck_info::Data {
+        duck_info::Data {
+            id: duck.id.clone(),
+            title: duck.title.clone(),
+            story: duck.story.clone(),
+            location: self
+                .location_of(&duck.id)
+                .map(|l| duck_info::location::Data {
+                    id: l.id.clone(),
+                    description: l.description.clone(),
+                }),
+            topics: duck.topics.clone(),
+            duck_icon_url: duck.duck_icon_url.clone(),
+            is_hidden: duck.is_hidden,
+            related_exhibit: self
+                .exhibit_of(&duck.id)
+                .map(|e| duck_info::related_exhibit::Data {
+                    location: e.location.clone(),
+                    title: e.title.clone(),
+                    sign: e.sign.clone(),
+                    artists: e.artists.clone(),
+                }),
+            next_duck_story: self.next_duck_of(&duck.id).map(|n| {
+                duck_info::next_duck_story::Data {
+                    id: n.id.clone(),
+                    title: n.title.clone(),
+                    location: self.location_of(&n.id).cloned(),
+                    topics: n.topics.clone(),
+                    is_hidden: n.is_hidden,
+                }
+            }),
+            prev_duck_story_id: duck.prev_duck_story_id.clone(),
+            prerequisite_ids: duck.prerequisite_ids.clone(),
+            challenge: duck.challenge.clone(),
+            hints: duck.hints.clone(),
+            season_id: duck.season_id.clone(),
+            deleted_at: duck.deleted_at,
+        }
+    }
+
+    /// set or clear the trash marker of a duck
+    fn trash_duck(
+        &mut self,
+        id: &str,
+        deleted_at: Option<DateTime<FixedOffset>>,
+    ) -> anyhow::Result<duck::Data> {
+        let duck = self
+            .ducks
+            .get_mut(id)
+            .ok_or_else(|| anyhow!("duck {} not found", id))?;
+        duck.deleted_at = deleted_at;
+        Ok(duck.clone())
+    }
+}
+
+#[async_trait]
+impl DuckStore for MemoryDB {
+    // C
+
+    async fn create_duck(&self, data: NewDuckData) -> anyhow::Result<duck::Data> {
+        self.0.lock().unwrap().insert_duck(data)
+    }
+
+    async fn create_many_ducks(&self, data: Vec<NewDuckData>) -> anyhow::Result<i64> {
+        let mut tables = self.0.lock().unwrap();
+        let mut count = 0;
+        for duck in data {
+            tables.insert_duck(duck)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    // R
+
+    async fn get_duck(&self, id: String) -> anyhow::Result<Option<duck_info::Data>> {
+        let tables = self.0.lock().unwrap();
+        Ok(tables
+            .ducks
+            .get(&id)
+            .filter(|d| d.deleted_at.is_none())
+            .map(|d| tables.duck_info(d)))
+    }
+
+    async fn get_all_ducks(&self) -> anyhow::Result<Vec<duck_info::Data>> {
+        let tables = self.0.lock().unwrap();
+        Ok(tables
+            .ducks
+            .values()
+            .filter(|d| d.deleted_at.is_none())
+            .map(|d| tables.duck_info(d))
+            .collect())
+    }
+
+    async fn get_trashed_ducks(&self) -> anyhow::Result<Vec<duck_info::Data>> {
+        let tables = self.0.lock().unwrap();
+        Ok(tables
+            .ducks
+            .values()
+            .filter(|d| d.deleted_at.is_some())
+            .map(|d| tables.duck_info(d))
+            .collect())
+    }
+
+    // U
+
+    async fn update_duck(&self, id: String, data: UpdateDuckData) -> anyhow::Result<duck::Data> {
+        let mut tables = self.0.lock().unwrap();
+        if !tables.ducks.contains_key(&id) {
+            bail!("duck {} not found", id);
+        }
+        tables.check_duck_connections(
+            Some(&id),
+            &data.location_id,
+            &data.related_exhibit_id,
+            &data.prev_duck_story_id,
+            &data.season_id.clone().flatten(),
+        )?;
+        let duck = tables.ducks.get_mut(&id).unwrap();
+        if let Some(title) = data.title {
+            duck.title = serde_json::to_value(title)?;
+        }
+        if let Some(story) = data.story {
+            duck.story = serde_json::to_value(story)?;
+        }
+        if let Some(topics) = data.topics {
+            duck.topics = serde_json::to_value(topics)?;
+        }
+        if let Some(duck_icon_url) = data.duck_icon_url {
+            duck.duck_icon_url = duck_icon_url;
+        }
+        if let Some(is_hidden) = data.is_hidden {
+            duck.is_hidden = is_hidden;
+        }
+        if let Some(prerequisite_ids) = data.prerequisite_ids {
+            duck.prerequisite_ids = prerequisite_ids;
+        }
+        if let Some(season_id) = data.season_id {
+            duck.season_id = season_id;
+        }
+        duck.updated_at = now();
+        tables.connect_duck(
+            &id,
+            data.location_id,
+            data.related_exhibit_id,
+            data.prev_duck_story_id,
+        );
+        Ok(tables.ducks[&id].clone())
+    }
+
+    async fn restore_duck(&self, id: String) -> anyhow::Result<duck::Data> {
+        self.0.lock().unwrap().trash_duck(&id, None)
+    }
+
+    // D
+
+    async fn delete_duck(&self, id: String) -> anyhow::Result<duck::Data> {
+        self.0.lock().unwrap().trash_duck(&id, Some(now()))
+    }
+
+    async fn delete_duck_history(&self, user_id: String) -> anyhow::Result<i64> {
+        let mut tables = self.0.lock().unwrap();
+        let user_id = match tables.user_by_wechat(&user_id) {
+            None => user_id,
+            Some(user) => user.id.clone(),
+        };
+        let archived = tables.archived_duck_ids();
+        let before = tables.duck_history.len();
+        tables
+            .duck_history
+            .retain(|_, h| h.user_id != user_id || archived.contains(&h.duck_id));
+        Ok((before - tables.duck_history.len()) as i64)
+    }
+
+    async fn delete_all_ducks(&self) -> anyhow::Result<i64> {
+        let mut tables = self.0.lock().unwrap();
+        let archived = tables.archived_duck_ids();
+        let ids: Vec<String> = tables
+            .ducks
+            .keys()
+            .filter(|id| !archived.contains(*id))
+            .cloned()
+            .collect();
+        for id in &ids {
+            tables.remove_duck(id);
+        }
+        Ok(ids.len() as i64)
+    }
+}
@@ -0,0 +1,21 @@
+use crate::db_api::events::{receive_events, EventStore, GameEvent};
+use crate::db_api::memory::MemoryDB;
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+
+#[async_trait]
+impl EventStore for MemoryDB {
+    // C
+
+    async fn publish_event(&self, event: GameEvent) -> anyhow::Result<()> {
+        // no subscriber is not an error
+        let _ = self.1.send(event);
+        Ok(())
+    }
+
+    // R
+
+    fn subscribe_events(&self) -> BoxStream<'static, GameEvent> {
+        receive_events(self.1.subscribe())
+    }
+}
@@ -0,0 +1,133 @@
+use crate::db_api::exhibits::{ExhibitStore, NewExhibitData, UpdateExhibitData};
+use crate::db_api::memory::{now, MemoryDB, Tables};
+use crate::prisma::exhibit;
+use anyhow::anyhow;
+use async_trait::async_trait;
+
+impl Tables {
+    fn insert_exhibit(&mut self, data: NewExhibitData) -> anyhow::Result<exhibit::Data> {
+        let id = self.new_id();
+        let created_at = now();
+        let exhibit = exhibit::Data {
+            id: id.clone(),
+            created_at,
+            updated_at: created_at,
+            location: serde_json::to_value(data.location)?,
+            title: serde_json::to_value(data.title)?,
+            sign: serde_json::to_value(data.sign)?,
+            artists: serde_json::to_value(data.artists)?,
+            related_duck_id: None,
+            related_duck: None,
+            deleted_at: None,
+        };
+        self.exhibits.insert(id, exhibit.clone());
+        Ok(exhibit)
+    }
+}
+
+#[async_trait]
+impl ExhibitStore for MemoryDB {
+    // C
+
+    async fn create_exhibit(&self, data: NewExhibitData) -> anyhow::Result<exhibit::Data> {
+        self.0.lock().unwrap().insert_exhibit(data)
+    }
+
+    async fn create_many_exhibits(&self, data: Vec<NewExhibitData>) -> anyhow::Result<i64> {
+        let mut tables = self.0.lock().unwrap();
+        let mut count = 0;
+        for d in data {
+            tables.insert_exhibit(d)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    // R
+
+    async fn get_exhibit(&self, id: String) -> anyhow::Result<Option<exhibit::Data>> {
+        let tables = self.0.lock().unwrap();
+        Ok(tables
+            .exhibits
+            .get(&id)
+            .filter(|d| d.deleted_at.is_none())
+            .cloned())
+    }
+
+    async fn get_all_exhibits(&self) -> anyhow::Result<Vec<exhibit::Data>> {
+        let tables = self.0.lock().unwrap();
+        Ok(tables
+            .exhibits
+            .values()
+            .filter(|d| d.deleted_at.is_none())
+            .cloned()
+            .collect())
+    }
+
+    async fn get_trashed_exhibits(&self) -> anyhow::Result<Vec<exhibit::Data>> {
+        let tables = self.0.lock().unwrap();
+        Ok(tables
+            .exhibits
+            .values()
+            .filter(|d| d.deleted_at.is_some())
+            .cloned()
+            .collect())
+    }
+
+    // U
+
+    async fn update_exhibit(
+        &self,
+        id: String,
+        data: UpdateExhibitData,
+    ) -> anyhow::Result<exhibit::Data> {
+        let mut tables = self.0.lock().unwrap();
+        let exhibit = tables
+            .exhibits
+            .get_mut(&id)
+            .ok_or_else(|| anyhow!("exhibit {} not found", id))?;
+        if let Some(location) = data.location {
+            exhibit.location = serde_json::to_value(location)?;
+        }
+        if let Some(title) = data.title {
+            exhibit.title = serde_json::to_value(title)?;
+        }
+        if let Some(sign) = data.sign {
+            exhibit.sign = serde_json::to_value(sign)?;
+        }
+        if let Some(artists) = data.artists {
+            exhibit.artists = serde_json::to_value(artists)?;
+        }
+        exhibit.updated_at = now();
+        Ok(exhibit.clone())
+    }
+
+    async fn restore_exhibit(&self, id: String) -> anyhow::Result<exhibit::Data> {
+        let mut tables = self.0.lock().unwrap();
+        let exhibit = tables
+            .exhibits
+            .get_mut(&id)
+            .ok_or_else(|| anyhow!("exhibit {} not found", id))?;
+        exhibit.deleted_at = None;
+        Ok(exhibit.clone())
+    }
+
+    // D
+
+    async fn delete_exhibit(&self, id: String) -> anyhow::Result<exhibit::Data> {
+        let mut tables = self.0.lock().unwrap();
+        let exhibit = tables
+            .exhibits
+            .get_mut(&id)
+            .ok_or_else(|| anyhow!("exhibit {} not found", id))?;
+        exhibit.deleted_at = Some(now());
+        Ok(exhibit.clone())
+    }
+
+    async fn delete_all_exhibits(&self) -> anyhow::Result<i64> {
+        let mut tables = self.0.lock().unwrap();
+        let count = tables.exhibits.len() as i64;
+        tables.exhibits.clear();
+        Ok(count)
+    }
+}
@@ -0,0 +1,41 @@
+use crate::db_api::geofence::{DiscoveryStore, NewDiscoveryFlag};
+use crate::db_api::memory::{now, MemoryDB};
+use crate::prisma::{discovery_flag, location};
+use async_trait::async_trait;
+
+#[async_trait]
+impl DiscoveryStore for MemoryDB {
+    // C
+
+    async fn record_discovery_flag(&self, flag: NewDiscoveryFlag) -> anyhow::Result<()> {
+        let mut tables = self.0.lock().unwrap();
+        let id = tables.new_id();
+        let data = discovery_flag::Data {
+            id: id.clone(),
+            created_at: now(),
+            wechat_open_id: flag.wechat_open_id,
+            duck_id: flag.duck_id,
+            reason: flag.reason.as_str().to_string(),
+            detail: flag.detail,
+            rejected: flag.rejected,
+        };
+        tables.discovery_flags.insert(id, data);
+        Ok(())
+    }
+
+    // R
+
+    async fn get_discovery_flags(&self) -> anyhow::Result<Vec<discovery_flag::Data>> {
+        let tables = self.0.lock().unwrap();
+        // ids increase with insertion
+        Ok(tables.discovery_flags.values().rev().cloned().collect())
+    }
+
+    async fn get_location_of_duck(
+        &self,
+        duck_id: String,
+    ) -> anyhow::Result<Option<location::Data>> {
+        let tables = self.0.lock().unwrap();
+        Ok(tables.location_of(&duck_id).cloned())
+    }
+}
@@ -0,0 +1,81 @@
+use crate::db_api::hints::{Hint, HintStore};
+use crate::db_api::memory::{now, MemoryDB};
+use crate::prisma::{duck, hint_reveal};
+use anyhow::anyhow;
+use async_trait::async_trait;
+
+#[async_trait]
+impl HintStore for MemoryDB {
+    // C
+
+    async fn record_hint_reveal(
+        &self,
+        wechat_openid: String,
+        duck_id: String,
+        hint: usize,
+        cost: u32,
+    ) -> anyhow::Result<hint_reveal::Data> {
+        let hint = i32::try_from(hint)?;
+        let mut tables = self.0.lock().unwrap();
+        let revealed = tables
+            .hint_reveals
+            .values()
+            .any(|r| r.wechat_open_id == wechat_openid && r.duck_id == duck_id && r.hint == hint);
+        if revealed {
+            return Err(anyhow!(
+                "hint {} of duck {} already revealed",
+                hint,
+                duck_id
+            ));
+        }
+        let id = tables.new_id();
+        let reveal = hint_reveal::Data {
+            id: id.clone(),
+            created_at: now(),
+            wechat_open_id: wechat_openid,
+            duck_id,
+            hint,
+            cost: i32::try_from(cost)?,
+        };
+        tables.hint_reveals.insert(id, reveal.clone());
+        Ok(reveal)
+    }
+
+    // R
+
+    async fn get_hint_reveals(
+        &self,
+        wechat_openid: Option<String>,
+        duck_id: Option<String>,
+    ) -> anyhow::Result<Vec<hint_reveal::Data>> {
+        let tables = self.0.lock().unwrap();
+        Ok(tables
+            .hint_reveals
+            .values()
+            .filter(|r| {
+                wechat_openid
+                    .as_ref()
+                    .map_or(true, |w| &r.wechat_open_id == w)
+            })
+            .filter(|r| duck_id.as_ref().map_or(true, |d| &r.duck_id == d))
+            .cloned()
+            .collect())
+    }
+
+    // U
+
+    async fn set_duck_hints(
+        &self,
+        duck_id: String,
+        hints: Vec<Hint>,
+    ) -> anyhow::Result<duck::Data> {
+        let mut tables = self.0.lock().unwrap();
+        let duck = tables
+            .ducks
+            .get_mut(&duck_id)
+            .ok_or_else(|| anyhow!("duck {} not found", duck_id))?;
+        duck.hints = Some(serde_json::to_value(hints)?);
+        duck.updated_at = now();
+        Ok(duck.clone())
+    }
+}
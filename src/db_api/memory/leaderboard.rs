@@ -0,0 +1,41 @@
+use crate::db_api::leaderboard::{leaderboard_entries, LeaderboardStore, RankedPlayer};
+use crate::db_api::memory::MemoryDB;
+use async_trait::async_trait;
+
+#[async_trait]
+impl LeaderboardStore for MemoryDB {
+    // R
+
+    async fn get_leaderboard(
+        &self,
+        season_id: Option<String>,
+    ) -> anyhow::Result<Vec<RankedPlayer>> {
+        let tables = self.0.lock().unwrap();
+        let rankings = match &season_id {
+            Some(season_id) => tables
+                .season_rankings
+                .values()
+                .filter(|r| &r.season_id == season_id)
+                .map(|r| (r.ranking, r.created_at, r.user_wechat_open_id.clone()))
+                .collect(),
+            None => tables
+                .rankings
+                .values()
+                .map(|r| (r.ranking, r.created_at, r.user_wechat_open_id.clone()))
+                .collect(),
+        };
+        let nicknames = tables
+            .users
+            .values()
+            .filter_map(|u| Some((u.wechat_open_id.clone(), u.nickname.clone()?)))
+            .collect();
+        Ok(leaderboard_entries(rankings, &nicknames))
+    }
+
+    // D
+
+    /// nothing is cached in memory
+    async fn clear_leaderboard_cache(&self, _season_id: Option<String>) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+use crate::db_api::locations::{LocationStore, NewLocationData, UpdateLocationData};
+use crate::db_api::memory::{now, MemoryDB, Tables};
+use crate::prisma::location;
+use anyhow::{anyhow, bail};
+use async_trait::async_trait;
+
+impl Tables {
+    fn insert_location(&mut self, data: NewLocationData) -> anyhow::Result<location::Data> {
+        self.check_location_duck(None, &data.duck_id)?;
+        let id = self.new_id();
+        let location = location::Data {
+            id: id.clone(),
+            description: serde_json::to_value(data.description)?,
+            coordinate: serde_json::to_value(data.coordinate)?,
+            duck_id: data.duck_id,
+            duck: None,
+            deleted_at: None,
+            geofence_radius: data.geofence_radius,
+            geofence_mode: data.geofence_mode.map(|m| m.as_str().to_string()),
+        };
+        self.locations.insert(id, location.clone());
+        Ok(location)
+    }
+
+    /// the duck must exist, and `duckId` is unique among locations
+    pub(super) fn check_location_duck(
+        &self,
+        location_id: Option<&str>,
+        duck_id: &Option<String>,
+    ) -> anyhow::Result<()> {
+        if let Some(duck_id) = duck_id {
+            if !self.ducks.contains_key(duck_id) {
+                bail!("duck {} not found", duck_id);
+            }
+            if let Some(other) = self.location_of(duck_id) {
+                if Some(other.id.as_str()) != location_id {
+                    bail!("unique constraint failed on duckId");
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl LocationStore for MemoryDB {
+    // C
+
+    async fn create_location(&self, data: NewLocationData) -> anyhow::Result<location::Data> {
+        self.0.lock().unwrap().insert_location(data)
+    }
+
+    async fn create_many_locations(&self, data: Vec<NewLocationData>) -> anyhow::Result<i64> {
+        let mut tables = self.0.lock().unwrap();
+        let mut count = 0;
+        for d in data {
+            tables.insert_location(d)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    // R
+
+    async fn get_location(&self, id: String) -> anyhow::Result<Option<location::Data>> {
+        let tables = self.0.lock().unwrap();
+        Ok(tables
+            .locations
+            .get(&id)
+            .filter(|d| d.deleted_at.is_none())
+            .cloned())
+    }
+
+    async fn get_all_locations(&self) -> anyhow::Result<Vec<location::Data>> {
+        let tables = self.0.lock().unwrap();
+        Ok(tables
+            .locations
+            .values()
+            .filter(|d| d.deleted_at.is_none())
+            .cloned()
+            .collect())
+    }
+
+    async fn get_trashed_locations(&self) -> anyhow::Result<Vec<location::Data>> {
+        let tables = self.0.lock().unwrap();
+        Ok(tables
+            .locations
+            .values()
+            .filter(|d| d.deleted_at.is_some())
+            .cloned()
+            .collect())
+    }
+
+    // U
+
+    async fn update_location(
+        &self,
+        id: String,
+        data: UpdateLocationData,
+    ) -> anyhow::Result<location::Data> {
+        let mut tables = self.0.lock().unwrap();
+        if !tables.locations.contains_key(&id) {
+            bail!("location {} not found", id);
+        }
+        tables.check_location_duck(Some(&id), &data.duck_id)?;
+        let location = tables.locations.get_mut(&id).unwrap();
+        if let Some(description) = data.description {
+            location.description = serde_json::to_value(description)?;
+        }
+        if let Some(coordinate) = data.coordinate {
+            location.coordinate = serde_json::to_value(coordinate)?;
+        }
+        if let Some(duck_id) = data.duck_id {
+            location.duck_id = Some(duck_id);
+        }
+        if let Some(radius) = data.geofence_radius {
+            location.geofence_radius = Some(radius);
+        }
+        if let Some(mode) = data.geofence_mode {
+            location.geofence_mode = Some(mode.as_str().to_string());
+        }
+        Ok(location.clone())
+    }
+
+    async fn restore_location(&self, id: String) -> anyhow::Result<location::Data> {
+        let mut tables = self.0.lock().unwrap();
+        let location = tables
+            .locations
+            .get_mut(&id)
+            .ok_or_else(|| anyhow!("location {} not found", id))?;
+        location.deleted_at = None;
+        Ok(location.clone())
+    }
+
+    // D
+
+    async fn delete_location(&self, id: String) -> anyhow::Result<location::Data> {
+        let mut tables = self.0.lock().unwrap();
+        let location = tables
+            .locations
+            .get_mut(&id)
+            .ok_or_else(|| anyhow!("location {} not found", id))?;
+        location.deleted_at = Some(now());
+        Ok(location.clone())
+    }
+
+    async fn delete_all_locations(&self) -> anyhow::Result<i64> {
+        let mut tables = self.0.lock().unwrap();
+        let count = tables.locations.len() as i64;
+        tables.locations.clear();
+        Ok(count)
+    }
+}
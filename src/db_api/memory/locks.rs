@@ -0,0 +1,31 @@
+use crate::db_api::locks::{LockStore, LOCK_ATTEMPTS, LOCK_RETRY_MILLISECONDS};
+use crate::db_api::memory::MemoryDB;
+use anyhow::anyhow;
+use async_trait::async_trait;
+use std::time::Duration;
+
+#[async_trait]
+impl LockStore for MemoryDB {
+    async fn lock(&self, name: String) -> anyhow::Result<String> {
+        for _ in 0..LOCK_ATTEMPTS {
+            {
+                let mut tables = self.0.lock().unwrap();
+                if !tables.locks.contains_key(&name) {
+                    let token = tables.new_id();
+                    tables.locks.insert(name, token.clone());
+                    return Ok(token);
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(LOCK_RETRY_MILLISECONDS)).await;
+        }
+        Err(anyhow!("lock {} is busy", name))
+    }
+
+    async fn unlock(&self, name: String, token: String) -> anyhow::Result<()> {
+        let mut tables = self.0.lock().unwrap();
+        if tables.locks.get(&name) == Some(&token) {
+            tables.locks.remove(&name);
+        }
+        Ok(())
+    }
+}
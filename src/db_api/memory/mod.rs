@@ -0,0 +1,130 @@
+//! in-memory storage backend, which needs no outside services
+mod achievements;
+mod admins;
+mod archive;
+mod audit;
+mod challenges;
+mod codes;
+mod completion;
+mod dangerous;
+mod ducks;
+mod events;
+mod exhibits;
+mod geofence;
+mod hints;
+mod leaderboard;
+mod locations;
+mod locks;
+mod nearby;
+mod public;
+mod rankings;
+mod seasons;
+mod story;
+mod teams;
+
+use crate::admin_auth::gen_token;
+use crate::db_api::codes::FIRST_KID;
+use crate::db_api::events::{GameEvent, EVENTS_CAPACITY};
+use crate::prisma::{
+    achievement, admin_account, audit_log, challenge_attempt, completion_rule, discovery_flag,
+    duck, duck_code, duck_history, exhibit, hint_reveal, location, pending_discovery, qr_key,
+    ranking, season, season_ranking, snapshot, team, team_ranking, user, user_achievement,
+};
+use prisma_client_rust::chrono::{DateTime, FixedOffset, Utc};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+
+/// keeps every collection in memory, following the relations of the prisma schema,
+/// and sends events to the subscribers of this instance only
+pub struct MemoryDB(Mutex<Tables>, broadcast::Sender<GameEvent>);
+
+impl Default for MemoryDB {
+    fn default() -> Self {
+        let (events, _) = broadcast::channel(EVENTS_CAPACITY);
+        MemoryDB(Mutex::default(), events)
+    }
+}
+
+impl MemoryDB {
+    /// empty database as after startup, with the first key signing duck codes
+    pub fn started() -> Self {
+        let db = MemoryDB::default();
+        db.0.lock()
+            .unwrap()
+            .insert_qr_key(FIRST_KID.to_string(), gen_token());
+        db
+    }
+}
+
+/// documents of each collection, keyed by id (ids increase with insertion)
+#[derive(Default)]
+struct Tables {
+    last_id: u64,
+    users: BTreeMap<String, user::Data>,
+    duck_history: BTreeMap<String, duck_history::Data>,
+    ducks: BTreeMap<String, duck::Data>,
+    locations: BTreeMap<String, location::Data>,
+    exhibits: BTreeMap<String, exhibit::Data>,
+    rankings: BTreeMap<String, ranking::Data>,
+    ranking_counter: i32,
+    admin_accounts: BTreeMap<String, admin_account::Data>,
+    audit_log: BTreeMap<String, audit_log::Data>,
+    snapshots: BTreeMap<String, snapshot::Data>,
+    /// expiry and snapshot digest of confirmation tokens, by key
+    confirmations: BTreeMap<String, (DateTime<FixedOffset>, String)>,
+    qr_keys: BTreeMap<String, qr_key::Data>,
+    duck_codes: BTreeMap<String, duck_code::Data>,
+    discovery_flags: BTreeMap<String, discovery_flag::Data>,
+    completion_rules: BTreeMap<String, completion_rule::Data>,
+    seasons: BTreeMap<String, season::Data>,
+    season_rankings: BTreeMap<String, season_ranking::Data>,
+    achievements: BTreeMap<String, achievement::Data>,
+    user_achievements: BTreeMap<String, user_achievement::Data>,
+    pending_discoveries: BTreeMap<String, pending_discovery::Data>,
+    challenge_attempts: BTreeMap<String, challenge_attempt::Data>,
+    hint_reveals: BTreeMap<String, hint_reveal::Data>,
+    teams: BTreeMap<String, team::Data>,
+    team_rankings: BTreeMap<String, team_ranking::Data>,
+    /// token of each lock held, by name
+    locks: BTreeMap<String, String>,
+}
+
+impl Tables {
+    /// generate an ObjectId-like hex string
+    fn new_id(&mut self) -> String {
+        self.last_id += 1;
+        format!("{:024x}", self.last_id)
+    }
+
+    fn user_by_wechat(&self, wechat_openid: &str) -> Option<&user::Data> {
+        self.users
+            .values()
+            .find(|u| u.wechat_open_id == wechat_openid)
+    }
+
+    /// location connected to a duck
+    fn location_of(&self, duck_id: &str) -> Option<&location::Data> {
+        self.locations
+            .values()
+            .find(|l| l.duck_id.as_deref() == Some(duck_id))
+    }
+
+    /// exhibit connected to a duck
+    fn exhibit_of(&self, duck_id: &str) -> Option<&exhibit::Data> {
+        self.exhibits
+            .values()
+            .find(|e| e.related_duck_id.as_deref() == Some(duck_id))
+    }
+
+    /// duck whose previous story is the given duck
+    fn next_duck_of(&self, duck_id: &str) -> Option<&duck::Data> {
+        self.ducks
+            .values()
+            .find(|d| d.prev_duck_story_id.as_deref() == Some(duck_id))
+    }
+}
+
+fn now() -> DateTime<FixedOffset> {
+    Utc::now().into()
+}
@@ -0,0 +1,28 @@
+use crate::coordinates::Coordinate;
+use crate::db_api::memory::MemoryDB;
+use crate::db_api::nearby::NearbyStore;
+use async_trait::async_trait;
+
+#[async_trait]
+impl NearbyStore for MemoryDB {
+    // R
+
+    async fn nearby_locations(
+        &self,
+        center: Coordinate,
+        radius: f64,
+    ) -> anyhow::Result<Vec<(String, f64)>> {
+        let tables = self.0.lock().unwrap();
+        let mut data: Vec<(String, f64)> = tables
+            .locations
+            .values()
+            .filter_map(|l| {
+                let coordinate = Coordinate::from_value(&l.coordinate).ok()?;
+                let distance = center.distance(&coordinate)?;
+                (distance <= radius).then(|| (l.id.clone(), distance))
+            })
+            .collect();
+        data.sort_by(|a, b| a.1.total_cmp(&b.1));
+        Ok(data)
+    }
+}
@@ -0,0 +1,231 @@
+use crate::db_api::memory::{now, MemoryDB, Tables};
+use crate::db_api::public::{duck_preview, preview_without_trash, user_info, UserStore};
+use crate::prisma::{duck, duck_history, user};
+use anyhow::bail;
+use async_trait::async_trait;
+use user_info::duck_history::duck as history_duck;
+
+impl Tables {
+    /// find the user by wechat openid, creating one if missing
+    pub(super) fn upsert_user(&mut self, wechat_openid: String) -> user::Data {
+        if let Some(user) = self.user_by_wechat(&wechat_openid) {
+            return user.clone();
+        }
+        let id = self.new_id();
+        let user = user::Data {
+            id: id.clone(),
+            created_at: now(),
+            wechat_open_id: wechat_openid,
+            nickname: None,
+            duck_history: None,
+            ranking: None,
+            achievements: None,
+            team: None,
+            team_id: None,
+        };
+        self.users.insert(id, user.clone());
+        user
+    }
+
+    /// previews of the ducks matching a filter, without the trash
+    fn preview_ducks(&self, filter: impl Fn(&duck::Data) -> bool) -> Vec<duck_preview::Data> {
+        let ducks = self
+            .ducks
+            .values()
+            .filter(|d| d.deleted_at.is_none() && filter(d))
+            .map(|d| duck_preview::Data {
+                id: d.id.clone(),
+                title: d.title.clone(),
+                location: self
+                    .location_of(&d.id)
+                    .map(|l| duck_preview::location::Data {
+                        id: l.id.clone(),
+                        coordinate: l.coordinate.clone(),
+                        description: l.description.clone(),
+                        deleted_at: l.deleted_at,
+                    }),
+                topics: d.topics.clone(),
+                is_hidden: d.is_hidden,
+                season_id: d.season_id.clone(),
+                deleted_at: d.deleted_at,
+            })
+            .collect();
+        preview_without_trash(ducks)
+    }
+
+    pub(super) fn user_info(&self, user: &user::Data) -> user_info::Data {
+        user_info::Data {
+            id: user.id.clone(),
+            created_at: user.created_at,
+            wechat_open_id: user.wechat_open_id.clone(),
+            nickname: user.nickname.clone(),
+            ranking: self
+                .rankings
+                .values()
+                .find(|r| r.user_wechat_open_id == user.wechat_open_id)
+                .map(|r| user_info::ranking::Data { ranking: r.ranking }),
+            duck_history: self
+                .duck_history
+                .values()
+                .filter(|h| h.user_id == user.id)
+                .filter_map(|h| {
+                    let duck = self.ducks.get(&h.duck_id)?;
+                    Some(user_info::duck_history::Data {
+                        created_at: h.created_at,
+                        duck: self.history_duck(duck),
+                    })
+                })
+                .collect(),
+            achievements: self
+                .user_achievements
+                .values()
+                .filter(|a| a.user_wechat_open_id == user.wechat_open_id)
+                .filter_map(|a| {
+                    let achievement = self.achievements.get(&a.achievement_id)?;
+                    Some(user_info::achievements::Data {
+                        created_at: a.created_at,
+                        achievement: user_info::achievements::achievement::Data {
+                            id: achievement.id.clone(),
+                            title: achievement.title.clone(),
+                            description: achievement.description.clone(),
+                            icon_url: achievement.icon_url.clone(),
+                        },
+                    })
+                })
+                .collect(),
+            team: user
+                .team_id
+                .as_ref()
+                .and_then(|id| self.teams.get(id))
+                .map(|t| user_info::team::Data {
+                    id: t.id.clone(),
+                    name: t.name.clone(),
+                    join_code: t.join_code.clone(),
+                }),
+        }
+        .without_trash()
+    }
+
+    fn history_duck(&self, duck: &duck::Data) -> history_duck::Data {
+        history_duck::Data {
+            id: duck.id.clone(),
+            title: duck.title.clone(),
+            story: duck.story.clone(),
+            location: self
+                .location_of(&duck.id)
+                .map(|l| history_duck::location::Data {
+                    id: l.id.clone(),
+                    coordinate: l.coordinate.clone(),
+                    description: l.description.clone(),
+                    deleted_at: l.deleted_at,
+                }),
+            topics: duck.topics.clone(),
+            duck_icon_url: duck.duck_icon_url.clone(),
+            is_hidden: duck.is_hidden,
+            related_exhibit: self.exhibit_of(&duck.id).map(|e| {
+                history_duck::related_exhibit::Data {
+                    location: e.location.clone(),
+                    title: e.title.clone(),
+                    sign: e.sign.clone(),
+                    artists: e.artists.clone(),
+                    deleted_at: e.deleted_at,
+                }
+            }),
+            next_duck_story: self.next_duck_of(&duck.id).map(|n| {
+                history_duck::next_duck_story::Data {
+                    id: n.id.clone(),
+                    title: n.title.clone(),
+                    location: self.location_of(&n.id).map(|l| {
+                        history_duck::next_duck_story::location::Data {
+                            id: l.id.clone(),
+                            coordinate: l.coordinate.clone(),
+                            description: l.description.clone(),
+                            deleted_at: l.deleted_at,
+                        }
+                    }),
+                    topics: n.topics.clone(),
+                    is_hidden: n.is_hidden,
+                    deleted_at: n.deleted_at,
+                }
+            }),
+            season_id: duck.season_id.clone(),
+            deleted_at: duck.deleted_at,
+        }
+    }
+}
+
+#[async_trait]
+impl UserStore for MemoryDB {
+    // C/R
+
+    async fn preview_ducks(&self) -> anyhow::Result<Vec<duck_preview::Data>> {
+        let tables = self.0.lock().unwrap();
+        Ok(tables.preview_ducks(|_| true))
+    }
+
+    async fn preview_ducks_at(
+        &self,
+        location_ids: Vec<String>,
+    ) -> anyhow::Result<Vec<duck_preview::Data>> {
+        let tables = self.0.lock().unwrap();
+        Ok(tables.preview_ducks(|d| {
+            tables
+                .location_of(&d.id)
+                .map_or(false, |l| location_ids.contains(&l.id))
+        }))
+    }
+
+    async fn upsert_user_info(&self, wechat_openid: String) -> anyhow::Result<user_info::Data> {
+        let mut tables = self.0.lock().unwrap();
+        let user = tables.upsert_user(wechat_openid);
+        Ok(tables.user_info(&user))
+    }
+
+    async fn record_duck_view(
+        &self,
+        wechat_openid: String,
+        duck_id: String,
+    ) -> anyhow::Result<user_info::Data> {
+        let mut tables = self.0.lock().unwrap();
+        if !tables
+            .ducks
+            .get(&duck_id)
+            .map_or(false, |d| d.deleted_at.is_none())
+        {
+            bail!("duck {} not found", duck_id);
+        }
+        let user = tables.upsert_user(wechat_openid);
+        let recorded = tables
+            .duck_history
+            .values()
+            .any(|h| h.user_id == user.id && h.duck_id == duck_id);
+        if !recorded {
+            let id = tables.new_id();
+            let history = duck_history::Data {
+                id: id.clone(),
+                created_at: now(),
+                user_id: user.id.clone(),
+                user: None,
+                duck_id,
+                duck: None,
+            };
+            tables.duck_history.insert(id, history);
+        }
+        Ok(tables.user_info(&user))
+    }
+
+    // U
+
+    async fn set_nickname(
+        &self,
+        wechat_openid: String,
+        nickname: Option<String>,
+    ) -> anyhow::Result<user_info::Data> {
+        let mut tables = self.0.lock().unwrap();
+        let user = tables.upsert_user(wechat_openid);
+        let user = tables.users.get_mut(&user.id).unwrap();
+        user.nickname = nickname;
+        let user = user.clone();
+        Ok(tables.user_info(&user))
+    }
+}
@@ -0,0 +1,65 @@
+use crate::db_api::memory::{now, MemoryDB};
+use crate::db_api::rankings::RankingStore;
+use crate::prisma::ranking;
+use anyhow::anyhow;
+use async_trait::async_trait;
+
+#[async_trait]
+impl RankingStore for MemoryDB {
+    // C/U
+
+    async fn upsert_ranking(&self, wechat_id: String) -> anyhow::Result<ranking::Data> {
+        let mut tables = self.0.lock().unwrap();
+        if let Some(data) = tables
+            .rankings
+            .values()
+            .find(|r| r.user_wechat_open_id == wechat_id)
+        {
+            return Ok(data.clone());
+        }
+        tables
+            .user_by_wechat(&wechat_id)
+            .ok_or_else(|| anyhow!("user {} not found", wechat_id))?;
+        tables.ranking_counter += 1;
+        let id = tables.new_id();
+        let data = ranking::Data {
+            id: id.clone(),
+            created_at: now(),
+            user_wechat_open_id: wechat_id,
+            user: None,
+            ranking: tables.ranking_counter,
+        };
+        tables.rankings.insert(id, data.clone());
+        Ok(data)
+    }
+
+    async fn renumber_rankings(&self) -> anyhow::Result<i64> {
+        let mut tables = self.0.lock().unwrap();
+        let mut rankings: Vec<&mut ranking::Data> = tables.rankings.values_mut().collect();
+        rankings.sort_by(|a, b| (a.ranking, a.created_at).cmp(&(b.ranking, b.created_at)));
+        let mut changed = 0;
+        for (i, data) in rankings.into_iter().enumerate() {
+            let new_ranking = i as i32 + 1;
+            if data.ranking != new_ranking {
+                data.ranking = new_ranking;
+                changed += 1;
+            }
+        }
+        tables.ranking_counter = tables.rankings.len() as i32;
+        Ok(changed)
+    }
+
+    // R
+    async fn get_all_rankings(&self) -> anyhow::Result<Vec<ranking::Data>> {
+        Ok(self.0.lock().unwrap().rankings.values().cloned().collect())
+    }
+
+    // D
+    async fn delete_all_rankings(&self) -> anyhow::Result<i64> {
+        let mut tables = self.0.lock().unwrap();
+        let count = tables.rankings.len() as i64;
+        tables.rankings.clear();
+        tables.ranking_counter = 0;
+        Ok(count)
+    }
+}
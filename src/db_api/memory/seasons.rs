@@ -0,0 +1,136 @@
+use crate::db_api::memory::{now, MemoryDB, Tables};
+use crate::db_api::seasons::{NewSeasonData, SeasonStore, UpdateSeasonData};
+use crate::prisma::{season, season_ranking};
+use anyhow::anyhow;
+use async_trait::async_trait;
+
+impl Tables {
+    fn season_ranking_of(&self, season_id: &str, wechat_id: &str) -> Option<&season_ranking::Data> {
+        self.season_rankings
+            .values()
+            .find(|r| r.season_id == season_id && r.user_wechat_open_id == wechat_id)
+    }
+
+    fn season_mut(&mut self, id: &str) -> anyhow::Result<&mut season::Data> {
+        self.seasons
+            .get_mut(id)
+            .ok_or_else(|| anyhow!("season {} not found", id))
+    }
+}
+
+#[async_trait]
+impl SeasonStore for MemoryDB {
+    // C
+
+    async fn create_season(&self, data: NewSeasonData) -> anyhow::Result<season::Data> {
+        let mut tables = self.0.lock().unwrap();
+        let id = tables.new_id();
+        let season = season::Data {
+            id: id.clone(),
+            created_at: now(),
+            title: serde_json::to_value(data.title)?,
+            starts_at: data.starts_at,
+            ends_at: data.ends_at,
+            archived_at: None,
+            ducks: None,
+            rankings: None,
+        };
+        tables.seasons.insert(id, season.clone());
+        Ok(season)
+    }
+
+    // R
+
+    async fn get_season(&self, id: String) -> anyhow::Result<Option<season::Data>> {
+        Ok(self.0.lock().unwrap().seasons.get(&id).cloned())
+    }
+
+    async fn get_all_seasons(&self) -> anyhow::Result<Vec<season::Data>> {
+        Ok(self.0.lock().unwrap().seasons.values().cloned().collect())
+    }
+
+    async fn get_season_rankings(
+        &self,
+        season_id: String,
+    ) -> anyhow::Result<Vec<season_ranking::Data>> {
+        let tables = self.0.lock().unwrap();
+        let mut rankings: Vec<season_ranking::Data> = tables
+            .season_rankings
+            .values()
+            .filter(|r| r.season_id == season_id)
+            .cloned()
+            .collect();
+        rankings.sort_by_key(|r| r.ranking);
+        Ok(rankings)
+    }
+
+    async fn get_season_ranking(
+        &self,
+        season_id: String,
+        wechat_id: String,
+    ) -> anyhow::Result<Option<season_ranking::Data>> {
+        let tables = self.0.lock().unwrap();
+        Ok(tables.season_ranking_of(&season_id, &wechat_id).cloned())
+    }
+
+    // U
+
+    async fn update_season(
+        &self,
+        id: String,
+        data: UpdateSeasonData,
+    ) -> anyhow::Result<season::Data> {
+        let mut tables = self.0.lock().unwrap();
+        let season = tables.season_mut(&id)?;
+        if let Some(title) = data.title {
+            season.title = serde_json::to_value(title)?;
+        }
+        if let Some(starts_at) = data.starts_at {
+            season.starts_at = starts_at;
+        }
+        if let Some(ends_at) = data.ends_at {
+            season.ends_at = ends_at;
+        }
+        Ok(season.clone())
+    }
+
+    async fn archive_season(&self, id: String) -> anyhow::Result<season::Data> {
+        let mut tables = self.0.lock().unwrap();
+        let season = tables.season_mut(&id)?;
+        season.archived_at = Some(now());
+        Ok(season.clone())
+    }
+
+    // C/U
+
+    async fn upsert_season_ranking(
+        &self,
+        season_id: String,
+        wechat_id: String,
+    ) -> anyhow::Result<season_ranking::Data> {
+        let mut tables = self.0.lock().unwrap();
+        if let Some(data) = tables.season_ranking_of(&season_id, &wechat_id) {
+            return Ok(data.clone());
+        }
+        tables.season_mut(&season_id)?;
+        let ranking = tables
+            .season_rankings
+            .values()
+            .filter(|r| r.season_id == season_id)
+            .map(|r| r.ranking)
+            .max()
+            .unwrap_or(0)
+            + 1;
+        let id = tables.new_id();
+        let data = season_ranking::Data {
+            id: id.clone(),
+            created_at: now(),
+            season_id,
+            season: None,
+            user_wechat_open_id: wechat_id,
+            ranking,
+        };
+        tables.season_rankings.insert(id, data.clone());
+        Ok(data)
+    }
+}
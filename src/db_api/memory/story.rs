@@ -0,0 +1,22 @@
+use crate::db_api::memory::MemoryDB;
+use crate::db_api::story::{story_node, StoryStore};
+use async_trait::async_trait;
+
+#[async_trait]
+impl StoryStore for MemoryDB {
+    // R
+
+    async fn get_story_nodes(&self) -> anyhow::Result<Vec<story_node::Data>> {
+        let tables = self.0.lock().unwrap();
+        Ok(tables
+            .ducks
+            .values()
+            .map(|d| story_node::Data {
+                id: d.id.clone(),
+                prev_duck_story_id: d.prev_duck_story_id.clone(),
+                prerequisite_ids: d.prerequisite_ids.clone(),
+                deleted_at: d.deleted_at,
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,146 @@
+use crate::db_api::memory::{now, MemoryDB};
+use crate::db_api::public::user_info;
+use crate::db_api::teams::TeamStore;
+use crate::prisma::{team, team_ranking};
+use anyhow::{anyhow, bail};
+use async_trait::async_trait;
+use std::collections::HashMap;
+
+#[async_trait]
+impl TeamStore for MemoryDB {
+    // C
+
+    async fn create_team(&self, name: String, join_code: String) -> anyhow::Result<team::Data> {
+        let mut tables = self.0.lock().unwrap();
+        if tables.teams.values().any(|t| t.join_code == join_code) {
+            bail!("join code {} taken", join_code);
+        }
+        let id = tables.new_id();
+        let team = team::Data {
+            id: id.clone(),
+            created_at: now(),
+            name,
+            join_code,
+            members: None,
+            rankings: None,
+        };
+        tables.teams.insert(id, team.clone());
+        Ok(team)
+    }
+
+    // C/U
+
+    async fn upsert_team_ranking(
+        &self,
+        team_id: String,
+        season_id: Option<String>,
+    ) -> anyhow::Result<team_ranking::Data> {
+        let mut tables = self.0.lock().unwrap();
+        let rankings = tables
+            .team_rankings
+            .values()
+            .filter(|r| r.season_id == season_id);
+        let mut last_ranking = 0;
+        for ranking in rankings {
+            if ranking.team_id == team_id {
+                return Ok(ranking.clone());
+            }
+            last_ranking = last_ranking.max(ranking.ranking);
+        }
+        if !tables.teams.contains_key(&team_id) {
+            bail!("team {} not found", team_id);
+        }
+        let id = tables.new_id();
+        let data = team_ranking::Data {
+            id: id.clone(),
+            created_at: now(),
+            team_id,
+            team: None,
+            season_id,
+            ranking: last_ranking + 1,
+        };
+        tables.team_rankings.insert(id, data.clone());
+        Ok(data)
+    }
+
+    // R
+
+    async fn get_team(&self, id: String) -> anyhow::Result<Option<team::Data>> {
+        Ok(self.0.lock().unwrap().teams.get(&id).cloned())
+    }
+
+    async fn get_team_by_code(&self, join_code: String) -> anyhow::Result<Option<team::Data>> {
+        let tables = self.0.lock().unwrap();
+        Ok(tables
+            .teams
+            .values()
+            .find(|t| t.join_code == join_code)
+            .cloned())
+    }
+
+    async fn get_teams(&self, ids: Vec<String>) -> anyhow::Result<Vec<team::Data>> {
+        let tables = self.0.lock().unwrap();
+        Ok(ids
+            .iter()
+            .filter_map(|id| tables.teams.get(id))
+            .cloned()
+            .collect())
+    }
+
+    async fn get_team_members(&self, team_id: String) -> anyhow::Result<Vec<user_info::Data>> {
+        let tables = self.0.lock().unwrap();
+        Ok(tables
+            .users
+            .values()
+            .filter(|u| u.team_id.as_ref() == Some(&team_id))
+            .map(|u| tables.user_info(u))
+            .collect())
+    }
+
+    async fn count_team_members(
+        &self,
+        team_ids: Vec<String>,
+    ) -> anyhow::Result<HashMap<String, usize>> {
+        let tables = self.0.lock().unwrap();
+        let mut counts = HashMap::new();
+        for team_id in tables.users.values().filter_map(|u| u.team_id.as_ref()) {
+            if team_ids.contains(team_id) {
+                *counts.entry(team_id.clone()).or_default() += 1;
+            }
+        }
+        Ok(counts)
+    }
+
+    async fn get_team_rankings(
+        &self,
+        season_id: Option<String>,
+    ) -> anyhow::Result<Vec<team_ranking::Data>> {
+        let tables = self.0.lock().unwrap();
+        Ok(tables
+            .team_rankings
+            .values()
+            .filter(|r| r.season_id == season_id)
+            .cloned()
+            .collect())
+    }
+
+    // U
+
+    async fn set_user_team(
+        &self,
+        wechat_openid: String,
+        team_id: Option<String>,
+    ) -> anyhow::Result<user_info::Data> {
+        let mut tables = self.0.lock().unwrap();
+        if let Some(team_id) = &team_id {
+            if !tables.teams.contains_key(team_id) {
+                return Err(anyhow!("team {} not found", team_id));
+            }
+        }
+        let user = tables.upsert_user(wechat_openid);
+        let user = tables.users.get_mut(&user.id).unwrap();
+        user.team_id = team_id;
+        let user = user.clone();
+        Ok(tables.user_info(&user))
+    }
+}
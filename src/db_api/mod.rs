@@ -1,19 +1,165 @@
+pub mod achievements;
+pub mod admins;
+pub mod archive;
+pub mod audit;
+pub mod challenges;
+pub mod codes;
+pub mod completion;
+pub mod dangerous;
 pub mod ducks;
+pub mod events;
 pub mod exhibits;
+pub mod geofence;
+pub mod hints;
+pub mod leaderboard;
 pub mod locations;
+pub mod locks;
+#[cfg(any(test, feature = "memory"))]
+pub mod memory;
+pub mod nearby;
 pub mod public;
 pub mod rankings;
+pub mod seasons;
+pub mod story;
+pub mod teams;
 
+use crate::db_api::achievements::AchievementStore;
+use crate::db_api::admins::AdminStore;
+use crate::db_api::archive::ArchiveStore;
+use crate::db_api::audit::AuditStore;
+use crate::db_api::challenges::ChallengeStore;
+use crate::db_api::codes::CodeStore;
+use crate::db_api::completion::CompletionStore;
+use crate::db_api::dangerous::DangerousStore;
+use crate::db_api::ducks::DuckStore;
+use crate::db_api::events::{relay_events, EventStore, GameEvent, EVENTS_CAPACITY};
+use crate::db_api::exhibits::ExhibitStore;
+use crate::db_api::geofence::DiscoveryStore;
+use crate::db_api::hints::HintStore;
+use crate::db_api::leaderboard::LeaderboardStore;
+use crate::db_api::locations::LocationStore;
+use crate::db_api::locks::LockStore;
+#[cfg(any(test, feature = "memory"))]
+use crate::db_api::memory::MemoryDB;
+use crate::db_api::nearby::NearbyStore;
+use crate::db_api::public::UserStore;
+use crate::db_api::rankings::RankingStore;
+use crate::db_api::seasons::SeasonStore;
+use crate::db_api::story::StoryStore;
+use crate::db_api::teams::TeamStore;
 use crate::prisma::{new_client_with_url, PrismaClient};
 use serde::{Deserialize, Serialize};
+use std::ops::Deref;
 use std::sync::Arc;
+use tokio::sync::broadcast;
 
+/// Filter of the documents of a model whose optional field is null or unset.
+///
+/// Documents written before a field existed lack it, and on mongodb a
+/// `field: null` filter does not match them: documents out of the trash,
+/// of the main game or of players are those with a null or unset field.
+macro_rules! null_or_unset {
+    ($model:ident::$field:ident) => {
+        $model::WhereParam::Or(vec![
+            $model::$field::equals(None),
+            $model::$field::is_set(false),
+        ])
+    };
+}
+pub(crate) use null_or_unset;
+
+/// all operations a storage backend must support
+pub trait Storage:
+    AchievementStore
+    + AdminStore
+    + ArchiveStore
+    + AuditStore
+    + ChallengeStore
+    + CodeStore
+    + CompletionStore
+    + DangerousStore
+    + DiscoveryStore
+    + DuckStore
+    + EventStore
+    + ExhibitStore
+    + HintStore
+    + LeaderboardStore
+    + LocationStore
+    + LockStore
+    + NearbyStore
+    + UserStore
+    + RankingStore
+    + SeasonStore
+    + StoryStore
+    + TeamStore
+    + Send
+    + Sync
+{
+}
+
+impl<T> Storage for T where
+    T: AchievementStore
+        + AdminStore
+        + ArchiveStore
+        + AuditStore
+        + ChallengeStore
+        + CodeStore
+        + CompletionStore
+        + DangerousStore
+        + DiscoveryStore
+        + DuckStore
+        + EventStore
+        + ExhibitStore
+        + HintStore
+        + LeaderboardStore
+        + LocationStore
+        + LockStore
+        + NearbyStore
+        + UserStore
+        + RankingStore
+        + SeasonStore
+        + StoryStore
+        + TeamStore
+        + Send
+        + Sync
+{
+}
+
+/// shared handle to the storage backend
 #[derive(Clone)]
-pub struct DB(Arc<PrismaClient>);
+pub struct DB(Arc<dyn Storage>);
 
 impl DB {
-    pub async fn new(url: &str) -> anyhow::Result<Self> {
-        Ok(DB(Arc::new(new_client_with_url(url).await?)))
+    /// mongodb backend, with redis for atomic counters, the index of locations and events
+    pub async fn new(url: &str, redis_url: &str) -> anyhow::Result<Self> {
+        Ok(DB(Arc::new(PrismaDB::new(url, redis_url).await?)))
+    }
+
+    /// in-memory backend, for tests
+    #[cfg(any(test, feature = "memory"))]
+    pub fn memory() -> Self {
+        DB(Arc::new(MemoryDB::started()))
+    }
+}
+
+impl Deref for DB {
+    type Target = dyn Storage;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+/// mongodb client, with redis for atomic counters, the index of locations
+/// and events, relayed to the local subscribers of the channel
+pub struct PrismaDB(PrismaClient, redis::Client, broadcast::Sender<GameEvent>);
+
+impl PrismaDB {
+    pub async fn new(url: &str, redis_url: &str) -> anyhow::Result<Self> {
+        let redis = redis::Client::open(redis_url)?;
+        let (events, _) = broadcast::channel(EVENTS_CAPACITY);
+        tokio::spawn(relay_events(redis.clone(), events.clone()));
+        Ok(PrismaDB(new_client_with_url(url).await?, redis, events))
     }
 }
 
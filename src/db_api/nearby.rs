@@ -0,0 +1,153 @@
+//! geospatial search of the ducks around a player
+use crate::coordinates::{Coordinate, CoordinateSystem};
+use crate::db_api::public::{duck_preview, DuckPreview};
+use crate::db_api::{PrismaDB, DB};
+use crate::prisma::location;
+use async_trait::async_trait;
+use redis::AsyncCommands;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// redis key of the geospatial index of locations, by location id
+const LOCATION_INDEX_KEY: &str = "cyberduck:location_index";
+/// redis key set once the index is built, as the index of no location is no key
+const LOCATION_INDEX_BUILT_KEY: &str = "cyberduck:location_index_built";
+/// latitudes redis can index
+const MAX_INDEXED_LATITUDE: f64 = 85.051_128_78;
+
+/// preview of a duck, with its distance to the player in metres
+#[derive(Serialize)]
+pub struct NearbyDuck {
+    #[serde(flatten)]
+    pub duck: DuckPreview,
+    pub distance: f64,
+}
+
+#[async_trait]
+pub trait NearbyStore {
+    // R
+    /// Locations with a geographic coordinate within `radius` metres of `center`,
+    /// nearest first, as location ids with their distances.
+    ///
+    /// Locations in the trash are returned.
+    async fn nearby_locations(
+        &self,
+        center: Coordinate,
+        radius: f64,
+    ) -> anyhow::Result<Vec<(String, f64)>>;
+}
+
+#[async_trait]
+impl NearbyStore for PrismaDB {
+    // R
+
+    async fn nearby_locations(
+        &self,
+        center: Coordinate,
+        radius: f64,
+    ) -> anyhow::Result<Vec<(String, f64)>> {
+        let center = center.to_system(CoordinateSystem::Wgs84)?;
+        let mut con = self.1.get_async_connection().await?;
+        // the index is missing on first use, or after redis lost its data
+        let indexed: bool = con.exists(LOCATION_INDEX_BUILT_KEY).await?;
+        if !indexed {
+            self.reindex_locations().await?;
+        }
+        let data = redis::cmd("GEORADIUS")
+            .arg(LOCATION_INDEX_KEY)
+            .arg(center.x())
+            .arg(center.y())
+            .arg(radius)
+            .arg("m")
+            .arg("WITHDIST")
+            .arg("ASC")
+            .query_async(&mut con)
+            .await?;
+        Ok(data)
+    }
+}
+
+impl PrismaDB {
+    /// Rebuild the geospatial index of locations in redis,
+    /// after changes to the coordinates of many locations.
+    pub(crate) async fn reindex_locations(&self) -> anyhow::Result<()> {
+        let locations = self.0.location().find_many(vec![]).exec().await?;
+        let mut pipe = redis::pipe();
+        pipe.atomic().del(LOCATION_INDEX_KEY).ignore();
+        for location in locations {
+            if let Some(point) = indexed_point(&location.coordinate) {
+                pipe.cmd("GEOADD")
+                    .arg(LOCATION_INDEX_KEY)
+                    .arg(point.x())
+                    .arg(point.y())
+                    .arg(location.id)
+                    .ignore();
+            }
+        }
+        pipe.set(LOCATION_INDEX_BUILT_KEY, 1).ignore();
+        let mut con = self.1.get_async_connection().await?;
+        let _: () = pipe.query_async(&mut con).await?;
+        Ok(())
+    }
+
+    /// Update the geospatial index after a change to the coordinate of one location.
+    pub(crate) async fn index_location(&self, location: &location::Data) -> anyhow::Result<()> {
+        let mut con = self.1.get_async_connection().await?;
+        match indexed_point(&location.coordinate) {
+            Some(point) => {
+                let _: () = redis::cmd("GEOADD")
+                    .arg(LOCATION_INDEX_KEY)
+                    .arg(point.x())
+                    .arg(point.y())
+                    .arg(&location.id)
+                    .query_async(&mut con)
+                    .await?;
+            }
+            None => con.zrem(LOCATION_INDEX_KEY, &location.id).await?,
+        }
+        Ok(())
+    }
+}
+
+/// WGS84 point of a coordinate, if redis can index it
+fn indexed_point(coordinate: &Value) -> Option<Coordinate> {
+    let point = Coordinate::from_value(coordinate)
+        .ok()?
+        .to_system(CoordinateSystem::Wgs84)
+        .ok()?;
+    (point.y().abs() <= MAX_INDEXED_LATITUDE).then_some(point)
+}
+
+impl DB {
+    /// Ducks of a season, or of the main game, within `radius` metres of `center`,
+    /// nearest first.
+    ///
+    /// Hidden ducks not unlocked, and ducks already found by the player, are left out.
+    pub async fn nearby_ducks(
+        &self,
+        wechat_openid: Option<&str>,
+        season_id: Option<&str>,
+        center: Coordinate,
+        radius: f64,
+    ) -> anyhow::Result<Vec<NearbyDuck>> {
+        let locations = self.nearby_locations(center, radius).await?;
+        let ids = locations.iter().map(|(id, _)| id.clone()).collect();
+        let (_, progress) = self.progress_of(wechat_openid).await?;
+        let mut ducks: HashMap<String, duck_preview::Data> = self
+            .preview_ducks_at(ids)
+            .await?
+            .into_iter()
+            .filter(|d| d.season_id.as_deref() == season_id)
+            .filter(|d| progress.shows(&d.id, d.is_hidden) && !progress.is_found(&d.id))
+            .filter_map(|d| Some((d.location.as_ref()?.id.clone(), d)))
+            .collect();
+        Ok(locations
+            .into_iter()
+            .filter_map(|(location_id, distance)| {
+                let duck = DuckPreview::new(ducks.remove(&location_id)?, &progress);
+                Some(NearbyDuck { duck, distance })
+            })
+            .collect())
+    }
+}
@@ -1,22 +1,32 @@
 //! pubic api to query user states
-use crate::db_api::DB;
-use crate::prisma::{duck, duck_history, user};
+use crate::db_api::story::Progress;
+use crate::db_api::{null_or_unset, PrismaDB, DB};
+use crate::prisma::{duck, duck_history, location, user};
+use anyhow::bail;
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::Value;
 
 duck::select! { duck_preview {
+    id
     title
     location: select {
         id
         coordinate
         description
+        deleted_at
     }
     topics
     is_hidden
+    season_id
+    deleted_at
 }}
 
 user::select! { user_info {
     id
     created_at
     wechat_open_id
+    nickname
     ranking: select {
         ranking
     }
@@ -30,6 +40,7 @@ user::select! { user_info {
                 id
                 coordinate
                 description
+                deleted_at
             }
             topics
             duck_icon_url
@@ -39,6 +50,7 @@ user::select! { user_info {
                 title
                 sign
                 artists
+                deleted_at
             }
             next_duck_story: select {
                 id
@@ -47,29 +59,195 @@ user::select! { user_info {
                     id
                     coordinate
                     description
+                    deleted_at
                 }
                 topics
                 is_hidden
+                deleted_at
             }
+            season_id
+            deleted_at
+        }
+    }
+    achievements: select {
+        created_at
+        achievement: select {
+            id
+            title
+            description
+            icon_url
         }
     }
+    team: select {
+        id
+        name
+        join_code
+    }
 }}
 
+// Players never see the trash. Queries leave out trashed ducks, but related
+// documents come whatever their fields, so trashed ones are dropped after.
+
+/// drop the trashed locations of ducks
+pub(crate) fn preview_without_trash(ducks: Vec<duck_preview::Data>) -> Vec<duck_preview::Data> {
+    ducks
+        .into_iter()
+        .map(|mut d| {
+            d.location = d.location.filter(|l| l.deleted_at.is_none());
+            d
+        })
+        .collect()
+}
+
+impl user_info::Data {
+    /// Drop the history of trashed ducks, and trashed documents related to a duck.
+    ///
+    /// The history itself is kept in the database, so restoring a duck brings it back.
+    pub(crate) fn without_trash(mut self) -> Self {
+        self.duck_history.retain(|h| h.duck.deleted_at.is_none());
+        for history in self.duck_history.iter_mut() {
+            let duck = &mut history.duck;
+            duck.location = duck.location.take().filter(|l| l.deleted_at.is_none());
+            duck.related_exhibit = duck
+                .related_exhibit
+                .take()
+                .filter(|e| e.deleted_at.is_none());
+            duck.next_duck_story = duck
+                .next_duck_story
+                .take()
+                .filter(|n| n.deleted_at.is_none())
+                .map(|mut n| {
+                    n.location = n.location.filter(|l| l.deleted_at.is_none());
+                    n
+                });
+        }
+        self
+    }
+}
+
+// Hidden ducks are kept secret until unlocked. The frontend cannot be
+// trusted with this, so their title and location are redacted here.
+
+/// preview of a duck, telling whether it is locked behind the story
+#[derive(Serialize)]
+pub struct DuckPreview {
+    #[serde(flatten)]
+    pub duck: duck_preview::Data,
+    pub locked: bool,
+}
+
+impl DuckPreview {
+    /// drop the title and location of a hidden duck not unlocked
+    pub(crate) fn new(mut duck: duck_preview::Data, progress: &Progress) -> Self {
+        if !progress.shows(&duck.id, duck.is_hidden) {
+            duck.title = Value::Null;
+            duck.location = None;
+        }
+        let locked = progress.is_locked(&duck.id);
+        DuckPreview { duck, locked }
+    }
+}
+
+impl user_info::Data {
+    /// Drop the title and location of the hidden ducks the history points to,
+    /// unless unlocked.
+    pub(crate) fn redacted(mut self, progress: &Progress) -> Self {
+        for history in self.duck_history.iter_mut() {
+            if let Some(next) = history.duck.next_duck_story.as_mut() {
+                if !progress.shows(&next.id, next.is_hidden) {
+                    next.title = Value::Null;
+                    next.location = None;
+                }
+            }
+        }
+        self
+    }
+}
+
 impl DB {
+    /// Previews of the ducks of a season, or of the main game, as a player sees them,
+    /// or as anyone sees them without `wechat_openid`.
+    pub async fn player_previews(
+        &self,
+        wechat_openid: Option<&str>,
+        season_id: Option<&str>,
+    ) -> anyhow::Result<Vec<DuckPreview>> {
+        let (_, progress) = self.progress_of(wechat_openid).await?;
+        Ok(self
+            .preview_ducks()
+            .await?
+            .into_iter()
+            .filter(|d| d.season_id.as_deref() == season_id)
+            .map(|d| DuckPreview::new(d, &progress))
+            .collect())
+    }
+
+    /// Info of a player as shown to them.
+    pub async fn player_info(&self, data: user_info::Data) -> anyhow::Result<user_info::Data> {
+        let progress = self.progress(Some(&data)).await?;
+        Ok(data.redacted(&progress))
+    }
+}
+
+#[async_trait]
+pub trait UserStore {
+    // C/R
+    // ducks and related documents in the trash are not returned,
+    // hidden ducks are not redacted, see `DB::player_previews` and `DB::player_info`
+    async fn preview_ducks(&self) -> anyhow::Result<Vec<duck_preview::Data>>;
+    async fn preview_ducks_at(
+        &self,
+        location_ids: Vec<String>,
+    ) -> anyhow::Result<Vec<duck_preview::Data>>;
+    async fn upsert_user_info(&self, wechat_openid: String) -> anyhow::Result<user_info::Data>;
+    /// fails if the duck is missing or in the trash
+    async fn record_duck_view(
+        &self,
+        wechat_openid: String,
+        duck_id: String,
+    ) -> anyhow::Result<user_info::Data>;
+    // U
+    /// `None` clears the nickname
+    async fn set_nickname(
+        &self,
+        wechat_openid: String,
+        nickname: Option<String>,
+    ) -> anyhow::Result<user_info::Data>;
+}
+
+#[async_trait]
+impl UserStore for PrismaDB {
     // C/R
 
-    pub async fn preview_ducks(&self) -> anyhow::Result<Vec<duck_preview::Data>> {
+    async fn preview_ducks(&self) -> anyhow::Result<Vec<duck_preview::Data>> {
         let data = self
             .0
             .duck()
-            .find_many(vec![])
+            .find_many(vec![null_or_unset!(duck::deleted_at)])
             .select(duck_preview::select())
             .exec()
             .await?;
-        Ok(data)
+        Ok(preview_without_trash(data))
     }
 
-    pub async fn upsert_user_info(&self, wechat_openid: String) -> anyhow::Result<user_info::Data> {
+    async fn preview_ducks_at(
+        &self,
+        location_ids: Vec<String>,
+    ) -> anyhow::Result<Vec<duck_preview::Data>> {
+        let data = self
+            .0
+            .duck()
+            .find_many(vec![
+                null_or_unset!(duck::deleted_at),
+                duck::location::is(vec![location::id::in_vec(location_ids)]),
+            ])
+            .select(duck_preview::select())
+            .exec()
+            .await?;
+        Ok(preview_without_trash(data))
+    }
+
+    async fn upsert_user_info(&self, wechat_openid: String) -> anyhow::Result<user_info::Data> {
         let data = self
             .0
             .user()
@@ -81,14 +259,23 @@ impl DB {
             .select(user_info::select())
             .exec()
             .await?;
-        Ok(data)
+        Ok(data.without_trash())
     }
 
-    pub async fn record_duck_view(
+    async fn record_duck_view(
         &self,
         wechat_openid: String,
         duck_id: String,
     ) -> anyhow::Result<user_info::Data> {
+        let duck = self
+            .0
+            .duck()
+            .find_unique(duck::UniqueWhereParam::IdEquals(duck_id.clone()))
+            .exec()
+            .await?;
+        if !duck.map_or(false, |d| d.deleted_at.is_none()) {
+            bail!("duck {} not found", duck_id);
+        }
         // try creating new user if wechat_openid is new
         let user = self
             .0
@@ -120,4 +307,28 @@ impl DB {
         let data = self.upsert_user_info(wechat_openid).await?;
         Ok(data)
     }
+
+    // U
+
+    async fn set_nickname(
+        &self,
+        wechat_openid: String,
+        nickname: Option<String>,
+    ) -> anyhow::Result<user_info::Data> {
+        let data = self
+            .0
+            .user()
+            .upsert(
+                user::UniqueWhereParam::WechatOpenIdEquals(wechat_openid.clone()),
+                (
+                    wechat_openid,
+                    vec![user::SetParam::SetNickname(nickname.clone())],
+                ),
+                vec![user::SetParam::SetNickname(nickname)],
+            )
+            .select(user_info::select())
+            .exec()
+            .await?;
+        Ok(data.without_trash())
+    }
 }
@@ -1,36 +1,178 @@
 //! admin api to manage rankings
-use crate::db_api::DB;
+use crate::db_api::locks::with_lock;
+use crate::db_api::PrismaDB;
 use crate::prisma::{ranking, user};
+use async_trait::async_trait;
+use redis::AsyncCommands;
+use std::future::Future;
 
-impl DB {
-    pub async fn upsert_ranking(&self, wechat_id: String) -> anyhow::Result<ranking::Data> {
-        let new_ranking = self.0.ranking().count(vec![]).exec().await? + 1;
-        let data = self
-            .0
-            .ranking()
-            .upsert(
-                ranking::UniqueWhereParam::UserWechatOpenIdEquals(wechat_id.clone()),
-                (
-                    user::UniqueWhereParam::WechatOpenIdEquals(wechat_id),
-                    new_ranking as i32,
-                    vec![],
-                ),
-                vec![],
-            )
-            .exec()
-            .await?;
-        Ok(data)
+/// redis key of the counter used to allocate rankings
+const RANKING_COUNTER_KEY: &str = "cyberduck:ranking_counter";
+/// lock held while allocating a ranking or renumbering them,
+/// so that a renumbering never hands out a ranking being allocated
+const RANKING_LOCK: &str = "rankings";
+
+#[async_trait]
+pub trait RankingStore {
+    // C/U
+    /// Assign the next ranking to a user, or return the ranking already assigned.
+    async fn upsert_ranking(&self, wechat_id: String) -> anyhow::Result<ranking::Data>;
+    /// Renumber all rankings to 1..=n, ordered by (ranking, createdAt),
+    /// which repairs duplicated or missing ranks.
+    ///
+    /// Returns the number of rankings changed.
+    async fn renumber_rankings(&self) -> anyhow::Result<i64>;
+    // R
+    async fn get_all_rankings(&self) -> anyhow::Result<Vec<ranking::Data>>;
+    // D
+    async fn delete_all_rankings(&self) -> anyhow::Result<i64>;
+}
+
+#[async_trait]
+impl RankingStore for PrismaDB {
+    // C/U
+
+    async fn upsert_ranking(&self, wechat_id: String) -> anyhow::Result<ranking::Data> {
+        self.rank_player(wechat_id, vec![]).await
+    }
+
+    async fn renumber_rankings(&self) -> anyhow::Result<i64> {
+        with_lock(self, RANKING_LOCK, async {
+            let mut rankings = self.0.ranking().find_many(vec![]).exec().await?;
+            rankings.sort_by(|a, b| (a.ranking, a.created_at).cmp(&(b.ranking, b.created_at)));
+            let mut changed = 0;
+            for (i, data) in rankings.iter().enumerate() {
+                let new_ranking = i as i32 + 1;
+                if data.ranking != new_ranking {
+                    self.0
+                        .ranking()
+                        .update(
+                            ranking::UniqueWhereParam::IdEquals(data.id.clone()),
+                            vec![ranking::SetParam::SetRanking(new_ranking)],
+                        )
+                        .exec()
+                        .await?;
+                    changed += 1;
+                }
+            }
+            let mut con = self.1.get_async_connection().await?;
+            let _: () = con.set(RANKING_COUNTER_KEY, rankings.len() as i32).await?;
+            Ok(changed)
+        })
+        .await?
     }
 
     // R
-    pub async fn get_all_rankings(&self) -> anyhow::Result<Vec<ranking::Data>> {
+    async fn get_all_rankings(&self) -> anyhow::Result<Vec<ranking::Data>> {
         let data = self.0.ranking().find_many(vec![]).exec().await?;
         Ok(data)
     }
 
     // D
-    pub async fn delete_all_rankings(&self) -> anyhow::Result<i64> {
-        let data = self.0.ranking().delete_many(vec![]).exec().await?;
-        Ok(data)
+    async fn delete_all_rankings(&self) -> anyhow::Result<i64> {
+        with_lock(self, RANKING_LOCK, async {
+            let data = self.0.ranking().delete_many(vec![]).exec().await?;
+            let mut con = self.1.get_async_connection().await?;
+            let _: () = con.del(RANKING_COUNTER_KEY).await?;
+            Ok(data)
+        })
+        .await?
+    }
+}
+
+impl PrismaDB {
+    /// Rank a player after every ranked player, unless already ranked,
+    /// setting `params` on a new ranking.
+    pub(crate) async fn rank_player(
+        &self,
+        wechat_id: String,
+        params: Vec<ranking::SetParam>,
+    ) -> anyhow::Result<ranking::Data> {
+        let wechat_id = &wechat_id;
+        let existing = || {
+            let wechat_id = wechat_id.clone();
+            async move {
+                let data = self
+                    .0
+                    .ranking()
+                    .find_unique(ranking::UniqueWhereParam::UserWechatOpenIdEquals(wechat_id))
+                    .exec()
+                    .await?;
+                anyhow::Ok(data)
+            }
+        };
+        let max_stored = async {
+            let rankings = self.0.ranking().find_many(vec![]).exec().await?;
+            anyhow::Ok(rankings.iter().map(|r| r.ranking).max().unwrap_or(0))
+        };
+        self.allocate_ranking(
+            RANKING_LOCK,
+            RANKING_COUNTER_KEY,
+            existing,
+            max_stored,
+            |new_ranking| async move {
+                let data = self
+                    .0
+                    .ranking()
+                    .create(
+                        user::UniqueWhereParam::WechatOpenIdEquals(wechat_id.clone()),
+                        new_ranking,
+                        params,
+                    )
+                    .exec()
+                    .await?;
+                Ok(data)
+            },
+        )
+        .await
+    }
+
+    /// Drop the ranking counter, which the next allocation seeds again
+    /// from the rankings stored.
+    pub(crate) async fn reseed_ranking_counter(&self) -> anyhow::Result<()> {
+        let mut con = self.1.get_async_connection().await?;
+        let _: () = con.del(RANKING_COUNTER_KEY).await?;
+        Ok(())
+    }
+
+    /// Allocate the next ranking of a counter with redis `INCR` and store it
+    /// with `create`, holding `lock` from the check for an existing ranking
+    /// until the new one is stored, so that a ranking is never handed out twice.
+    ///
+    /// A missing counter is seeded with `max_stored`, the largest ranking stored.
+    /// Answers the ranking found by `existing` instead, if any.
+    pub(crate) async fn allocate_ranking<T, E, EF, M, C, CF>(
+        &self,
+        lock: &str,
+        counter_key: &str,
+        existing: E,
+        max_stored: M,
+        create: C,
+    ) -> anyhow::Result<T>
+    where
+        T: Send,
+        E: Fn() -> EF + Send + Sync,
+        EF: Future<Output = anyhow::Result<Option<T>>> + Send,
+        M: Future<Output = anyhow::Result<i32>> + Send,
+        C: FnOnce(i32) -> CF + Send,
+        CF: Future<Output = anyhow::Result<T>> + Send,
+    {
+        if let Some(data) = existing().await? {
+            return Ok(data);
+        }
+        with_lock(self, lock, async {
+            // ranked by another request while waiting for the lock
+            if let Some(data) = existing().await? {
+                return Ok(data);
+            }
+            let mut con = self.1.get_async_connection().await?;
+            let seeded: bool = con.exists(counter_key).await?;
+            if !seeded {
+                let _: () = con.set_nx(counter_key, max_stored.await?).await?;
+            }
+            let ranking: i32 = con.incr(counter_key, 1).await?;
+            create(ranking).await
+        })
+        .await?
     }
 }
@@ -0,0 +1,420 @@
+//! seasons, game events with their own ducks, histories and leaderboards
+use crate::db_api::ducks::nullable;
+use crate::db_api::public::user_info;
+use crate::db_api::{Bilingual, PrismaDB, DB};
+use crate::prisma::{season, season_ranking};
+use async_trait::async_trait;
+use prisma_client_rust::chrono::{DateTime, FixedOffset, Utc};
+use prisma_client_rust::Direction;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Ducks without a season belong to the main game, which keeps the global
+// `Ranking`. Players are scoped to one season at a time, or to the main game
+// with `None`: they see, find and are ranked for the ducks of that scope only.
+
+/// redis key prefix of the counters used to allocate the rankings of seasons
+const SEASON_RANKING_COUNTER_KEY: &str = "cyberduck:season_ranking_counter";
+/// lock prefix held while allocating a ranking of a season
+const SEASON_RANKING_LOCK: &str = "season_rankings";
+
+/// a change to an archived season, which is read-only
+#[derive(Debug)]
+pub struct ArchivedSeason(pub String);
+
+impl fmt::Display for ArchivedSeason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "season {} is archived", self.0)
+    }
+}
+
+impl std::error::Error for ArchivedSeason {}
+
+/// query struct for POST request
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewSeasonData {
+    pub(crate) title: Bilingual,
+    pub(crate) starts_at: DateTime<FixedOffset>,
+    pub(crate) ends_at: Option<DateTime<FixedOffset>>,
+}
+
+/// query struct for PATCH request
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateSeasonData {
+    pub(crate) title: Option<Bilingual>,
+    pub(crate) starts_at: Option<DateTime<FixedOffset>>,
+    /// `null` lets the season run without an end
+    #[serde(default, deserialize_with = "nullable")]
+    pub(crate) ends_at: Option<Option<DateTime<FixedOffset>>>,
+}
+
+/// reason for rejecting the dates of a season, if any
+fn check_dates(
+    starts_at: DateTime<FixedOffset>,
+    ends_at: Option<DateTime<FixedOffset>>,
+) -> Result<(), &'static str> {
+    match ends_at {
+        Some(ends_at) if ends_at <= starts_at => Err("season must end after it starts"),
+        _ => Ok(()),
+    }
+}
+
+impl NewSeasonData {
+    /// reason for rejecting the season, if any
+    pub fn check(&self) -> Result<(), &'static str> {
+        check_dates(self.starts_at, self.ends_at)
+    }
+
+    fn into_db_data(
+        self,
+    ) -> anyhow::Result<(
+        serde_json::Value,
+        DateTime<FixedOffset>,
+        Vec<season::SetParam>,
+    )> {
+        Ok((
+            serde_json::to_value(self.title)?,
+            self.starts_at,
+            vec![season::SetParam::SetEndsAt(self.ends_at)],
+        ))
+    }
+}
+
+impl UpdateSeasonData {
+    /// reason for rejecting the change to `season`, if any
+    pub fn check(&self, season: &season::Data) -> Result<(), &'static str> {
+        check_dates(
+            self.starts_at.unwrap_or(season.starts_at),
+            self.ends_at.unwrap_or(season.ends_at),
+        )
+    }
+
+    fn into_db_data(self) -> anyhow::Result<Vec<season::SetParam>> {
+        let mut params = vec![];
+        if let Some(title) = self.title {
+            params.push(season::SetParam::SetTitle(serde_json::to_value(title)?));
+        }
+        if let Some(starts_at) = self.starts_at {
+            params.push(season::SetParam::SetStartsAt(starts_at));
+        }
+        if let Some(ends_at) = self.ends_at {
+            params.push(season::SetParam::SetEndsAt(ends_at));
+        }
+        Ok(params)
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SeasonStatus {
+    Upcoming,
+    Running,
+    Ended,
+    /// read-only
+    Archived,
+}
+
+impl season::Data {
+    pub fn status(&self) -> SeasonStatus {
+        let now = Utc::now();
+        if self.archived_at.is_some() {
+            SeasonStatus::Archived
+        } else if now < self.starts_at {
+            SeasonStatus::Upcoming
+        } else if self.ends_at.map_or(false, |ends_at| now >= ends_at) {
+            SeasonStatus::Ended
+        } else {
+            SeasonStatus::Running
+        }
+    }
+}
+
+/// response struct for listing seasons
+#[derive(Serialize)]
+pub struct SeasonInfo {
+    #[serde(flatten)]
+    pub season: season::Data,
+    pub status: SeasonStatus,
+}
+
+impl From<season::Data> for SeasonInfo {
+    fn from(season: season::Data) -> Self {
+        let status = season.status();
+        SeasonInfo { season, status }
+    }
+}
+
+#[async_trait]
+pub trait SeasonStore {
+    // C
+    async fn create_season(&self, data: NewSeasonData) -> anyhow::Result<season::Data>;
+    // R
+    async fn get_season(&self, id: String) -> anyhow::Result<Option<season::Data>>;
+    /// oldest first
+    async fn get_all_seasons(&self) -> anyhow::Result<Vec<season::Data>>;
+    /// best first
+    async fn get_season_rankings(
+        &self,
+        season_id: String,
+    ) -> anyhow::Result<Vec<season_ranking::Data>>;
+    async fn get_season_ranking(
+        &self,
+        season_id: String,
+        wechat_id: String,
+    ) -> anyhow::Result<Option<season_ranking::Data>>;
+    // U
+    async fn update_season(
+        &self,
+        id: String,
+        data: UpdateSeasonData,
+    ) -> anyhow::Result<season::Data>;
+    /// make a season read-only
+    async fn archive_season(&self, id: String) -> anyhow::Result<season::Data>;
+    // C/U
+    /// Assign the next ranking of a season to a user,
+    /// or return the ranking already assigned.
+    async fn upsert_season_ranking(
+        &self,
+        season_id: String,
+        wechat_id: String,
+    ) -> anyhow::Result<season_ranking::Data>;
+}
+
+#[async_trait]
+impl SeasonStore for PrismaDB {
+    // C
+
+    async fn create_season(&self, data: NewSeasonData) -> anyhow::Result<season::Data> {
+        let (title, starts_at, params) = data.into_db_data()?;
+        let data = self
+            .0
+            .season()
+            .create(title, starts_at, params)
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    // R
+
+    async fn get_season(&self, id: String) -> anyhow::Result<Option<season::Data>> {
+        let data = self
+            .0
+            .season()
+            .find_unique(season::UniqueWhereParam::IdEquals(id))
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    async fn get_all_seasons(&self) -> anyhow::Result<Vec<season::Data>> {
+        let data = self
+            .0
+            .season()
+            .find_many(vec![])
+            .order_by(season::id::order(Direction::Asc))
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    async fn get_season_rankings(
+        &self,
+        season_id: String,
+    ) -> anyhow::Result<Vec<season_ranking::Data>> {
+        let data = self
+            .0
+            .season_ranking()
+            .find_many(vec![season_ranking::season_id::equals(season_id)])
+            .order_by(season_ranking::ranking::order(Direction::Asc))
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    async fn get_season_ranking(
+        &self,
+        season_id: String,
+        wechat_id: String,
+    ) -> anyhow::Result<Option<season_ranking::Data>> {
+        let data = self
+            .0
+            .season_ranking()
+            .find_unique(
+                season_ranking::UniqueWhereParam::SeasonIdUserWechatOpenIdEquals(
+                    season_id, wechat_id,
+                ),
+            )
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    // U
+
+    async fn update_season(
+        &self,
+        id: String,
+        data: UpdateSeasonData,
+    ) -> anyhow::Result<season::Data> {
+        let data = self
+            .0
+            .season()
+            .update(season::UniqueWhereParam::IdEquals(id), data.into_db_data()?)
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    async fn archive_season(&self, id: String) -> anyhow::Result<season::Data> {
+        let data = self
+            .0
+            .season()
+            .update(
+                season::UniqueWhereParam::IdEquals(id),
+                vec![season::SetParam::SetArchivedAt(Some(Utc::now().into()))],
+            )
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    // C/U
+
+    async fn upsert_season_ranking(
+        &self,
+        season_id: String,
+        wechat_id: String,
+    ) -> anyhow::Result<season_ranking::Data> {
+        let (season_id, wechat_id) = (&season_id, &wechat_id);
+        let existing = || self.get_season_ranking(season_id.clone(), wechat_id.clone());
+        let max_stored = async {
+            let rankings = self.get_season_rankings(season_id.clone()).await?;
+            anyhow::Ok(rankings.iter().map(|r| r.ranking).max().unwrap_or(0))
+        };
+        self.allocate_ranking(
+            &format!("{}:{}", SEASON_RANKING_LOCK, season_id),
+            &format!("{}:{}", SEASON_RANKING_COUNTER_KEY, season_id),
+            existing,
+            max_stored,
+            |new_ranking| async move {
+                let data = self
+                    .0
+                    .season_ranking()
+                    .create(
+                        season::UniqueWhereParam::IdEquals(season_id.clone()),
+                        wechat_id.clone(),
+                        new_ranking,
+                        vec![],
+                    )
+                    .exec()
+                    .await?;
+                Ok(data)
+            },
+        )
+        .await
+    }
+}
+
+impl DB {
+    /// A season which is not archived, or the main game.
+    ///
+    /// Fails if the season does not exist.
+    pub async fn is_writable_season(&self, season_id: Option<&str>) -> anyhow::Result<bool> {
+        let season_id = match season_id {
+            None => return Ok(true),
+            Some(season_id) => season_id,
+        };
+        let season = self
+            .get_season(season_id.to_string())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("season {} not found", season_id))?;
+        Ok(season.archived_at.is_none())
+    }
+
+    /// Fail with `ArchivedSeason` on a change to an archived season.
+    ///
+    /// Fails as well if the season does not exist.
+    pub async fn require_writable(&self, season_id: Option<&str>) -> anyhow::Result<()> {
+        if !self.is_writable_season(season_id).await? {
+            let season_id = season_id.unwrap_or_default().to_string();
+            return Err(ArchivedSeason(season_id).into());
+        }
+        Ok(())
+    }
+
+    /// Fail with `ArchivedSeason` on a change to a duck of an archived season,
+    /// even in the trash.
+    pub async fn require_writable_duck(&self, duck_id: &str) -> anyhow::Result<()> {
+        let season_id = self.season_of_duck(duck_id).await?;
+        self.require_writable(season_id.as_deref()).await
+    }
+
+    /// Fail with `ArchivedSeason` on a change to the location of a duck
+    /// of an archived season, even in the trash.
+    pub async fn require_writable_location(&self, location_id: &str) -> anyhow::Result<()> {
+        let location = match self.get_location(location_id.to_string()).await? {
+            Some(location) => location,
+            None => self
+                .get_trashed_locations()
+                .await?
+                .into_iter()
+                .find(|l| l.id == location_id)
+                .ok_or_else(|| anyhow::anyhow!("location {} not found", location_id))?,
+        };
+        match location.duck_id {
+            Some(duck_id) => self.require_writable_duck(&duck_id).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Season of a duck, `None` for the main game, even for a duck in the trash.
+    ///
+    /// Fails if the duck does not exist.
+    pub async fn season_of_duck(&self, duck_id: &str) -> anyhow::Result<Option<String>> {
+        if let Some(duck) = self.get_duck(duck_id.to_string()).await? {
+            return Ok(duck.season_id);
+        }
+        self.get_trashed_ducks()
+            .await?
+            .into_iter()
+            .find(|d| d.id == duck_id)
+            .map(|d| d.season_id)
+            .ok_or_else(|| anyhow::anyhow!("duck {} not found", duck_id))
+    }
+
+    /// Whether players can find the ducks of a season, or of the main game.
+    ///
+    /// Fails if the season does not exist.
+    pub async fn is_running_season(&self, season_id: Option<&str>) -> anyhow::Result<bool> {
+        let season_id = match season_id {
+            None => return Ok(true),
+            Some(season_id) => season_id,
+        };
+        let season = self
+            .get_season(season_id.to_string())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("season {} not found", season_id))?;
+        Ok(season.status() == SeasonStatus::Running)
+    }
+
+    /// Scope the info of a player to a season, or to the main game:
+    /// the history of the ducks of the scope, and the ranking in it.
+    pub async fn scoped_user_info(
+        &self,
+        mut user: user_info::Data,
+        season_id: Option<&str>,
+    ) -> anyhow::Result<user_info::Data> {
+        user.duck_history
+            .retain(|h| h.duck.season_id.as_deref() == season_id);
+        if let Some(season_id) = season_id {
+            user.ranking = self
+                .get_season_ranking(season_id.to_string(), user.wechat_open_id.clone())
+                .await?
+                .map(|r| user_info::ranking::Data { ranking: r.ranking });
+        }
+        Ok(user)
+    }
+}
@@ -0,0 +1,281 @@
+//! story graph of ducks, which locks a duck until its prerequisites are found
+use crate::db_api::public::user_info;
+use crate::db_api::{PrismaDB, DB};
+use crate::prisma::duck;
+use async_trait::async_trait;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+// A duck needs its `prevDuckStory` and each of its `prerequisiteIds`.
+// Several ducks may need the same one, so stories branch, and a duck
+// needing several ones joins branches.
+
+duck::select! { story_node {
+    id
+    prev_duck_story_id
+    prerequisite_ids
+    deleted_at
+}}
+
+#[async_trait]
+pub trait StoryStore {
+    // R
+    /// ducks in the trash are returned
+    async fn get_story_nodes(&self) -> anyhow::Result<Vec<story_node::Data>>;
+}
+
+#[async_trait]
+impl StoryStore for PrismaDB {
+    // R
+
+    async fn get_story_nodes(&self) -> anyhow::Result<Vec<story_node::Data>> {
+        let data = self
+            .0
+            .duck()
+            .find_many(vec![])
+            .select(story_node::select())
+            .exec()
+            .await?;
+        Ok(data)
+    }
+}
+
+/// prerequisites of the ducks out of the trash
+pub struct StoryGraph {
+    prerequisites: HashMap<String, Vec<String>>,
+    /// ducks a prerequisite points to, but which are missing or in the trash
+    missing: Vec<MissingPrerequisite>,
+}
+
+/// reference to a duck which is missing or in the trash,
+/// ignored when unlocking the duck
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MissingPrerequisite {
+    pub duck_id: String,
+    pub prerequisite_id: String,
+}
+
+/// problems of a story graph, `valid` without any
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoryReport {
+    pub valid: bool,
+    /// ducks needing each other, which can never be found
+    pub cycles: Vec<Vec<String>>,
+    /// ducks needing a duck of a cycle
+    pub unreachable: Vec<String>,
+    /// prerequisites pointing to missing ducks or to the trash
+    pub orphans: Vec<MissingPrerequisite>,
+}
+
+impl StoryGraph {
+    pub fn new(nodes: Vec<story_node::Data>) -> Self {
+        let ducks: HashSet<&str> = nodes
+            .iter()
+            .filter(|n| n.deleted_at.is_none())
+            .map(|n| n.id.as_str())
+            .collect();
+        let mut prerequisites = HashMap::new();
+        let mut missing = vec![];
+        for node in nodes.iter().filter(|n| n.deleted_at.is_none()) {
+            let mut needed: Vec<String> = vec![];
+            let ids = node.prev_duck_story_id.iter().chain(&node.prerequisite_ids);
+            for id in ids {
+                if !ducks.contains(id.as_str()) {
+                    missing.push(MissingPrerequisite {
+                        duck_id: node.id.clone(),
+                        prerequisite_id: id.clone(),
+                    });
+                } else if !needed.contains(id) {
+                    needed.push(id.clone());
+                }
+            }
+            prerequisites.insert(node.id.clone(), needed);
+        }
+        StoryGraph {
+            prerequisites,
+            missing,
+        }
+    }
+
+    /// ducks to find before a duck
+    pub fn prerequisites(&self, id: &str) -> &[String] {
+        self.prerequisites.get(id).map_or(&[], |p| p.as_slice())
+    }
+
+    /// Look for cycles, the ducks stuck behind them, and dangling prerequisites.
+    pub fn check(self) -> StoryReport {
+        let cycles = self.cycles();
+        let in_cycle: HashSet<&String> = cycles.iter().flatten().collect();
+        // ducks which can be reached, once their prerequisites are
+        let mut reachable: HashSet<&String> = HashSet::new();
+        loop {
+            let before = reachable.len();
+            for (id, needed) in &self.prerequisites {
+                if needed.iter().all(|p| reachable.contains(p)) {
+                    reachable.insert(id);
+                }
+            }
+            if reachable.len() == before {
+                break;
+            }
+        }
+        let mut unreachable: Vec<String> = self
+            .prerequisites
+            .keys()
+            .filter(|id| !reachable.contains(id) && !in_cycle.contains(id))
+            .cloned()
+            .collect();
+        unreachable.sort();
+        StoryReport {
+            valid: cycles.is_empty() && self.missing.is_empty(),
+            cycles,
+            unreachable,
+            orphans: self.missing,
+        }
+    }
+
+    /// strongly connected components with more than one duck, or a duck needing itself
+    fn cycles(&self) -> Vec<Vec<String>> {
+        let mut ids: Vec<&String> = self.prerequisites.keys().collect();
+        ids.sort();
+        let mut tarjan = Tarjan {
+            graph: self,
+            index: HashMap::new(),
+            low: HashMap::new(),
+            stack: vec![],
+            on_stack: HashSet::new(),
+            components: vec![],
+        };
+        for id in ids {
+            if !tarjan.index.contains_key(id.as_str()) {
+                tarjan.visit(id);
+            }
+        }
+        let mut cycles: Vec<Vec<String>> = tarjan
+            .components
+            .into_iter()
+            .filter(|c| c.len() > 1 || self.prerequisites(&c[0]).contains(&c[0]))
+            .map(|mut c| {
+                c.sort();
+                c
+            })
+            .collect();
+        cycles.sort();
+        cycles
+    }
+}
+
+/// state of Tarjan's strongly connected components algorithm
+struct Tarjan<'a> {
+    graph: &'a StoryGraph,
+    index: HashMap<&'a str, usize>,
+    low: HashMap<&'a str, usize>,
+    stack: Vec<&'a str>,
+    on_stack: HashSet<&'a str>,
+    components: Vec<Vec<String>>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn visit(&mut self, id: &'a str) {
+        let index = self.index.len();
+        self.index.insert(id, index);
+        self.low.insert(id, index);
+        self.stack.push(id);
+        self.on_stack.insert(id);
+        let graph = self.graph;
+        for next in graph.prerequisites(id) {
+            let next = next.as_str();
+            if !self.index.contains_key(next) {
+                self.visit(next);
+                let low = self.low[id].min(self.low[next]);
+                self.low.insert(id, low);
+            } else if self.on_stack.contains(next) {
+                let low = self.low[id].min(self.index[next]);
+                self.low.insert(id, low);
+            }
+        }
+        if self.low[id] == self.index[id] {
+            let mut component = vec![];
+            while let Some(member) = self.stack.pop() {
+                self.on_stack.remove(member);
+                component.push(member.to_string());
+                if member == id {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
+}
+
+/// ducks found by a player, against the story graph
+pub struct Progress {
+    found: HashSet<String>,
+    graph: StoryGraph,
+}
+
+impl Progress {
+    /// nothing is found without a player
+    pub fn new(user: Option<&user_info::Data>, graph: StoryGraph) -> Self {
+        let found = user
+            .iter()
+            .flat_map(|u| u.duck_history.iter())
+            .map(|h| h.duck.id.clone())
+            .collect();
+        Progress { found, graph }
+    }
+
+    /// whether the player found a duck already
+    pub fn is_found(&self, id: &str) -> bool {
+        self.found.contains(id)
+    }
+
+    /// whether a duck does not count yet, as some of its prerequisites are not found
+    pub fn is_locked(&self, id: &str) -> bool {
+        !self.found.contains(id)
+            && self
+                .graph
+                .prerequisites(id)
+                .iter()
+                .any(|p| !self.found.contains(p))
+    }
+
+    /// Whether the title and location of a duck are shown.
+    ///
+    /// A hidden duck is shown once found, or once its prerequisites are,
+    /// a hidden duck without prerequisites only once found.
+    pub fn shows(&self, id: &str, is_hidden: bool) -> bool {
+        !is_hidden
+            || self.found.contains(id)
+            || (!self.graph.prerequisites(id).is_empty() && !self.is_locked(id))
+    }
+}
+
+impl DB {
+    /// Check the story graph for cycles and dangling prerequisites.
+    pub async fn check_story_graph(&self) -> anyhow::Result<StoryReport> {
+        let graph = StoryGraph::new(self.get_story_nodes().await?);
+        Ok(graph.check())
+    }
+
+    /// Progress of a player with their info, nothing found without `wechat_openid`.
+    pub async fn progress_of(
+        &self,
+        wechat_openid: Option<&str>,
+    ) -> anyhow::Result<(Option<user_info::Data>, Progress)> {
+        let user = match wechat_openid {
+            Some(wechat_openid) => Some(self.upsert_user_info(wechat_openid.to_string()).await?),
+            None => None,
+        };
+        let progress = self.progress(user.as_ref()).await?;
+        Ok((user, progress))
+    }
+
+    /// Progress of a player, from their info.
+    pub async fn progress(&self, user: Option<&user_info::Data>) -> anyhow::Result<Progress> {
+        let graph = StoryGraph::new(self.get_story_nodes().await?);
+        Ok(Progress::new(user, graph))
+    }
+}
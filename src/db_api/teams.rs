@@ -0,0 +1,438 @@
+//! teams of players, such as families or school groups, sharing their progress
+use crate::db_api::leaderboard::anonymised_handle;
+use crate::db_api::public::user_info;
+use crate::db_api::{null_or_unset, PrismaDB, DB};
+use crate::prisma::{team, team_ranking, user};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use prisma_client_rust::chrono::{DateTime, FixedOffset};
+use rand::distributions::{Alphanumeric, Distribution};
+use rand::rngs::OsRng;
+use redis::AsyncCommands;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// redis key prefix of the team ranking counters, by season
+const TEAM_RANKING_COUNTER_KEY: &str = "cyberduck:team_ranking_counter";
+/// lock prefix held while allocating a team ranking, by season
+const TEAM_RANKING_LOCK: &str = "team_rankings";
+const JOIN_CODE_LENGTH: usize = 6;
+/// new codes drawn when a code is already taken
+const JOIN_CODE_ATTEMPTS: usize = 5;
+pub const MAX_TEAM_SIZE: usize = 50;
+
+// team of a player, to count the members of teams
+user::select! { team_member {
+    team_id
+}}
+
+/// a member of a team as the other members see them
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamMember {
+    /// nickname of the player, or their anonymised handle
+    pub name: String,
+    /// ducks found by the member
+    pub ducks: usize,
+    /// whether this is the player asking
+    pub me: bool,
+}
+
+/// a duck found by at least one member
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamDuck {
+    pub duck_id: String,
+    pub title: Value,
+    /// first find among the members
+    pub found_at: DateTime<FixedOffset>,
+    /// names of the members who found it
+    pub found_by: Vec<String>,
+}
+
+/// response struct for the shared progress of a team
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamProgress {
+    pub id: String,
+    pub name: String,
+    pub join_code: String,
+    pub members: Vec<TeamMember>,
+    /// ducks found by the members, merged, first found first
+    pub ducks: Vec<TeamDuck>,
+    pub ranking: Option<i32>,
+}
+
+/// a ranked team as anyone sees it
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamLeaderboardEntry {
+    pub rank: i32,
+    pub name: String,
+    pub members: usize,
+    pub completed_at: DateTime<FixedOffset>,
+}
+
+/// name of a player as their team sees it
+fn member_name(member: &user_info::Data) -> String {
+    member
+        .nickname
+        .clone()
+        .unwrap_or_else(|| anonymised_handle(&member.wechat_open_id))
+}
+
+/// ducks of a season, or of the main game, found by any member
+fn found_by_team<'a>(members: &'a [user_info::Data], season_id: Option<&str>) -> HashSet<&'a str> {
+    members
+        .iter()
+        .flat_map(|m| m.duck_history.iter())
+        .filter(|h| h.duck.season_id.as_deref() == season_id)
+        .map(|h| h.duck.id.as_str())
+        .collect()
+}
+
+#[async_trait]
+pub trait TeamStore {
+    // C
+    /// fails if the join code is taken
+    async fn create_team(&self, name: String, join_code: String) -> anyhow::Result<team::Data>;
+    // C/U
+    /// Rank a team in a season, or in the main game, unless already ranked there.
+    async fn upsert_team_ranking(
+        &self,
+        team_id: String,
+        season_id: Option<String>,
+    ) -> anyhow::Result<team_ranking::Data>;
+    // R
+    async fn get_team(&self, id: String) -> anyhow::Result<Option<team::Data>>;
+    async fn get_team_by_code(&self, join_code: String) -> anyhow::Result<Option<team::Data>>;
+    /// teams among `ids`, in no order
+    async fn get_teams(&self, ids: Vec<String>) -> anyhow::Result<Vec<team::Data>>;
+    /// ducks and related documents in the trash are not returned
+    async fn get_team_members(&self, team_id: String) -> anyhow::Result<Vec<user_info::Data>>;
+    /// number of members of the teams among `team_ids` with any member
+    async fn count_team_members(
+        &self,
+        team_ids: Vec<String>,
+    ) -> anyhow::Result<HashMap<String, usize>>;
+    /// rankings of every team in a season, or in the main game
+    async fn get_team_rankings(
+        &self,
+        season_id: Option<String>,
+    ) -> anyhow::Result<Vec<team_ranking::Data>>;
+    // U
+    /// Move a player to a team, `None` leaves their team.
+    async fn set_user_team(
+        &self,
+        wechat_openid: String,
+        team_id: Option<String>,
+    ) -> anyhow::Result<user_info::Data>;
+}
+
+#[async_trait]
+impl TeamStore for PrismaDB {
+    // C
+
+    async fn create_team(&self, name: String, join_code: String) -> anyhow::Result<team::Data> {
+        let data = self.0.team().create(name, join_code, vec![]).exec().await?;
+        Ok(data)
+    }
+
+    // C/U
+
+    async fn upsert_team_ranking(
+        &self,
+        team_id: String,
+        season_id: Option<String>,
+    ) -> anyhow::Result<team_ranking::Data> {
+        self.rank_team(team_id, season_id, vec![]).await
+    }
+
+    // R
+
+    async fn get_team(&self, id: String) -> anyhow::Result<Option<team::Data>> {
+        let data = self
+            .0
+            .team()
+            .find_unique(team::UniqueWhereParam::IdEquals(id))
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    async fn get_team_by_code(&self, join_code: String) -> anyhow::Result<Option<team::Data>> {
+        let data = self
+            .0
+            .team()
+            .find_unique(team::UniqueWhereParam::JoinCodeEquals(join_code))
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    async fn get_teams(&self, ids: Vec<String>) -> anyhow::Result<Vec<team::Data>> {
+        let data = self
+            .0
+            .team()
+            .find_many(vec![team::id::in_vec(ids)])
+            .exec()
+            .await?;
+        Ok(data)
+    }
+
+    async fn get_team_members(&self, team_id: String) -> anyhow::Result<Vec<user_info::Data>> {
+        let data = self
+            .0
+            .user()
+            .find_many(vec![user::team_id::equals(Some(team_id))])
+            .select(user_info::select())
+            .exec()
+            .await?;
+        Ok(data.into_iter().map(|m| m.without_trash()).collect())
+    }
+
+    async fn count_team_members(
+        &self,
+        team_ids: Vec<String>,
+    ) -> anyhow::Result<HashMap<String, usize>> {
+        let data = self
+            .0
+            .user()
+            .find_many(vec![user::team_id::in_vec(team_ids)])
+            .select(team_member::select())
+            .exec()
+            .await?;
+        let mut counts = HashMap::new();
+        for team_id in data.into_iter().filter_map(|m| m.team_id) {
+            *counts.entry(team_id).or_default() += 1;
+        }
+        Ok(counts)
+    }
+
+    async fn get_team_rankings(
+        &self,
+        season_id: Option<String>,
+    ) -> anyhow::Result<Vec<team_ranking::Data>> {
+        let season = match season_id {
+            Some(season_id) => team_ranking::season_id::equals(Some(season_id)),
+            None => null_or_unset!(team_ranking::season_id),
+        };
+        let data = self.0.team_ranking().find_many(vec![season]).exec().await?;
+        Ok(data)
+    }
+
+    // U
+
+    async fn set_user_team(
+        &self,
+        wechat_openid: String,
+        team_id: Option<String>,
+    ) -> anyhow::Result<user_info::Data> {
+        let team = || match &team_id {
+            Some(team_id) => {
+                user::SetParam::ConnectTeam(team::UniqueWhereParam::IdEquals(team_id.clone()))
+            }
+            None => user::SetParam::DisconnectTeam,
+        };
+        let data = self
+            .0
+            .user()
+            .upsert(
+                user::UniqueWhereParam::WechatOpenIdEquals(wechat_openid.clone()),
+                (wechat_openid, vec![team()]),
+                vec![team()],
+            )
+            .select(user_info::select())
+            .exec()
+            .await?;
+        Ok(data.without_trash())
+    }
+}
+
+impl PrismaDB {
+    /// Drop the team ranking counter of the main game, which the next ranking
+    /// seeds again from the rankings stored.
+    pub(crate) async fn reseed_team_ranking_counter(&self) -> anyhow::Result<()> {
+        let key = format!("{}:main", TEAM_RANKING_COUNTER_KEY);
+        let mut con = self.1.get_async_connection().await?;
+        let _: () = con.del(key).await?;
+        Ok(())
+    }
+
+    /// Rank a team after every team ranked in a season, or in the main game,
+    /// unless already ranked there, setting `params` on a new ranking.
+    pub(crate) async fn rank_team(
+        &self,
+        team_id: String,
+        season_id: Option<String>,
+        mut params: Vec<team_ranking::SetParam>,
+    ) -> anyhow::Result<team_ranking::Data> {
+        let (team_id, season_id) = (&team_id, &season_id);
+        let existing = || async move {
+            let rankings = self.get_team_rankings(season_id.clone()).await?;
+            anyhow::Ok(rankings.into_iter().find(|r| &r.team_id == team_id))
+        };
+        let max_stored = async {
+            let rankings = self.get_team_rankings(season_id.clone()).await?;
+            anyhow::Ok(rankings.iter().map(|r| r.ranking).max().unwrap_or(0))
+        };
+        params.push(team_ranking::SetParam::SetSeasonId(season_id.clone()));
+        let scope = season_id.as_deref().unwrap_or("main");
+        self.allocate_ranking(
+            &format!("{}:{}", TEAM_RANKING_LOCK, scope),
+            &format!("{}:{}", TEAM_RANKING_COUNTER_KEY, scope),
+            existing,
+            max_stored,
+            |new_ranking| async move {
+                let data = self
+                    .0
+                    .team_ranking()
+                    .create(
+                        team::UniqueWhereParam::IdEquals(team_id.clone()),
+                        new_ranking,
+                        params,
+                    )
+                    .exec()
+                    .await?;
+                Ok(data)
+            },
+        )
+        .await
+    }
+}
+
+impl DB {
+    /// Create a team with a new join code.
+    pub async fn new_team(&self, name: String) -> anyhow::Result<team::Data> {
+        for _ in 0..JOIN_CODE_ATTEMPTS {
+            let join_code = String::from_iter(
+                Alphanumeric
+                    .sample_iter(OsRng::default())
+                    .take(JOIN_CODE_LENGTH)
+                    .map(|u| (u as char).to_ascii_uppercase()),
+            );
+            if self.get_team_by_code(join_code.clone()).await?.is_none() {
+                return self.create_team(name, join_code).await;
+            }
+        }
+        Err(anyhow!("no free join code"))
+    }
+
+    /// Shared progress of a team in a season, or in the main game,
+    /// as one of its members sees it.
+    pub async fn team_progress(
+        &self,
+        team: team::Data,
+        season_id: Option<&str>,
+        wechat_openid: &str,
+    ) -> anyhow::Result<TeamProgress> {
+        let members = self.get_team_members(team.id.clone()).await?;
+        let ranking = self.rank_team_if_complete(&team.id, season_id).await?;
+        let mut ducks: BTreeMap<&str, TeamDuck> = BTreeMap::new();
+        for member in &members {
+            let name = member_name(member);
+            for history in member
+                .duck_history
+                .iter()
+                .filter(|h| h.duck.season_id.as_deref() == season_id)
+            {
+                let duck = ducks
+                    .entry(history.duck.id.as_str())
+                    .or_insert_with(|| TeamDuck {
+                        duck_id: history.duck.id.clone(),
+                        title: history.duck.title.clone(),
+                        found_at: history.created_at,
+                        found_by: vec![],
+                    });
+                duck.found_at = duck.found_at.min(history.created_at);
+                duck.found_by.push(name.clone());
+            }
+        }
+        let mut ducks: Vec<TeamDuck> = ducks.into_values().collect();
+        ducks.sort_by_key(|d| d.found_at);
+        let members = members
+            .iter()
+            .map(|m| TeamMember {
+                name: member_name(m),
+                ducks: m
+                    .duck_history
+                    .iter()
+                    .filter(|h| h.duck.season_id.as_deref() == season_id)
+                    .count(),
+                me: m.wechat_open_id == wechat_openid,
+            })
+            .collect();
+        Ok(TeamProgress {
+            id: team.id,
+            name: team.name,
+            join_code: team.join_code,
+            members,
+            ducks,
+            ranking,
+        })
+    }
+
+    /// Rank a team whose members together meet every team completion rule
+    /// of a season, or of the main game, unless the season is not running.
+    ///
+    /// Answers the ranking of the team, if any.
+    pub async fn rank_team_if_complete(
+        &self,
+        team_id: &str,
+        season_id: Option<&str>,
+    ) -> anyhow::Result<Option<i32>> {
+        let season = season_id.map(str::to_string);
+        let ranked = self
+            .get_team_rankings(season.clone())
+            .await?
+            .into_iter()
+            .find(|r| r.team_id == team_id);
+        if let Some(ranked) = ranked {
+            return Ok(Some(ranked.ranking));
+        }
+        let members = self.get_team_members(team_id.to_string()).await?;
+        let found = found_by_team(&members, season_id);
+        if !self.meets_completion_rules(&found, season_id, true).await? {
+            return Ok(None);
+        }
+        if !self.is_running_season(season_id).await? {
+            return Ok(None);
+        }
+        let ranking = self
+            .upsert_team_ranking(team_id.to_string(), season)
+            .await?;
+        Ok(Some(ranking.ranking))
+    }
+
+    /// Ranked teams of a season, or of the main game, best first.
+    pub async fn team_leaderboard(
+        &self,
+        season_id: Option<&str>,
+    ) -> anyhow::Result<Vec<TeamLeaderboardEntry>> {
+        let mut rankings = self
+            .get_team_rankings(season_id.map(str::to_string))
+            .await?;
+        rankings.sort_by(|a, b| (a.ranking, a.created_at).cmp(&(b.ranking, b.created_at)));
+        let ids: Vec<String> = rankings.iter().map(|r| r.team_id.clone()).collect();
+        let mut teams: HashMap<String, team::Data> = self
+            .get_teams(ids.clone())
+            .await?
+            .into_iter()
+            .map(|t| (t.id.clone(), t))
+            .collect();
+        let members = self.count_team_members(ids).await?;
+        Ok(rankings
+            .into_iter()
+            .filter_map(|ranking| {
+                let team = teams.remove(&ranking.team_id)?;
+                Some(TeamLeaderboardEntry {
+                    rank: ranking.ranking,
+                    members: members.get(&team.id).copied().unwrap_or(0),
+                    name: team.name,
+                    completed_at: ranking.created_at,
+                })
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,84 @@
+use crate::admin_auth::{roles, RequireAdmin};
+use crate::api_error::{ApiError, ResultExt};
+use crate::db_api::achievements::{NewAchievementData, UpdateAchievementData};
+use crate::prisma::achievement;
+use crate::DB;
+use axum::extract::{Path, State};
+use axum::Json;
+
+/// POST admin/achievement
+pub async fn create_achievement(
+    RequireAdmin(admin, _): RequireAdmin<roles::Editor>,
+    State(db): State<DB>,
+    Json(data): Json<NewAchievementData>,
+) -> Result<Json<achievement::Data>, ApiError> {
+    data.rule.check().map_err(ApiError::BadRequest)?;
+    let rsp = db
+        .audited(&admin)
+        .create_achievement(data)
+        .await
+        .or_api(ApiError::Internal("error creating achievement"))?;
+    Ok(Json(rsp))
+}
+
+/// GET admin/achievement/:id
+pub async fn get_achievement(
+    _: RequireAdmin<roles::Viewer>,
+    State(db): State<DB>,
+    Path(achievement_id): Path<String>,
+) -> Result<Json<achievement::Data>, ApiError> {
+    let rsp = db
+        .get_achievement(achievement_id)
+        .await
+        .or_api(ApiError::Internal("error getting achievement"))?
+        .ok_or(ApiError::NotFound("achievement id does not exist"))?;
+    Ok(Json(rsp))
+}
+
+/// GET admin/achievements
+pub async fn get_all_achievements(
+    _: RequireAdmin<roles::Viewer>,
+    State(db): State<DB>,
+) -> Result<Json<Vec<achievement::Data>>, ApiError> {
+    let rsp = db
+        .get_all_achievements()
+        .await
+        .or_api(ApiError::Internal("error getting achievements"))?;
+    Ok(Json(rsp))
+}
+
+/// PATCH admin/achievement/:id
+///
+/// players keep the achievement even if they no longer meet a new rule
+pub async fn update_achievement(
+    RequireAdmin(admin, _): RequireAdmin<roles::Editor>,
+    State(db): State<DB>,
+    Path(achievement_id): Path<String>,
+    Json(data): Json<UpdateAchievementData>,
+) -> Result<Json<achievement::Data>, ApiError> {
+    if let Some(rule) = &data.rule {
+        rule.check().map_err(ApiError::BadRequest)?;
+    }
+    let rsp = db
+        .audited(&admin)
+        .update_achievement(achievement_id, data)
+        .await
+        .or_api(ApiError::NotFound("error updating achievement"))?;
+    Ok(Json(rsp))
+}
+
+/// DELETE admin/achievement/:id
+///
+/// take the achievement back from every player
+pub async fn delete_achievement(
+    RequireAdmin(admin, _): RequireAdmin<roles::Operator>,
+    State(db): State<DB>,
+    Path(achievement_id): Path<String>,
+) -> Result<Json<achievement::Data>, ApiError> {
+    let rsp = db
+        .audited(&admin)
+        .delete_achievement(achievement_id)
+        .await
+        .or_api(ApiError::NotFound("error deleting achievement"))?;
+    Ok(Json(rsp))
+}
@@ -0,0 +1,108 @@
+use crate::admin_auth::{gen_token, hash_token, roles, RequireAdmin, Role, ROOT_ADMIN_NAME};
+use crate::api_error::{ApiError, ResultExt};
+use crate::db_api::admins::admin_account_info;
+use crate::DB;
+use axum::extract::{Path, State};
+use axum::Json;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Deserialize)]
+pub struct NewAdminData {
+    name: String,
+    role: Role,
+}
+
+impl NewAdminData {
+    /// reason for rejecting the account, if any
+    fn check(&self) -> Result<(), &'static str> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            Err("empty admin name")
+        } else if name.eq_ignore_ascii_case(ROOT_ADMIN_NAME) {
+            // audit records of the configured token go by this name
+            Err("admin name is reserved")
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// POST admin/accounts
+///
+/// the token is only returned once, in this response
+pub async fn create_admin(
+    RequireAdmin(admin, _): RequireAdmin<roles::Operator>,
+    State(db): State<DB>,
+    Json(data): Json<NewAdminData>,
+) -> Result<Json<Value>, ApiError> {
+    data.check().map_err(ApiError::BadRequest)?;
+    let new_token = gen_token();
+    let rsp = db
+        .audited(&admin)
+        .create_admin(
+            data.name,
+            data.role.as_str().to_string(),
+            hash_token(&new_token),
+        )
+        .await
+        .or_api(ApiError::BadRequest("error creating admin account"))?;
+    Ok(Json(json!({
+        "name": rsp.name,
+        "role": rsp.role,
+        "token": new_token,
+    })))
+}
+
+/// GET admin/accounts
+pub async fn get_all_admins(
+    _: RequireAdmin<roles::Operator>,
+    State(db): State<DB>,
+) -> Result<Json<Vec<admin_account_info::Data>>, ApiError> {
+    let rsp = db
+        .get_all_admins()
+        .await
+        .or_api(ApiError::Internal("error getting admin accounts"))?;
+    Ok(Json(rsp))
+}
+
+/// POST admin/accounts/:name/token
+///
+/// issue a new token, which also lifts a revocation
+pub async fn reset_admin_token(
+    RequireAdmin(admin, _): RequireAdmin<roles::Operator>,
+    State(db): State<DB>,
+    Path(name): Path<String>,
+) -> Result<Json<Value>, ApiError> {
+    let new_token = gen_token();
+    let rsp = db
+        .audited(&admin)
+        .reset_admin_token(name, hash_token(&new_token))
+        .await
+        .or_api(ApiError::NotFound("error resetting admin token"))?;
+    Ok(Json(json!({
+        "name": rsp.name,
+        "role": rsp.role,
+        "token": new_token,
+    })))
+}
+
+/// DELETE admin/accounts/:name
+///
+/// revoke the token of an account, effective immediately
+pub async fn revoke_admin(
+    RequireAdmin(admin, _): RequireAdmin<roles::Operator>,
+    State(db): State<DB>,
+    Path(name): Path<String>,
+) -> Result<Json<Value>, ApiError> {
+    let rsp = db
+        .audited(&admin)
+        .revoke_admin(name)
+        .await
+        .or_api(ApiError::NotFound("error revoking admin account"))?;
+    Ok(Json(json!({
+        "name": rsp.name,
+        "role": rsp.role,
+        "revokedAt": rsp.revoked_at,
+    })))
+}
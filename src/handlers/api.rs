@@ -1,19 +1,38 @@
-use crate::db_api::public::user_info;
+use crate::api_error::{ApiError, ResultExt};
+use crate::coordinates::{Coordinate, CoordinateSystem};
+use crate::db_api::challenges::{Answer, ChallengeQuestion};
+use crate::db_api::events::GameEvent;
+use crate::db_api::hints::{HintUnlock, PlayerHints};
+use crate::db_api::leaderboard::{
+    LeaderboardEntry, LeaderboardPage, DEFAULT_AROUND, DEFAULT_PAGE_SIZE,
+};
+use crate::db_api::nearby::NearbyDuck;
+use crate::db_api::public::{duck_preview, user_info, DuckPreview};
+use crate::db_api::seasons::SeasonInfo;
+use crate::db_api::teams::{TeamLeaderboardEntry, TeamProgress, MAX_TEAM_SIZE};
+use crate::handlers::seasons::SeasonParam;
 use crate::wechat_login::CodeResponse;
 use crate::{DB, SERVER_CONFIG};
 use axum::extract::{Path, Query, State};
-use axum::http::StatusCode;
-use axum::response::{IntoResponse, Redirect, Response};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::Redirect;
 use axum::Json;
 use axum_database_sessions::{AxumRedisPool, AxumSession};
+use futures::future;
+use futures::stream::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use serde_json::{json, Value};
 use tracing::{error, info};
 use url::Url;
 
 const WECHAT_ID_KEY: &str = "wechat_openid";
 const LOGIN_STATE_KEY: &str = "login_state";
-const DUCK_COUNT_THRESHOLD: usize = 10;
+/// largest radius of nearby-duck searches, in metres
+const MAX_NEARBY_RADIUS: f64 = 5_000.0;
+/// in characters
+const MAX_NICKNAME_LENGTH: usize = 20;
+/// in characters
+const MAX_TEAM_NAME_LENGTH: usize = 30;
 
 pub type Session = AxumSession<AxumRedisPool>;
 
@@ -29,9 +48,9 @@ pub struct LoginParams {
 }
 
 /// GET login?redirect_url=REDIRECT_URL
-pub async fn login(session: Session, Query(login_params): Query<LoginParams>) -> impl IntoResponse {
-    if check_login(&session).await.is_none() {
-        // already logged in
+pub async fn login(session: Session, Query(login_params): Query<LoginParams>) -> Redirect {
+    if check_login(&session).await.is_err() {
+        // not logged in yet
         let (state, redirect) = SERVER_CONFIG.wechat.auth_url();
         session.set(
             LOGIN_STATE_KEY,
@@ -56,146 +75,839 @@ pub struct LoginCallbackParams {
 pub async fn login_callback(
     session: Session,
     Query(login_callback_params): Query<LoginCallbackParams>,
-) -> Response {
-    if let Some(state) = session.get::<LoginState>(LOGIN_STATE_KEY) {
-        session.remove(LOGIN_STATE_KEY);
-        if login_callback_params.state.eq(&state.state) {
-            match SERVER_CONFIG
-                .wechat
-                .request_id(&login_callback_params.code)
-                .await
-            {
-                Ok(rsp) => match rsp {
-                    CodeResponse::Success { openid, .. } => {
-                        session.set(WECHAT_ID_KEY, &openid);
-                        info!("login success from openid: {}", openid);
-                        Redirect::to(state.redirect_url.as_str()).into_response()
-                    }
-                    CodeResponse::Failure { errcode, errmsg } => {
-                        error!(
-                            "error from wechat sns server: (code: {}, msg: {})",
-                            errcode, errmsg,
-                        );
-                        (StatusCode::BAD_REQUEST, "invalid login state").into_response()
-                    }
-                },
-                Err(e) => {
-                    error!("error requesting access code: {}", e);
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        "error requesting access code",
-                    )
-                        .into_response()
-                }
-            }
-        } else {
+) -> Result<Redirect, ApiError> {
+    let state = match session.get::<LoginState>(LOGIN_STATE_KEY) {
+        Some(state) => state,
+        None => {
+            error!("login state not found during login callback");
+            return Err(ApiError::BadRequest("invalid login state"));
+        }
+    };
+    session.remove(LOGIN_STATE_KEY);
+    if login_callback_params.state.ne(&state.state) {
+        error!(
+            "unmatched state during login callback: (cookie_state={}, callback_state={})",
+            state.state, login_callback_params.state
+        );
+        return Err(ApiError::BadRequest("invalid login state"));
+    }
+    let rsp = SERVER_CONFIG
+        .wechat
+        .request_id(&login_callback_params.code)
+        .await
+        .or_api(ApiError::Internal("error requesting access code"))?;
+    match rsp {
+        CodeResponse::Success { openid, .. } => {
+            session.set(WECHAT_ID_KEY, &openid);
+            info!("login success from openid: {}", openid);
+            Ok(Redirect::to(state.redirect_url.as_str()))
+        }
+        CodeResponse::Failure { errcode, errmsg } => {
             error!(
-                "unmatched state during login callback: (cookie_state={}, callback_state={})",
-                state.state, login_callback_params.state
+                "error from wechat sns server: (code: {}, msg: {})",
+                errcode, errmsg,
             );
-            (StatusCode::BAD_REQUEST, "invalid login state").into_response()
+            Err(ApiError::BadRequest("invalid login state"))
         }
-    } else {
-        error!("login state not found during login callback");
-        (StatusCode::BAD_REQUEST, "invalid login state").into_response()
     }
 }
 
-/// GET api/user-info
-pub async fn user_info(session: Session, State(db): State<DB>) -> Response {
-    if let Some(wechat_openid) = check_login(&session).await {
-        match db.upsert_user_info(wechat_openid.clone()).await {
-            Ok(data) => {
-                info!("user info request success: openid={}", wechat_openid);
-                Json(data).into_response()
-            }
-            Err(e) => {
-                error!("error getting or creating user: {}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "error getting or creating user",
-                )
-                    .into_response()
-            }
+/// GET api/user-info[?season=SEASON_ID]
+///
+/// history and ranking of a season, or of the main game without `season`
+pub async fn user_info(
+    session: Session,
+    State(db): State<DB>,
+    Query(params): Query<SeasonParam>,
+) -> Result<Json<user_info::Data>, ApiError> {
+    let wechat_openid = check_login(&session).await?;
+    let season_id = params.season.as_deref();
+    let data = db
+        .upsert_user_info(wechat_openid.clone())
+        .await
+        .or_api(ApiError::Internal("error getting or creating user"))?;
+    let mut data = db
+        .scoped_user_info(data, season_id)
+        .await
+        .or_api(ApiError::Internal("error getting or creating user"))?;
+    db.rank_if_complete(&mut data, season_id)
+        .await
+        .or_api(ApiError::Internal("error recording ranking"))?;
+    let data = db
+        .player_info(data)
+        .await
+        .or_api(ApiError::Internal("error getting or creating user"))?;
+    info!("user info request success: openid={}", wechat_openid);
+    Ok(Json(data))
+}
+
+#[derive(Deserialize)]
+pub struct NicknameData {
+    nickname: Option<String>,
+}
+
+/// PATCH api/user-info with `{"nickname": NICKNAME}`
+///
+/// the nickname shown on the leaderboard, `null` for the anonymised handle
+pub async fn set_nickname(
+    session: Session,
+    State(db): State<DB>,
+    Json(data): Json<NicknameData>,
+) -> Result<Json<user_info::Data>, ApiError> {
+    let wechat_openid = check_login(&session).await?;
+    let nickname = match data.nickname.as_deref().map(str::trim) {
+        Some(nickname) if !is_valid_nickname(nickname) => {
+            return Err(ApiError::BadRequest("invalid nickname"))
         }
-    } else {
-        (StatusCode::UNAUTHORIZED, "please login first").into_response()
-    }
+        nickname => nickname.map(str::to_string),
+    };
+    let data = db
+        .set_nickname(wechat_openid, nickname)
+        .await
+        .or_api(ApiError::Internal("error setting nickname"))?;
+    // the name shows on every leaderboard the player is ranked in
+    db.clear_leaderboard_caches()
+        .await
+        .or_api(ApiError::Internal("error clearing leaderboards"))?;
+    let data = db
+        .player_info(data)
+        .await
+        .or_api(ApiError::Internal("error getting story progress"))?;
+    Ok(Json(data))
 }
 
 /// DELETE api/user-info
-pub async fn clear_history(session: Session, State(db): State<DB>) -> Response {
-    if let Some(wechat_openid) = check_login(&session).await {
-        match db.delete_duck_history(wechat_openid).await {
-            Ok(n) => Json(json!({ "number_of_records_removed": n })).into_response(),
-            Err(e) => {
-                error!("error removing game history: {}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "error removing game history",
-                )
-                    .into_response()
-            }
+pub async fn clear_history(
+    session: Session,
+    State(db): State<DB>,
+) -> Result<Json<Value>, ApiError> {
+    let wechat_openid = check_login(&session).await?;
+    let n = db
+        .delete_duck_history(wechat_openid)
+        .await
+        .or_api(ApiError::Internal("error removing game history"))?;
+    Ok(Json(json!({ "number_of_records_removed": n })))
+}
+
+#[derive(Deserialize)]
+pub struct FindDuckParams {
+    /// signed code carried by the QR code of the duck
+    code: Option<String>,
+    /// longitude reported by the player
+    x: Option<f64>,
+    /// latitude reported by the player
+    y: Option<f64>,
+    /// system of the position, `wgs84` by default
+    #[serde(default)]
+    system: CoordinateSystem,
+}
+
+/// response of find duck, the challenge of a duck not found yet comes first
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum FindDuckResponse {
+    Found(user_info::Data),
+    #[serde(rename_all = "camelCase")]
+    Challenge {
+        challenge: ChallengeQuestion,
+        attempts_left: usize,
+    },
+}
+
+/// GET api/find-duck/:duck_id?code=CODE&x=LONGITUDE&y=LATITUDE&system=wgs84|gcj02
+///
+/// answers the history and ranking of the season of the duck,
+/// with every achievement of the player,
+/// or the challenge to answer with `answer_challenge` before the find counts
+pub async fn find_duck(
+    session: Session,
+    State(db): State<DB>,
+    Path(duck_id): Path<String>,
+    Query(params): Query<FindDuckParams>,
+) -> Result<Json<FindDuckResponse>, ApiError> {
+    let wechat_openid = check_login(&session).await?;
+    let code = params
+        .code
+        .ok_or(ApiError::Forbidden("invalid duck code"))?;
+    let valid = db
+        .verify_duck_code(&duck_id, &code)
+        .await
+        .or_api(ApiError::Internal("error verifying duck code"))?;
+    if !valid {
+        info!(
+            "user (openid: {}) rejected with invalid code for duck (duck_id: {})",
+            wechat_openid, duck_id
+        );
+        return Err(ApiError::Forbidden("invalid duck code"));
+    }
+    let season_id = db
+        .season_of_duck(&duck_id)
+        .await
+        .or_api(ApiError::NotFound("duck id does not exist"))?;
+    let running = db
+        .is_running_season(season_id.as_deref())
+        .await
+        .or_api(ApiError::Internal("error getting season"))?;
+    if !running {
+        info!(
+            "user (openid: {}) rejected outside the season of duck (duck_id: {})",
+            wechat_openid, duck_id
+        );
+        return Err(ApiError::Forbidden("season is not running"));
+    }
+    let (_, progress) = db
+        .progress_of(Some(&wechat_openid))
+        .await
+        .or_api(ApiError::Internal("error getting story progress"))?;
+    if progress.is_locked(&duck_id) {
+        info!(
+            "user (openid: {}) rejected before finding the prerequisites of duck (duck_id: {})",
+            wechat_openid, duck_id
+        );
+        return Err(ApiError::Forbidden("duck is locked"));
+    }
+    let position = match (params.x, params.y) {
+        (Some(x), Some(y)) => Some(
+            Coordinate::new(params.system, x, y)
+                .or_api(ApiError::BadRequest("invalid position"))?,
+        ),
+        _ => None,
+    };
+    let first_find = !progress.is_found(&duck_id);
+    // finding a duck again changes nothing, wherever the player is
+    if first_find {
+        let accepted = db
+            .check_discovery(&wechat_openid, &duck_id, position)
+            .await
+            .or_api(ApiError::Internal("error checking position"))?;
+        if !accepted {
+            info!(
+                "user (openid: {}) rejected outside the geofence of duck (duck_id: {})",
+                wechat_openid, duck_id
+            );
+            return Err(ApiError::Forbidden("too far from the duck"));
+        }
+        let challenge = db
+            .challenge_of(&duck_id)
+            .await
+            .or_api(ApiError::NotFound("duck id does not exist"))?;
+        if let Some(challenge) = challenge {
+            db.upsert_pending_discovery(wechat_openid.clone(), duck_id.clone())
+                .await
+                .or_api(ApiError::Internal("error recording pending discovery"))?;
+            let attempts_left = db
+                .attempts_left(&wechat_openid, &duck_id, &challenge)
+                .await
+                .or_api(ApiError::Internal("error getting challenge attempts"))?;
+            info!(
+                "user (openid: {}) asked the challenge of duck (duck_id: {})",
+                wechat_openid, duck_id
+            );
+            return Ok(Json(FindDuckResponse::Challenge {
+                challenge: challenge.question(),
+                attempts_left,
+            }));
         }
-    } else {
-        (StatusCode::UNAUTHORIZED, "please login first").into_response()
     }
+    let data = complete_discovery(&db, wechat_openid, duck_id, season_id, first_find).await?;
+    Ok(Json(FindDuckResponse::Found(data)))
 }
 
-/// GET api/find-duck/:duck_id
-pub async fn find_duck(
+#[derive(Deserialize)]
+pub struct AnswerData {
+    answer: Answer,
+}
+
+/// response struct for answering the challenge of a duck
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnswerResponse {
+    correct: bool,
+    /// wrong answers still allowed, after a wrong answer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attempts_left: Option<usize>,
+    /// history and ranking of the season of the duck, after a right answer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user_info: Option<user_info::Data>,
+}
+
+/// POST api/duck/:duck_id/answer with `{"answer": CHOICE_INDEX | "TEXT"}`
+///
+/// completes the find of a duck scanned within the hour once the answer to its challenge is right,
+/// after too many wrong answers the player must wait (429)
+pub async fn answer_challenge(
     session: Session,
     State(db): State<DB>,
     Path(duck_id): Path<String>,
-) -> Response {
-    if let Some(wechat_openid) = check_login(&session).await {
-        match db
-            .record_duck_view(wechat_openid.clone(), duck_id.clone())
+    Json(data): Json<AnswerData>,
+) -> Result<Json<AnswerResponse>, ApiError> {
+    let wechat_openid = check_login(&session).await?;
+    let pending = db
+        .get_pending_discovery(wechat_openid.clone(), duck_id.clone())
+        .await
+        .or_api(ApiError::Internal("error getting pending discovery"))?
+        .ok_or(ApiError::NotFound("duck is not waiting for an answer"))?;
+    if pending.is_expired() {
+        return Err(ApiError::Forbidden("scan of the duck expired"));
+    }
+    let season_id = db
+        .season_of_duck(&duck_id)
+        .await
+        .or_api(ApiError::NotFound("duck id does not exist"))?;
+    let running = db
+        .is_running_season(season_id.as_deref())
+        .await
+        .or_api(ApiError::Internal("error getting season"))?;
+    if !running {
+        return Err(ApiError::Forbidden("season is not running"));
+    }
+    let challenge = db
+        .challenge_of(&duck_id)
+        .await
+        .or_api(ApiError::NotFound("duck id does not exist"))?;
+    // a challenge removed since the scan needs no answer
+    if let Some(challenge) = challenge {
+        // answers of a player to a duck are checked one at a time,
+        // so that concurrent answers cannot exceed the attempts allowed
+        let lock = format!("challenge:{}:{}", wechat_openid, duck_id);
+        let attempt = db
+            .locked(&lock, async {
+                let attempts_left = db
+                    .attempts_left(&wechat_openid, &duck_id, &challenge)
+                    .await?;
+                if attempts_left == 0 {
+                    return anyhow::Ok(None);
+                }
+                let correct = challenge.accepts(&data.answer);
+                db.record_challenge_attempt(
+                    wechat_openid.clone(),
+                    duck_id.clone(),
+                    data.answer,
+                    correct,
+                )
+                .await?;
+                Ok(Some((attempts_left, correct)))
+            })
             .await
-        {
-            Ok(mut data) => {
+            .or_api(ApiError::Internal("error locking challenge attempts"))?
+            .or_api(ApiError::Internal("error recording answer"))?;
+        let (attempts_left, correct) = match attempt {
+            Some(attempt) => attempt,
+            None => {
                 info!(
-                    "user (openid: {}) find duck (duck_id: {})",
+                    "user (openid: {}) rejected after too many wrong answers for duck (duck_id: {})",
                     wechat_openid, duck_id
                 );
-                if data.duck_history.len() == DUCK_COUNT_THRESHOLD {
-                    match db.upsert_ranking(wechat_openid).await {
-                        Err(e) => {
-                            error!("error recording ranking: {}", e);
-                            return (StatusCode::INTERNAL_SERVER_ERROR, "error recording ranking")
-                                .into_response();
-                        }
-                        // add ranking info
-                        Ok(history) => {
-                            data.ranking = Some(user_info::ranking::Data {
-                                ranking: history.ranking,
-                            });
-                        }
-                    }
-                }
-                Json(data).into_response()
-            }
-            Err(e) => {
-                error!("error recording duck view: {}", e);
-                (StatusCode::NOT_FOUND, "error recording duck view").into_response()
+                return Err(ApiError::TooManyRequests("too many wrong answers"));
             }
+        };
+        if !correct {
+            info!(
+                "user (openid: {}) gave a wrong answer for duck (duck_id: {})",
+                wechat_openid, duck_id
+            );
+            return Ok(Json(AnswerResponse {
+                correct: false,
+                attempts_left: Some(attempts_left - 1),
+                user_info: None,
+            }));
         }
-    } else {
-        (StatusCode::UNAUTHORIZED, "please login first").into_response()
     }
+    db.delete_pending_discovery(wechat_openid.clone(), duck_id.clone())
+        .await
+        .or_api(ApiError::Internal("error completing pending discovery"))?;
+    let (_, progress) = db
+        .progress_of(Some(&wechat_openid))
+        .await
+        .or_api(ApiError::Internal("error getting story progress"))?;
+    let first_find = !progress.is_found(&duck_id);
+    let data = complete_discovery(&db, wechat_openid, duck_id, season_id, first_find).await?;
+    Ok(Json(AnswerResponse {
+        correct: true,
+        attempts_left: None,
+        user_info: Some(data),
+    }))
+}
+
+/// Record the find of a duck, answering the history and ranking of its season.
+async fn complete_discovery(
+    db: &DB,
+    wechat_openid: String,
+    duck_id: String,
+    season_id: Option<String>,
+    first_find: bool,
+) -> Result<user_info::Data, ApiError> {
+    let data = db
+        .record_duck_view(wechat_openid.clone(), duck_id.clone())
+        .await
+        .or_api(ApiError::NotFound("error recording duck view"))?;
+    info!(
+        "user (openid: {}) find duck (duck_id: {})",
+        wechat_openid, duck_id
+    );
+    if first_find {
+        db.notify(GameEvent::Discovery {
+            wechat_openid,
+            duck_id,
+        })
+        .await;
+    }
+    let data = db
+        .award_achievements(data)
+        .await
+        .or_api(ApiError::Internal("error awarding achievements"))?;
+    let mut data = db
+        .scoped_user_info(data, season_id.as_deref())
+        .await
+        .or_api(ApiError::Internal("error getting or creating user"))?;
+    db.rank_if_complete(&mut data, season_id.as_deref())
+        .await
+        .or_api(ApiError::Internal("error recording ranking"))?;
+    if let Some(team) = &data.team {
+        db.rank_team_if_complete(&team.id, season_id.as_deref())
+            .await
+            .or_api(ApiError::Internal("error recording team ranking"))?;
+    }
+    db.player_info(data)
+        .await
+        .or_api(ApiError::Internal("error getting story progress"))
+}
+
+/// GET api/duck/:duck_id/hints
+///
+/// hints of a duck with the points the player has left, texts of the hints not revealed are `null`
+pub async fn duck_hints(
+    session: Session,
+    State(db): State<DB>,
+    Path(duck_id): Path<String>,
+) -> Result<Json<PlayerHints>, ApiError> {
+    let wechat_openid = check_login(&session).await?;
+    require_unlocked(&db, &wechat_openid, &duck_id).await?;
+    let rsp = db
+        .player_hints(&wechat_openid, &duck_id)
+        .await
+        .or_api(ApiError::NotFound("duck id does not exist"))?;
+    Ok(Json(rsp))
+}
+
+/// POST api/duck/:duck_id/hints/:hint
+///
+/// reveals a hint once available, spending its points, revealing it again costs nothing
+pub async fn reveal_hint(
+    session: Session,
+    State(db): State<DB>,
+    Path((duck_id, hint)): Path<(String, usize)>,
+) -> Result<Json<PlayerHints>, ApiError> {
+    let wechat_openid = check_login(&session).await?;
+    require_unlocked(&db, &wechat_openid, &duck_id).await?;
+    let season_id = db
+        .season_of_duck(&duck_id)
+        .await
+        .or_api(ApiError::NotFound("duck id does not exist"))?;
+    let running = db
+        .is_running_season(season_id.as_deref())
+        .await
+        .or_api(ApiError::Internal("error getting season"))?;
+    if !running {
+        return Err(ApiError::Forbidden("season is not running"));
+    }
+    // points are spent one reveal at a time, so that concurrent reveals
+    // cannot spend more than the balance of the player
+    let lock = format!("hints:{}", wechat_openid);
+    let revealed = db
+        .locked(&lock, async {
+            let hints = db
+                .player_hints(&wechat_openid, &duck_id)
+                .await
+                .or_api(ApiError::NotFound("duck id does not exist"))?;
+            let view = hints
+                .hints
+                .get(hint)
+                .ok_or(ApiError::NotFound("hint does not exist"))?;
+            if view.revealed_at.is_some() {
+                return Ok(false);
+            }
+            if !view.available {
+                return Err(ApiError::Forbidden("hint is locked"));
+            }
+            let cost = match view.unlock {
+                HintUnlock::Points { cost } => cost,
+                _ => 0,
+            };
+            db.record_hint_reveal(wechat_openid.clone(), duck_id.clone(), hint, cost)
+                .await
+                .or_api(ApiError::Internal("error revealing hint"))?;
+            Ok(true)
+        })
+        .await
+        .or_api(ApiError::Internal("error locking hints"))??;
+    if revealed {
+        info!(
+            "user (openid: {}) revealed hint {} of duck (duck_id: {})",
+            wechat_openid, hint, duck_id
+        );
+    }
+    let rsp = db
+        .player_hints(&wechat_openid, &duck_id)
+        .await
+        .or_api(ApiError::Internal("error getting hints"))?;
+    Ok(Json(rsp))
+}
+
+/// reject ducks whose prerequisites the player has not found yet
+async fn require_unlocked(db: &DB, wechat_openid: &str, duck_id: &str) -> Result<(), ApiError> {
+    let (_, progress) = db
+        .progress_of(Some(wechat_openid))
+        .await
+        .or_api(ApiError::Internal("error getting story progress"))?;
+    if progress.is_locked(duck_id) {
+        return Err(ApiError::Forbidden("duck is locked"));
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct PreviewParams {
+    /// convert geographic coordinates to this system
+    system: Option<CoordinateSystem>,
+    /// the main game by default
+    season: Option<String>,
+}
+
+/// GET api/preview-ducks[?system=wgs84|gcj02][&season=SEASON_ID]
+///
+/// ducks of a season, or of the main game without `season`,
+/// hidden ducks have no title and location until unlocked by the player,
+/// and ducks behind prerequisites not found are `locked`
+pub async fn preview_ducks(
+    session: Session,
+    State(db): State<DB>,
+    Query(params): Query<PreviewParams>,
+) -> Result<Json<Vec<DuckPreview>>, ApiError> {
+    let wechat_openid = session.get::<String>(WECHAT_ID_KEY);
+    let mut data = db
+        .player_previews(wechat_openid.as_deref(), params.season.as_deref())
+        .await
+        .or_api(ApiError::Internal("error previewing ducks"))?;
+    if let Some(system) = params.system {
+        convert_coordinates(data.iter_mut().map(|d| &mut d.duck), system);
+    }
+    Ok(Json(data))
+}
+
+#[derive(Deserialize)]
+pub struct NearbyParams {
+    lat: f64,
+    lng: f64,
+    /// in metres
+    radius: f64,
+    /// system of the position, and of the coordinates answered, `wgs84` by default
+    #[serde(default)]
+    system: CoordinateSystem,
+    /// the main game by default
+    season: Option<String>,
+}
+
+/// GET api/nearby-ducks?lat=LATITUDE&lng=LONGITUDE&radius=METRES[&system=wgs84|gcj02][&season=SEASON_ID]
+///
+/// ducks of a season, or of the main game without `season`, nearest first, without hidden ducks not unlocked and, once logged in, without the ducks found
+pub async fn nearby_ducks(
+    session: Session,
+    State(db): State<DB>,
+    Query(params): Query<NearbyParams>,
+) -> Result<Json<Vec<NearbyDuck>>, ApiError> {
+    if !(params.radius > 0.0 && params.radius <= MAX_NEARBY_RADIUS) {
+        return Err(ApiError::BadRequest("radius out of range"));
+    }
+    if !params.system.is_geographic() {
+        return Err(ApiError::BadRequest("invalid position"));
+    }
+    let center = Coordinate::new(params.system, params.lng, params.lat)
+        .or_api(ApiError::BadRequest("invalid position"))?;
+    let wechat_openid = session.get::<String>(WECHAT_ID_KEY);
+    let mut data = db
+        .nearby_ducks(
+            wechat_openid.as_deref(),
+            params.season.as_deref(),
+            center,
+            params.radius,
+        )
+        .await
+        .or_api(ApiError::Internal("error searching nearby ducks"))?;
+    convert_coordinates(data.iter_mut().map(|d| &mut d.duck.duck), params.system);
+    Ok(Json(data))
+}
+
+#[derive(Deserialize)]
+pub struct LeaderboardParams {
+    /// the main game by default
+    season: Option<String>,
+    #[serde(default)]
+    page: usize,
+    page_size: Option<usize>,
+}
+
+/// GET api/leaderboard[?season=SEASON_ID][&page=0][&page_size=20]
+///
+/// best first, with the entry of the player asking once logged in
+pub async fn leaderboard(
+    session: Session,
+    State(db): State<DB>,
+    Query(params): Query<LeaderboardParams>,
+) -> Result<Json<LeaderboardPage>, ApiError> {
+    let wechat_openid = session.get::<String>(WECHAT_ID_KEY);
+    let rsp = db
+        .leaderboard_page(
+            params.season.as_deref(),
+            wechat_openid.as_deref(),
+            params.page,
+            params.page_size.unwrap_or(DEFAULT_PAGE_SIZE),
+        )
+        .await
+        .or_api(ApiError::Internal("error getting leaderboard"))?;
+    Ok(Json(rsp))
+}
+
+#[derive(Deserialize)]
+pub struct AroundMeParams {
+    /// the main game by default
+    season: Option<String>,
+    /// entries on each side of the player
+    around: Option<usize>,
+}
+
+/// GET api/leaderboard/around-me[?season=SEASON_ID][&around=5]
+pub async fn leaderboard_around_me(
+    session: Session,
+    State(db): State<DB>,
+    Query(params): Query<AroundMeParams>,
+) -> Result<Json<Vec<LeaderboardEntry>>, ApiError> {
+    let wechat_openid = check_login(&session).await?;
+    let rsp = db
+        .leaderboard_around(
+            params.season.as_deref(),
+            &wechat_openid,
+            params.around.unwrap_or(DEFAULT_AROUND),
+        )
+        .await
+        .or_api(ApiError::Internal("error getting leaderboard"))?
+        .ok_or(ApiError::NotFound("player is not ranked"))?;
+    Ok(Json(rsp))
 }
 
-/// GET api/preview-ducks
-pub async fn preview_ducks(State(db): State<DB>) -> Response {
-    match db.preview_ducks().await {
-        Ok(data) => Json(data).into_response(),
-        Err(e) => {
-            error!("error previewing ducks: {}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, "error previewing ducks").into_response()
+#[derive(Deserialize)]
+pub struct NewTeamData {
+    name: String,
+}
+
+/// POST api/team with `{"name": NAME}`
+///
+/// creates a team and joins it, leaving the team of the player if any
+pub async fn create_team(
+    session: Session,
+    State(db): State<DB>,
+    Json(data): Json<NewTeamData>,
+) -> Result<Json<TeamProgress>, ApiError> {
+    let wechat_openid = check_login(&session).await?;
+    let name = data.name.trim();
+    let length = name.chars().count();
+    if !(1..=MAX_TEAM_NAME_LENGTH).contains(&length) || name.chars().any(char::is_control) {
+        return Err(ApiError::BadRequest("invalid team name"));
+    }
+    let team = db
+        .new_team(name.to_string())
+        .await
+        .or_api(ApiError::Internal("error creating team"))?;
+    db.set_user_team(wechat_openid.clone(), Some(team.id.clone()))
+        .await
+        .or_api(ApiError::Internal("error joining team"))?;
+    info!(
+        "user (openid: {}) created team (team_id: {})",
+        wechat_openid, team.id
+    );
+    let rsp = db
+        .team_progress(team, None, &wechat_openid)
+        .await
+        .or_api(ApiError::Internal("error getting team progress"))?;
+    Ok(Json(rsp))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JoinTeamData {
+    join_code: String,
+}
+
+/// POST api/team/join with `{"joinCode": CODE}`
+///
+/// joins a team, leaving the team of the player if any
+pub async fn join_team(
+    session: Session,
+    State(db): State<DB>,
+    Json(data): Json<JoinTeamData>,
+) -> Result<Json<TeamProgress>, ApiError> {
+    let wechat_openid = check_login(&session).await?;
+    let team = db
+        .get_team_by_code(data.join_code.trim().to_ascii_uppercase())
+        .await
+        .or_api(ApiError::Internal("error getting team"))?
+        .ok_or(ApiError::NotFound("invalid join code"))?;
+    // players join a team one at a time, so that it cannot grow past its size
+    let lock = format!("team:{}", team.id);
+    let joined = db
+        .locked(&lock, async {
+            let members = db
+                .get_team_members(team.id.clone())
+                .await
+                .or_api(ApiError::Internal("error getting team members"))?;
+            if members.iter().any(|m| m.wechat_open_id == wechat_openid) {
+                return Ok(false);
+            }
+            if members.len() >= MAX_TEAM_SIZE {
+                return Err(ApiError::Forbidden("team is full"));
+            }
+            db.set_user_team(wechat_openid.clone(), Some(team.id.clone()))
+                .await
+                .or_api(ApiError::Internal("error joining team"))?;
+            Ok(true)
+        })
+        .await
+        .or_api(ApiError::Internal("error locking team"))??;
+    if joined {
+        info!(
+            "user (openid: {}) joined team (team_id: {})",
+            wechat_openid, team.id
+        );
+    }
+    let rsp = db
+        .team_progress(team, None, &wechat_openid)
+        .await
+        .or_api(ApiError::Internal("error getting team progress"))?;
+    Ok(Json(rsp))
+}
+
+/// GET api/team[?season=SEASON_ID]
+///
+/// ducks found by any member of the team of the player, in a season or in the main game,
+/// with the ranking of the team
+pub async fn team_progress(
+    session: Session,
+    State(db): State<DB>,
+    Query(params): Query<SeasonParam>,
+) -> Result<Json<TeamProgress>, ApiError> {
+    let wechat_openid = check_login(&session).await?;
+    let data = db
+        .upsert_user_info(wechat_openid.clone())
+        .await
+        .or_api(ApiError::Internal("error getting or creating user"))?;
+    let team_id = data
+        .team
+        .ok_or(ApiError::NotFound("player has no team"))?
+        .id;
+    let team = db
+        .get_team(team_id)
+        .await
+        .or_api(ApiError::Internal("error getting team"))?
+        .ok_or(ApiError::NotFound("player has no team"))?;
+    let rsp = db
+        .team_progress(team, params.season.as_deref(), &wechat_openid)
+        .await
+        .or_api(ApiError::Internal("error getting team progress"))?;
+    Ok(Json(rsp))
+}
+
+/// DELETE api/team
+///
+/// leaves the team of the player, the team keeps its ranking
+pub async fn leave_team(
+    session: Session,
+    State(db): State<DB>,
+) -> Result<Json<user_info::Data>, ApiError> {
+    let wechat_openid = check_login(&session).await?;
+    let data = db
+        .set_user_team(wechat_openid.clone(), None)
+        .await
+        .or_api(ApiError::Internal("error leaving team"))?;
+    info!("user (openid: {}) left their team", wechat_openid);
+    let data = db
+        .player_info(data)
+        .await
+        .or_api(ApiError::Internal("error getting story progress"))?;
+    Ok(Json(data))
+}
+
+/// GET api/team-leaderboard[?season=SEASON_ID]
+///
+/// ranked teams, best first
+pub async fn team_leaderboard(
+    State(db): State<DB>,
+    Query(params): Query<SeasonParam>,
+) -> Result<Json<Vec<TeamLeaderboardEntry>>, ApiError> {
+    let rsp = db
+        .team_leaderboard(params.season.as_deref())
+        .await
+        .or_api(ApiError::Internal("error getting team leaderboard"))?;
+    Ok(Json(rsp))
+}
+
+/// GET api/events
+///
+/// server-sent events: `discovery` and `ranked` for the player logged in,
+/// whichever device or instance they come from, and `leaderboard_changed` for everyone
+pub async fn events(
+    session: Session,
+    State(db): State<DB>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let wechat_openid = session.get::<String>(WECHAT_ID_KEY);
+    let stream = db
+        .subscribe_events()
+        .filter(move |event| future::ready(event.is_for(wechat_openid.as_deref())))
+        .map(|event| {
+            let (name, data) = event.payload();
+            Event::default().event(name).json_data(data)
+        });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// GET api/seasons
+///
+/// every season, archived ones included, with its status
+pub async fn get_seasons(State(db): State<DB>) -> Result<Json<Vec<SeasonInfo>>, ApiError> {
+    let rsp = db
+        .get_all_seasons()
+        .await
+        .or_api(ApiError::Internal("error getting seasons"))?;
+    Ok(Json(rsp.into_iter().map(SeasonInfo::from).collect()))
+}
+
+/// Convert the coordinates of previews to a system,
+/// leaving the ones which cannot be converted as they are.
+fn convert_coordinates<'a>(
+    previews: impl Iterator<Item = &'a mut duck_preview::Data>,
+    system: CoordinateSystem,
+) {
+    for location in previews.filter_map(|d| d.location.as_mut()) {
+        let converted = Coordinate::from_value(&location.coordinate)
+            .and_then(|c| c.to_system(system))
+            .ok()
+            .and_then(|c| serde_json::to_value(c).ok());
+        if let Some(coordinate) = converted {
+            location.coordinate = coordinate;
         }
     }
 }
 
-async fn check_login(session: &Session) -> Option<String> {
-    session.get::<String>(WECHAT_ID_KEY)
+/// Nicknames are shown to everyone: short, printable,
+/// and not mistaken for the handle of another player.
+fn is_valid_nickname(nickname: &str) -> bool {
+    let length = nickname.chars().count();
+    (1..=MAX_NICKNAME_LENGTH).contains(&length)
+        && !nickname.chars().any(char::is_control)
+        && !nickname.starts_with("duck-")
+}
+
+async fn check_login(session: &Session) -> Result<String, ApiError> {
+    session
+        .get::<String>(WECHAT_ID_KEY)
+        .ok_or(ApiError::Unauthorized("please login first"))
 }
@@ -0,0 +1,48 @@
+use crate::admin_auth::{roles, RequireAdmin};
+use crate::api_error::{ApiError, ResultExt};
+use crate::db_api::archive::{Archive, ImportMode};
+use crate::DB;
+use axum::extract::{Query, State};
+use axum::Json;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::error;
+
+/// largest archive accepted by an import
+pub const MAX_ARCHIVE_SIZE: usize = 64 * 1024 * 1024;
+
+#[derive(Deserialize)]
+pub struct ImportParam {
+    mode: ImportMode,
+}
+
+/// GET admin/export
+pub async fn export_archive(
+    _: RequireAdmin<roles::Operator>,
+    State(db): State<DB>,
+) -> Result<Json<Archive>, ApiError> {
+    let rsp = db
+        .export_archive()
+        .await
+        .or_api(ApiError::Internal("error exporting archive"))?;
+    Ok(Json(rsp))
+}
+
+/// POST admin/import?mode=replace|merge|content
+pub async fn import_archive(
+    RequireAdmin(admin, _): RequireAdmin<roles::Operator>,
+    State(db): State<DB>,
+    Query(param): Query<ImportParam>,
+    Json(archive): Json<Archive>,
+) -> Result<Json<Value>, ApiError> {
+    if let Err(e) = archive.check() {
+        error!("invalid archive: {}", e);
+        return Err(ApiError::BadRequest("invalid archive"));
+    }
+    let rsp = db
+        .audited(&admin)
+        .import_archive(archive, param.mode)
+        .await
+        .or_api(ApiError::Internal("error importing archive"))?;
+    Ok(Json(json!({ "imported": rsp })))
+}
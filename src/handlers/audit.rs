@@ -0,0 +1,19 @@
+use crate::admin_auth::{roles, RequireAdmin};
+use crate::api_error::{ApiError, ResultExt};
+use crate::db_api::audit::{AuditLogPage, AuditLogQuery};
+use crate::DB;
+use axum::extract::{Query, State};
+use axum::Json;
+
+/// GET admin/audit-log?entity_type=TYPE&from=TIME&to=TIME&page=N&page_size=N
+pub async fn get_audit_log(
+    _: RequireAdmin<roles::Operator>,
+    State(db): State<DB>,
+    Query(query): Query<AuditLogQuery>,
+) -> Result<Json<AuditLogPage>, ApiError> {
+    let rsp = db
+        .get_audit_log(query)
+        .await
+        .or_api(ApiError::Internal("error getting audit log"))?;
+    Ok(Json(rsp))
+}
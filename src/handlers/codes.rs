@@ -0,0 +1,94 @@
+use crate::admin_auth::{roles, RequireAdmin};
+use crate::api_error::{ApiError, ResultExt};
+use crate::db_api::codes::qr_key_info;
+use crate::handlers::posters::poster_config;
+use crate::DB;
+use axum::extract::{Path, State};
+use axum::Json;
+use serde_json::{json, Value};
+
+/// GET admin/qr-keys
+///
+/// newest first, the newest key which is not retired signs the codes
+pub async fn get_qr_keys(
+    _: RequireAdmin<roles::Operator>,
+    State(db): State<DB>,
+) -> Result<Json<Vec<qr_key_info::Data>>, ApiError> {
+    let rsp = db
+        .get_qr_keys()
+        .await
+        .or_api(ApiError::Internal("error getting qr keys"))?;
+    Ok(Json(rsp.iter().map(|k| k.info()).collect()))
+}
+
+/// POST admin/qr-keys
+///
+/// the new key signs the codes from now on, codes of older keys stay valid
+pub async fn rotate_qr_key(
+    RequireAdmin(admin, _): RequireAdmin<roles::Operator>,
+    State(db): State<DB>,
+) -> Result<Json<qr_key_info::Data>, ApiError> {
+    let rsp = db
+        .audited(&admin)
+        .new_qr_key()
+        .await
+        .or_api(ApiError::Internal("error creating qr key"))?;
+    Ok(Json(rsp.info()))
+}
+
+/// DELETE admin/qr-keys/:kid
+///
+/// codes signed with the key are rejected from now on
+pub async fn retire_qr_key(
+    RequireAdmin(admin, _): RequireAdmin<roles::Operator>,
+    State(db): State<DB>,
+    Path(kid): Path<String>,
+) -> Result<Json<qr_key_info::Data>, ApiError> {
+    let rsp = db
+        .audited(&admin)
+        .retire_qr_key(kid)
+        .await
+        .or_api(ApiError::NotFound("qr key does not exist"))?;
+    Ok(Json(rsp.info()))
+}
+
+/// GET admin/duck/:id/qrcode/url
+pub async fn get_duck_url(
+    _: RequireAdmin<roles::Viewer>,
+    State(db): State<DB>,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, ApiError> {
+    let duck = db
+        .get_duck(id)
+        .await
+        .or_api(ApiError::Internal("error getting duck"))?
+        .ok_or(ApiError::NotFound("duck id does not exist"))?;
+    let code = db
+        .sign_duck_code(&duck.id)
+        .await
+        .or_api(ApiError::Internal("error signing duck code"))?;
+    let url = poster_config()?
+        .duck_url(&duck.id, &code)
+        .or_api(ApiError::Internal("error building duck url"))?;
+    Ok(Json(json!({ "url": url, "code": code })))
+}
+
+/// POST admin/duck/:id/qrcode/invalidate
+///
+/// printed codes of the duck are rejected, new codes must be printed
+pub async fn invalidate_duck_codes(
+    RequireAdmin(admin, _): RequireAdmin<roles::Editor>,
+    State(db): State<DB>,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, ApiError> {
+    db.get_duck(id.clone())
+        .await
+        .or_api(ApiError::Internal("error getting duck"))?
+        .ok_or(ApiError::NotFound("duck id does not exist"))?;
+    let epoch = db
+        .audited(&admin)
+        .bump_duck_code_epoch(id)
+        .await
+        .or_api(ApiError::Internal("error invalidating duck codes"))?;
+    Ok(Json(json!({ "epoch": epoch })))
+}
@@ -0,0 +1,77 @@
+use crate::admin_auth::{roles, RequireAdmin};
+use crate::api_error::{ApiError, ResultExt};
+use crate::db_api::completion::CompletionRule;
+use crate::handlers::seasons::{SeasonParam, WritableExt};
+use crate::DB;
+use axum::extract::{Query, State};
+use axum::Json;
+
+/// GET admin/completion-rules[?season=SEASON_ID]
+///
+/// rules in effect, the default duck count while none is set
+pub async fn get_completion_rules(
+    _: RequireAdmin<roles::Viewer>,
+    State(db): State<DB>,
+    Query(params): Query<SeasonParam>,
+) -> Result<Json<Vec<CompletionRule>>, ApiError> {
+    let rsp = db
+        .completion_rules(params.season.as_deref(), false)
+        .await
+        .or_api(ApiError::Internal("error getting completion rules"))?;
+    Ok(Json(rsp))
+}
+
+/// PUT admin/completion-rules[?season=SEASON_ID]
+///
+/// replace every rule, an empty list brings back the default duck count
+pub async fn set_completion_rules(
+    RequireAdmin(admin, _): RequireAdmin<roles::Operator>,
+    State(db): State<DB>,
+    Query(params): Query<SeasonParam>,
+    Json(rules): Json<Vec<CompletionRule>>,
+) -> Result<Json<Vec<CompletionRule>>, ApiError> {
+    for rule in &rules {
+        rule.check().map_err(ApiError::BadRequest)?;
+    }
+    let rsp = db
+        .audited(&admin)
+        .set_completion_rules(params.season, false, rules)
+        .await
+        .or_writable_api(ApiError::Internal("error setting completion rules"))?;
+    Ok(Json(rsp))
+}
+
+/// GET admin/team-completion-rules[?season=SEASON_ID]
+///
+/// rules teams must meet together, the default duck count while none is set
+pub async fn get_team_completion_rules(
+    _: RequireAdmin<roles::Viewer>,
+    State(db): State<DB>,
+    Query(params): Query<SeasonParam>,
+) -> Result<Json<Vec<CompletionRule>>, ApiError> {
+    let rsp = db
+        .completion_rules(params.season.as_deref(), true)
+        .await
+        .or_api(ApiError::Internal("error getting completion rules"))?;
+    Ok(Json(rsp))
+}
+
+/// PUT admin/team-completion-rules[?season=SEASON_ID]
+///
+/// replace every rule of teams, an empty list brings back the default duck count
+pub async fn set_team_completion_rules(
+    RequireAdmin(admin, _): RequireAdmin<roles::Operator>,
+    State(db): State<DB>,
+    Query(params): Query<SeasonParam>,
+    Json(rules): Json<Vec<CompletionRule>>,
+) -> Result<Json<Vec<CompletionRule>>, ApiError> {
+    for rule in &rules {
+        rule.check().map_err(ApiError::BadRequest)?;
+    }
+    let rsp = db
+        .audited(&admin)
+        .set_completion_rules(params.season, true, rules)
+        .await
+        .or_writable_api(ApiError::Internal("error setting completion rules"))?;
+    Ok(Json(rsp))
+}
@@ -0,0 +1,128 @@
+use crate::admin_auth::{gen_token, hash_token, roles, Admin, RequireAdmin};
+use crate::api_error::{ApiError, ResultExt};
+use crate::db_api::dangerous::{snapshot_info, DangerousOp};
+use crate::DB;
+use axum::extract::{Path, State};
+use axum::Json;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tracing::info;
+
+/// how long a confirmation token of a `/dangerous` route stays valid
+const CONFIRMATION_TTL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Deserialize)]
+pub struct ConfirmParam {
+    pub(crate) confirm: Option<String>,
+}
+
+/// outcome of the first or second call to a `/dangerous` route
+pub enum Phase {
+    /// what would be deleted, and the token to confirm with
+    DryRun(Json<Value>),
+    /// the deletion may go ahead, the snapshot to undo it is saved
+    Confirmed(snapshot_info::Data),
+}
+
+/// Two-phase protocol of `/dangerous` routes.
+///
+/// Without a token, report what `op` would delete and issue a confirmation token,
+/// which is valid once, for the same admin and operation.
+/// With a valid token, and only if the documents are still those of the dry run,
+/// save a snapshot of what is about to be deleted.
+pub async fn confirm(
+    db: &DB,
+    admin: &Admin,
+    op: DangerousOp,
+    token: Option<String>,
+) -> Result<Phase, ApiError> {
+    let snapshot = db
+        .collect_snapshot(&op)
+        .await
+        .or_api(ApiError::Internal("error collecting snapshot"))?;
+    match token {
+        None => {
+            let report = db
+                .dry_run_report(&snapshot)
+                .await
+                .or_api(ApiError::Internal("error counting rankings"))?;
+            let digest = snapshot
+                .digest()
+                .or_api(ApiError::Internal("error hashing snapshot"))?;
+            let token = gen_token();
+            db.save_confirmation(
+                confirmation_key(admin, &op, &token),
+                digest,
+                CONFIRMATION_TTL,
+            )
+            .await
+            .or_api(ApiError::Internal("error saving confirmation"))?;
+            Ok(Phase::DryRun(Json(json!({
+                "dry_run": report,
+                "confirmation_token": token,
+                "expires_in_seconds": CONFIRMATION_TTL.as_secs(),
+            }))))
+        }
+        Some(token) => {
+            let digest = db
+                .take_confirmation(confirmation_key(admin, &op, &token))
+                .await
+                .or_api(ApiError::Internal("error checking confirmation"))?
+                .ok_or(ApiError::BadRequest(
+                    "invalid or expired confirmation token",
+                ))?;
+            let current = snapshot
+                .digest()
+                .or_api(ApiError::Internal("error hashing snapshot"))?;
+            if digest != current {
+                return Err(ApiError::Conflict(
+                    "the data changed since the dry run, which must be repeated",
+                ));
+            }
+            let snapshot = db
+                .save_snapshot(admin.name.clone(), admin.route.clone(), snapshot)
+                .await
+                .or_api(ApiError::Internal("error saving snapshot"))?;
+            info!(
+                "admin {} confirmed {}, snapshot {} saved",
+                admin.name, admin.route, snapshot.id
+            );
+            Ok(Phase::Confirmed(snapshot))
+        }
+    }
+}
+
+fn confirmation_key(admin: &Admin, op: &DangerousOp, token: &str) -> String {
+    hash_token(&format!("{}\n{}\n{}", admin.name, op.scope(), token))
+}
+
+/// GET admin/snapshots
+pub async fn get_snapshots(
+    _: RequireAdmin<roles::Operator>,
+    State(db): State<DB>,
+) -> Result<Json<Vec<snapshot_info::Data>>, ApiError> {
+    let rsp = db
+        .get_snapshots()
+        .await
+        .or_api(ApiError::Internal("error getting snapshots"))?;
+    Ok(Json(rsp))
+}
+
+/// POST admin/snapshots/:id/restore
+///
+/// undo the bulk delete which took the snapshot
+pub async fn restore_snapshot(
+    RequireAdmin(admin, _): RequireAdmin<roles::Operator>,
+    State(db): State<DB>,
+    Path(snapshot_id): Path<String>,
+) -> Result<Json<Value>, ApiError> {
+    let rsp = db
+        .audited(&admin)
+        .restore_snapshot(snapshot_id)
+        .await
+        .or_api(ApiError::NotFound("error restoring snapshot"))?;
+    Ok(Json(json!({
+        "number_of_documents_restored": rsp,
+    })))
+}
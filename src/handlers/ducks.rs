@@ -1,174 +1,242 @@
-use crate::db_api::ducks::{NewDuckData, UpdateDuckData};
-use crate::{DB, SERVER_CONFIG};
+use crate::admin_auth::{roles, RequireAdmin};
+use crate::api_error::{ApiError, ResultExt};
+use crate::db_api::challenges::{Challenge, ChallengeResults};
+use crate::db_api::dangerous::DangerousOp;
+use crate::db_api::ducks::{duck_info, NewDuckData, UpdateDuckData};
+use crate::db_api::hints::{Hint, HintStats};
+use crate::handlers::dangerous::{confirm, ConfirmParam, Phase};
+use crate::handlers::seasons::WritableExt;
+use crate::prisma::duck;
+use crate::DB;
 use axum::extract::{Path, Query, State};
-use axum::http::StatusCode;
-use axum::response::{IntoResponse, Response};
 use axum::Json;
-use axum_auth::AuthBearer;
 use serde::Deserialize;
-use serde_json::json;
-use tracing::error;
+use serde_json::{json, Value};
 
 /// POST admin/duck
 pub async fn create_duck(
-    AuthBearer(token): AuthBearer,
+    RequireAdmin(admin, _): RequireAdmin<roles::Editor>,
     State(db): State<DB>,
     Json(data): Json<NewDuckData>,
-) -> Response {
-    if token.eq(&SERVER_CONFIG.admin_token) {
-        match db.create_duck(data).await {
-            Ok(rsp) => Json(rsp).into_response(),
-            Err(e) => {
-                error!("error creating duck: {}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "error creating duck").into_response()
-            }
-        }
-    } else {
-        (StatusCode::UNAUTHORIZED, "provide admin token").into_response()
-    }
+) -> Result<Json<duck::Data>, ApiError> {
+    let rsp = db
+        .audited(&admin)
+        .create_duck(data)
+        .await
+        .or_writable_api(ApiError::Internal("error creating duck"))?;
+    Ok(Json(rsp))
 }
 
 /// GET admin/duck/:id
 pub async fn get_duck(
-    AuthBearer(token): AuthBearer,
+    _: RequireAdmin<roles::Viewer>,
     State(db): State<DB>,
     Path(duck_id): Path<String>,
-) -> Response {
-    if token.eq(&SERVER_CONFIG.admin_token) {
-        match db.get_duck(duck_id).await {
-            Ok(Some(rsp)) => Json(rsp).into_response(),
-            Ok(None) => (StatusCode::NOT_FOUND, "duck id does not exist").into_response(),
-            Err(e) => {
-                error!("error getting duck: {}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "error getting duck").into_response()
-            }
-        }
-    } else {
-        (StatusCode::UNAUTHORIZED, "provide admin token").into_response()
-    }
+) -> Result<Json<duck_info::Data>, ApiError> {
+    let rsp = db
+        .get_duck(duck_id)
+        .await
+        .or_api(ApiError::Internal("error getting duck"))?
+        .ok_or(ApiError::NotFound("duck id does not exist"))?;
+    Ok(Json(rsp))
 }
 
 /// PATCH admin/duck/:id
 pub async fn update_duck(
-    AuthBearer(token): AuthBearer,
+    RequireAdmin(admin, _): RequireAdmin<roles::Editor>,
     State(db): State<DB>,
     Path(duck_id): Path<String>,
     Json(data): Json<UpdateDuckData>,
-) -> Response {
-    if token.eq(&SERVER_CONFIG.admin_token) {
-        match db.update_duck(duck_id, data).await {
-            Ok(rsp) => Json(rsp).into_response(),
-            Err(e) => {
-                error!("error updating duck: {}", e);
-                (StatusCode::NOT_FOUND, "error updating duck").into_response()
-            }
-        }
-    } else {
-        (StatusCode::UNAUTHORIZED, "provide admin token").into_response()
-    }
+) -> Result<Json<duck::Data>, ApiError> {
+    let rsp = db
+        .audited(&admin)
+        .update_duck(duck_id, data)
+        .await
+        .or_writable_api(ApiError::NotFound("error updating duck"))?;
+    Ok(Json(rsp))
+}
+
+/// POST admin/duck/:id/restore
+pub async fn restore_duck(
+    RequireAdmin(admin, _): RequireAdmin<roles::Editor>,
+    State(db): State<DB>,
+    Path(duck_id): Path<String>,
+) -> Result<Json<duck::Data>, ApiError> {
+    let rsp = db
+        .audited(&admin)
+        .restore_duck(duck_id)
+        .await
+        .or_writable_api(ApiError::NotFound("error restoring duck"))?;
+    Ok(Json(rsp))
 }
 
 /// DELETE admin/duck/:id
+///
+/// move the duck to the trash, see `restore_duck`
 pub async fn delete_duck(
-    AuthBearer(token): AuthBearer,
+    RequireAdmin(admin, _): RequireAdmin<roles::Editor>,
+    State(db): State<DB>,
+    Path(duck_id): Path<String>,
+) -> Result<Json<duck::Data>, ApiError> {
+    let rsp = db
+        .audited(&admin)
+        .delete_duck(duck_id)
+        .await
+        .or_writable_api(ApiError::NotFound("error deleting duck"))?;
+    Ok(Json(rsp))
+}
+
+/// PUT admin/duck/:id/challenge
+///
+/// players answer the challenge before a find of the duck counts
+pub async fn set_duck_challenge(
+    RequireAdmin(admin, _): RequireAdmin<roles::Editor>,
+    State(db): State<DB>,
+    Path(duck_id): Path<String>,
+    Json(challenge): Json<Challenge>,
+) -> Result<Json<duck::Data>, ApiError> {
+    challenge.check().map_err(ApiError::BadRequest)?;
+    let rsp = db
+        .audited(&admin)
+        .set_duck_challenge(duck_id, Some(challenge))
+        .await
+        .or_writable_api(ApiError::NotFound("error setting duck challenge"))?;
+    Ok(Json(rsp))
+}
+
+/// DELETE admin/duck/:id/challenge
+pub async fn delete_duck_challenge(
+    RequireAdmin(admin, _): RequireAdmin<roles::Editor>,
+    State(db): State<DB>,
+    Path(duck_id): Path<String>,
+) -> Result<Json<duck::Data>, ApiError> {
+    let rsp = db
+        .audited(&admin)
+        .set_duck_challenge(duck_id, None)
+        .await
+        .or_writable_api(ApiError::NotFound("error removing duck challenge"))?;
+    Ok(Json(rsp))
+}
+
+/// GET admin/duck/:id/challenge/results
+pub async fn get_challenge_results(
+    _: RequireAdmin<roles::Viewer>,
+    State(db): State<DB>,
+    Path(duck_id): Path<String>,
+) -> Result<Json<ChallengeResults>, ApiError> {
+    let rsp = db
+        .challenge_results(&duck_id)
+        .await
+        .or_api(ApiError::Internal("error getting challenge results"))?;
+    Ok(Json(rsp))
+}
+
+/// PUT admin/duck/:id/hints
+///
+/// replaces the hints of the duck, in the order players reveal them
+pub async fn set_duck_hints(
+    RequireAdmin(admin, _): RequireAdmin<roles::Editor>,
     State(db): State<DB>,
     Path(duck_id): Path<String>,
-) -> Response {
-    if token.eq(&SERVER_CONFIG.admin_token) {
-        match db.delete_duck(duck_id).await {
-            Ok(rsp) => Json(rsp).into_response(),
-            Err(e) => {
-                error!("error deleting duck: {}", e);
-                (StatusCode::NOT_FOUND, "error deleting duck").into_response()
-            }
-        }
-    } else {
-        (StatusCode::UNAUTHORIZED, "provide admin token").into_response()
-    }
+    Json(hints): Json<Vec<Hint>>,
+) -> Result<Json<duck::Data>, ApiError> {
+    let rsp = db
+        .audited(&admin)
+        .set_duck_hints(duck_id, hints)
+        .await
+        .or_writable_api(ApiError::NotFound("error setting duck hints"))?;
+    Ok(Json(rsp))
+}
+
+/// GET admin/duck/:id/hints/stats
+pub async fn get_hint_stats(
+    _: RequireAdmin<roles::Viewer>,
+    State(db): State<DB>,
+    Path(duck_id): Path<String>,
+) -> Result<Json<Vec<HintStats>>, ApiError> {
+    let rsp = db
+        .hint_stats(&duck_id)
+        .await
+        .or_api(ApiError::NotFound("duck id does not exist"))?;
+    Ok(Json(rsp))
 }
 
 /// POST admin/many-ducks
 pub async fn create_many_ducks(
-    AuthBearer(token): AuthBearer,
+    RequireAdmin(admin, _): RequireAdmin<roles::Editor>,
     State(db): State<DB>,
     Json(data): Json<Vec<NewDuckData>>,
-) -> Response {
-    if token.eq(&SERVER_CONFIG.admin_token) {
-        match db.create_many_ducks(data).await {
-            Ok(rsp) => Json(json!({
-                "number_of_ducks_created": rsp,
-            }))
-            .into_response(),
-            Err(e) => {
-                error!("error creating ducks: {}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "error creating ducks").into_response()
-            }
-        }
-    } else {
-        (StatusCode::UNAUTHORIZED, "provide admin token").into_response()
-    }
+) -> Result<Json<Value>, ApiError> {
+    let rsp = db
+        .audited(&admin)
+        .create_many_ducks(data)
+        .await
+        .or_writable_api(ApiError::Internal("error creating ducks"))?;
+    Ok(Json(json!({
+        "number_of_ducks_created": rsp,
+    })))
 }
 
 /// GET admin/many-ducks
-pub async fn get_all_ducks(AuthBearer(token): AuthBearer, State(db): State<DB>) -> Response {
-    if token.eq(&SERVER_CONFIG.admin_token) {
-        match db.get_all_ducks().await {
-            Ok(rsp) => Json(rsp).into_response(),
-            Err(e) => {
-                error!("error getting ducks: {}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "error getting ducks").into_response()
-            }
-        }
-    } else {
-        (StatusCode::UNAUTHORIZED, "provide admin token").into_response()
-    }
+pub async fn get_all_ducks(
+    _: RequireAdmin<roles::Viewer>,
+    State(db): State<DB>,
+) -> Result<Json<Vec<duck_info::Data>>, ApiError> {
+    let rsp = db
+        .get_all_ducks()
+        .await
+        .or_api(ApiError::Internal("error getting ducks"))?;
+    Ok(Json(rsp))
 }
 
 #[derive(Deserialize)]
 pub struct DeleteDuckHistoryParam {
     user_id: String,
+    confirm: Option<String>,
 }
 
-/// DELETE admin/duck-history/dangerous?user_id=USER_ID
+/// DELETE admin/duck-history/dangerous?user_id=USER_ID[&confirm=TOKEN]
 pub async fn delete_duck_history(
-    AuthBearer(token): AuthBearer,
+    RequireAdmin(admin, _): RequireAdmin<roles::Operator>,
     State(db): State<DB>,
     Query(params): Query<DeleteDuckHistoryParam>,
-) -> Response {
-    if token.eq(&SERVER_CONFIG.admin_token) {
-        match db.delete_duck_history(params.user_id).await {
-            Ok(rsp) => Json(json!({
-                "number_of_duck_view_records_deleted": rsp,
-            }))
-            .into_response(),
-            Err(e) => {
-                error!("error deleting duck history: {}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "error deleting ducks history",
-                )
-                    .into_response()
-            }
-        }
-    } else {
-        (StatusCode::UNAUTHORIZED, "provide admin token").into_response()
-    }
+) -> Result<Json<Value>, ApiError> {
+    let op = DangerousOp::DeleteDuckHistory {
+        user_id: params.user_id.clone(),
+    };
+    let snapshot = match confirm(&db, &admin, op, params.confirm).await? {
+        Phase::DryRun(report) => return Ok(report),
+        Phase::Confirmed(snapshot) => snapshot,
+    };
+    let rsp = db
+        .audited(&admin)
+        .delete_duck_history(params.user_id)
+        .await
+        .or_api(ApiError::Internal("error deleting ducks history"))?;
+    Ok(Json(json!({
+        "number_of_duck_view_records_deleted": rsp,
+        "snapshot_id": snapshot.id,
+    })))
 }
 
-/// DELETE admin/dangerous/many-ducks
-pub async fn delete_all_ducks(AuthBearer(token): AuthBearer, State(db): State<DB>) -> Response {
-    if token.eq(&SERVER_CONFIG.admin_token) {
-        match db.delete_all_ducks().await {
-            Ok(rsp) => Json(json!({
-                "number_of_ducks_deleted": rsp,
-            }))
-            .into_response(),
-            Err(e) => {
-                error!("error deleting ducks: {}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "error deleting ducks").into_response()
-            }
-        }
-    } else {
-        (StatusCode::UNAUTHORIZED, "provide admin token").into_response()
-    }
+/// DELETE admin/many-ducks/dangerous[?confirm=TOKEN]
+pub async fn delete_all_ducks(
+    RequireAdmin(admin, _): RequireAdmin<roles::Operator>,
+    State(db): State<DB>,
+    Query(params): Query<ConfirmParam>,
+) -> Result<Json<Value>, ApiError> {
+    let op = DangerousOp::DeleteAllDucks;
+    let snapshot = match confirm(&db, &admin, op, params.confirm).await? {
+        Phase::DryRun(report) => return Ok(report),
+        Phase::Confirmed(snapshot) => snapshot,
+    };
+    let rsp = db
+        .audited(&admin)
+        .delete_all_ducks()
+        .await
+        .or_api(ApiError::Internal("error deleting ducks"))?;
+    Ok(Json(json!({
+        "number_of_ducks_deleted": rsp,
+        "snapshot_id": snapshot.id,
+    })))
 }
@@ -1,142 +1,133 @@
+use crate::admin_auth::{roles, RequireAdmin};
+use crate::api_error::{ApiError, ResultExt};
+use crate::db_api::dangerous::DangerousOp;
 use crate::db_api::exhibits::{NewExhibitData, UpdateExhibitData};
-use crate::{DB, SERVER_CONFIG};
-use axum::extract::{Path, State};
-use axum::http::StatusCode;
-use axum::response::{IntoResponse, Response};
+use crate::handlers::dangerous::{confirm, ConfirmParam, Phase};
+use crate::prisma::exhibit;
+use crate::DB;
+use axum::extract::{Path, Query, State};
 use axum::Json;
-use axum_auth::AuthBearer;
-use serde_json::json;
-use tracing::error;
+use serde_json::{json, Value};
 
 /// POST admin/exhibit
 pub async fn create_exhibit(
-    AuthBearer(token): AuthBearer,
+    RequireAdmin(admin, _): RequireAdmin<roles::Editor>,
     State(db): State<DB>,
     Json(data): Json<NewExhibitData>,
-) -> Response {
-    if token.eq(&SERVER_CONFIG.admin_token) {
-        match db.create_exhibit(data).await {
-            Ok(rsp) => Json(rsp).into_response(),
-            Err(e) => {
-                error!("error creating exhibit: {}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "error creating exhibit").into_response()
-            }
-        }
-    } else {
-        (StatusCode::UNAUTHORIZED, "provide admin token").into_response()
-    }
+) -> Result<Json<exhibit::Data>, ApiError> {
+    let rsp = db
+        .audited(&admin)
+        .create_exhibit(data)
+        .await
+        .or_api(ApiError::Internal("error creating exhibit"))?;
+    Ok(Json(rsp))
 }
 
 /// GET admin/exhibit/:id
 pub async fn get_exhibit(
-    AuthBearer(token): AuthBearer,
+    _: RequireAdmin<roles::Viewer>,
     State(db): State<DB>,
     Path(exhibit_id): Path<String>,
-) -> Response {
-    if token.eq(&SERVER_CONFIG.admin_token) {
-        match db.get_exhibit(exhibit_id).await {
-            Ok(Some(rsp)) => Json(rsp).into_response(),
-            Ok(None) => (StatusCode::NOT_FOUND, "exhibit id does not exist").into_response(),
-            Err(e) => {
-                error!("error getting exhibit: {}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "error getting exhibit").into_response()
-            }
-        }
-    } else {
-        (StatusCode::UNAUTHORIZED, "provide admin token").into_response()
-    }
+) -> Result<Json<exhibit::Data>, ApiError> {
+    let rsp = db
+        .get_exhibit(exhibit_id)
+        .await
+        .or_api(ApiError::Internal("error getting exhibit"))?
+        .ok_or(ApiError::NotFound("exhibit id does not exist"))?;
+    Ok(Json(rsp))
 }
 
 /// PATCH admin/exhibit/:id
 pub async fn update_exhibit(
-    AuthBearer(token): AuthBearer,
+    RequireAdmin(admin, _): RequireAdmin<roles::Editor>,
     State(db): State<DB>,
     Path(exhibit_id): Path<String>,
     Json(data): Json<UpdateExhibitData>,
-) -> Response {
-    if token.eq(&SERVER_CONFIG.admin_token) {
-        match db.update_exhibit(exhibit_id, data).await {
-            Ok(rsp) => Json(rsp).into_response(),
-            Err(e) => {
-                error!("error updating exhibit: {}", e);
-                (StatusCode::NOT_FOUND, "error updating exhibit").into_response()
-            }
-        }
-    } else {
-        (StatusCode::UNAUTHORIZED, "provide admin token").into_response()
-    }
+) -> Result<Json<exhibit::Data>, ApiError> {
+    let rsp = db
+        .audited(&admin)
+        .update_exhibit(exhibit_id, data)
+        .await
+        .or_api(ApiError::NotFound("error updating exhibit"))?;
+    Ok(Json(rsp))
+}
+
+/// POST admin/exhibit/:id/restore
+pub async fn restore_exhibit(
+    RequireAdmin(admin, _): RequireAdmin<roles::Editor>,
+    State(db): State<DB>,
+    Path(exhibit_id): Path<String>,
+) -> Result<Json<exhibit::Data>, ApiError> {
+    let rsp = db
+        .audited(&admin)
+        .restore_exhibit(exhibit_id)
+        .await
+        .or_api(ApiError::NotFound("error restoring exhibit"))?;
+    Ok(Json(rsp))
 }
 
 /// DELETE admin/exhibit/:id
+///
+/// move the exhibit to the trash, see `restore_exhibit`
 pub async fn delete_exhibit(
-    AuthBearer(token): AuthBearer,
+    RequireAdmin(admin, _): RequireAdmin<roles::Editor>,
     State(db): State<DB>,
     Path(exhibit_id): Path<String>,
-) -> Response {
-    if token.eq(&SERVER_CONFIG.admin_token) {
-        match db.delete_exhibit(exhibit_id).await {
-            Ok(rsp) => Json(rsp).into_response(),
-            Err(e) => {
-                error!("error deleting exhibit: {}", e);
-                (StatusCode::NOT_FOUND, "error deleting exhibit").into_response()
-            }
-        }
-    } else {
-        (StatusCode::UNAUTHORIZED, "provide admin token").into_response()
-    }
+) -> Result<Json<exhibit::Data>, ApiError> {
+    let rsp = db
+        .audited(&admin)
+        .delete_exhibit(exhibit_id)
+        .await
+        .or_api(ApiError::NotFound("error deleting exhibit"))?;
+    Ok(Json(rsp))
 }
 
 /// POST admin/many-exhibits
 pub async fn create_many_exhibits(
-    AuthBearer(token): AuthBearer,
+    RequireAdmin(admin, _): RequireAdmin<roles::Editor>,
     State(db): State<DB>,
     Json(data): Json<Vec<NewExhibitData>>,
-) -> Response {
-    if token.eq(&SERVER_CONFIG.admin_token) {
-        match db.create_many_exhibits(data).await {
-            Ok(rsp) => Json(json!({
-                "number_of_exhibits_created": rsp,
-            }))
-            .into_response(),
-            Err(e) => {
-                error!("error creating exhibits: {}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "error creating exhibits").into_response()
-            }
-        }
-    } else {
-        (StatusCode::UNAUTHORIZED, "provide admin token").into_response()
-    }
+) -> Result<Json<Value>, ApiError> {
+    let rsp = db
+        .audited(&admin)
+        .create_many_exhibits(data)
+        .await
+        .or_api(ApiError::Internal("error creating exhibits"))?;
+    Ok(Json(json!({
+        "number_of_exhibits_created": rsp,
+    })))
 }
 
 /// GET admin/many-exhibits
-pub async fn get_all_exhibits(AuthBearer(token): AuthBearer, State(db): State<DB>) -> Response {
-    if token.eq(&SERVER_CONFIG.admin_token) {
-        match db.get_all_exhibits().await {
-            Ok(rsp) => Json(rsp).into_response(),
-            Err(e) => {
-                error!("error getting exhibits: {}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "error getting exhibits").into_response()
-            }
-        }
-    } else {
-        (StatusCode::UNAUTHORIZED, "provide admin token").into_response()
-    }
+pub async fn get_all_exhibits(
+    _: RequireAdmin<roles::Viewer>,
+    State(db): State<DB>,
+) -> Result<Json<Vec<exhibit::Data>>, ApiError> {
+    let rsp = db
+        .get_all_exhibits()
+        .await
+        .or_api(ApiError::Internal("error getting exhibits"))?;
+    Ok(Json(rsp))
 }
 
-/// DELETE admin/dangerous/many-exhibits
-pub async fn delete_all_exhibits(AuthBearer(token): AuthBearer, State(db): State<DB>) -> Response {
-    if token.eq(&SERVER_CONFIG.admin_token) {
-        match db.delete_all_exhibits().await {
-            Ok(rsp) => Json(json!({
-                "number_of_exhibits_deleted": rsp,
-            }))
-            .into_response(),
-            Err(e) => {
-                error!("error deleting exhibits: {}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "error deleting exhibits").into_response()
-            }
-        }
-    } else {
-        (StatusCode::UNAUTHORIZED, "provide admin token").into_response()
-    }
+/// DELETE admin/many-exhibits/dangerous[?confirm=TOKEN]
+pub async fn delete_all_exhibits(
+    RequireAdmin(admin, _): RequireAdmin<roles::Operator>,
+    State(db): State<DB>,
+    Query(params): Query<ConfirmParam>,
+) -> Result<Json<Value>, ApiError> {
+    let op = DangerousOp::DeleteAllExhibits;
+    let snapshot = match confirm(&db, &admin, op, params.confirm).await? {
+        Phase::DryRun(report) => return Ok(report),
+        Phase::Confirmed(snapshot) => snapshot,
+    };
+    let rsp = db
+        .audited(&admin)
+        .delete_all_exhibits()
+        .await
+        .or_api(ApiError::Internal("error deleting exhibits"))?;
+    Ok(Json(json!({
+        "number_of_exhibits_deleted": rsp,
+        "snapshot_id": snapshot.id,
+    })))
 }
@@ -0,0 +1,20 @@
+use crate::admin_auth::{roles, RequireAdmin};
+use crate::api_error::{ApiError, ResultExt};
+use crate::prisma::discovery_flag;
+use crate::DB;
+use axum::extract::State;
+use axum::Json;
+
+/// GET admin/discovery-flags
+///
+/// suspicious finds, newest first
+pub async fn get_discovery_flags(
+    _: RequireAdmin<roles::Operator>,
+    State(db): State<DB>,
+) -> Result<Json<Vec<discovery_flag::Data>>, ApiError> {
+    let rsp = db
+        .get_discovery_flags()
+        .await
+        .or_api(ApiError::Internal("error getting discovery flags"))?;
+    Ok(Json(rsp))
+}
@@ -1,150 +1,172 @@
+use crate::admin_auth::{roles, RequireAdmin};
+use crate::api_error::{ApiError, ResultExt};
+use crate::coordinates::CoordinateSystem;
+use crate::db_api::dangerous::DangerousOp;
 use crate::db_api::locations::{NewLocationData, UpdateLocationData};
-use crate::{DB, SERVER_CONFIG};
-use axum::extract::{Path, State};
-use axum::http::StatusCode;
-use axum::response::{IntoResponse, Response};
+use crate::handlers::dangerous::{confirm, ConfirmParam, Phase};
+use crate::handlers::seasons::WritableExt;
+use crate::prisma::location;
+use crate::DB;
+use axum::extract::{Path, Query, State};
 use axum::Json;
-use axum_auth::AuthBearer;
-use serde_json::json;
-use tracing::error;
+use serde::Deserialize;
+use serde_json::{json, Value};
 
 /// POST admin/location
 pub async fn create_location(
-    AuthBearer(token): AuthBearer,
+    RequireAdmin(admin, _): RequireAdmin<roles::Editor>,
     State(db): State<DB>,
     Json(data): Json<NewLocationData>,
-) -> Response {
-    if token.eq(&SERVER_CONFIG.admin_token) {
-        match db.create_location(data).await {
-            Ok(rsp) => Json(rsp).into_response(),
-            Err(e) => {
-                error!("error creating location: {}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "error creating location").into_response()
-            }
-        }
-    } else {
-        (StatusCode::UNAUTHORIZED, "provide admin token").into_response()
-    }
+) -> Result<Json<location::Data>, ApiError> {
+    let rsp = db
+        .audited(&admin)
+        .create_location(data)
+        .await
+        .or_writable_api(ApiError::Internal("error creating location"))?;
+    Ok(Json(rsp))
 }
 
 /// GET admin/location/:id
 pub async fn get_location(
-    AuthBearer(token): AuthBearer,
+    _: RequireAdmin<roles::Viewer>,
     State(db): State<DB>,
     Path(location_id): Path<String>,
-) -> Response {
-    if token.eq(&SERVER_CONFIG.admin_token) {
-        match db.get_location(location_id).await {
-            Ok(Some(rsp)) => Json(rsp).into_response(),
-            Ok(None) => (StatusCode::NOT_FOUND, "location id does not exist").into_response(),
-            Err(e) => {
-                error!("error getting location: {}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "error getting location").into_response()
-            }
-        }
-    } else {
-        (StatusCode::UNAUTHORIZED, "provide admin token").into_response()
-    }
+) -> Result<Json<location::Data>, ApiError> {
+    let rsp = db
+        .get_location(location_id)
+        .await
+        .or_api(ApiError::Internal("error getting location"))?
+        .ok_or(ApiError::NotFound("location id does not exist"))?;
+    Ok(Json(rsp))
 }
 
 /// PATCH admin/location/:id
 pub async fn update_location(
-    AuthBearer(token): AuthBearer,
+    RequireAdmin(admin, _): RequireAdmin<roles::Editor>,
     State(db): State<DB>,
     Path(location_id): Path<String>,
     Json(data): Json<UpdateLocationData>,
-) -> Response {
-    if token.eq(&SERVER_CONFIG.admin_token) {
-        match db.update_location(location_id, data).await {
-            Ok(rsp) => Json(rsp).into_response(),
-            Err(e) => {
-                error!("error updating location: {}", e);
-                (StatusCode::NOT_FOUND, "error updating location").into_response()
-            }
-        }
-    } else {
-        (StatusCode::UNAUTHORIZED, "provide admin token").into_response()
-    }
+) -> Result<Json<location::Data>, ApiError> {
+    let rsp = db
+        .audited(&admin)
+        .update_location(location_id, data)
+        .await
+        .or_writable_api(ApiError::NotFound("error updating location"))?;
+    Ok(Json(rsp))
+}
+
+/// POST admin/location/:id/restore
+pub async fn restore_location(
+    RequireAdmin(admin, _): RequireAdmin<roles::Editor>,
+    State(db): State<DB>,
+    Path(location_id): Path<String>,
+) -> Result<Json<location::Data>, ApiError> {
+    let rsp = db
+        .audited(&admin)
+        .restore_location(location_id)
+        .await
+        .or_writable_api(ApiError::NotFound("error restoring location"))?;
+    Ok(Json(rsp))
 }
 
 /// DELETE admin/location/:id
+///
+/// move the location to the trash, see `restore_location`
 pub async fn delete_location(
-    AuthBearer(token): AuthBearer,
+    RequireAdmin(admin, _): RequireAdmin<roles::Editor>,
     State(db): State<DB>,
     Path(location_id): Path<String>,
-) -> Response {
-    if token.eq(&SERVER_CONFIG.admin_token) {
-        match db.delete_location(location_id).await {
-            Ok(rsp) => Json(rsp).into_response(),
-            Err(e) => {
-                error!("error deleting location: {}", e);
-                (StatusCode::NOT_FOUND, "error deleting location").into_response()
-            }
-        }
-    } else {
-        (StatusCode::UNAUTHORIZED, "provide admin token").into_response()
-    }
+) -> Result<Json<location::Data>, ApiError> {
+    let rsp = db
+        .audited(&admin)
+        .delete_location(location_id)
+        .await
+        .or_writable_api(ApiError::NotFound("error deleting location"))?;
+    Ok(Json(rsp))
 }
 
 /// POST admin/many-locations
 pub async fn create_many_locations(
-    AuthBearer(token): AuthBearer,
+    RequireAdmin(admin, _): RequireAdmin<roles::Editor>,
     State(db): State<DB>,
     Json(data): Json<Vec<NewLocationData>>,
-) -> Response {
-    if token.eq(&SERVER_CONFIG.admin_token) {
-        match db.create_many_locations(data).await {
-            Ok(rsp) => Json(json!({
-                "number_of_locations_created": rsp,
-            }))
-            .into_response(),
-            Err(e) => {
-                error!("error creating locations: {}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "error creating locations",
-                )
-                    .into_response()
-            }
-        }
-    } else {
-        (StatusCode::UNAUTHORIZED, "provide admin token").into_response()
-    }
+) -> Result<Json<Value>, ApiError> {
+    let rsp = db
+        .audited(&admin)
+        .create_many_locations(data)
+        .await
+        .or_writable_api(ApiError::Internal("error creating locations"))?;
+    Ok(Json(json!({
+        "number_of_locations_created": rsp,
+    })))
 }
 
 /// GET admin/many-locations
-pub async fn get_all_locations(AuthBearer(token): AuthBearer, State(db): State<DB>) -> Response {
-    if token.eq(&SERVER_CONFIG.admin_token) {
-        match db.get_all_locations().await {
-            Ok(rsp) => Json(rsp).into_response(),
-            Err(e) => {
-                error!("error getting locations: {}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "error getting locations").into_response()
-            }
-        }
-    } else {
-        (StatusCode::UNAUTHORIZED, "provide admin token").into_response()
-    }
+pub async fn get_all_locations(
+    _: RequireAdmin<roles::Viewer>,
+    State(db): State<DB>,
+) -> Result<Json<Vec<location::Data>>, ApiError> {
+    let rsp = db
+        .get_all_locations()
+        .await
+        .or_api(ApiError::Internal("error getting locations"))?;
+    Ok(Json(rsp))
+}
+
+/// DELETE admin/many-locations/dangerous[?confirm=TOKEN]
+pub async fn delete_all_locations(
+    RequireAdmin(admin, _): RequireAdmin<roles::Operator>,
+    State(db): State<DB>,
+    Query(params): Query<ConfirmParam>,
+) -> Result<Json<Value>, ApiError> {
+    let op = DangerousOp::DeleteAllLocations;
+    let snapshot = match confirm(&db, &admin, op, params.confirm).await? {
+        Phase::DryRun(report) => return Ok(report),
+        Phase::Confirmed(snapshot) => snapshot,
+    };
+    let rsp = db
+        .audited(&admin)
+        .delete_all_locations()
+        .await
+        .or_api(ApiError::Internal("error deleting locations"))?;
+    Ok(Json(json!({
+        "number_of_locations_deleted": rsp,
+        "snapshot_id": snapshot.id,
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct MigrateCoordinatesParam {
+    /// system of the untyped coordinates
+    #[serde(default)]
+    system: CoordinateSystem,
+    /// write the changes, instead of only listing them
+    #[serde(default)]
+    apply: bool,
 }
 
-/// DELETE admin/dangerous/many-locations
-pub async fn delete_all_locations(AuthBearer(token): AuthBearer, State(db): State<DB>) -> Response {
-    if token.eq(&SERVER_CONFIG.admin_token) {
-        match db.delete_all_locations().await {
-            Ok(rsp) => Json(json!({
-                "number_of_locations_deleted": rsp,
-            }))
-            .into_response(),
-            Err(e) => {
-                error!("error deleting locations: {}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "error deleting locations",
-                )
-                    .into_response()
-            }
-        }
-    } else {
-        (StatusCode::UNAUTHORIZED, "provide admin token").into_response()
+/// POST admin/many-locations/migrate-coordinates?system=wgs84[&apply=true]
+///
+/// turn the coordinates stored by older versions into typed ones
+pub async fn migrate_coordinates(
+    RequireAdmin(admin, _): RequireAdmin<roles::Operator>,
+    State(db): State<DB>,
+    Query(params): Query<MigrateCoordinatesParam>,
+) -> Result<Json<Value>, ApiError> {
+    let plan = db
+        .plan_coordinate_migration(params.system)
+        .await
+        .or_api(ApiError::Internal("error planning coordinate migration"))?;
+    let mut migrated = 0;
+    if params.apply {
+        migrated = db
+            .audited(&admin)
+            .migrate_coordinates(&plan)
+            .await
+            .or_api(ApiError::Internal("error migrating coordinates"))?;
     }
+    Ok(Json(json!({
+        "plan": plan,
+        "number_of_locations_migrated": migrated,
+    })))
 }
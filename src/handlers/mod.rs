@@ -1,5 +1,17 @@
+pub mod achievements;
+pub mod admins;
 pub mod api;
+pub mod archive;
+pub mod audit;
+pub mod codes;
+pub mod completion;
+pub mod dangerous;
 pub mod ducks;
 pub mod exhibits;
+pub mod geofence;
 pub mod locations;
+pub mod posters;
 pub mod rankings;
+pub mod seasons;
+pub mod story;
+pub mod trash;
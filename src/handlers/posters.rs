@@ -0,0 +1,139 @@
+use crate::admin_auth::{roles, RequireAdmin};
+use crate::api_error::{ApiError, ResultExt};
+use crate::db_api::ducks::duck_info;
+use crate::posters::{ImageFormat, PosterConfig};
+use crate::{DB, SERVER_CONFIG};
+use axum::extract::{Path, Query, State};
+use http::header;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// response of a generated file
+type FileResponse = ([(header::HeaderName, &'static str); 2], Vec<u8>);
+
+/// the poster settings, which deployments not printing codes leave out
+pub fn poster_config() -> Result<&'static PosterConfig, ApiError> {
+    SERVER_CONFIG
+        .posters
+        .as_ref()
+        .ok_or(ApiError::NotFound("posters are not configured"))
+}
+
+#[derive(Deserialize)]
+pub struct FormatParam {
+    #[serde(default)]
+    format: ImageFormat,
+}
+
+/// GET admin/duck/:id/qrcode?format=png|svg
+pub async fn get_qr_code(
+    _: RequireAdmin<roles::Viewer>,
+    State(db): State<DB>,
+    Path(id): Path<String>,
+    Query(param): Query<FormatParam>,
+) -> Result<FileResponse, ApiError> {
+    let config = poster_config()?;
+    let duck = db
+        .get_duck(id)
+        .await
+        .or_api(ApiError::Internal("error getting duck"))?
+        .ok_or(ApiError::NotFound("duck id does not exist"))?;
+    let code = db
+        .sign_duck_code(&duck.id)
+        .await
+        .or_api(ApiError::Internal("error signing duck code"))?;
+    let data = tokio::task::spawn_blocking(move || config.qr_code(&duck, &code, param.format))
+        .await
+        .or_api(ApiError::Internal("error generating qr code"))?
+        .or_api(ApiError::Internal("error generating qr code"))?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, param.format.content_type()),
+            (header::CONTENT_DISPOSITION, "inline"),
+        ],
+        data,
+    ))
+}
+
+/// GET admin/qrcodes.zip?format=png|svg
+///
+/// codes of all ducks, named after their titles and locations
+pub async fn get_qr_codes_zip(
+    _: RequireAdmin<roles::Viewer>,
+    State(db): State<DB>,
+    Query(param): Query<FormatParam>,
+) -> Result<FileResponse, ApiError> {
+    let config = poster_config()?;
+    let ducks = with_codes(&db).await?;
+    let data = tokio::task::spawn_blocking(move || config.qr_codes_zip(&ducks, param.format))
+        .await
+        .or_api(ApiError::Internal("error generating qr codes"))?
+        .or_api(ApiError::Internal("error generating qr codes"))?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"qrcodes.zip\"",
+            ),
+        ],
+        data,
+    ))
+}
+
+/// GET admin/posters.pdf
+///
+/// one printable sheet per location with a duck
+pub async fn get_poster_sheets(
+    _: RequireAdmin<roles::Viewer>,
+    State(db): State<DB>,
+) -> Result<FileResponse, ApiError> {
+    let config = poster_config()?;
+    let locations = db
+        .get_all_locations()
+        .await
+        .or_api(ApiError::Internal("error getting all locations"))?;
+    let mut ducks: HashMap<String, (duck_info::Data, String)> = with_codes(&db)
+        .await?
+        .into_iter()
+        .map(|(duck, code)| (duck.id.clone(), (duck, code)))
+        .collect();
+    let sheets: Vec<_> = locations
+        .into_iter()
+        .filter_map(|location| {
+            let (duck, code) = ducks.remove(location.duck_id.as_ref()?)?;
+            Some((location, duck, code))
+        })
+        .collect();
+    let data = tokio::task::spawn_blocking(move || config.poster_sheets(&sheets))
+        .await
+        .or_api(ApiError::Internal("error generating posters"))?
+        .or_api(ApiError::Internal("error generating posters"))?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/pdf"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"posters.pdf\"",
+            ),
+        ],
+        data,
+    ))
+}
+
+/// all ducks, with their signed codes
+async fn with_codes(db: &DB) -> Result<Vec<(duck_info::Data, String)>, ApiError> {
+    let ducks = db
+        .get_all_ducks()
+        .await
+        .or_api(ApiError::Internal("error getting all ducks"))?;
+    let mut rsp = Vec::with_capacity(ducks.len());
+    for duck in ducks {
+        let code = db
+            .sign_duck_code(&duck.id)
+            .await
+            .or_api(ApiError::Internal("error signing duck code"))?;
+        rsp.push((duck, code));
+    }
+    Ok(rsp)
+}
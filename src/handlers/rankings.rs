@@ -1,40 +1,49 @@
-use crate::{IntoResponse, Response, DB, SERVER_CONFIG};
+use crate::admin_auth::{roles, RequireAdmin};
+use crate::api_error::{ApiError, ResultExt};
+use crate::prisma::ranking;
+use crate::DB;
 use axum::extract::State;
 use axum::Json;
-use axum_auth::AuthBearer;
-use http::StatusCode;
-use serde_json::json;
-use tracing::error;
+use serde_json::{json, Value};
 
 /// GET admin/rankings
-pub async fn get_all_rankings(AuthBearer(token): AuthBearer, State(db): State<DB>) -> Response {
-    if token.eq(&SERVER_CONFIG.admin_token) {
-        match db.get_all_rankings().await {
-            Ok(rsp) => Json(rsp).into_response(),
-            Err(e) => {
-                error!("error getting rankings: {}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "error getting rankings").into_response()
-            }
-        }
-    } else {
-        (StatusCode::UNAUTHORIZED, "provide admin token").into_response()
-    }
+pub async fn get_all_rankings(
+    _: RequireAdmin<roles::Viewer>,
+    State(db): State<DB>,
+) -> Result<Json<Vec<ranking::Data>>, ApiError> {
+    let rsp = db
+        .get_all_rankings()
+        .await
+        .or_api(ApiError::Internal("error getting rankings"))?;
+    Ok(Json(rsp))
 }
 
-/// GET admin/rankings
-pub async fn delete_all_rankings(AuthBearer(token): AuthBearer, State(db): State<DB>) -> Response {
-    if token.eq(&SERVER_CONFIG.admin_token) {
-        match db.delete_all_rankings().await {
-            Ok(rsp) => Json(json!({
-                "number_of_rankings_deleted": rsp,
-            }))
-            .into_response(),
-            Err(e) => {
-                error!("error deleting rankings: {}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "error deleting rankings").into_response()
-            }
-        }
-    } else {
-        (StatusCode::UNAUTHORIZED, "provide admin token").into_response()
-    }
+/// DELETE admin/rankings
+pub async fn delete_all_rankings(
+    RequireAdmin(admin, _): RequireAdmin<roles::Operator>,
+    State(db): State<DB>,
+) -> Result<Json<Value>, ApiError> {
+    let rsp = db
+        .audited(&admin)
+        .delete_all_rankings()
+        .await
+        .or_api(ApiError::Internal("error deleting rankings"))?;
+    Ok(Json(json!({
+        "number_of_rankings_deleted": rsp,
+    })))
+}
+
+/// POST admin/rankings/renumber
+pub async fn renumber_rankings(
+    RequireAdmin(admin, _): RequireAdmin<roles::Operator>,
+    State(db): State<DB>,
+) -> Result<Json<Value>, ApiError> {
+    let rsp = db
+        .audited(&admin)
+        .renumber_rankings()
+        .await
+        .or_api(ApiError::Internal("error renumbering rankings"))?;
+    Ok(Json(json!({
+        "number_of_rankings_renumbered": rsp,
+    })))
 }
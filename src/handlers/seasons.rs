@@ -0,0 +1,114 @@
+use crate::admin_auth::{roles, RequireAdmin};
+use crate::api_error::{ApiError, ResultExt};
+use crate::db_api::seasons::{ArchivedSeason, NewSeasonData, SeasonInfo, UpdateSeasonData};
+use crate::prisma::{season, season_ranking};
+use crate::DB;
+use axum::extract::{Path, State};
+use axum::Json;
+use serde::Deserialize;
+
+/// scope of a request, the main game without `season`
+#[derive(Deserialize)]
+pub struct SeasonParam {
+    pub(crate) season: Option<String>,
+}
+
+/// answers to changes rejected because their season is archived
+pub(crate) trait WritableExt<T> {
+    /// Like `or_api`, but answer 403 to a change of an archived season.
+    fn or_writable_api(self, err: ApiError) -> Result<T, ApiError>;
+}
+
+impl<T> WritableExt<T> for anyhow::Result<T> {
+    fn or_writable_api(self, err: ApiError) -> Result<T, ApiError> {
+        match self {
+            Err(e) if e.is::<ArchivedSeason>() => {
+                Err(e).or_api(ApiError::Forbidden("season is archived"))
+            }
+            result => result.or_api(err),
+        }
+    }
+}
+
+/// POST admin/season
+pub async fn create_season(
+    RequireAdmin(admin, _): RequireAdmin<roles::Editor>,
+    State(db): State<DB>,
+    Json(data): Json<NewSeasonData>,
+) -> Result<Json<season::Data>, ApiError> {
+    data.check().map_err(ApiError::BadRequest)?;
+    let rsp = db
+        .audited(&admin)
+        .create_season(data)
+        .await
+        .or_api(ApiError::Internal("error creating season"))?;
+    Ok(Json(rsp))
+}
+
+/// GET admin/seasons
+pub async fn get_all_seasons(
+    _: RequireAdmin<roles::Viewer>,
+    State(db): State<DB>,
+) -> Result<Json<Vec<SeasonInfo>>, ApiError> {
+    let rsp = db
+        .get_all_seasons()
+        .await
+        .or_api(ApiError::Internal("error getting seasons"))?;
+    Ok(Json(rsp.into_iter().map(SeasonInfo::from).collect()))
+}
+
+/// PATCH admin/season/:id
+pub async fn update_season(
+    RequireAdmin(admin, _): RequireAdmin<roles::Editor>,
+    State(db): State<DB>,
+    Path(season_id): Path<String>,
+    Json(data): Json<UpdateSeasonData>,
+) -> Result<Json<season::Data>, ApiError> {
+    let season = db
+        .get_season(season_id.clone())
+        .await
+        .or_api(ApiError::Internal("error getting season"))?
+        .ok_or(ApiError::NotFound("season id does not exist"))?;
+    data.check(&season).map_err(ApiError::BadRequest)?;
+    let rsp = db
+        .audited(&admin)
+        .update_season(season_id, data)
+        .await
+        .or_writable_api(ApiError::NotFound("error updating season"))?;
+    Ok(Json(rsp))
+}
+
+/// POST admin/season/:id/archive
+///
+/// the season stays readable, but its ducks can no longer be found or edited
+pub async fn archive_season(
+    RequireAdmin(admin, _): RequireAdmin<roles::Operator>,
+    State(db): State<DB>,
+    Path(season_id): Path<String>,
+) -> Result<Json<season::Data>, ApiError> {
+    let rsp = db
+        .audited(&admin)
+        .archive_season(season_id)
+        .await
+        .or_writable_api(ApiError::NotFound("error archiving season"))?;
+    Ok(Json(rsp))
+}
+
+/// GET admin/season/:id/rankings
+///
+/// best first
+pub async fn get_season_rankings(
+    _: RequireAdmin<roles::Viewer>,
+    State(db): State<DB>,
+    Path(season_id): Path<String>,
+) -> Result<Json<Vec<season_ranking::Data>>, ApiError> {
+    db.get_season(season_id.clone())
+        .await
+        .or_api(ApiError::Internal("error getting season"))?
+        .ok_or(ApiError::NotFound("season id does not exist"))?;
+    let rsp = db
+        .get_season_rankings(season_id)
+        .await
+        .or_api(ApiError::Internal("error getting season rankings"))?;
+    Ok(Json(rsp))
+}
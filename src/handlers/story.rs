@@ -0,0 +1,20 @@
+use crate::admin_auth::{roles, RequireAdmin};
+use crate::api_error::{ApiError, ResultExt};
+use crate::db_api::story::StoryReport;
+use crate::DB;
+use axum::extract::State;
+use axum::Json;
+
+/// GET admin/story-graph
+///
+/// cycles, ducks stuck behind them, and prerequisites pointing to missing ducks
+pub async fn check_story_graph(
+    _: RequireAdmin<roles::Viewer>,
+    State(db): State<DB>,
+) -> Result<Json<StoryReport>, ApiError> {
+    let rsp = db
+        .check_story_graph()
+        .await
+        .or_api(ApiError::Internal("error checking story graph"))?;
+    Ok(Json(rsp))
+}
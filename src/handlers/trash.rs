@@ -0,0 +1,32 @@
+use crate::admin_auth::{roles, RequireAdmin};
+use crate::api_error::{ApiError, ResultExt};
+use crate::DB;
+use axum::extract::State;
+use axum::Json;
+use serde_json::{json, Value};
+
+/// GET admin/trash
+///
+/// ducks, exhibits and locations that can be restored
+pub async fn get_trash(
+    _: RequireAdmin<roles::Viewer>,
+    State(db): State<DB>,
+) -> Result<Json<Value>, ApiError> {
+    let ducks = db
+        .get_trashed_ducks()
+        .await
+        .or_api(ApiError::Internal("error getting trashed ducks"))?;
+    let exhibits = db
+        .get_trashed_exhibits()
+        .await
+        .or_api(ApiError::Internal("error getting trashed exhibits"))?;
+    let locations = db
+        .get_trashed_locations()
+        .await
+        .or_api(ApiError::Internal("error getting trashed locations"))?;
+    Ok(Json(json!({
+        "ducks": ducks,
+        "exhibits": exhibits,
+        "locations": locations,
+    })))
+}
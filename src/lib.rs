@@ -0,0 +1,206 @@
+pub mod admin_auth;
+pub mod api_error;
+pub mod configuration;
+pub mod coordinates;
+pub mod db_api;
+pub mod handlers;
+pub mod posters;
+pub mod prisma;
+pub mod redis_session_layer;
+pub mod wechat_login;
+
+use crate::api_error::{request_id, ApiError};
+use crate::db_api::DB;
+use crate::handlers::{
+    achievements, admins, api, archive, audit, codes, completion, dangerous, ducks, exhibits,
+    geofence, locations, posters, rankings, seasons, story, trash,
+};
+use axum::extract::DefaultBodyLimit;
+use axum::middleware;
+use axum::routing::{delete, get, get_service, patch, post, put};
+use axum::Router;
+use axum_database_sessions::{AxumRedisPool, AxumSessionLayer};
+use configuration::Configuration;
+use http::{HeaderValue, Method};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tower_http::services::ServeDir;
+
+#[macro_use]
+extern crate lazy_static;
+
+lazy_static! {
+    pub static ref SERVER_CONFIG: Configuration = Configuration::load().unwrap();
+}
+
+/// build the application router on top of a storage backend and a session layer
+pub fn app(db: DB, session: AxumSessionLayer<AxumRedisPool>) -> Router {
+    let admin = Router::new()
+        .route("/duck", post(ducks::create_duck))
+        .route(
+            "/duck/:id",
+            get(ducks::get_duck)
+                .patch(ducks::update_duck)
+                .delete(ducks::delete_duck),
+        )
+        .route("/duck/:id/restore", post(ducks::restore_duck))
+        .route(
+            "/duck/:id/challenge",
+            put(ducks::set_duck_challenge).delete(ducks::delete_duck_challenge),
+        )
+        .route(
+            "/duck/:id/challenge/results",
+            get(ducks::get_challenge_results),
+        )
+        .route("/duck/:id/hints", put(ducks::set_duck_hints))
+        .route("/duck/:id/hints/stats", get(ducks::get_hint_stats))
+        .route("/duck/:id/qrcode", get(posters::get_qr_code))
+        .route("/duck/:id/qrcode/url", get(codes::get_duck_url))
+        .route(
+            "/duck/:id/qrcode/invalidate",
+            post(codes::invalidate_duck_codes),
+        )
+        .route("/qrcodes.zip", get(posters::get_qr_codes_zip))
+        .route("/posters.pdf", get(posters::get_poster_sheets))
+        .route(
+            "/many-ducks",
+            get(ducks::get_all_ducks).post(ducks::create_many_ducks),
+        )
+        .route("/many-ducks/dangerous", delete(ducks::delete_all_ducks))
+        .route(
+            "/duck-history/dangerous",
+            delete(ducks::delete_duck_history),
+        )
+        .route("/exhibit", post(exhibits::create_exhibit))
+        .route(
+            "/exhibit/:id",
+            get(exhibits::get_exhibit)
+                .patch(exhibits::update_exhibit)
+                .delete(exhibits::delete_exhibit),
+        )
+        .route("/exhibit/:id/restore", post(exhibits::restore_exhibit))
+        .route(
+            "/many-exhibits",
+            get(exhibits::get_all_exhibits).post(exhibits::create_many_exhibits),
+        )
+        .route(
+            "/many-exhibits/dangerous",
+            delete(exhibits::delete_all_exhibits),
+        )
+        .route("/location", post(locations::create_location))
+        .route(
+            "/location/:id",
+            get(locations::get_location)
+                .patch(locations::update_location)
+                .delete(locations::delete_location),
+        )
+        .route("/location/:id/restore", post(locations::restore_location))
+        .route(
+            "/many-locations",
+            get(locations::get_all_locations).post(locations::create_many_locations),
+        )
+        .route(
+            "/many-locations/migrate-coordinates",
+            post(locations::migrate_coordinates),
+        )
+        .route(
+            "/rankings",
+            get(rankings::get_all_rankings).delete(rankings::delete_all_rankings),
+        )
+        .route("/rankings/renumber", post(rankings::renumber_rankings))
+        .route("/season", post(seasons::create_season))
+        .route("/season/:id", patch(seasons::update_season))
+        .route("/season/:id/archive", post(seasons::archive_season))
+        .route("/season/:id/rankings", get(seasons::get_season_rankings))
+        .route("/seasons", get(seasons::get_all_seasons))
+        .route("/achievement", post(achievements::create_achievement))
+        .route(
+            "/achievement/:id",
+            get(achievements::get_achievement)
+                .patch(achievements::update_achievement)
+                .delete(achievements::delete_achievement),
+        )
+        .route("/achievements", get(achievements::get_all_achievements))
+        .route("/trash", get(trash::get_trash))
+        .route(
+            "/many-locations/dangerous",
+            delete(locations::delete_all_locations),
+        )
+        .route(
+            "/accounts",
+            get(admins::get_all_admins).post(admins::create_admin),
+        )
+        .route("/accounts/:name", delete(admins::revoke_admin))
+        .route("/accounts/:name/token", post(admins::reset_admin_token))
+        .route("/audit-log", get(audit::get_audit_log))
+        .route("/snapshots", get(dangerous::get_snapshots))
+        .route("/snapshots/:id/restore", post(dangerous::restore_snapshot))
+        .route(
+            "/qr-keys",
+            get(codes::get_qr_keys).post(codes::rotate_qr_key),
+        )
+        .route("/qr-keys/:kid", delete(codes::retire_qr_key))
+        .route("/discovery-flags", get(geofence::get_discovery_flags))
+        .route("/story-graph", get(story::check_story_graph))
+        .route(
+            "/completion-rules",
+            get(completion::get_completion_rules).put(completion::set_completion_rules),
+        )
+        .route(
+            "/team-completion-rules",
+            get(completion::get_team_completion_rules).put(completion::set_team_completion_rules),
+        )
+        .route("/export", get(archive::export_archive))
+        .route(
+            "/import",
+            post(archive::import_archive).layer(DefaultBodyLimit::max(archive::MAX_ARCHIVE_SIZE)),
+        );
+
+    let api_cors_layer = CorsLayer::new()
+        .allow_credentials(true)
+        .allow_methods([Method::GET, Method::POST, Method::PATCH, Method::DELETE])
+        .allow_origin(AllowOrigin::exact(
+            HeaderValue::from_str(SERVER_CONFIG.allow_origin.as_str()).unwrap(),
+        ));
+
+    let api = Router::new()
+        .route(
+            "/user-info",
+            get(api::user_info)
+                .patch(api::set_nickname)
+                .delete(api::clear_history),
+        )
+        .route("/preview-ducks", get(api::preview_ducks))
+        .route("/nearby-ducks", get(api::nearby_ducks))
+        .route("/find-duck/:duck_id", get(api::find_duck))
+        .route("/duck/:duck_id/answer", post(api::answer_challenge))
+        .route("/duck/:duck_id/hints", get(api::duck_hints))
+        .route("/duck/:duck_id/hints/:hint", post(api::reveal_hint))
+        .route("/seasons", get(api::get_seasons))
+        .route("/leaderboard", get(api::leaderboard))
+        .route("/leaderboard/around-me", get(api::leaderboard_around_me))
+        .route(
+            "/team",
+            get(api::team_progress)
+                .post(api::create_team)
+                .delete(api::leave_team),
+        )
+        .route("/team/join", post(api::join_team))
+        .route("/team-leaderboard", get(api::team_leaderboard))
+        .route("/events", get(api::events))
+        .layer(api_cors_layer);
+
+    let callback_path = &SERVER_CONFIG.wechat.redirect_uri.path();
+    Router::new()
+        .nest("/admin", admin)
+        .nest("/api", api)
+        .route("/login", get(api::login))
+        .route(callback_path, get(api::login_callback))
+        .fallback_service(
+            // serve static files
+            get_service(ServeDir::new("public"))
+                .handle_error(|_| async move { ApiError::Internal("static file error") }),
+        )
+        .with_state(db)
+        .layer(session)
+        .layer(middleware::from_fn(request_id))
+}
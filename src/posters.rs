@@ -0,0 +1,276 @@
+//! QR codes of ducks, and printable poster sheets
+use crate::db_api::ducks::duck_info;
+use crate::prisma::location;
+use anyhow::Context;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, ImageOutputFormat, Rgba, RgbaImage};
+use printpdf::{BuiltinFont, Image, ImageTransform, Mm, PdfDocument};
+use qrcode::{Color, EcLevel, QrCode};
+use serde::Deserialize;
+use serde_json::Value;
+use std::io::{Cursor, Write};
+use std::path::PathBuf;
+use tracing::warn;
+use url::Url;
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+/// white modules around the code, as required by the QR specification
+const QUIET_ZONE: u32 = 4;
+/// pixels per module of PNG codes
+const MODULE_PIXELS: u32 = 10;
+/// the icon covers this fraction of the width of a code,
+/// well within what high error correction recovers
+const ICON_RATIO: u32 = 4;
+/// prefix of the large version of an icon in the asset store
+const LARGE_ICON_PREFIX: &str = "3x-";
+
+// A4 poster sheets, in millimetres
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const CODE_WIDTH: f32 = 120.0;
+const MARGIN: f32 = 45.0;
+
+#[derive(Deserialize)]
+pub struct PosterConfig {
+    /// base url of the frontend, the code of a duck opens `{frontend_url}/duck/{id}?code={code}`
+    pub frontend_url: Url,
+    /// directory of the duck icons, named after the last segment of `duckIconUrl`
+    pub icon_dir: PathBuf,
+    /// font with Chinese glyphs for the PDF sheets,
+    /// which only show English text without it
+    pub font: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    Svg,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Svg => "svg",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "image/png",
+            ImageFormat::Svg => "image/svg+xml",
+        }
+    }
+}
+
+impl PosterConfig {
+    /// url of a duck, carrying its signed code
+    pub fn duck_url(&self, duck_id: &str, code: &str) -> anyhow::Result<Url> {
+        let base = self.frontend_url.as_str().trim_end_matches('/');
+        let mut url = Url::parse(&format!("{}/duck/{}", base, duck_id))?;
+        url.query_pairs_mut().append_pair("code", code);
+        Ok(url)
+    }
+
+    /// QR code of a duck, with its icon in the middle if the asset store has it.
+    pub fn qr_code(
+        &self,
+        duck: &duck_info::Data,
+        code: &str,
+        format: ImageFormat,
+    ) -> anyhow::Result<Vec<u8>> {
+        let qr = QrCode::with_error_correction_level(
+            self.duck_url(&duck.id, code)?.as_str(),
+            EcLevel::H,
+        )?;
+        let icon = self.load_icon(&duck.duck_icon_url);
+        match format {
+            ImageFormat::Png => {
+                let mut data = Vec::new();
+                DynamicImage::ImageRgba8(render_png(&qr, icon.as_ref()))
+                    .write_to(&mut Cursor::new(&mut data), ImageOutputFormat::Png)?;
+                Ok(data)
+            }
+            ImageFormat::Svg => render_svg(&qr, icon.as_ref()),
+        }
+    }
+
+    /// zip archive of the codes of ducks, given with their signed codes
+    pub fn qr_codes_zip(
+        &self,
+        ducks: &[(duck_info::Data, String)],
+        format: ImageFormat,
+    ) -> anyhow::Result<Vec<u8>> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (duck, code) in ducks {
+            let location = match &duck.location {
+                Some(location) => text(&location.description, "cn"),
+                None => "",
+            };
+            // titles are kept readable for printers, ids keep names unique
+            let name = format!(
+                "{}({}) {}.{}",
+                text(&duck.title, "cn"),
+                location,
+                duck.id,
+                format.extension()
+            )
+            .replace('/', "_");
+            zip.start_file(name, FileOptions::default())?;
+            zip.write_all(&self.qr_code(duck, code, format)?)?;
+        }
+        Ok(zip.finish()?.into_inner())
+    }
+
+    /// PDF with one A4 sheet per location, given with its duck and the signed code,
+    /// showing the code, the bilingual title of the duck and the description of the location.
+    pub fn poster_sheets(
+        &self,
+        locations: &[(location::Data, duck_info::Data, String)],
+    ) -> anyhow::Result<Vec<u8>> {
+        let (doc, page, layer) = PdfDocument::new(
+            "Cyberduck posters",
+            Mm(PAGE_WIDTH),
+            Mm(PAGE_HEIGHT),
+            "poster",
+        );
+        let (font, bilingual) = match &self.font {
+            Some(path) => {
+                let file = std::fs::File::open(path)
+                    .with_context(|| format!("error opening font {}", path.display()))?;
+                (doc.add_external_font(file)?, true)
+            }
+            None => (doc.add_builtin_font(BuiltinFont::Helvetica)?, false),
+        };
+        let mut next_page = Some((page, layer));
+        for (location, duck, code) in locations {
+            let (page, layer) = match next_page.take() {
+                Some(first) => first,
+                None => doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "poster"),
+            };
+            let layer = doc.get_page(page).get_layer(layer);
+            let qr = QrCode::with_error_correction_level(
+                self.duck_url(&duck.id, code)?.as_str(),
+                EcLevel::H,
+            )?;
+            let icon = self.load_icon(&duck.duck_icon_url);
+            let png = render_png(&qr, icon.as_ref());
+            // printpdf ignores transparency
+            let rgb = DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(png).to_rgb8());
+            let dpi = rgb.width() as f32 / (CODE_WIDTH / 25.4);
+            Image::from_dynamic_image(&rgb).add_to_layer(
+                layer.clone(),
+                ImageTransform {
+                    translate_x: Some(Mm(MARGIN)),
+                    translate_y: Some(Mm(PAGE_HEIGHT - 30.0 - CODE_WIDTH)),
+                    dpi: Some(dpi),
+                    ..Default::default()
+                },
+            );
+            let mut lines = vec![(24.0, text(&duck.title, "en"))];
+            if bilingual {
+                lines.push((24.0, text(&duck.title, "cn")));
+            }
+            lines.push((14.0, text(&location.description, "en")));
+            if bilingual {
+                lines.push((14.0, text(&location.description, "cn")));
+            }
+            let mut y = PAGE_HEIGHT - 45.0 - CODE_WIDTH;
+            for (size, line) in lines {
+                layer.use_text(line, size, Mm(MARGIN), Mm(y), &font);
+                y -= size * 0.5;
+            }
+        }
+        Ok(doc.save_to_bytes()?)
+    }
+
+    /// Load the large version of an icon from the asset store, or the icon itself.
+    fn load_icon(&self, icon_url: &str) -> Option<RgbaImage> {
+        let name = icon_url.rsplit('/').next()?;
+        let candidates = [
+            self.icon_dir.join(format!("{}{}", LARGE_ICON_PREFIX, name)),
+            self.icon_dir.join(name),
+        ];
+        for path in candidates {
+            if let Ok(data) = std::fs::read(&path) {
+                match image::load_from_memory(&data) {
+                    Ok(icon) => return Some(icon.to_rgba8()),
+                    Err(e) => warn!("invalid duck icon {}: {}", path.display(), e),
+                }
+            }
+        }
+        warn!("duck icon not found in asset store: {}", icon_url);
+        None
+    }
+}
+
+/// text of one language of a bilingual field
+fn text<'a>(bilingual: &'a Value, language: &str) -> &'a str {
+    bilingual[language].as_str().unwrap_or_default()
+}
+
+fn render_png(code: &QrCode, icon: Option<&RgbaImage>) -> RgbaImage {
+    let width = code.width() as u32;
+    let side = (width + 2 * QUIET_ZONE) * MODULE_PIXELS;
+    let mut img = RgbaImage::from_pixel(side, side, Rgba([255, 255, 255, 255]));
+    for (i, color) in code.to_colors().into_iter().enumerate() {
+        if color == Color::Dark {
+            let x = (i as u32 % width + QUIET_ZONE) * MODULE_PIXELS;
+            let y = (i as u32 / width + QUIET_ZONE) * MODULE_PIXELS;
+            for dx in 0..MODULE_PIXELS {
+                for dy in 0..MODULE_PIXELS {
+                    img.put_pixel(x + dx, y + dy, Rgba([0, 0, 0, 255]));
+                }
+            }
+        }
+    }
+    if let Some(icon) = icon {
+        let icon_side = side / ICON_RATIO;
+        let icon = imageops::resize(icon, icon_side, icon_side, FilterType::Lanczos3);
+        let pos = ((side - icon_side) / 2) as i64;
+        imageops::overlay(&mut img, &icon, pos, pos);
+    }
+    img
+}
+
+/// SVG in units of modules, with the icon embedded as a PNG
+fn render_svg(code: &QrCode, icon: Option<&RgbaImage>) -> anyhow::Result<Vec<u8>> {
+    let width = code.width() as u32;
+    let side = width + 2 * QUIET_ZONE;
+    let mut path = String::new();
+    for (i, color) in code.to_colors().into_iter().enumerate() {
+        if color == Color::Dark {
+            let x = i as u32 % width + QUIET_ZONE;
+            let y = i as u32 / width + QUIET_ZONE;
+            path.push_str(&format!("M{},{}h1v1h-1z", x, y));
+        }
+    }
+    let mut svg = format!(
+        concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" version="1.1" viewBox="0 0 {side} {side}" shape-rendering="crispEdges">"#,
+            r##"<rect width="{side}" height="{side}" fill="#fff"/>"##,
+            r##"<path d="{path}" fill="#000"/>"##,
+        ),
+        side = side,
+        path = path
+    );
+    if let Some(icon) = icon {
+        let mut data = Vec::new();
+        DynamicImage::ImageRgba8(icon.clone())
+            .write_to(&mut Cursor::new(&mut data), ImageOutputFormat::Png)?;
+        let icon_side = side as f64 / ICON_RATIO as f64;
+        let pos = (side as f64 - icon_side) / 2.0;
+        svg.push_str(&format!(
+            r#"<image x="{pos}" y="{pos}" width="{w}" height="{w}" href="data:image/png;base64,{data}"/>"#,
+            pos = pos,
+            w = icon_side,
+            data = base64::encode(data)
+        ));
+    }
+    svg.push_str("</svg>");
+    Ok(svg.into_bytes())
+}
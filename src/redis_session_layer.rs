@@ -11,27 +11,36 @@ pub struct RedisSessionConfig {
     session_secret: String,
     session_expiration: i64,
     cookie_name: String,
-    redis_url: String,
+    pub redis_url: String,
 }
 
 impl RedisSessionConfig {
     pub async fn build_layer(&self) -> Result<AxumSessionLayer<AxumRedisPool>> {
         let redis = redis::Client::open(self.redis_url.as_str())?;
 
+        let session_store =
+            AxumRedisSessionStore::new(Some(AxumRedisPool::from(redis)), self.session_config()?);
+        session_store.initiate().await?;
+        Ok(AxumSessionLayer::new(session_store))
+    }
+
+    /// session layer keeping sessions in memory only, for tests
+    #[cfg(any(test, feature = "memory"))]
+    pub fn build_memory_layer(&self) -> Result<AxumSessionLayer<AxumRedisPool>> {
+        let session_store = AxumRedisSessionStore::new(None, self.session_config()?);
+        Ok(AxumSessionLayer::new(session_store))
+    }
+
+    fn session_config(&self) -> Result<AxumSessionConfig> {
         let session_secret = base64::decode(&self.session_secret)?;
 
-        let session_config = AxumSessionConfig::default()
+        Ok(AxumSessionConfig::default()
             .with_cookie_name(&SERVER_CONFIG.redis_session.cookie_name)
             .with_always_save(false)
             .with_cookie_same_site(SameSite::Strict)
             .with_http_only(true)
             .with_lifetime(Duration::seconds(self.session_expiration))
             .with_secure(true)
-            .with_key(Key::from(session_secret.as_slice()));
-
-        let session_store =
-            AxumRedisSessionStore::new(Some(AxumRedisPool::from(redis)), session_config);
-        session_store.initiate().await?;
-        Ok(AxumSessionLayer::new(session_store))
+            .with_key(Key::from(session_secret.as_slice())))
     }
 }
@@ -14,6 +14,10 @@ pub struct WechatLogin {
     appname: String,
     secret: String,
     pub redirect_uri: Url,
+    #[serde(default = "default_auth_url")]
+    auth_url: Url,
+    #[serde(default = "default_token_url")]
+    token_url: Url,
 }
 
 #[derive(Deserialize)]
@@ -35,7 +39,7 @@ pub enum CodeResponse {
 impl WechatLogin {
     pub fn auth_url(&self) -> (String, Url) {
         let state = gen_state();
-        let mut url = Url::parse(AUTH_URL).unwrap();
+        let mut url = self.auth_url.clone();
         url.query_pairs_mut()
             .append_pair("appid", &self.appid)
             .append_pair("redirect_uri", self.redirect_uri.as_str())
@@ -53,7 +57,7 @@ impl WechatLogin {
     }
 
     fn open_id_url(&self, code: &str) -> Url {
-        let mut url = Url::parse(TOKEN_URL).unwrap();
+        let mut url = self.token_url.clone();
         url.query_pairs_mut()
             .append_pair("appid", &self.appname)
             .append_pair("secret", &self.secret)
@@ -63,6 +67,14 @@ impl WechatLogin {
     }
 }
 
+fn default_auth_url() -> Url {
+    Url::parse(AUTH_URL).unwrap()
+}
+
+fn default_token_url() -> Url {
+    Url::parse(TOKEN_URL).unwrap()
+}
+
 #[inline]
 fn gen_state() -> String {
     String::from_iter(
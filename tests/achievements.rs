@@ -0,0 +1,126 @@
+mod common;
+
+use common::{admin, app, find_duck_uri, new_duck, Browser};
+use http::{Method, StatusCode};
+use serde_json::{json, Value};
+
+fn with_topic(topic: &str) -> Value {
+    json!({ "topics": [{ "en": topic, "cn": topic }] })
+}
+
+async fn new_achievement(app: &axum::Router, rule: Value) -> String {
+    let achievement = json!({
+        "title": { "en": "badge", "cn": "徽章" },
+        "description": { "en": "well done", "cn": "做得好" },
+        "rule": rule,
+    });
+    let (status, achievement) =
+        admin(app, Method::POST, "/admin/achievement", Some(achievement)).await;
+    assert_eq!(status, StatusCode::OK);
+    achievement["id"].as_str().unwrap().to_string()
+}
+
+async fn find(app: &axum::Router, browser: &mut Browser, id: &str) -> Value {
+    let uri = find_duck_uri(app, id).await;
+    let (status, user) = browser.send(app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    user
+}
+
+fn earned(user: &Value) -> Vec<&str> {
+    user["achievements"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|a| a["achievement"]["id"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn earn_achievements_when_finding_ducks() {
+    let app = app();
+    let bird = new_duck(&app, with_topic("birds")).await;
+    let other_bird = new_duck(&app, with_topic("birds")).await;
+    let fish = new_duck(&app, with_topic("fish")).await;
+    let birds = new_achievement(&app, json!({ "type": "topic", "topic": "birds" })).await;
+    let first = new_achievement(&app, json!({ "type": "first_of_day" })).await;
+    let streak = new_achievement(
+        &app,
+        json!({ "type": "ducks_within", "count": 3, "minutes": 60 }),
+    )
+    .await;
+
+    let mut early = Browser::default();
+    early.login(&app, "quentin").await;
+    let user = find(&app, &mut early, &bird).await;
+    assert_eq!(earned(&user), vec![first.as_str()]);
+    let user = find(&app, &mut early, &other_bird).await;
+    assert_eq!(earned(&user), vec![first.as_str(), birds.as_str()]);
+    let user = find(&app, &mut early, &fish).await;
+    assert_eq!(
+        earned(&user),
+        vec![first.as_str(), birds.as_str(), streak.as_str()]
+    );
+    assert_eq!(
+        user["achievements"][0]["achievement"]["title"]["en"],
+        "badge"
+    );
+
+    let mut late = Browser::default();
+    late.login(&app, "rachel").await;
+    let user = find(&app, &mut late, &fish).await;
+    assert_eq!(earned(&user), Vec::<&str>::new());
+    let (_, user) = late.send(&app, Method::GET, "/api/user-info", None).await;
+    assert_eq!(user["achievements"], json!([]));
+}
+
+#[tokio::test]
+async fn manage_achievements() {
+    let app = app();
+    for rule in [
+        json!({ "type": "topic", "topic": "" }),
+        json!({ "type": "ducks_within", "count": 0, "minutes": 60 }),
+        json!({ "type": "first_of_day", "utcOffsetMinutes": 1000 }),
+        json!({ "type": "unknown" }),
+    ] {
+        let achievement = json!({
+            "title": { "en": "badge", "cn": "徽章" },
+            "description": { "en": "well done", "cn": "做得好" },
+            "rule": rule,
+        });
+        let (status, _) = admin(&app, Method::POST, "/admin/achievement", Some(achievement)).await;
+        assert!(status.is_client_error());
+    }
+
+    let duck = new_duck(&app, with_topic("birds")).await;
+    let id = new_achievement(&app, json!({ "type": "story_chain", "duckId": duck })).await;
+    let uri = format!("/admin/achievement/{}", id);
+    let patch = json!({ "iconUrl": "https://icons.test/badge.png" });
+    let (status, achievement) = admin(&app, Method::PATCH, &uri, Some(patch)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(achievement["iconUrl"], "https://icons.test/badge.png");
+    let (_, achievements) = admin(&app, Method::GET, "/admin/achievements", None).await;
+    assert_eq!(achievements.as_array().unwrap().len(), 1);
+
+    let mut browser = Browser::default();
+    browser.login(&app, "simon").await;
+    let user = find(&app, &mut browser, &duck).await;
+    assert_eq!(earned(&user), vec![id.as_str()]);
+
+    let (status, _) = admin(&app, Method::DELETE, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = admin(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (_, user) = browser
+        .send(&app, Method::GET, "/api/user-info", None)
+        .await;
+    assert_eq!(user["achievements"], json!([]));
+    let (_, log) = admin(
+        &app,
+        Method::GET,
+        "/admin/audit-log?entity_type=achievement",
+        None,
+    )
+    .await;
+    assert_eq!(log["items"].as_array().unwrap().len(), 3);
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use common::{admin, app, duck_data, send};
+use http::{header, Method, Request, StatusCode};
+use serde_json::{json, Value};
+
+async fn create_account(app: &axum::Router, name: &str, role: &str) -> String {
+    let account = json!({ "name": name, "role": role });
+    let (status, created) = admin(app, Method::POST, "/admin/accounts", Some(account)).await;
+    assert_eq!(status, StatusCode::OK);
+    created["token"].as_str().unwrap().to_string()
+}
+
+async fn with_token(
+    app: &axum::Router,
+    token: &str,
+    method: Method,
+    uri: &str,
+    body: Option<Value>,
+) -> StatusCode {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {}", token));
+    send(app, request, body).await.0
+}
+
+#[tokio::test]
+async fn roles_are_checked_per_route() {
+    let app = app();
+    let viewer = create_account(&app, "volunteer", "viewer").await;
+    let editor = create_account(&app, "curator", "editor").await;
+    let duck = duck_data(json!({}));
+
+    let status = with_token(&app, &viewer, Method::GET, "/admin/many-ducks", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let status = with_token(
+        &app,
+        &viewer,
+        Method::POST,
+        "/admin/duck",
+        Some(duck.clone()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let status = with_token(&app, &editor, Method::POST, "/admin/duck", Some(duck)).await;
+    assert_eq!(status, StatusCode::OK);
+    let status = with_token(&app, &editor, Method::DELETE, "/admin/rankings", None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let uri = "/admin/many-ducks/dangerous";
+    let status = with_token(&app, &editor, Method::DELETE, uri, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let status = with_token(&app, &editor, Method::GET, "/admin/accounts", None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn revoke_and_reset_token() {
+    let app = app();
+    let token = create_account(&app, "script", "operator").await;
+    let status = with_token(&app, &token, Method::GET, "/admin/rankings", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = admin(&app, Method::DELETE, "/admin/accounts/script", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let status = with_token(&app, &token, Method::GET, "/admin/rankings", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let uri = "/admin/accounts/script/token";
+    let (status, reset) = admin(&app, Method::POST, uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let new_token = reset["token"].as_str().unwrap();
+    let status = with_token(&app, new_token, Method::GET, "/admin/rankings", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let status = with_token(&app, &token, Method::GET, "/admin/rankings", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (_, accounts) = admin(&app, Method::GET, "/admin/accounts", None).await;
+    assert_eq!(accounts[0]["name"], "script");
+    assert!(accounts[0].get("tokenHash").is_none());
+
+    let uri = "/admin/audit-log?entity_type=admin_account";
+    let (_, log) = admin(&app, Method::GET, uri, None).await;
+    let items = log["items"].as_array().unwrap();
+    assert_eq!(items.len(), 3);
+    assert_eq!(items[0]["route"], "POST /admin/accounts/:name/token");
+    assert_eq!(items[1]["entityId"], "script");
+    assert!(items[1]["after"]["revokedAt"].is_string());
+    assert!(items[2]["after"].get("tokenHash").is_none());
+}
+
+#[tokio::test]
+async fn duplicated_account_name() {
+    let app = app();
+    create_account(&app, "curator", "editor").await;
+    let account = json!({ "name": "curator", "role": "viewer" });
+    let (status, _) = admin(&app, Method::POST, "/admin/accounts", Some(account)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn root_name_is_reserved() {
+    let app = app();
+    for name in ["root", " Root "] {
+        let account = json!({ "name": name, "role": "viewer" });
+        let (status, _) = admin(&app, Method::POST, "/admin/accounts", Some(account)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
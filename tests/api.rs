@@ -0,0 +1,135 @@
+mod common;
+
+use common::{admin, app, call, confirmed, duck_data, into_json, send};
+use http::{Method, Request, StatusCode};
+use serde_json::{json, Value};
+
+fn bilingual(text: &str) -> Value {
+    json!({ "en": text, "cn": text })
+}
+
+fn titled_duck(title: &str) -> Value {
+    duck_data(json!({ "title": bilingual(title), "topics": [bilingual("topic")] }))
+}
+
+#[tokio::test]
+async fn admin_requires_token() {
+    let app = app();
+    let request = Request::builder()
+        .uri("/admin/many-ducks")
+        .header("Authorization", "Bearer wrong-token");
+    let (status, error) = send(&app, request, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(error["code"], "unauthorized");
+    assert_eq!(error["message"], "provide admin token");
+
+    let request = Request::builder().uri("/admin/many-ducks");
+    let (status, _) = send(&app, request, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn errors_carry_request_id() {
+    let app = app();
+    let request = Request::builder()
+        .uri("/admin/duck/000000000000000000000000")
+        .header("Authorization", format!("Bearer {}", common::ADMIN_TOKEN))
+        .header("x-request-id", "test-request-1");
+    let response = call(&app, request, None).await;
+    assert_eq!(response.headers()["x-request-id"], "test-request-1");
+    let (status, error) = into_json(response).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(error["code"], "not_found");
+    assert_eq!(error["requestId"], "test-request-1");
+
+    // invalid ids are replaced by generated ones
+    let request = Request::builder()
+        .uri("/api/user-info")
+        .header("x-request-id", "not a valid id");
+    let (_, error) = send(&app, request, None).await;
+    assert_ne!(error["requestId"], "not a valid id");
+    assert!(error["requestId"].is_string());
+}
+
+#[tokio::test]
+async fn duck_crud() {
+    let app = app();
+    let (status, duck) = admin(&app, Method::POST, "/admin/duck", Some(titled_duck("a"))).await;
+    assert_eq!(status, StatusCode::OK);
+    let id = duck["id"].as_str().unwrap();
+
+    let patch = json!({ "isHidden": true });
+    let uri = format!("/admin/duck/{}", id);
+    let (status, duck) = admin(&app, Method::PATCH, &uri, Some(patch)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(duck["isHidden"], true);
+
+    let (status, _) = admin(&app, Method::DELETE, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = admin(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn duck_relations() {
+    let app = app();
+    let location = json!({
+        "description": bilingual("lake"),
+        "coordinate": { "system": "wgs84", "x": 1.0, "y": 2.0 },
+    });
+    let (_, location) = admin(&app, Method::POST, "/admin/location", Some(location)).await;
+    let (_, first) = admin(
+        &app,
+        Method::POST,
+        "/admin/duck",
+        Some(titled_duck("first")),
+    )
+    .await;
+    let mut second = titled_duck("second");
+    second["locationId"] = location["id"].clone();
+    second["prevDuckStoryId"] = first["id"].clone();
+    let (status, second) = admin(&app, Method::POST, "/admin/duck", Some(second)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let uri = format!("/admin/duck/{}", first["id"].as_str().unwrap());
+    let (_, first) = admin(&app, Method::GET, &uri, None).await;
+    assert_eq!(first["nextDuckStory"]["id"], second["id"]);
+    assert_eq!(first["nextDuckStory"]["location"]["id"], location["id"]);
+
+    let request = Request::builder().uri("/api/preview-ducks");
+    let (status, previews) = send(&app, request, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(previews.as_array().unwrap().len(), 2);
+    assert_eq!(previews[1]["location"]["id"], location["id"]);
+}
+
+#[tokio::test]
+async fn bulk_create_and_delete() {
+    let app = app();
+    let exhibits = json!([{
+        "location": bilingual("hall"),
+        "title": bilingual("painting"),
+        "sign": bilingual("sign"),
+        "artists": [bilingual("artist")],
+    }]);
+    let (_, created) = admin(&app, Method::POST, "/admin/many-exhibits", Some(exhibits)).await;
+    assert_eq!(created["number_of_exhibits_created"], 1);
+    let ducks = json!([titled_duck("a"), titled_duck("b")]);
+    let (_, created) = admin(&app, Method::POST, "/admin/many-ducks", Some(ducks)).await;
+    assert_eq!(created["number_of_ducks_created"], 2);
+
+    let (_, ducks) = admin(&app, Method::GET, "/admin/many-ducks", None).await;
+    assert_eq!(ducks.as_array().unwrap().len(), 2);
+    let (_, deleted) = confirmed(&app, "/admin/many-ducks/dangerous").await;
+    assert_eq!(deleted["number_of_ducks_deleted"], 2);
+    let (_, deleted) = confirmed(&app, "/admin/many-exhibits/dangerous").await;
+    assert_eq!(deleted["number_of_exhibits_deleted"], 1);
+}
+
+#[tokio::test]
+async fn user_info_requires_login() {
+    let app = app();
+    let request = Request::builder().uri("/api/user-info");
+    let (status, _) = send(&app, request, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,253 @@
+mod common;
+
+use common::mock_wechat::openid_for;
+use common::{admin, app, duck_data, find_duck_uri, Browser};
+use http::{Method, StatusCode};
+use serde_json::{json, Value};
+
+fn duck(title: &str, prev_duck_story_id: Option<&Value>) -> Value {
+    duck_data(json!({
+        "title": { "en": title, "cn": title },
+        "prevDuckStoryId": prev_duck_story_id,
+    }))
+}
+
+/// a story of two ducks, found by a player
+async fn seed(app: &axum::Router) -> Value {
+    let location = json!({
+        "description": { "en": "lake", "cn": "湖" },
+        "coordinate": { "system": "wgs84", "x": 1.0, "y": 2.0 },
+    });
+    let (_, location) = admin(app, Method::POST, "/admin/location", Some(location)).await;
+    let (_, first) = admin(app, Method::POST, "/admin/duck", Some(duck("first", None))).await;
+    let mut second = duck("second", Some(&first["id"]));
+    second["locationId"] = location["id"].clone();
+    admin(app, Method::POST, "/admin/duck", Some(second)).await;
+    let mut browser = Browser::default();
+    browser.login(app, "dave").await;
+    let uri = find_duck_uri(app, first["id"].as_str().unwrap()).await;
+    browser.send(app, Method::GET, &uri, None).await;
+    let (status, archive) = admin(app, Method::GET, "/admin/export", None).await;
+    assert_eq!(status, StatusCode::OK);
+    archive
+}
+
+#[tokio::test]
+async fn export_then_import_content() {
+    let source = app();
+    let archive = seed(&source).await;
+    assert_eq!(archive["version"], 5);
+    assert_eq!(archive["ducks"].as_array().unwrap().len(), 2);
+    assert_eq!(archive["users"].as_array().unwrap().len(), 1);
+    assert_eq!(archive["duckHistory"].as_array().unwrap().len(), 1);
+
+    let target = app();
+    // shift the ids of the target, so that imported ids must be remapped
+    admin(
+        &target,
+        Method::POST,
+        "/admin/duck",
+        Some(duck("other", None)),
+    )
+    .await;
+    let uri = "/admin/import?mode=content";
+    let (status, report) = admin(&target, Method::POST, uri, Some(archive.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(report["imported"]["ducks"], 2);
+    assert_eq!(report["imported"]["locations"], 1);
+    assert_eq!(report["imported"]["users"], 0);
+
+    let (_, ducks) = admin(&target, Method::GET, "/admin/many-ducks", None).await;
+    let ducks = ducks.as_array().unwrap();
+    assert_eq!(ducks.len(), 3);
+    let first = ducks.iter().find(|d| d["title"]["en"] == "first").unwrap();
+    let second = ducks.iter().find(|d| d["title"]["en"] == "second").unwrap();
+    assert_ne!(first["id"], archive["ducks"][0]["id"]);
+    assert_eq!(first["nextDuckStory"]["id"], second["id"]);
+    assert_eq!(second["location"]["description"]["en"], "lake");
+}
+
+#[tokio::test]
+async fn import_merges_players() {
+    let app = app();
+    let archive = seed(&app).await;
+    let (status, report) = admin(
+        &app,
+        Method::POST,
+        "/admin/import?mode=merge",
+        Some(archive),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(report["imported"]["ducks"], 2);
+    // the player already exists, and gains the imported history
+    assert_eq!(report["imported"]["users"], 0);
+    assert_eq!(report["imported"]["duck_history"], 1);
+    let (_, ducks) = admin(&app, Method::GET, "/admin/many-ducks", None).await;
+    assert_eq!(ducks.as_array().unwrap().len(), 4);
+
+    let (status, log) = admin(
+        &app,
+        Method::GET,
+        "/admin/audit-log?entity_type=archive",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(log["items"][0]["after"]["ducks"], 2);
+}
+
+#[tokio::test]
+async fn import_replaces_everything() {
+    let app = app();
+    let archive = seed(&app).await;
+    admin(&app, Method::POST, "/admin/duck", Some(duck("extra", None))).await;
+    let rules = json!([{ "type": "duck_count", "count": 1 }]);
+    admin(&app, Method::PUT, "/admin/completion-rules", Some(rules)).await;
+    let uri = "/admin/import?mode=replace";
+    let (status, report) = admin(&app, Method::POST, uri, Some(archive)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(report["imported"]["users"], 1);
+    let (_, ducks) = admin(&app, Method::GET, "/admin/many-ducks", None).await;
+    assert_eq!(ducks.as_array().unwrap().len(), 2);
+    let (_, rules) = admin(&app, Method::GET, "/admin/completion-rules", None).await;
+    assert_eq!(rules, json!([{ "type": "duck_count", "count": 10 }]));
+}
+
+#[tokio::test]
+async fn import_rejects_invalid_archive() {
+    let app = app();
+    let mut archive = seed(&app).await;
+    archive["version"] = json!(99);
+    let uri = "/admin/import?mode=merge";
+    let (status, _) = admin(&app, Method::POST, uri, Some(archive.clone())).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    archive["version"] = json!(5);
+    archive["ducks"] = json!([]);
+    let (status, _) = admin(&app, Method::POST, uri, Some(archive)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (_, ducks) = admin(&app, Method::GET, "/admin/many-ducks", None).await;
+    assert_eq!(ducks.as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn replace_keeps_everything_on_repeated_players() {
+    let app = app();
+    let mut archive = seed(&app).await;
+    let user = archive["users"][0].clone();
+    archive["users"].as_array_mut().unwrap().push(user);
+    let uri = "/admin/import?mode=replace";
+    let (status, _) = admin(&app, Method::POST, uri, Some(archive)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (_, ducks) = admin(&app, Method::GET, "/admin/many-ducks", None).await;
+    assert_eq!(ducks.as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn merge_ranks_imported_players_last() {
+    let rules = json!([{ "type": "duck_count", "count": 1 }]);
+    let source = app();
+    admin(
+        &source,
+        Method::PUT,
+        "/admin/completion-rules",
+        Some(rules.clone()),
+    )
+    .await;
+    let archive = seed(&source).await;
+    assert_eq!(archive["rankings"][0]["ranking"], 1);
+    assert_eq!(archive["completionRules"].as_array().unwrap().len(), 1);
+
+    let target = app();
+    admin(&target, Method::PUT, "/admin/completion-rules", Some(rules)).await;
+    let (_, other) = admin(
+        &target,
+        Method::POST,
+        "/admin/duck",
+        Some(duck("other", None)),
+    )
+    .await;
+    let mut browser = Browser::default();
+    browser.login(&target, "erin").await;
+    let uri = find_duck_uri(&target, other["id"].as_str().unwrap()).await;
+    browser.send(&target, Method::GET, &uri, None).await;
+
+    let uri = "/admin/import?mode=merge";
+    let (status, report) = admin(&target, Method::POST, uri, Some(archive)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(report["imported"]["rankings"], 1);
+    // the rules already set are kept
+    assert_eq!(report["imported"]["completion_rules"], 0);
+    let (_, rankings) = admin(&target, Method::GET, "/admin/rankings", None).await;
+    let ranking_of = |name: &str| {
+        rankings
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|r| r["userWechatOpenId"] == openid_for(name))
+            .unwrap()["ranking"]
+            .clone()
+    };
+    assert_eq!(ranking_of("erin"), 1);
+    assert_eq!(ranking_of("dave"), 2);
+}
+
+#[tokio::test]
+async fn replace_restores_teams_and_achievements() {
+    let source = app();
+    let archive = seed(&source).await;
+    let first = archive["ducks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|d| d["title"]["en"] == "first")
+        .unwrap()
+        .clone();
+    let achievement = json!({
+        "title": { "en": "first", "cn": "第一" },
+        "description": { "en": "find the first duck", "cn": "找到第一只鸭子" },
+        "rule": { "type": "story_chain", "duckId": first["id"] },
+    });
+    let uri = "/admin/achievement";
+    let (status, _) = admin(&source, Method::POST, uri, Some(achievement)).await;
+    assert_eq!(status, StatusCode::OK);
+    let mut browser = Browser::default();
+    browser.login(&source, "dave").await;
+    let body = json!({ "name": "ducklings" });
+    let (status, team) = browser
+        .send(&source, Method::POST, "/api/team", Some(body))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, archive) = admin(&source, Method::GET, "/admin/export", None).await;
+
+    let target = app();
+    admin(
+        &target,
+        Method::POST,
+        "/admin/duck",
+        Some(duck("other", None)),
+    )
+    .await;
+    let uri = "/admin/import?mode=replace";
+    let (status, report) = admin(&target, Method::POST, uri, Some(archive)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(report["imported"]["achievements"], 1);
+    assert_eq!(report["imported"]["teams"], 1);
+
+    let (_, ducks) = admin(&target, Method::GET, "/admin/many-ducks", None).await;
+    let first = ducks
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|d| d["title"]["en"] == "first")
+        .unwrap()
+        .clone();
+    let (_, achievements) = admin(&target, Method::GET, "/admin/achievements", None).await;
+    assert_eq!(achievements[0]["rule"]["duckId"], first["id"]);
+    let mut browser = Browser::default();
+    browser.login(&target, "dave").await;
+    let (status, imported) = browser.send(&target, Method::GET, "/api/team", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(imported["joinCode"], team["joinCode"]);
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use common::{admin, app};
+use http::{Method, StatusCode};
+use serde_json::json;
+
+#[tokio::test]
+async fn mutations_are_audited() {
+    let app = app();
+    let exhibit = json!({
+        "location": { "en": "hall", "cn": "大厅" },
+        "title": { "en": "exhibit", "cn": "展品" },
+        "sign": { "en": "sign", "cn": "标牌" },
+        "artists": [],
+    });
+    let (status, exhibit) = admin(&app, Method::POST, "/admin/exhibit", Some(exhibit)).await;
+    assert_eq!(status, StatusCode::OK);
+    let id = exhibit["id"].as_str().unwrap();
+    let uri = format!("/admin/exhibit/{}", id);
+    let patch = json!({ "title": { "en": "renamed", "cn": "改名" } });
+    let (status, _) = admin(&app, Method::PATCH, &uri, Some(patch)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = admin(&app, Method::DELETE, "/admin/rankings", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, log) = admin(&app, Method::GET, "/admin/audit-log", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(log["total"], 3);
+    // newest first
+    let items = log["items"].as_array().unwrap();
+    assert_eq!(items[0]["entityType"], "ranking");
+    assert_eq!(items[0]["route"], "DELETE /admin/rankings");
+    let update = &items[1];
+    assert_eq!(update["admin"], "root");
+    assert_eq!(update["route"], "PATCH /admin/exhibit/:id");
+    assert_eq!(update["entityId"], id);
+    assert_eq!(update["before"]["title"]["en"], "exhibit");
+    assert_eq!(update["after"]["title"]["en"], "renamed");
+    assert!(items[2]["before"].is_null());
+    assert_eq!(items[2]["after"]["id"], id);
+}
+
+#[tokio::test]
+async fn audit_log_filters_and_pages() {
+    let app = app();
+    let (status, _) = admin(&app, Method::DELETE, "/admin/rankings", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = admin(&app, Method::POST, "/admin/rankings/renumber", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = admin(&app, Method::DELETE, "/admin/many-ducks/dangerous", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let uri = "/admin/audit-log?entity_type=ranking&page_size=1&page=1";
+    let (status, log) = admin(&app, Method::GET, uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(log["total"], 2);
+    assert_eq!(log["pageSize"], 1);
+    let items = log["items"].as_array().unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["route"], "DELETE /admin/rankings");
+
+    let uri = "/admin/audit-log?from=2000-01-01T00:00:00Z&to=2000-12-31T00:00:00Z";
+    let (status, log) = admin(&app, Method::GET, uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(log["total"], 0);
+
+    let uri = "/admin/audit-log?entity_type=unicorn";
+    let (status, _) = admin(&app, Method::GET, uri, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,199 @@
+mod common;
+
+use common::{admin, app, find_duck_uri, new_duck, Browser};
+use http::{Method, StatusCode};
+use serde_json::{json, Value};
+
+/// duck asking `challenge` before it counts as found
+async fn challenged_duck(app: &axum::Router, challenge: Value) -> String {
+    let id = new_duck(app, json!({})).await;
+    let uri = format!("/admin/duck/{}/challenge", id);
+    let (status, _) = admin(app, Method::PUT, &uri, Some(challenge)).await;
+    assert_eq!(status, StatusCode::OK);
+    id
+}
+
+fn multiple_choice() -> Value {
+    json!({
+        "type": "multiple_choice",
+        "question": { "en": "colour?", "cn": "颜色?" },
+        "choices": [
+            { "en": "red", "cn": "红" },
+            { "en": "yellow", "cn": "黄" },
+            { "en": "green", "cn": "绿" },
+            { "en": "blue", "cn": "蓝" },
+            { "en": "white", "cn": "白" },
+            { "en": "black", "cn": "黑" },
+        ],
+        "answer": 1,
+    })
+}
+
+async fn answer(
+    app: &axum::Router,
+    browser: &mut Browser,
+    id: &str,
+    answer: Value,
+) -> (StatusCode, Value) {
+    let uri = format!("/api/duck/{}/answer", id);
+    browser
+        .send(app, Method::POST, &uri, Some(json!({ "answer": answer })))
+        .await
+}
+
+async fn history(app: &axum::Router, browser: &mut Browser) -> usize {
+    let (_, user) = browser.send(app, Method::GET, "/api/user-info", None).await;
+    user["duckHistory"].as_array().unwrap().len()
+}
+
+#[tokio::test]
+async fn find_counts_once_the_challenge_is_answered() {
+    let app = app();
+    let id = challenged_duck(&app, multiple_choice()).await;
+    let mut browser = Browser::default();
+    browser.login(&app, "sam").await;
+
+    let (status, _) = answer(&app, &mut browser, &id, json!(1)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let uri = find_duck_uri(&app, &id).await;
+    let (status, pending) = browser.send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(pending["attemptsLeft"], 3);
+    assert_eq!(pending["challenge"]["type"], "multiple_choice");
+    assert_eq!(pending["challenge"]["choices"][1]["en"], "yellow");
+    assert!(pending["challenge"].get("answer").is_none());
+    assert_eq!(history(&app, &mut browser).await, 0);
+
+    let (status, wrong) = answer(&app, &mut browser, &id, json!(0)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(wrong, json!({ "correct": false, "attemptsLeft": 2 }));
+    assert_eq!(history(&app, &mut browser).await, 0);
+
+    let (status, right) = answer(&app, &mut browser, &id, json!(1)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(right["correct"], true);
+    assert_eq!(
+        right["userInfo"]["duckHistory"].as_array().unwrap().len(),
+        1
+    );
+    assert_eq!(history(&app, &mut browser).await, 1);
+
+    // found ducks are not asked again
+    let (status, _) = answer(&app, &mut browser, &id, json!(1)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let uri = find_duck_uri(&app, &id).await;
+    let (_, user) = browser.send(&app, Method::GET, &uri, None).await;
+    assert_eq!(user["duckHistory"].as_array().unwrap().len(), 1);
+
+    let uri = format!("/admin/duck/{}/challenge/results", id);
+    let (status, results) = admin(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        results,
+        json!({ "attempts": 2, "correct": 1, "players": 1, "solved": 1 })
+    );
+}
+
+#[tokio::test]
+async fn wrong_answers_are_rate_limited() {
+    let app = app();
+    let id = challenged_duck(
+        &app,
+        json!({
+            "type": "free_text",
+            "question": { "en": "name?", "cn": "名字?" },
+            "answers": [{ "en": "Mallard Duck", "cn": "绿头鸭" }],
+        }),
+    )
+    .await;
+    let mut browser = Browser::default();
+    browser.login(&app, "tess").await;
+    let uri = find_duck_uri(&app, &id).await;
+    browser.send(&app, Method::GET, &uri, None).await;
+
+    for left in [2, 1, 0] {
+        let (_, wrong) = answer(&app, &mut browser, &id, json!("goose")).await;
+        assert_eq!(wrong["attemptsLeft"], left);
+    }
+    let (status, _) = answer(&app, &mut browser, &id, json!("mallard duck")).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(history(&app, &mut browser).await, 0);
+
+    // other players are not limited, and texts ignore case and spaces
+    let mut other = Browser::default();
+    other.login(&app, "ursula").await;
+    let uri = find_duck_uri(&app, &id).await;
+    other.send(&app, Method::GET, &uri, None).await;
+    let (_, right) = answer(&app, &mut other, &id, json!("  mallard   DUCK ")).await;
+    assert_eq!(right["correct"], true);
+
+    // in either language
+    let mut third = Browser::default();
+    third.login(&app, "wendy").await;
+    let uri = find_duck_uri(&app, &id).await;
+    third.send(&app, Method::GET, &uri, None).await;
+    let (_, right) = answer(&app, &mut third, &id, json!("绿头鸭")).await;
+    assert_eq!(right["correct"], true);
+}
+
+#[tokio::test]
+async fn fewer_choices_allow_fewer_wrong_answers() {
+    let app = app();
+    let mut challenge = multiple_choice();
+    challenge["choices"] = json!([{ "en": "red", "cn": "红" }, { "en": "yellow", "cn": "黄" }]);
+    let id = challenged_duck(&app, challenge).await;
+    let mut browser = Browser::default();
+    browser.login(&app, "xena").await;
+    let uri = find_duck_uri(&app, &id).await;
+    let (_, pending) = browser.send(&app, Method::GET, &uri, None).await;
+    assert_eq!(pending["attemptsLeft"], 1);
+
+    let (_, wrong) = answer(&app, &mut browser, &id, json!(0)).await;
+    assert_eq!(wrong["attemptsLeft"], 0);
+    // the other choice must wait
+    let (status, _) = answer(&app, &mut browser, &id, json!(1)).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn concurrent_answers_share_the_limit() {
+    let app = app();
+    let id = challenged_duck(&app, multiple_choice()).await;
+    let mut browser = Browser::default();
+    browser.login(&app, "vera").await;
+    let uri = find_duck_uri(&app, &id).await;
+    browser.send(&app, Method::GET, &uri, None).await;
+
+    let answers = (0..6).map(|_| {
+        let mut browser = browser.clone();
+        let app = &app;
+        let id = &id;
+        async move { answer(app, &mut browser, id, json!(0)).await.0 }
+    });
+    let statuses = futures::future::join_all(answers).await;
+    let limited = statuses
+        .iter()
+        .filter(|s| **s == StatusCode::TOO_MANY_REQUESTS)
+        .count();
+    assert_eq!(limited, 3);
+}
+
+#[tokio::test]
+async fn invalid_challenges_are_rejected() {
+    let app = app();
+    let id = challenged_duck(&app, multiple_choice()).await;
+    let uri = format!("/admin/duck/{}/challenge", id);
+    let mut challenge = multiple_choice();
+    challenge["answer"] = json!(6);
+    let (status, _) = admin(&app, Method::PUT, &uri, Some(challenge)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = admin(&app, Method::DELETE, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let mut browser = Browser::default();
+    browser.login(&app, "victor").await;
+    let uri = find_duck_uri(&app, &id).await;
+    let (_, user) = browser.send(&app, Method::GET, &uri, None).await;
+    assert_eq!(user["duckHistory"].as_array().unwrap().len(), 1);
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use common::{admin, app, find_duck_uri, new_duck, Browser};
+use http::{Method, StatusCode};
+use serde_json::json;
+
+#[tokio::test]
+async fn find_duck_requires_signed_code() {
+    let app = app();
+    let id = new_duck(&app, json!({})).await;
+    let other = new_duck(&app, json!({})).await;
+    let mut browser = Browser::default();
+    browser.login(&app, "erin").await;
+
+    let uri = format!("/api/find-duck/{}", id);
+    let (status, _) = browser.send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = browser
+        .send(&app, Method::GET, &format!("{}?code=a.0.b", uri), None)
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    // a code is only valid for its own duck
+    let other_uri = find_duck_uri(&app, &other).await;
+    let swapped = other_uri.replace(&other, &id);
+    let (status, _) = browser.send(&app, Method::GET, &swapped, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let find_uri = find_duck_uri(&app, &id).await;
+    let (status, user) = browser.send(&app, Method::GET, &find_uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(user["duckHistory"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn invalidate_codes_of_duck() {
+    let app = app();
+    let id = new_duck(&app, json!({})).await;
+    let mut browser = Browser::default();
+    browser.login(&app, "frank").await;
+    let printed = find_duck_uri(&app, &id).await;
+
+    let uri = format!("/admin/duck/{}/qrcode/invalidate", id);
+    let (status, rsp) = admin(&app, Method::POST, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(rsp["epoch"], 1);
+    let (status, _) = browser.send(&app, Method::GET, &printed, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let reprinted = find_duck_uri(&app, &id).await;
+    let (status, _) = browser.send(&app, Method::GET, &reprinted, None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn rotate_and_retire_keys() {
+    let app = app();
+    let id = new_duck(&app, json!({})).await;
+    let mut browser = Browser::default();
+    browser.login(&app, "grace").await;
+    let printed = find_duck_uri(&app, &id).await;
+    let (_, keys) = admin(&app, Method::GET, "/admin/qr-keys", None).await;
+    let old_kid = keys[0]["kid"].as_str().unwrap().to_string();
+    assert!(keys[0].get("secret").is_none());
+
+    let (status, key) = admin(&app, Method::POST, "/admin/qr-keys", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_ne!(key["kid"], old_kid);
+    let reprinted = find_duck_uri(&app, &id).await;
+    assert!(reprinted.contains(key["kid"].as_str().unwrap()));
+    // codes of older keys stay valid until the key is retired
+    let (status, _) = browser.send(&app, Method::GET, &printed, None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let uri = format!("/admin/qr-keys/{}", old_kid);
+    let (status, retired) = admin(&app, Method::DELETE, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(retired["retiredAt"].is_string());
+    let (status, _) = browser.send(&app, Method::GET, &printed, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = browser.send(&app, Method::GET, &reprinted, None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn signing_never_creates_keys() {
+    let app = app();
+    let id = new_duck(&app, json!({})).await;
+    let (_, keys) = admin(&app, Method::GET, "/admin/qr-keys", None).await;
+    assert_eq!(keys.as_array().unwrap().len(), 1);
+    let uri = format!("/admin/qr-keys/{}", keys[0]["kid"].as_str().unwrap());
+    admin(&app, Method::DELETE, &uri, None).await;
+
+    let uri = format!("/admin/duck/{}/qrcode/url", id);
+    let (status, _) = admin(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    let (_, keys) = admin(&app, Method::GET, "/admin/qr-keys", None).await;
+    assert_eq!(keys.as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn exports_leave_out_keys() {
+    let app = app();
+    new_duck(&app, json!({})).await;
+    let (_, key) = admin(&app, Method::POST, "/admin/qr-keys", None).await;
+    assert!(key.get("secret").is_none());
+    let (status, archive) = admin(&app, Method::GET, "/admin/export", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!archive.to_string().contains(key["kid"].as_str().unwrap()));
+}
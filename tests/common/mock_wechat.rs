@@ -0,0 +1,60 @@
+//! stand-in for the wechat oauth access token server
+use axum::extract::Query;
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::net::{SocketAddr, TcpListener};
+
+pub const TOKEN_PATH: &str = "/sns/oauth2/access_token";
+/// `appname` of tests/config.yaml, sent as `appid` to the token server
+pub const APP_NAME: &str = "test-app-name";
+/// codes starting with this prefix are answered with `CodeResponse::Failure`
+pub const FAILING_CODE_PREFIX: &str = "fail";
+
+#[derive(Deserialize)]
+struct TokenParams {
+    appid: String,
+    code: String,
+    grant_type: String,
+}
+
+/// openid the mock server assigns to a login code
+pub fn openid_for(code: &str) -> String {
+    format!("openid-{}", code)
+}
+
+async fn access_token(Query(params): Query<TokenParams>) -> Json<Value> {
+    if params.appid != APP_NAME || params.grant_type != "authorization_code" {
+        Json(json!({ "errcode": 40013, "errmsg": "invalid appid" }))
+    } else if params.code.starts_with(FAILING_CODE_PREFIX) {
+        Json(json!({ "errcode": 40029, "errmsg": "invalid code" }))
+    } else {
+        Json(json!({
+            "access_token": "mock-access-token",
+            "expires_in": 7200,
+            "refresh_token": "mock-refresh-token",
+            "openid": openid_for(&params.code),
+            "scope": "snsapi_base",
+        }))
+    }
+}
+
+/// serve on a background thread (outliving each test's runtime),
+/// returning the bound address
+pub fn spawn() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async move {
+            let app = Router::new().route(TOKEN_PATH, get(access_token));
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service())
+                .await
+                .unwrap();
+        });
+    });
+    addr
+}
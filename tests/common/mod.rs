@@ -0,0 +1,228 @@
+//! test harness running the router on the in-memory backend,
+//! or on MongoDB and redis for the ignored tests of `tests/prisma.rs`
+#![allow(dead_code)]
+pub mod mock_wechat;
+
+use axum::body::Body;
+use axum::response::Response;
+use axum::Router;
+use cyberduck_backend::configuration::CONFIG_FILE_ENV;
+use cyberduck_backend::db_api::DB;
+use cyberduck_backend::SERVER_CONFIG;
+use http::{header, Method, Request, StatusCode};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Once;
+use tower::ServiceExt;
+use url::Url;
+
+pub const ADMIN_TOKEN: &str = "test-admin-token";
+pub const FRONTEND_URL: &str = "https://frontend.test/game";
+
+static INIT: Once = Once::new();
+
+/// variable holding the MongoDB url of the tests against the real backend
+pub const TEST_DB_URL_ENV: &str = "CYBERDUCK_TEST_DB_URL";
+/// variable holding the redis url of the tests against the real backend
+pub const TEST_REDIS_URL_ENV: &str = "CYBERDUCK_TEST_REDIS_URL";
+
+fn init() {
+    INIT.call_once(|| {
+        std::env::set_var(
+            CONFIG_FILE_ENV,
+            concat!(env!("CARGO_MANIFEST_DIR"), "/tests/config.yaml"),
+        );
+        let addr = mock_wechat::spawn();
+        std::env::set_var(
+            "CYBERDUCK_WECHAT__TOKEN_URL",
+            format!("http://{}{}", addr, mock_wechat::TOKEN_PATH),
+        );
+    });
+}
+
+/// router backed by a fresh in-memory database
+pub fn app() -> Router {
+    init();
+    let session = SERVER_CONFIG.redis_session.build_memory_layer().unwrap();
+    cyberduck_backend::app(DB::memory(), session)
+}
+
+/// router backed by the MongoDB and redis servers the environment points to,
+/// shared by every test: create what a test checks rather than expect it empty
+pub async fn prisma_app() -> Router {
+    init();
+    let db_url = std::env::var(TEST_DB_URL_ENV).expect("CYBERDUCK_TEST_DB_URL is not set");
+    let redis_url = std::env::var(TEST_REDIS_URL_ENV).expect("CYBERDUCK_TEST_REDIS_URL is not set");
+    let db = DB::new(&db_url, &redis_url).await.unwrap();
+    let session = SERVER_CONFIG.redis_session.build_memory_layer().unwrap();
+    cyberduck_backend::app(db, session)
+}
+
+/// send a request with the admin token, returning the status and JSON body
+pub async fn admin(
+    app: &Router,
+    method: Method,
+    uri: &str,
+    body: Option<Value>,
+) -> (StatusCode, Value) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {}", ADMIN_TOKEN));
+    send(app, request, body).await
+}
+
+/// call a `/dangerous` route twice as the root admin: first for the dry run,
+/// then with the confirmation token it issued
+pub async fn confirmed(app: &Router, uri: &str) -> (StatusCode, Value) {
+    let (status, dry_run) = admin(app, Method::DELETE, uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let token = dry_run["confirmation_token"].as_str().unwrap();
+    let separator = if uri.contains('?') { '&' } else { '?' };
+    let uri = format!("{}{}confirm={}", uri, separator, token);
+    admin(app, Method::DELETE, &uri, None).await
+}
+
+/// body creating a duck: placeholder title, story and icon, overridden by `fields`
+pub fn duck_data(fields: Value) -> Value {
+    let mut duck = json!({
+        "title": { "en": "duck", "cn": "鸭" },
+        "story": { "en": "story", "cn": "故事" },
+        "topics": [],
+        "duckIconUrl": "https://icons.test/duck.png",
+    });
+    let fields = match fields {
+        Value::Object(fields) => fields,
+        _ => panic!("duck fields must be an object"),
+    };
+    duck.as_object_mut().unwrap().extend(fields);
+    duck
+}
+
+/// create a duck from `duck_data(fields)`, returning its id
+pub async fn new_duck(app: &Router, fields: Value) -> String {
+    let (status, duck) = admin(app, Method::POST, "/admin/duck", Some(duck_data(fields))).await;
+    assert_eq!(status, StatusCode::OK, "{}", duck);
+    duck["id"].as_str().unwrap().to_string()
+}
+
+/// uri to find a duck, with the signed code its QR code would carry
+pub async fn find_duck_uri(app: &Router, duck_id: &str) -> String {
+    let uri = format!("/admin/duck/{}/qrcode/url", duck_id);
+    let (status, rsp) = admin(app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    format!(
+        "/api/find-duck/{}?code={}",
+        duck_id,
+        rsp["code"].as_str().unwrap()
+    )
+}
+
+/// send a request, returning the status and JSON body (or the body as a string)
+pub async fn send(
+    app: &Router,
+    request: http::request::Builder,
+    body: Option<Value>,
+) -> (StatusCode, Value) {
+    into_json(call(app, request, body).await).await
+}
+
+pub async fn call(app: &Router, request: http::request::Builder, body: Option<Value>) -> Response {
+    let request = match body {
+        None => request.body(Body::empty()),
+        Some(body) => request
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string())),
+    }
+    .unwrap();
+    app.clone().oneshot(request).await.unwrap()
+}
+
+pub async fn into_json(response: Response) -> (StatusCode, Value) {
+    let status = response.status();
+    let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let body = serde_json::from_slice(&bytes)
+        .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).into_owned()));
+    (status, body)
+}
+
+/// a player's browser, keeping session cookies between requests
+#[derive(Default, Clone)]
+pub struct Browser {
+    cookies: HashMap<String, String>,
+}
+
+impl Browser {
+    pub async fn call(
+        &mut self,
+        app: &Router,
+        method: Method,
+        uri: &str,
+        body: Option<Value>,
+    ) -> Response {
+        let mut request = Request::builder().method(method).uri(uri);
+        if !self.cookies.is_empty() {
+            let cookies: Vec<String> = self
+                .cookies
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect();
+            request = request.header(header::COOKIE, cookies.join("; "));
+        }
+        let response = call(app, request, body).await;
+        for set_cookie in response.headers().get_all(header::SET_COOKIE) {
+            let cookie = set_cookie.to_str().unwrap();
+            let pair = cookie.split(';').next().unwrap();
+            if let Some((name, value)) = pair.split_once('=') {
+                self.cookies.insert(name.to_string(), value.to_string());
+            }
+        }
+        response
+    }
+
+    pub async fn send(
+        &mut self,
+        app: &Router,
+        method: Method,
+        uri: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        into_json(self.call(app, method, uri, body).await).await
+    }
+
+    /// start a login, returning the `state` sent to the wechat authorize page
+    pub async fn start_login(&mut self, app: &Router) -> String {
+        let uri = format!("/login?redirect_url={}", FRONTEND_URL);
+        let response = self.call(app, Method::GET, &uri, None).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let location = Url::parse(location(&response)).unwrap();
+        let (_, state) = location
+            .query_pairs()
+            .find(|(key, _)| key == "state")
+            .unwrap();
+        state.into_owned()
+    }
+
+    /// the wechat authorize page redirecting back with `code` and `state`
+    pub async fn login_callback(&mut self, app: &Router, code: &str, state: &str) -> Response {
+        let uri = format!("/login/callback?code={}&state={}", code, state);
+        self.call(app, Method::GET, &uri, None).await
+    }
+
+    /// complete a login, the mock server assigns `mock_wechat::openid_for(code)`
+    pub async fn login(&mut self, app: &Router, code: &str) {
+        let state = self.start_login(app).await;
+        let response = self.login_callback(app, code, &state).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(location(&response), FRONTEND_URL);
+    }
+}
+
+pub fn location(response: &Response) -> &str {
+    response
+        .headers()
+        .get(header::LOCATION)
+        .unwrap()
+        .to_str()
+        .unwrap()
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use common::{admin, app, find_duck_uri, new_duck, Browser};
+use http::{Method, StatusCode};
+use serde_json::{json, Value};
+
+async fn find(app: &axum::Router, browser: &mut Browser, id: &str) -> Value {
+    let uri = find_duck_uri(app, id).await;
+    let (status, user) = browser.send(app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    user
+}
+
+async fn set_rules(app: &axum::Router, rules: Value) -> StatusCode {
+    let uri = "/admin/completion-rules";
+    let (status, _) = admin(app, Method::PUT, uri, Some(rules)).await;
+    status
+}
+
+#[tokio::test]
+async fn rank_after_ten_ducks_by_default() {
+    let app = app();
+    let (status, rules) = admin(&app, Method::GET, "/admin/completion-rules", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(rules, json!([{ "type": "duck_count", "count": 10 }]));
+
+    let mut browser = Browser::default();
+    browser.login(&app, "rupert").await;
+    for i in 0..10 {
+        let user = find(&app, &mut browser, &new_duck(&app, json!({})).await).await;
+        assert_eq!(user["ranking"].is_null(), i < 9);
+    }
+    let (_, user) = browser
+        .send(&app, Method::GET, "/api/user-info", None)
+        .await;
+    assert_eq!(user["ranking"]["ranking"], 1);
+}
+
+#[tokio::test]
+async fn rank_players_past_the_rules() {
+    let app = app();
+    let mut browser = Browser::default();
+    browser.login(&app, "sybil").await;
+    for _ in 0..3 {
+        find(&app, &mut browser, &new_duck(&app, json!({})).await).await;
+    }
+    let (_, user) = browser
+        .send(&app, Method::GET, "/api/user-info", None)
+        .await;
+    assert!(user["ranking"].is_null());
+
+    // already past the new count, ranked at the next request
+    let rules = json!([{ "type": "duck_count", "count": 2 }]);
+    assert_eq!(set_rules(&app, rules).await, StatusCode::OK);
+    let (_, user) = browser
+        .send(&app, Method::GET, "/api/user-info", None)
+        .await;
+    assert_eq!(user["ranking"]["ranking"], 1);
+    let uri = "/admin/audit-log?entity_type=completion_rule";
+    let (_, log) = admin(&app, Method::GET, uri, None).await;
+    assert_eq!(log["items"][0]["after"][0]["count"], 2);
+}
+
+#[tokio::test]
+async fn complete_topic_and_story_chain() {
+    let app = app();
+    let birds = json!([{ "en": "birds", "cn": "鸟" }]);
+    let heron = new_duck(&app, json!({ "topics": birds.clone() })).await;
+    let swan = new_duck(&app, json!({ "topics": birds })).await;
+    let first = new_duck(&app, json!({})).await;
+    let last = new_duck(&app, json!({})).await;
+    let uri = format!("/admin/duck/{}", last);
+    let patch = json!({ "prevDuckStoryId": first });
+    admin(&app, Method::PATCH, &uri, Some(patch)).await;
+    let rules = json!([
+        { "type": "topic", "topic": "鸟" },
+        { "type": "story_chain", "duckId": last },
+    ]);
+    assert_eq!(set_rules(&app, rules).await, StatusCode::OK);
+
+    let mut browser = Browser::default();
+    browser.login(&app, "trent").await;
+    find(&app, &mut browser, &heron).await;
+    find(&app, &mut browser, &first).await;
+    let user = find(&app, &mut browser, &last).await;
+    assert!(user["ranking"].is_null());
+    let user = find(&app, &mut browser, &swan).await;
+    assert_eq!(user["ranking"]["ranking"], 1);
+}
+
+#[tokio::test]
+async fn reject_invalid_rules() {
+    let app = app();
+    let invalid = [
+        json!([{ "type": "duck_count", "count": 0 }]),
+        json!([{ "type": "topic", "topic": "" }]),
+    ];
+    for rules in invalid {
+        assert_eq!(set_rules(&app, rules).await, StatusCode::BAD_REQUEST);
+    }
+    let rules = json!([{ "type": "favourite_colour" }]);
+    assert_eq!(
+        set_rules(&app, rules).await,
+        StatusCode::UNPROCESSABLE_ENTITY
+    );
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use common::{admin, app, find_duck_uri, new_duck, send, Browser};
+use http::{Method, Request, StatusCode};
+use serde_json::{json, Value};
+
+fn location(coordinate: Value) -> Value {
+    json!({
+        "description": { "en": "pond", "cn": "池塘" },
+        "coordinate": coordinate,
+    })
+}
+
+#[tokio::test]
+async fn reject_invalid_coordinates() {
+    let app = app();
+    let invalid = [
+        json!({ "x": "114.1694", "y": "22.3193" }),
+        json!({ "system": "wgs84", "x": "", "y": "" }),
+        // swapped axes
+        json!({ "system": "gcj02", "x": 22.3193, "y": 114.1694 }),
+        json!({ "system": "floor_plan", "x": -1.0, "y": 20.0 }),
+        json!({ "system": "mercator", "x": 1.0, "y": 2.0 }),
+    ];
+    for coordinate in invalid {
+        let body = location(coordinate.clone());
+        let (status, _) = admin(&app, Method::POST, "/admin/location", Some(body)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", coordinate);
+    }
+
+    let coordinate = json!({ "system": "floor_plan", "x": 320.0, "y": 200.0 });
+    let body = location(coordinate.clone());
+    let (status, location) = admin(&app, Method::POST, "/admin/location", Some(body)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(location["coordinate"], coordinate);
+}
+
+#[tokio::test]
+async fn preview_in_chinese_map_coordinates() {
+    let app = app();
+    let wgs84 = json!({ "system": "wgs84", "x": 114.1694, "y": 22.3193 });
+    let (_, geographic) = admin(&app, Method::POST, "/admin/location", Some(location(wgs84))).await;
+    let plan = json!({ "system": "floor_plan", "x": 320.0, "y": 200.0 });
+    let (_, floor) = admin(&app, Method::POST, "/admin/location", Some(location(plan))).await;
+    new_duck(&app, json!({ "locationId": geographic["id"] })).await;
+    new_duck(&app, json!({ "locationId": floor["id"] })).await;
+
+    let request = Request::builder().uri("/api/preview-ducks?system=gcj02");
+    let (status, previews) = send(&app, request, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let converted = &previews[0]["location"]["coordinate"];
+    assert_eq!(converted["system"], "gcj02");
+    assert!((converted["x"].as_f64().unwrap() - 114.174367).abs() < 1e-5);
+    assert!((converted["y"].as_f64().unwrap() - 22.316551).abs() < 1e-5);
+    // floor plans have no geographic conversion
+    assert_eq!(
+        previews[1]["location"]["coordinate"]["system"],
+        "floor_plan"
+    );
+}
+
+#[tokio::test]
+async fn find_with_chinese_map_position() {
+    let app = app();
+    let wgs84 = json!({ "system": "wgs84", "x": 114.1694, "y": 22.3193 });
+    let mut body = location(wgs84);
+    body["geofenceRadius"] = json!(50.0);
+    body["geofenceMode"] = json!("enforce");
+    let (_, location) = admin(&app, Method::POST, "/admin/location", Some(body)).await;
+    let id = new_duck(&app, json!({ "locationId": location["id"] })).await;
+    let mut browser = Browser::default();
+    browser.login(&app, "kate").await;
+    let uri = find_duck_uri(&app, &id).await;
+
+    // the WGS84 numbers read as GCJ-02 are about 500m away
+    let wrong = format!("{}&x=114.1694&y=22.3193&system=gcj02", uri);
+    let (status, _) = browser.send(&app, Method::GET, &wrong, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let invalid = format!("{}&x=22.3193&y=114.1694", uri);
+    let (status, _) = browser.send(&app, Method::GET, &invalid, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let right = format!("{}&x=114.174367&y=22.316551&system=gcj02", uri);
+    let (status, _) = browser.send(&app, Method::GET, &right, None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn migrate_untyped_coordinates() {
+    let app = app();
+    let typed = json!({ "system": "wgs84", "x": 1.0, "y": 2.0 });
+    admin(&app, Method::POST, "/admin/location", Some(location(typed))).await;
+    // older versions stored strings, archives still carry them
+    let (_, mut archive) = admin(&app, Method::GET, "/admin/export", None).await;
+    let typed_location = archive["locations"][0].clone();
+    let legacy = [
+        json!({ "x": "114.1694", "y": "22.3193" }),
+        json!({ "x": "22.3193", "y": "114.1694" }),
+        json!({ "x": "", "y": "" }),
+    ];
+    for (i, coordinate) in legacy.into_iter().enumerate() {
+        let mut location = typed_location.clone();
+        location["id"] = json!(format!("ffffffffffffffffffffff{:02x}", i));
+        location["coordinate"] = coordinate;
+        archive["locations"].as_array_mut().unwrap().push(location);
+    }
+    let uri = "/admin/import?mode=replace";
+    let (status, _) = admin(&app, Method::POST, uri, Some(archive)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let uri = "/admin/many-locations/migrate-coordinates?system=wgs84";
+    let (status, rsp) = admin(&app, Method::POST, uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(rsp["number_of_locations_migrated"], 0);
+    let plan = &rsp["plan"];
+    assert_eq!(plan["unchanged"], 1);
+    assert_eq!(plan["changes"].as_array().unwrap().len(), 2);
+    assert_eq!(plan["changes"][0]["swapped"], false);
+    assert_eq!(plan["changes"][1]["swapped"], true);
+    assert_eq!(plan["changes"][1]["after"]["x"], 114.1694);
+    assert_eq!(plan["invalid"].as_array().unwrap().len(), 1);
+
+    let uri = "/admin/many-locations/migrate-coordinates?system=wgs84&apply=true";
+    let (status, rsp) = admin(&app, Method::POST, uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(rsp["number_of_locations_migrated"], 2);
+    let (_, locations) = admin(&app, Method::GET, "/admin/many-locations", None).await;
+    let typed = locations
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|l| l["coordinate"]["system"] == "wgs84")
+        .count();
+    assert_eq!(typed, 3);
+    let uri = "/admin/audit-log?entity_type=location";
+    let (_, log) = admin(&app, Method::GET, uri, None).await;
+    assert_eq!(log["items"][0]["before"]["x"], "22.3193");
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use common::{admin, app, confirmed, find_duck_uri, new_duck, Browser};
+use http::{Method, StatusCode};
+use serde_json::json;
+
+#[tokio::test]
+async fn dry_run_then_confirm() {
+    let app = app();
+    let duck = new_duck(&app, json!({})).await;
+    let mut browser = Browser::default();
+    browser.login(&app, "carol").await;
+    let uri = find_duck_uri(&app, &duck).await;
+    browser.send(&app, Method::GET, &uri, None).await;
+
+    let uri = "/admin/many-ducks/dangerous";
+    let (status, dry_run) = admin(&app, Method::DELETE, uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(dry_run["dry_run"]["ducks"], 1);
+    assert_eq!(dry_run["dry_run"]["duck_history"], 1);
+    assert_eq!(dry_run["dry_run"]["rankings"], 0);
+    let token = dry_run["confirmation_token"].as_str().unwrap();
+    let (_, ducks) = admin(&app, Method::GET, "/admin/many-ducks", None).await;
+    assert_eq!(ducks.as_array().unwrap().len(), 1);
+
+    // tokens are bound to the operation
+    let wrong_uri = format!("/admin/many-exhibits/dangerous?confirm={}", token);
+    let (status, _) = admin(&app, Method::DELETE, &wrong_uri, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = admin(&app, Method::DELETE, &format!("{}?confirm=nope", uri), None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let confirm_uri = format!("{}?confirm={}", uri, token);
+    let (status, deleted) = admin(&app, Method::DELETE, &confirm_uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(deleted["number_of_ducks_deleted"], 1);
+    assert!(deleted["snapshot_id"].is_string());
+    // and valid only once
+    let (status, _) = admin(&app, Method::DELETE, &confirm_uri, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn snapshot_undoes_delete() {
+    let app = app();
+    let location = json!({
+        "description": { "en": "lake", "cn": "湖" },
+        "coordinate": { "system": "wgs84", "x": 1.0, "y": 2.0 },
+    });
+    let (_, location) = admin(&app, Method::POST, "/admin/location", Some(location)).await;
+    let duck = new_duck(&app, json!({ "locationId": location["id"] })).await;
+    let mut browser = Browser::default();
+    browser.login(&app, "dave").await;
+    let find_uri = find_duck_uri(&app, &duck).await;
+    browser.send(&app, Method::GET, &find_uri, None).await;
+
+    let (status, deleted) = confirmed(&app, "/admin/many-ducks/dangerous").await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, user) = browser
+        .send(&app, Method::GET, "/api/user-info", None)
+        .await;
+    assert_eq!(user["duckHistory"].as_array().unwrap().len(), 0);
+
+    let (_, snapshots) = admin(&app, Method::GET, "/admin/snapshots", None).await;
+    assert_eq!(snapshots[0]["id"], deleted["snapshot_id"]);
+    assert_eq!(snapshots[0]["route"], "DELETE /admin/many-ducks/dangerous");
+    let uri = format!(
+        "/admin/snapshots/{}/restore",
+        deleted["snapshot_id"].as_str().unwrap()
+    );
+    let (status, restored) = admin(&app, Method::POST, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    // the duck, the history and the location reconnected to the duck
+    assert_eq!(restored["number_of_documents_restored"], 3);
+
+    let duck_uri = format!("/admin/duck/{}", duck);
+    let (_, restored_duck) = admin(&app, Method::GET, &duck_uri, None).await;
+    assert_eq!(restored_duck["location"]["id"], location["id"]);
+    let (_, user) = browser
+        .send(&app, Method::GET, "/api/user-info", None)
+        .await;
+    assert_eq!(user["duckHistory"][0]["duck"]["id"], duck);
+}
+
+#[tokio::test]
+async fn restore_skips_ducks_found_again() {
+    let app = app();
+    let rules = json!([{ "type": "duck_count", "count": 1 }]);
+    let (status, _) = admin(&app, Method::PUT, "/admin/completion-rules", Some(rules)).await;
+    assert_eq!(status, StatusCode::OK);
+    let duck = new_duck(&app, json!({})).await;
+    let mut browser = Browser::default();
+    browser.login(&app, "erin").await;
+    let find_uri = find_duck_uri(&app, &duck).await;
+    browser.send(&app, Method::GET, &find_uri, None).await;
+
+    let uri = "/admin/duck-history/dangerous?user_id=openid-erin";
+    let (_, dry_run) = admin(&app, Method::DELETE, uri, None).await;
+    assert_eq!(dry_run["dry_run"]["duck_history"], 1);
+    assert_eq!(dry_run["dry_run"]["rankings"], 1);
+    let (status, deleted) = confirmed(&app, uri).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // found again before the restore
+    browser.send(&app, Method::GET, &find_uri, None).await;
+    let uri = format!(
+        "/admin/snapshots/{}/restore",
+        deleted["snapshot_id"].as_str().unwrap()
+    );
+    let (status, restored) = admin(&app, Method::POST, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(restored["number_of_documents_restored"], 0);
+    let (_, snapshots) = admin(&app, Method::GET, "/admin/snapshots", None).await;
+    assert!(snapshots[0]["restoredAt"].is_string());
+    let (_, user) = browser
+        .send(&app, Method::GET, "/api/user-info", None)
+        .await;
+    assert_eq!(user["duckHistory"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn confirm_refuses_changed_data() {
+    let app = app();
+    new_duck(&app, json!({})).await;
+    let uri = "/admin/many-ducks/dangerous";
+    let (_, dry_run) = admin(&app, Method::DELETE, uri, None).await;
+    assert_eq!(dry_run["dry_run"]["ducks"], 1);
+    let token = dry_run["confirmation_token"].as_str().unwrap();
+
+    // a duck the dry run did not report
+    new_duck(&app, json!({})).await;
+    let confirm_uri = format!("{}?confirm={}", uri, token);
+    let (status, _) = admin(&app, Method::DELETE, &confirm_uri, None).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (_, ducks) = admin(&app, Method::GET, "/admin/many-ducks", None).await;
+    assert_eq!(ducks.as_array().unwrap().len(), 2);
+    let (_, snapshots) = admin(&app, Method::GET, "/admin/snapshots", None).await;
+    assert!(snapshots.as_array().unwrap().is_empty());
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use axum::body::BoxBody;
+use common::{admin, app, find_duck_uri, new_duck, Browser};
+use http::{header, Method, StatusCode};
+use hyper::body::HttpBody;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// server-sent events of an open `/api/events` response
+struct Events {
+    body: BoxBody,
+    buffer: String,
+}
+
+impl Events {
+    async fn open(app: &axum::Router, browser: &mut Browser) -> Events {
+        let response = browser.call(app, Method::GET, "/api/events", None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/event-stream"
+        );
+        Events {
+            body: response.into_body(),
+            buffer: String::new(),
+        }
+    }
+
+    /// name and data of the next event
+    async fn next(&mut self) -> (String, Value) {
+        loop {
+            if let Some(end) = self.buffer.find("\n\n") {
+                let event: String = self.buffer.drain(..end + 2).collect();
+                let field = |name: &str| {
+                    event
+                        .lines()
+                        .find_map(|l| l.strip_prefix(name))
+                        .map(str::to_string)
+                };
+                if let (Some(name), Some(data)) = (field("event: "), field("data: ")) {
+                    return (name, serde_json::from_str(&data).unwrap());
+                }
+                continue;
+            }
+            let chunk = tokio::time::timeout(Duration::from_secs(5), self.body.data())
+                .await
+                .expect("no event pushed")
+                .unwrap()
+                .unwrap();
+            self.buffer.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+    }
+}
+
+async fn find(app: &axum::Router, browser: &mut Browser, id: &str) {
+    let uri = find_duck_uri(app, id).await;
+    let (status, _) = browser.send(app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn push_progress_to_other_devices() {
+    let app = app();
+    let rules = json!([{ "type": "duck_count", "count": 2 }]);
+    admin(&app, Method::PUT, "/admin/completion-rules", Some(rules)).await;
+    let first = new_duck(&app, json!({})).await;
+    let second = new_duck(&app, json!({})).await;
+
+    let mut phone = Browser::default();
+    phone.login(&app, "olga").await;
+    let mut laptop = Browser::default();
+    laptop.login(&app, "olga").await;
+    let mut other = Browser::default();
+    other.login(&app, "paul").await;
+    let mut phone_events = Events::open(&app, &mut phone).await;
+    let mut other_events = Events::open(&app, &mut other).await;
+    let mut anonymous_events = Events::open(&app, &mut Browser::default()).await;
+
+    find(&app, &mut laptop, &first).await;
+    // found again, not a discovery
+    find(&app, &mut laptop, &first).await;
+    find(&app, &mut laptop, &second).await;
+    assert_eq!(
+        phone_events.next().await,
+        ("discovery".to_string(), json!({ "duckId": first }))
+    );
+    assert_eq!(
+        phone_events.next().await,
+        ("discovery".to_string(), json!({ "duckId": second }))
+    );
+    assert_eq!(
+        phone_events.next().await,
+        (
+            "ranked".to_string(),
+            json!({ "seasonId": null, "ranking": 1 })
+        )
+    );
+    let leaderboard_changed = (
+        "leaderboard_changed".to_string(),
+        json!({ "seasonId": null }),
+    );
+    assert_eq!(phone_events.next().await, leaderboard_changed);
+    assert_eq!(other_events.next().await, leaderboard_changed);
+    assert_eq!(anonymous_events.next().await, leaderboard_changed);
+}
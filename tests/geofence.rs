@@ -0,0 +1,104 @@
+mod common;
+
+use common::mock_wechat::openid_for;
+use common::{admin, app, find_duck_uri, new_duck, Browser};
+use http::{Method, StatusCode};
+use serde_json::{json, Value};
+
+/// duck at a location, with the given geofence
+async fn duck_at(app: &axum::Router, x: f64, y: f64, geofence: Value) -> String {
+    let id = new_duck(app, json!({})).await;
+    let mut location = json!({
+        "description": { "en": "pond", "cn": "池塘" },
+        "coordinate": { "system": "wgs84", "x": x, "y": y },
+        "duckId": id,
+    });
+    location
+        .as_object_mut()
+        .unwrap()
+        .extend(geofence.as_object().unwrap().clone());
+    let (status, _) = admin(app, Method::POST, "/admin/location", Some(location)).await;
+    assert_eq!(status, StatusCode::OK);
+    id
+}
+
+async fn flags(app: &axum::Router) -> Vec<Value> {
+    let (status, flags) = admin(app, Method::GET, "/admin/discovery-flags", None).await;
+    assert_eq!(status, StatusCode::OK);
+    flags.as_array().unwrap().clone()
+}
+
+#[tokio::test]
+async fn enforce_geofence() {
+    let app = app();
+    let geofence = json!({ "geofenceRadius": 50.0, "geofenceMode": "enforce" });
+    let id = duck_at(&app, 114.1694, 22.3193, geofence).await;
+    let mut browser = Browser::default();
+    browser.login(&app, "heidi").await;
+    let uri = find_duck_uri(&app, &id).await;
+
+    // about 1km away
+    let far = format!("{}&x=114.1694&y=22.3283", uri);
+    let (status, _) = browser.send(&app, Method::GET, &far, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = browser.send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let flags = flags(&app).await;
+    assert_eq!(flags[0]["reason"], "missing_position");
+    assert_eq!(flags[1]["reason"], "outside_geofence");
+    assert_eq!(flags[1]["rejected"], true);
+    assert_eq!(flags[1]["wechatOpenId"], openid_for("heidi"));
+
+    // about 20m away
+    let near = format!("{}&x=114.1694&y=22.3195", uri);
+    let (status, user) = browser.send(&app, Method::GET, &near, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(user["duckHistory"].as_array().unwrap().len(), 1);
+
+    // found again from afar, which is neither rejected nor flagged
+    let (status, _) = browser.send(&app, Method::GET, &far, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(flags(&app).await.len(), 2);
+}
+
+#[tokio::test]
+async fn log_only_geofence() {
+    let app = app();
+    let geofence = json!({ "geofenceRadius": 50.0, "geofenceMode": "log_only" });
+    let id = duck_at(&app, 114.1694, 22.3193, geofence).await;
+    let mut browser = Browser::default();
+    browser.login(&app, "ivan").await;
+
+    let far = format!("{}&x=114.1694&y=22.3283", find_duck_uri(&app, &id).await);
+    let (status, _) = browser.send(&app, Method::GET, &far, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let flags = flags(&app).await;
+    assert_eq!(flags.len(), 1);
+    assert_eq!(flags[0]["reason"], "outside_geofence");
+    assert_eq!(flags[0]["rejected"], false);
+    assert!(flags[0]["detail"]["distance"].as_f64().unwrap() > 900.0);
+}
+
+#[tokio::test]
+async fn flag_impossible_travel() {
+    let app = app();
+    // no geofence, about 11km apart
+    let first = duck_at(&app, 114.1694, 22.3193, json!({})).await;
+    let second = duck_at(&app, 114.1694, 22.4193, json!({})).await;
+    let mut browser = Browser::default();
+    browser.login(&app, "judy").await;
+
+    let uri = find_duck_uri(&app, &first).await;
+    let (status, _) = browser.send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(flags(&app).await.is_empty());
+    let uri = find_duck_uri(&app, &second).await;
+    let (status, _) = browser.send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let flags = flags(&app).await;
+    assert_eq!(flags.len(), 1);
+    assert_eq!(flags[0]["reason"], "impossible_travel");
+    assert_eq!(flags[0]["duckId"], second.as_str());
+    assert_eq!(flags[0]["detail"]["previousDuckId"], first.as_str());
+    assert_eq!(flags[0]["rejected"], false);
+}
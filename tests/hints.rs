@@ -0,0 +1,154 @@
+mod common;
+
+use common::{admin, app, find_duck_uri, new_duck, Browser};
+use http::{Method, StatusCode};
+use serde_json::{json, Value};
+
+fn hint(text: &str, unlock: Value) -> Value {
+    json!({ "text": { "en": text, "cn": text }, "unlock": unlock })
+}
+
+async fn reveal(
+    app: &axum::Router,
+    browser: &mut Browser,
+    id: &str,
+    hint: usize,
+) -> (StatusCode, Value) {
+    let uri = format!("/api/duck/{}/hints/{}", id, hint);
+    browser.send(app, Method::POST, &uri, None).await
+}
+
+fn available(hints: &Value) -> Vec<bool> {
+    hints["hints"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|h| h["available"].as_bool().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn hints_are_revealed_in_order() {
+    let app = app();
+    let id = new_duck(&app, json!({ "isHidden": true })).await;
+    let other = new_duck(&app, json!({ "isHidden": true })).await;
+    let hints = json!([
+        hint("by the lake", json!({ "type": "elapsed", "minutes": 0 })),
+        hint(
+            "under a bench",
+            json!({ "type": "ducks_found", "count": 1 })
+        ),
+        hint("the red bench", json!({ "type": "points", "cost": 10 })),
+        hint("look down", json!({ "type": "elapsed", "minutes": 600 })),
+    ]);
+    let uri = format!("/admin/duck/{}/hints", id);
+    let (status, _) = admin(&app, Method::PUT, &uri, Some(hints)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let mut browser = Browser::default();
+    let uri = format!("/api/duck/{}/hints", id);
+    let (status, _) = browser.send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    browser.login(&app, "wendy").await;
+    let (status, hints) = browser.send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(hints["points"], 0);
+    assert_eq!(available(&hints), vec![true, false, false, false]);
+    assert_eq!(hints["hints"][0]["text"], Value::Null);
+
+    let (status, _) = reveal(&app, &mut browser, &id, 1).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, hints) = reveal(&app, &mut browser, &id, 0).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(hints["hints"][0]["text"]["en"], "by the lake");
+    assert!(hints["hints"][0]["revealedAt"].is_string());
+    assert_eq!(available(&hints), vec![false, false, false, false]);
+
+    let find = find_duck_uri(&app, &other).await;
+    browser.send(&app, Method::GET, &find, None).await;
+    let (_, hints) = browser.send(&app, Method::GET, &uri, None).await;
+    assert_eq!(hints["points"], 10);
+    assert_eq!(available(&hints), vec![false, true, false, false]);
+    reveal(&app, &mut browser, &id, 1).await;
+    let (status, hints) = reveal(&app, &mut browser, &id, 2).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(hints["points"], 0);
+    assert_eq!(hints["hints"][2]["text"]["en"], "the red bench");
+
+    // revealing again costs nothing
+    let (_, hints) = reveal(&app, &mut browser, &id, 2).await;
+    assert_eq!(hints["points"], 0);
+    let (status, _) = reveal(&app, &mut browser, &id, 3).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = reveal(&app, &mut browser, &id, 4).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let uri = format!("/admin/duck/{}/hints/stats", id);
+    let (status, stats) = admin(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let reveals: Vec<_> = stats
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| {
+            (
+                s["reveals"].as_u64().unwrap(),
+                s["pointsSpent"].as_i64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(reveals, vec![(1, 0), (1, 0), (1, 10), (0, 0)]);
+}
+
+#[tokio::test]
+async fn concurrent_reveals_share_the_points() {
+    let app = app();
+    let ids = [
+        new_duck(&app, json!({ "isHidden": true })).await,
+        new_duck(&app, json!({ "isHidden": true })).await,
+    ];
+    for id in &ids {
+        let hints = json!([hint("far away", json!({ "type": "points", "cost": 10 }))]);
+        let uri = format!("/admin/duck/{}/hints", id);
+        admin(&app, Method::PUT, &uri, Some(hints)).await;
+    }
+    let found = new_duck(&app, json!({ "isHidden": true })).await;
+    let mut browser = Browser::default();
+    browser.login(&app, "xena").await;
+    let find = find_duck_uri(&app, &found).await;
+    browser.send(&app, Method::GET, &find, None).await;
+
+    let reveals = ids.iter().map(|id| {
+        let mut browser = browser.clone();
+        let app = &app;
+        async move { reveal(app, &mut browser, id, 0).await.0 }
+    });
+    let mut statuses = futures::future::join_all(reveals).await;
+    statuses.sort();
+    assert_eq!(statuses, vec![StatusCode::OK, StatusCode::FORBIDDEN]);
+    let uri = format!("/api/duck/{}/hints", ids[0]);
+    let (_, hints) = browser.send(&app, Method::GET, &uri, None).await;
+    assert_eq!(hints["points"], 0);
+}
+
+#[tokio::test]
+async fn ducks_without_hints() {
+    let app = app();
+    let id = new_duck(&app, json!({ "isHidden": true })).await;
+    let mut browser = Browser::default();
+    browser.login(&app, "xavier").await;
+    let uri = format!("/api/duck/{}/hints", id);
+    let (status, hints) = browser.send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(hints, json!({ "points": 0, "hints": [] }));
+
+    let (status, _) = browser
+        .send(
+            &app,
+            Method::GET,
+            "/api/duck/ffffffffffffffffffffffff/hints",
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use common::{admin, app, find_duck_uri, new_duck, Browser};
+use http::{Method, StatusCode};
+use serde_json::{json, Value};
+
+/// a ranked player, after finding the only duck needed
+async fn ranked(app: &axum::Router, duck: &str, code: &str) -> Browser {
+    let mut browser = Browser::default();
+    browser.login(app, code).await;
+    let uri = find_duck_uri(app, duck).await;
+    let (status, user) = browser.send(app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!user["ranking"].is_null());
+    browser
+}
+
+async fn one_duck_game(app: &axum::Router) -> String {
+    let rules = json!([{ "type": "duck_count", "count": 1 }]);
+    admin(app, Method::PUT, "/admin/completion-rules", Some(rules)).await;
+    new_duck(app, json!({})).await
+}
+
+fn names(entries: &Value) -> Vec<&str> {
+    entries
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["name"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn paginate_without_openids() {
+    let app = app();
+    let duck = one_duck_game(&app).await;
+    let mut first = ranked(&app, &duck, "xavier").await;
+    let patch = json!({ "nickname": "  Xavier  " });
+    let (status, user) = first
+        .send(&app, Method::PATCH, "/api/user-info", Some(patch))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(user["nickname"], "Xavier");
+    let mut second = ranked(&app, &duck, "yvonne").await;
+    ranked(&app, &duck, "zoe").await;
+
+    let uri = "/api/leaderboard?page=1&page_size=2";
+    let (status, page) = second.send(&app, Method::GET, uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(page["total"], 3);
+    assert_eq!(page["items"].as_array().unwrap().len(), 1);
+    assert_eq!(page["items"][0]["rank"], 3);
+    assert_eq!(page["me"]["rank"], 2);
+    assert_eq!(page["me"]["me"], true);
+    assert!(!page.to_string().contains("openid"));
+
+    let (_, page) = Browser::default()
+        .send(&app, Method::GET, "/api/leaderboard", None)
+        .await;
+    let names = names(&page["items"]);
+    assert_eq!(names[0], "Xavier");
+    assert!(names[1].starts_with("duck-"));
+    assert_ne!(names[1], names[2]);
+    assert!(page["me"].is_null());
+}
+
+#[tokio::test]
+async fn around_me() {
+    let app = app();
+    let duck = one_duck_game(&app).await;
+    let mut players = vec![];
+    for code in ["amy", "ben", "cat", "dan", "eve"] {
+        players.push(ranked(&app, &duck, code).await);
+    }
+    let uri = "/api/leaderboard/around-me?around=1";
+    let (status, entries) = players[2].send(&app, Method::GET, uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let ranks: Vec<i64> = entries
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["rank"].as_i64().unwrap())
+        .collect();
+    assert_eq!(ranks, vec![2, 3, 4]);
+    assert_eq!(entries[1]["me"], true);
+    let (_, entries) = players[0].send(&app, Method::GET, uri, None).await;
+    assert_eq!(entries.as_array().unwrap().len(), 2);
+
+    let mut unranked = Browser::default();
+    unranked.login(&app, "fay").await;
+    let (status, _) = unranked.send(&app, Method::GET, uri, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn around_me_with_a_shared_rank() {
+    let source = app();
+    let duck = one_duck_game(&source).await;
+    ranked(&source, &duck, "amy").await;
+    let (_, archive) = admin(&source, Method::GET, "/admin/export", None).await;
+
+    let app = app();
+    let duck = one_duck_game(&app).await;
+    let mut ben = ranked(&app, &duck, "ben").await;
+    let patch = json!({ "nickname": "Ben" });
+    ben.send(&app, Method::PATCH, "/api/user-info", Some(patch))
+        .await;
+    // amy joins with the rank earned elsewhere, the same as the one of ben
+    let uri = "/admin/import?mode=merge";
+    let (status, _) = admin(&app, Method::POST, uri, Some(archive)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let uri = "/api/leaderboard/around-me?around=0";
+    let (status, entries) = ben.send(&app, Method::GET, uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(names(&entries), vec!["Ben"]);
+    assert_eq!(entries[0]["me"], true);
+}
+
+#[tokio::test]
+async fn reject_invalid_nicknames() {
+    let app = app();
+    let mut browser = Browser::default();
+    browser.login(&app, "gus").await;
+    let long = "x".repeat(21);
+    for nickname in ["", "   ", "duck-12345678", "a\nb", long.as_str()] {
+        let patch = json!({ "nickname": nickname });
+        let (status, _) = browser
+            .send(&app, Method::PATCH, "/api/user-info", Some(patch))
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+    let patch = json!({ "nickname": null });
+    let (status, user) = browser
+        .send(&app, Method::PATCH, "/api/user-info", Some(patch))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(user["nickname"].is_null());
+}
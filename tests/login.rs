@@ -0,0 +1,66 @@
+mod common;
+
+use common::mock_wechat::openid_for;
+use common::{app, find_duck_uri, location, new_duck, Browser, FRONTEND_URL};
+use http::{Method, StatusCode};
+use serde_json::json;
+
+#[tokio::test]
+async fn callback_without_login_state() {
+    let app = app();
+    let mut browser = Browser::default();
+    let response = browser.login_callback(&app, "code", "state").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn callback_with_unmatched_state() {
+    let app = app();
+    let mut browser = Browser::default();
+    browser.start_login(&app).await;
+    let response = browser.login_callback(&app, "code", "forged-state").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    // the login state is consumed by the failed attempt
+    let (status, _) = browser
+        .send(&app, Method::GET, "/api/user-info", None)
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn callback_with_wechat_failure() {
+    let app = app();
+    let mut browser = Browser::default();
+    let state = browser.start_login(&app).await;
+    let response = browser.login_callback(&app, "fail-code", &state).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let (status, _) = browser
+        .send(&app, Method::GET, "/api/user-info", None)
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn login_and_find_duck() {
+    let app = app();
+    let duck = new_duck(&app, json!({})).await;
+
+    let mut browser = Browser::default();
+    browser.login(&app, "alice").await;
+    let (status, user) = browser
+        .send(&app, Method::GET, "/api/user-info", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(user["wechatOpenId"], openid_for("alice"));
+    assert_eq!(user["duckHistory"].as_array().unwrap().len(), 0);
+
+    let uri = find_duck_uri(&app, &duck).await;
+    let (status, user) = browser.send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(user["duckHistory"][0]["duck"]["id"], duck);
+
+    // logged in players are sent straight back to the frontend
+    let uri = format!("/login?redirect_url={}", FRONTEND_URL);
+    let response = browser.call(&app, Method::GET, &uri, None).await;
+    assert_eq!(location(&response), FRONTEND_URL);
+}
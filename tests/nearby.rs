@@ -0,0 +1,88 @@
+mod common;
+
+use common::{admin, app, find_duck_uri, new_duck, send, Browser};
+use http::{Method, Request, StatusCode};
+use serde_json::{json, Value};
+
+/// duck at a WGS84 position, returning its id
+async fn duck_at(app: &axum::Router, title: &str, x: f64, y: f64, hidden: bool) -> String {
+    let location = json!({
+        "description": { "en": title, "cn": title },
+        "coordinate": { "system": "wgs84", "x": x, "y": y },
+    });
+    let (_, location) = admin(app, Method::POST, "/admin/location", Some(location)).await;
+    let duck = json!({
+        "title": { "en": title, "cn": title },
+        "isHidden": hidden,
+        "locationId": location["id"],
+    });
+    new_duck(app, duck).await
+}
+
+fn titles(ducks: &Value) -> Vec<&str> {
+    ducks
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|d| d["title"]["en"].as_str().unwrap())
+        .collect()
+}
+
+const NEARBY: &str = "/api/nearby-ducks?lat=22.3193&lng=114.1694&radius=1000";
+
+#[tokio::test]
+async fn nearest_first() {
+    let app = app();
+    // about 300m, 10m, 3km away, and a hidden duck next to the player
+    duck_at(&app, "far", 114.1694, 22.3220, false).await;
+    duck_at(&app, "near", 114.1694, 22.3194, false).await;
+    duck_at(&app, "away", 114.1694, 22.3463, false).await;
+    duck_at(&app, "hidden", 114.1694, 22.3193, true).await;
+
+    let (status, ducks) = send(&app, Request::builder().uri(NEARBY), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(titles(&ducks), ["near", "far"]);
+    let distance = ducks[1]["distance"].as_f64().unwrap();
+    assert!((distance - 300.0).abs() < 10.0);
+    assert_eq!(ducks[0]["location"]["coordinate"]["system"], "wgs84");
+
+    // the position read as GCJ-02 is about 500m west and 300m north
+    let uri = "/api/nearby-ducks?lat=22.3193&lng=114.1694&radius=550&system=gcj02";
+    let (status, ducks) = send(&app, Request::builder().uri(uri), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(titles(&ducks), ["far"]);
+    assert_eq!(ducks[0]["location"]["coordinate"]["system"], "gcj02");
+}
+
+#[tokio::test]
+async fn leave_out_found_ducks() {
+    let app = app();
+    let near = duck_at(&app, "near", 114.1694, 22.3194, false).await;
+    duck_at(&app, "far", 114.1694, 22.3220, false).await;
+    let mut browser = Browser::default();
+    browser.login(&app, "leo").await;
+    let (_, ducks) = browser.send(&app, Method::GET, NEARBY, None).await;
+    assert_eq!(titles(&ducks), ["near", "far"]);
+
+    let uri = find_duck_uri(&app, &near).await;
+    browser.send(&app, Method::GET, &uri, None).await;
+    let (status, ducks) = browser.send(&app, Method::GET, NEARBY, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(titles(&ducks), ["far"]);
+}
+
+#[tokio::test]
+async fn reject_invalid_search() {
+    let app = app();
+    let invalid = [
+        "/api/nearby-ducks?lat=22.3193&lng=114.1694&radius=0",
+        "/api/nearby-ducks?lat=22.3193&lng=114.1694&radius=50000",
+        "/api/nearby-ducks?lat=114.1694&lng=22.3193&radius=1000",
+        "/api/nearby-ducks?lat=22.3193&lng=114.1694&radius=1000&system=floor_plan",
+        "/api/nearby-ducks?lat=22.3193&radius=1000",
+    ];
+    for uri in invalid {
+        let (status, _) = send(&app, Request::builder().uri(uri), None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+    }
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use common::{admin, app, call, new_duck, ADMIN_TOKEN};
+use http::{header, Method, Request, StatusCode};
+use serde_json::json;
+
+/// download a file as the root admin, returning the status, content type and body
+async fn download(app: &axum::Router, uri: &str) -> (StatusCode, String, Vec<u8>) {
+    let request = Request::builder()
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {}", ADMIN_TOKEN));
+    let response = call(app, request, None).await;
+    let status = response.status();
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .map(|v| v.to_str().unwrap().to_string())
+        .unwrap_or_default();
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    (status, content_type, body.to_vec())
+}
+
+async fn located_duck(app: &axum::Router) -> String {
+    let location = json!({
+        "description": { "en": "lake", "cn": "湖" },
+        "coordinate": { "system": "wgs84", "x": 1.0, "y": 2.0 },
+    });
+    let (_, location) = admin(app, Method::POST, "/admin/location", Some(location)).await;
+    new_duck(app, json!({ "locationId": location["id"] })).await
+}
+
+#[tokio::test]
+async fn qr_code_of_duck() {
+    let app = app();
+    let id = located_duck(&app).await;
+
+    let (status, content_type, png) = download(&app, &format!("/admin/duck/{}/qrcode", id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type, "image/png");
+    assert!(png.starts_with(b"\x89PNG"));
+
+    let uri = format!("/admin/duck/{}/qrcode?format=svg", id);
+    let (status, content_type, svg) = download(&app, &uri).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type, "image/svg+xml");
+    let svg = String::from_utf8(svg).unwrap();
+    assert!(svg.contains("<svg"));
+    // icon from the asset store
+    assert!(svg.contains("data:image/png;base64,"));
+
+    let uri = "/admin/duck/000000000000000000000999/qrcode";
+    let (status, _, _) = download(&app, uri).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn bulk_codes_and_posters() {
+    let app = app();
+    located_duck(&app).await;
+    // trashed locations get no sheet
+    let location = json!({
+        "description": { "en": "pond", "cn": "池" },
+        "coordinate": { "system": "wgs84", "x": 1.0, "y": 2.0 },
+    });
+    let (_, location) = admin(&app, Method::POST, "/admin/location", Some(location)).await;
+    new_duck(&app, json!({ "locationId": location["id"] })).await;
+    let uri = format!("/admin/location/{}", location["id"].as_str().unwrap());
+    let (status, _) = admin(&app, Method::DELETE, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, content_type, zip) = download(&app, "/admin/qrcodes.zip?format=svg").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type, "application/zip");
+    assert!(zip.starts_with(b"PK"));
+
+    let (status, content_type, pdf) = download(&app, "/admin/posters.pdf").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type, "application/pdf");
+    assert!(pdf.starts_with(b"%PDF"));
+}
@@ -0,0 +1,166 @@
+//! the MongoDB and redis backend, ignored unless both servers are given:
+//! `CYBERDUCK_TEST_DB_URL=.. CYBERDUCK_TEST_REDIS_URL=.. cargo test --test prisma -- --ignored`
+mod common;
+
+use common::{admin, find_duck_uri, new_duck, prisma_app, send, Browser};
+use http::{Method, Request, StatusCode};
+use serde_json::json;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// name no earlier run has used in the shared database
+fn unique(name: &str) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    format!("{}-{}", name, nanos)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "needs MongoDB and redis"]
+async fn concurrent_finds_get_distinct_rankings() {
+    let app = prisma_app().await;
+    let season = json!({
+        "title": { "en": "race", "cn": "赛" },
+        "startsAt": "2020-01-01T00:00:00Z",
+    });
+    let (_, season) = admin(&app, Method::POST, "/admin/season", Some(season)).await;
+    let season = season["id"].as_str().unwrap().to_string();
+    let uri = format!("/admin/completion-rules?season={}", season);
+    let rules = json!([{ "type": "duck_count", "count": 1 }]);
+    let (status, _) = admin(&app, Method::PUT, &uri, Some(rules)).await;
+    assert_eq!(status, StatusCode::OK);
+    let duck = new_duck(&app, json!({ "seasonId": season })).await;
+    let find_uri = find_duck_uri(&app, &duck).await;
+
+    let mut tasks = vec![];
+    for i in 0..10 {
+        let app = app.clone();
+        let find_uri = find_uri.clone();
+        let player = unique(&format!("racer-{}", i));
+        tasks.push(tokio::spawn(async move {
+            let mut browser = Browser::default();
+            browser.login(&app, &player).await;
+            let (status, _) = browser.send(&app, Method::GET, &find_uri, None).await;
+            assert_eq!(status, StatusCode::OK);
+        }));
+    }
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    let uri = format!("/admin/season/{}/rankings", season);
+    let (_, rankings) = admin(&app, Method::GET, &uri, None).await;
+    let mut ranks: Vec<i64> = rankings
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["ranking"].as_i64().unwrap())
+        .collect();
+    ranks.sort_unstable();
+    assert_eq!(ranks, (1..=10).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB and redis"]
+async fn trash_and_restore_a_duck() {
+    let app = prisma_app().await;
+    let duck = new_duck(&app, json!({})).await;
+    let uri = format!("/admin/duck/{}", duck);
+    let (status, _) = admin(&app, Method::DELETE, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = admin(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (_, trash) = admin(&app, Method::GET, "/admin/trash", None).await;
+    assert!(trash["ducks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|d| d["id"] == duck));
+
+    let restore_uri = format!("/admin/duck/{}/restore", duck);
+    let (status, _) = admin(&app, Method::POST, &restore_uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = admin(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+const NEARBY: &str = "/api/nearby-ducks?lat=22.3193&lng=114.1694&radius=100";
+
+#[tokio::test]
+#[ignore = "needs MongoDB and redis"]
+async fn nearby_ducks_from_the_redis_index() {
+    let app = prisma_app().await;
+    let title = unique("nearby");
+    let location = json!({
+        "description": { "en": title, "cn": title },
+        "coordinate": { "system": "wgs84", "x": 114.1694, "y": 22.3194 },
+    });
+    let (_, location) = admin(&app, Method::POST, "/admin/location", Some(location)).await;
+    let duck = json!({
+        "title": { "en": title, "cn": title },
+        "locationId": location["id"],
+    });
+    new_duck(&app, duck).await;
+
+    let (status, ducks) = send(&app, Request::builder().uri(NEARBY), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let found = ducks
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|d| d["title"]["en"] == title.as_str())
+        .unwrap();
+    assert!((found["distance"].as_f64().unwrap() - 10.0).abs() < 5.0);
+
+    // moving the location updates the index
+    let uri = format!("/admin/location/{}", location["id"].as_str().unwrap());
+    let patch = json!({ "coordinate": { "system": "wgs84", "x": 114.1694, "y": 22.4194 } });
+    let (status, _) = admin(&app, Method::PATCH, &uri, Some(patch)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, ducks) = send(&app, Request::builder().uri(NEARBY), None).await;
+    assert!(!ducks
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|d| d["title"]["en"] == title.as_str()));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "needs MongoDB and redis"]
+async fn concurrent_rule_replacements_do_not_mix() {
+    let app = prisma_app().await;
+    let season = json!({
+        "title": { "en": "rules", "cn": "规则" },
+        "startsAt": "2020-01-01T00:00:00Z",
+    });
+    let (_, season) = admin(&app, Method::POST, "/admin/season", Some(season)).await;
+    let uri = format!(
+        "/admin/completion-rules?season={}",
+        season["id"].as_str().unwrap()
+    );
+
+    let mut tasks = vec![];
+    for count in 1..=5 {
+        let app = app.clone();
+        let uri = uri.clone();
+        tasks.push(tokio::spawn(async move {
+            let rules = json!([
+                { "type": "duck_count", "count": count },
+                { "type": "topic", "topic": format!("topic-{}", count) },
+            ]);
+            let (status, _) = admin(&app, Method::PUT, &uri, Some(rules)).await;
+            assert_eq!(status, StatusCode::OK);
+        }));
+    }
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    // the rules of a single replacement
+    let (_, rules) = admin(&app, Method::GET, &uri, None).await;
+    let rules = rules.as_array().unwrap();
+    assert_eq!(rules.len(), 2);
+    let count = rules[0]["count"].as_i64().unwrap();
+    assert_eq!(rules[1]["topic"], format!("topic-{}", count));
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use common::{admin, app, find_duck_uri, new_duck, Browser};
+use http::{Method, StatusCode};
+use serde_json::json;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn renumber_while_ranking_players() {
+    let app = app();
+    let rules = json!([{ "type": "duck_count", "count": 1 }]);
+    let (status, _) = admin(&app, Method::PUT, "/admin/completion-rules", Some(rules)).await;
+    assert_eq!(status, StatusCode::OK);
+    let duck = new_duck(&app, json!({})).await;
+    let uri = find_duck_uri(&app, &duck).await;
+
+    let mut tasks = vec![];
+    for i in 0..20 {
+        let app = app.clone();
+        let uri = uri.clone();
+        tasks.push(tokio::spawn(async move {
+            let mut browser = Browser::default();
+            browser.login(&app, &format!("player-{}", i)).await;
+            let (status, _) = browser.send(&app, Method::GET, &uri, None).await;
+            assert_eq!(status, StatusCode::OK);
+        }));
+        if i % 5 == 0 {
+            let app = app.clone();
+            tasks.push(tokio::spawn(async move {
+                let uri = "/admin/rankings/renumber";
+                let (status, _) = admin(&app, Method::POST, uri, None).await;
+                assert_eq!(status, StatusCode::OK);
+            }));
+        }
+    }
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    let (_, rankings) = admin(&app, Method::GET, "/admin/rankings", None).await;
+    let mut ranks: Vec<i64> = rankings
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["ranking"].as_i64().unwrap())
+        .collect();
+    ranks.sort_unstable();
+    assert_eq!(ranks, (1..=20).collect::<Vec<_>>());
+}
@@ -0,0 +1,252 @@
+mod common;
+
+use common::mock_wechat::openid_for;
+use common::{admin, app, confirmed, duck_data, find_duck_uri, new_duck, Browser};
+use http::{Method, StatusCode};
+use serde_json::{json, Value};
+
+async fn new_season(app: &axum::Router, starts_at: &str, ends_at: Option<&str>) -> String {
+    let season = json!({
+        "title": { "en": "spring", "cn": "春" },
+        "startsAt": starts_at,
+        "endsAt": ends_at,
+    });
+    let (status, season) = admin(app, Method::POST, "/admin/season", Some(season)).await;
+    assert_eq!(status, StatusCode::OK);
+    season["id"].as_str().unwrap().to_string()
+}
+
+async fn find(app: &axum::Router, browser: &mut Browser, id: &str) -> (StatusCode, Value) {
+    let uri = find_duck_uri(app, id).await;
+    browser.send(app, Method::GET, &uri, None).await
+}
+
+fn ids(ducks: &Value) -> Vec<&str> {
+    ducks
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|d| d["id"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn scope_ducks_history_and_rankings() {
+    let app = app();
+    let season = new_season(&app, "2020-01-01T00:00:00Z", None).await;
+    let main_duck = new_duck(&app, json!({})).await;
+    let season_duck = new_duck(&app, json!({ "seasonId": season })).await;
+    let uri = format!("/admin/completion-rules?season={}", season);
+    let rules = json!([{ "type": "duck_count", "count": 1 }]);
+    let (status, _) = admin(&app, Method::PUT, &uri, Some(rules)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let mut browser = Browser::default();
+    browser.login(&app, "ursula").await;
+    let (_, previews) = browser
+        .send(&app, Method::GET, "/api/preview-ducks", None)
+        .await;
+    assert_eq!(ids(&previews), vec![main_duck.as_str()]);
+    let uri = format!("/api/preview-ducks?season={}", season);
+    let (_, previews) = browser.send(&app, Method::GET, &uri, None).await;
+    assert_eq!(ids(&previews), vec![season_duck.as_str()]);
+
+    let (status, user) = find(&app, &mut browser, &season_duck).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(user["ranking"]["ranking"], 1);
+    let (_, user) = browser
+        .send(&app, Method::GET, "/api/user-info", None)
+        .await;
+    assert!(user["ranking"].is_null());
+    assert_eq!(user["duckHistory"], json!([]));
+    let uri = format!("/api/user-info?season={}", season);
+    let (_, user) = browser.send(&app, Method::GET, &uri, None).await;
+    assert_eq!(user["ranking"]["ranking"], 1);
+    assert_eq!(user["duckHistory"][0]["duck"]["id"], season_duck);
+
+    let uri = format!("/admin/season/{}/rankings", season);
+    let (_, rankings) = admin(&app, Method::GET, &uri, None).await;
+    assert_eq!(rankings[0]["userWechatOpenId"], openid_for("ursula"));
+    let (_, rankings) = admin(&app, Method::GET, "/admin/rankings", None).await;
+    assert_eq!(rankings, json!([]));
+}
+
+#[tokio::test]
+async fn find_only_while_running() {
+    let app = app();
+    let upcoming = new_season(&app, "2099-01-01T00:00:00Z", None).await;
+    let ended = new_season(&app, "2020-01-01T00:00:00Z", Some("2021-01-01T00:00:00Z")).await;
+    let (_, seasons) = Browser::default()
+        .send(&app, Method::GET, "/api/seasons", None)
+        .await;
+    let status: Vec<&str> = seasons
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["status"].as_str().unwrap())
+        .collect();
+    assert_eq!(status, vec!["upcoming", "ended"]);
+
+    let mut browser = Browser::default();
+    browser.login(&app, "victor").await;
+    for season in [upcoming, ended] {
+        let duck = new_duck(&app, json!({ "seasonId": season })).await;
+        let (status, _) = find(&app, &mut browser, &duck).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+}
+
+#[tokio::test]
+async fn archived_seasons_are_read_only() {
+    let app = app();
+    let season = new_season(&app, "2020-01-01T00:00:00Z", None).await;
+    let duck = new_duck(&app, json!({ "seasonId": season })).await;
+    let location = json!({
+        "description": { "en": "pond", "cn": "池塘" },
+        "coordinate": { "system": "wgs84", "x": 1.0, "y": 2.0 },
+        "duckId": duck,
+    });
+    let (_, location) = admin(&app, Method::POST, "/admin/location", Some(location)).await;
+    let mut browser = Browser::default();
+    browser.login(&app, "wendy").await;
+    let (status, _) = find(&app, &mut browser, &duck).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let uri = format!("/admin/season/{}/archive", season);
+    let (status, archived) = admin(&app, Method::POST, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!archived["archivedAt"].is_null());
+    let (_, log) = admin(
+        &app,
+        Method::GET,
+        "/admin/audit-log?entity_type=season",
+        None,
+    )
+    .await;
+    assert_eq!(log["items"][0]["entityId"], season);
+
+    let duck_uri = format!("/admin/duck/{}", duck);
+    let patch = json!({ "isHidden": true });
+    let (status, _) = admin(&app, Method::PATCH, &duck_uri, Some(patch)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = admin(&app, Method::DELETE, &duck_uri, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let location_uri = format!("/admin/location/{}", location["id"].as_str().unwrap());
+    let (status, _) = admin(&app, Method::DELETE, &location_uri, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let duck = duck_data(json!({ "title": { "en": "late", "cn": "迟" }, "seasonId": season }));
+    let (status, _) = admin(&app, Method::POST, "/admin/duck", Some(duck)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = admin(&app, Method::POST, &uri, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // history stays readable
+    let uri = format!("/api/user-info?season={}", season);
+    let (status, user) = browser.send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(user["duckHistory"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn archive_keeps_seasons() {
+    let source = app();
+    let season = new_season(&source, "2020-01-01T00:00:00Z", None).await;
+    let uri = format!("/admin/completion-rules?season={}", season);
+    let rules = json!([{ "type": "duck_count", "count": 1 }]);
+    admin(&source, Method::PUT, &uri, Some(rules)).await;
+    let season_duck = new_duck(&source, json!({ "seasonId": season })).await;
+    let mut browser = Browser::default();
+    browser.login(&source, "wendy").await;
+    find(&source, &mut browser, &season_duck).await;
+    let (_, archive) = admin(&source, Method::GET, "/admin/export", None).await;
+    assert_eq!(archive["seasons"].as_array().unwrap().len(), 1);
+    assert_eq!(archive["seasonRankings"].as_array().unwrap().len(), 1);
+
+    let target = app();
+    let uri = "/admin/import?mode=replace";
+    let (status, report) = admin(&target, Method::POST, uri, Some(archive)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(report["imported"]["seasons"], 1);
+    assert_eq!(report["imported"]["season_rankings"], 1);
+
+    let (_, seasons) = admin(&target, Method::GET, "/admin/seasons", None).await;
+    let imported = seasons[0]["id"].as_str().unwrap();
+    let (_, ducks) = admin(&target, Method::GET, "/admin/many-ducks", None).await;
+    assert_eq!(ducks[0]["seasonId"], imported);
+    let uri = format!("/admin/season/{}/rankings", imported);
+    let (_, rankings) = admin(&target, Method::GET, &uri, None).await;
+    assert_eq!(rankings[0]["userWechatOpenId"], openid_for("wendy"));
+}
+
+#[tokio::test]
+async fn bulk_deletes_keep_archived_seasons() {
+    let app = app();
+    let season = new_season(&app, "2020-01-01T00:00:00Z", None).await;
+    let season_duck = new_duck(&app, json!({ "seasonId": season })).await;
+    let main_duck = new_duck(&app, json!({})).await;
+    let mut browser = Browser::default();
+    browser.login(&app, "xavier").await;
+    find(&app, &mut browser, &season_duck).await;
+    find(&app, &mut browser, &main_duck).await;
+    let uri = format!("/admin/season/{}/archive", season);
+    admin(&app, Method::POST, &uri, None).await;
+
+    let (status, cleared) = browser
+        .send(&app, Method::DELETE, "/api/user-info", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(cleared["number_of_records_removed"], 1);
+    let uri = format!("/api/user-info?season={}", season);
+    let (_, user) = browser.send(&app, Method::GET, &uri, None).await;
+    assert_eq!(user["duckHistory"].as_array().unwrap().len(), 1);
+
+    let (status, deleted) = confirmed(&app, "/admin/many-ducks/dangerous").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(deleted["number_of_ducks_deleted"], 1);
+    let (_, ducks) = admin(&app, Method::GET, "/admin/many-ducks", None).await;
+    assert_eq!(ids(&ducks), vec![season_duck.as_str()]);
+}
+
+#[tokio::test]
+async fn move_duck_back_to_main_game() {
+    let app = app();
+    let season = new_season(&app, "2020-01-01T00:00:00Z", None).await;
+    let duck = new_duck(&app, json!({ "seasonId": season })).await;
+    let uri = format!("/admin/duck/{}", duck);
+    let (status, moved) = admin(&app, Method::PATCH, &uri, Some(json!({ "seasonId": null }))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(moved["seasonId"].is_null());
+
+    // a missing field leaves the season as it is
+    let patch = json!({ "seasonId": season });
+    admin(&app, Method::PATCH, &uri, Some(patch)).await;
+    let (_, kept) = admin(&app, Method::PATCH, &uri, Some(json!({ "isHidden": true }))).await;
+    assert_eq!(kept["seasonId"], season);
+}
+
+#[tokio::test]
+async fn seasons_end_after_they_start() {
+    let app = app();
+    let season = json!({
+        "title": { "en": "spring", "cn": "春" },
+        "startsAt": "2020-02-01T00:00:00Z",
+        "endsAt": "2020-01-01T00:00:00Z",
+    });
+    let (status, _) = admin(&app, Method::POST, "/admin/season", Some(season)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let season = new_season(&app, "2020-01-01T00:00:00Z", Some("2020-02-01T00:00:00Z")).await;
+    let uri = format!("/admin/season/{}", season);
+    let patch = json!({ "startsAt": "2020-03-01T00:00:00Z" });
+    let (status, _) = admin(&app, Method::PATCH, &uri, Some(patch)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // a missing field keeps the end, `null` removes it
+    let patch = json!({ "title": { "en": "summer", "cn": "夏" } });
+    let (_, kept) = admin(&app, Method::PATCH, &uri, Some(patch)).await;
+    assert!(!kept["endsAt"].is_null());
+    let patch = json!({ "startsAt": "2020-03-01T00:00:00Z", "endsAt": null });
+    let (status, cleared) = admin(&app, Method::PATCH, &uri, Some(patch)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(cleared["endsAt"].is_null());
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use common::{admin, app, find_duck_uri, new_duck, Browser};
+use http::{Method, StatusCode};
+use serde_json::json;
+
+async fn story_duck(app: &axum::Router, title: &str, prerequisites: &[&str]) -> String {
+    let duck = json!({
+        "title": { "en": title, "cn": title },
+        "prerequisiteIds": prerequisites,
+    });
+    new_duck(app, duck).await
+}
+
+async fn find(app: &axum::Router, browser: &mut Browser, id: &str) -> StatusCode {
+    let uri = find_duck_uri(app, id).await;
+    let (status, _) = browser.send(app, Method::GET, &uri, None).await;
+    status
+}
+
+async fn locked(app: &axum::Router, browser: &mut Browser, id: &str) -> bool {
+    let (_, previews) = browser
+        .send(app, Method::GET, "/api/preview-ducks", None)
+        .await;
+    let preview = previews
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|d| d["id"] == id)
+        .unwrap();
+    preview["locked"].as_bool().unwrap()
+}
+
+#[tokio::test]
+async fn find_in_story_order() {
+    let app = app();
+    let first = story_duck(&app, "first", &[]).await;
+    let second = story_duck(&app, "second", &[]).await;
+    let uri = format!("/admin/duck/{}", second);
+    let patch = json!({ "prevDuckStoryId": first });
+    let (status, _) = admin(&app, Method::PATCH, &uri, Some(patch)).await;
+    assert_eq!(status, StatusCode::OK);
+    let mut browser = Browser::default();
+    browser.login(&app, "olivia").await;
+
+    assert!(locked(&app, &mut browser, &second).await);
+    assert_eq!(
+        find(&app, &mut browser, &second).await,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(find(&app, &mut browser, &first).await, StatusCode::OK);
+    assert!(!locked(&app, &mut browser, &second).await);
+    assert_eq!(find(&app, &mut browser, &second).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn branch_and_join() {
+    let app = app();
+    let start = story_duck(&app, "start", &[]).await;
+    let left = story_duck(&app, "left", &[&start]).await;
+    let right = story_duck(&app, "right", &[&start]).await;
+    let end = story_duck(&app, "end", &[&left, &right]).await;
+    let mut browser = Browser::default();
+    browser.login(&app, "peggy").await;
+
+    assert_eq!(find(&app, &mut browser, &start).await, StatusCode::OK);
+    assert!(!locked(&app, &mut browser, &left).await);
+    assert!(!locked(&app, &mut browser, &right).await);
+    assert_eq!(find(&app, &mut browser, &left).await, StatusCode::OK);
+    assert!(locked(&app, &mut browser, &end).await);
+    assert_eq!(find(&app, &mut browser, &end).await, StatusCode::FORBIDDEN);
+    assert_eq!(find(&app, &mut browser, &right).await, StatusCode::OK);
+    assert_eq!(find(&app, &mut browser, &end).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn check_story_graph() {
+    let app = app();
+    let (status, report) = admin(&app, Method::GET, "/admin/story-graph", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(report["valid"], true);
+
+    let a = story_duck(&app, "a", &[]).await;
+    let b = story_duck(&app, "b", &[&a]).await;
+    let stuck = story_duck(&app, "stuck", &[&b]).await;
+    let uri = format!("/admin/duck/{}", a);
+    let patch = json!({ "prerequisiteIds": [b] });
+    let (status, _) = admin(&app, Method::PATCH, &uri, Some(patch)).await;
+    assert_eq!(status, StatusCode::OK);
+    let trashed = story_duck(&app, "trashed", &[]).await;
+    let orphan = story_duck(&app, "orphan", &[&trashed]).await;
+    let uri = format!("/admin/duck/{}", trashed);
+    admin(&app, Method::DELETE, &uri, None).await;
+
+    let (status, report) = admin(&app, Method::GET, "/admin/story-graph", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(report["valid"], false);
+    let mut cycle = vec![a.clone(), b.clone()];
+    cycle.sort();
+    assert_eq!(report["cycles"], json!([cycle]));
+    assert_eq!(report["unreachable"], json!([stuck]));
+    assert_eq!(
+        report["orphans"],
+        json!([{ "duckId": orphan, "prerequisiteId": trashed }])
+    );
+
+    // the trashed prerequisite no longer holds the orphan back
+    let mut browser = Browser::default();
+    browser.login(&app, "quentin").await;
+    assert_eq!(find(&app, &mut browser, &orphan).await, StatusCode::OK);
+}
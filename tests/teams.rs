@@ -0,0 +1,163 @@
+mod common;
+
+use common::{admin, app, find_duck_uri, new_duck, Browser};
+use http::{Method, StatusCode};
+use serde_json::{json, Value};
+
+async fn find(app: &axum::Router, browser: &mut Browser, id: &str) {
+    let uri = find_duck_uri(app, id).await;
+    let (status, _) = browser.send(app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn teams_share_their_progress() {
+    let app = app();
+    let first = new_duck(&app, json!({})).await;
+    let second = new_duck(&app, json!({})).await;
+    let rules = json!([{ "type": "duck_count", "count": 2 }]);
+    let (status, _) = admin(
+        &app,
+        Method::PUT,
+        "/admin/team-completion-rules",
+        Some(rules.clone()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, team_rules) = admin(&app, Method::GET, "/admin/team-completion-rules", None).await;
+    assert_eq!(team_rules, rules);
+    let (_, player_rules) = admin(&app, Method::GET, "/admin/completion-rules", None).await;
+    assert_eq!(player_rules, json!([{ "type": "duck_count", "count": 10 }]));
+
+    let mut parent = Browser::default();
+    parent.login(&app, "yolanda").await;
+    let (status, team) = parent
+        .send(
+            &app,
+            Method::POST,
+            "/api/team",
+            Some(json!({ "name": " The Ducklings " })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(team["name"], "The Ducklings");
+    let code = team["joinCode"].as_str().unwrap().to_lowercase();
+
+    let mut child = Browser::default();
+    child.login(&app, "zach").await;
+    let (status, _) = child
+        .send(
+            &app,
+            Method::POST,
+            "/api/team/join",
+            Some(json!({ "joinCode": "nope" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, team) = child
+        .send(
+            &app,
+            Method::POST,
+            "/api/team/join",
+            Some(json!({ "joinCode": code })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(team["members"].as_array().unwrap().len(), 2);
+    let (_, user) = child.send(&app, Method::GET, "/api/user-info", None).await;
+    assert_eq!(user["team"]["name"], "The Ducklings");
+
+    find(&app, &mut parent, &first).await;
+    find(&app, &mut child, &first).await;
+    let (_, team) = child.send(&app, Method::GET, "/api/team", None).await;
+    assert_eq!(team["ducks"].as_array().unwrap().len(), 1);
+    assert_eq!(team["ducks"][0]["foundBy"].as_array().unwrap().len(), 2);
+    assert_eq!(team["ranking"], Value::Null);
+
+    find(&app, &mut child, &second).await;
+    let (_, team) = parent.send(&app, Method::GET, "/api/team", None).await;
+    assert_eq!(team["ducks"].as_array().unwrap().len(), 2);
+    assert_eq!(team["ranking"], 1);
+    let me: Vec<_> = team["members"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| (m["ducks"].as_u64().unwrap(), m["me"].as_bool().unwrap()))
+        .collect();
+    assert_eq!(me, vec![(1, true), (2, false)]);
+    let (_, user) = parent.send(&app, Method::GET, "/api/user-info", None).await;
+    assert_eq!(user["ranking"], Value::Null);
+
+    let (status, leaderboard) = Browser::default()
+        .send(&app, Method::GET, "/api/team-leaderboard", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(leaderboard.as_array().unwrap().len(), 1);
+    assert_eq!(leaderboard[0]["rank"], 1);
+    assert_eq!(leaderboard[0]["name"], "The Ducklings");
+    assert_eq!(leaderboard[0]["members"], 2);
+}
+
+#[tokio::test]
+async fn players_leave_their_team() {
+    let app = app();
+    let mut browser = Browser::default();
+    browser.login(&app, "amelia").await;
+    let (status, _) = browser
+        .send(&app, Method::POST, "/api/team", Some(json!({ "name": "" })))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = browser.send(&app, Method::GET, "/api/team", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    browser
+        .send(
+            &app,
+            Method::POST,
+            "/api/team",
+            Some(json!({ "name": "Class 3" })),
+        )
+        .await;
+    let (_, user) = browser.send(&app, Method::DELETE, "/api/team", None).await;
+    assert_eq!(user["team"], Value::Null);
+    let (status, _) = browser.send(&app, Method::GET, "/api/team", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn teams_stay_within_their_size() {
+    let app = app();
+    let mut owner = Browser::default();
+    owner.login(&app, "bianca").await;
+    let (_, team) = owner
+        .send(
+            &app,
+            Method::POST,
+            "/api/team",
+            Some(json!({ "name": "Everyone" })),
+        )
+        .await;
+    let code = team["joinCode"].clone();
+
+    // the owner and 49 others fill the team of 50
+    let mut players = vec![];
+    for i in 0..50 {
+        let mut browser = Browser::default();
+        browser.login(&app, &format!("player{}", i)).await;
+        players.push(browser);
+    }
+    let joins = players.into_iter().map(|mut browser| {
+        let app = &app;
+        let body = json!({ "joinCode": code });
+        async move {
+            let uri = "/api/team/join";
+            browser.send(app, Method::POST, uri, Some(body)).await.0
+        }
+    });
+    let statuses = futures::future::join_all(joins).await;
+    let full = statuses
+        .iter()
+        .filter(|s| **s == StatusCode::FORBIDDEN)
+        .count();
+    assert_eq!(full, 1);
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use common::{admin, app, find_duck_uri, new_duck, Browser};
+use http::{Method, StatusCode};
+use serde_json::json;
+
+#[tokio::test]
+async fn trashed_duck_keeps_history() {
+    let app = app();
+    let id = new_duck(&app, json!({})).await;
+    let mut browser = Browser::default();
+    browser.login(&app, "bob").await;
+    let find_uri = find_duck_uri(&app, &id).await;
+    let (status, _) = browser.send(&app, Method::GET, &find_uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let uri = format!("/admin/duck/{}", id);
+    let (status, _) = admin(&app, Method::DELETE, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = admin(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (_, trash) = admin(&app, Method::GET, "/admin/trash", None).await;
+    assert_eq!(trash["ducks"][0]["id"], id);
+    assert!(trash["ducks"][0]["deletedAt"].is_string());
+
+    // players no longer see the duck, nor can they find it
+    let (_, previews) = browser
+        .send(&app, Method::GET, "/api/preview-ducks", None)
+        .await;
+    assert_eq!(previews.as_array().unwrap().len(), 0);
+    let (_, user) = browser
+        .send(&app, Method::GET, "/api/user-info", None)
+        .await;
+    assert_eq!(user["duckHistory"].as_array().unwrap().len(), 0);
+    let (status, _) = browser.send(&app, Method::GET, &find_uri, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let restore_uri = format!("/admin/duck/{}/restore", id);
+    let (status, _) = admin(&app, Method::POST, &restore_uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, trash) = admin(&app, Method::GET, "/admin/trash", None).await;
+    assert_eq!(trash["ducks"].as_array().unwrap().len(), 0);
+    let (_, user) = browser
+        .send(&app, Method::GET, "/api/user-info", None)
+        .await;
+    assert_eq!(user["duckHistory"][0]["duck"]["id"], id);
+}
+
+#[tokio::test]
+async fn trashed_location_is_hidden_from_players() {
+    let app = app();
+    let location = json!({
+        "description": { "en": "lake", "cn": "湖" },
+        "coordinate": { "system": "wgs84", "x": 1.0, "y": 2.0 },
+    });
+    let (_, location) = admin(&app, Method::POST, "/admin/location", Some(location)).await;
+    new_duck(&app, json!({ "locationId": location["id"] })).await;
+
+    let uri = format!("/admin/location/{}", location["id"].as_str().unwrap());
+    let (status, _) = admin(&app, Method::DELETE, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let mut browser = Browser::default();
+    let (_, previews) = browser
+        .send(&app, Method::GET, "/api/preview-ducks", None)
+        .await;
+    assert!(previews[0]["location"].is_null());
+
+    let (status, _) = admin(&app, Method::POST, &format!("{}/restore", uri), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, previews) = browser
+        .send(&app, Method::GET, "/api/preview-ducks", None)
+        .await;
+    assert_eq!(previews[0]["location"]["id"], location["id"]);
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use common::{admin, app, find_duck_uri, new_duck, send, Browser};
+use http::{Method, Request, StatusCode};
+use serde_json::{json, Value};
+
+/// duck at a WGS84 position, following another duck if given
+async fn located_duck(app: &axum::Router, title: &str, hidden: bool, prev: Option<&str>) -> String {
+    let location = json!({
+        "description": { "en": title, "cn": title },
+        "coordinate": { "system": "wgs84", "x": 114.1694, "y": 22.3193 },
+    });
+    let (_, location) = admin(app, Method::POST, "/admin/location", Some(location)).await;
+    let duck = json!({
+        "title": { "en": title, "cn": title },
+        "isHidden": hidden,
+        "locationId": location["id"],
+        "prevDuckStoryId": prev,
+    });
+    new_duck(app, duck).await
+}
+
+fn preview<'a>(previews: &'a Value, id: &str) -> &'a Value {
+    previews
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|d| d["id"] == id)
+        .unwrap()
+}
+
+#[tokio::test]
+async fn redact_locked_hidden_ducks() {
+    let app = app();
+    let first = located_duck(&app, "first", false, None).await;
+    let second = located_duck(&app, "second", true, Some(&first)).await;
+    let secret = located_duck(&app, "secret", true, None).await;
+
+    let (status, previews) = send(&app, Request::builder().uri("/api/preview-ducks"), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(preview(&previews, &first)["title"]["en"], "first");
+    for id in [&second, &secret] {
+        let duck = preview(&previews, id);
+        assert!(duck["title"].is_null());
+        assert!(duck["location"].is_null());
+        assert_eq!(duck["isHidden"], true);
+    }
+
+    // finding the first duck unlocks the second
+    let mut browser = Browser::default();
+    browser.login(&app, "mallory").await;
+    let uri = find_duck_uri(&app, &first).await;
+    let (status, user) = browser.send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let next = &user["duckHistory"][0]["duck"]["nextDuckStory"];
+    assert_eq!(next["title"]["en"], "second");
+    assert!(next["location"].is_object());
+    let (_, previews) = browser
+        .send(&app, Method::GET, "/api/preview-ducks", None)
+        .await;
+    assert_eq!(preview(&previews, &second)["title"]["en"], "second");
+    assert!(preview(&previews, &second)["location"].is_object());
+    assert!(preview(&previews, &secret)["title"].is_null());
+
+    // finding a hidden duck unlocks it
+    let uri = find_duck_uri(&app, &secret).await;
+    browser.send(&app, Method::GET, &uri, None).await;
+    let (_, previews) = browser
+        .send(&app, Method::GET, "/api/preview-ducks", None)
+        .await;
+    assert_eq!(preview(&previews, &secret)["title"]["en"], "secret");
+
+    // other players still see nothing
+    let (_, previews) = send(&app, Request::builder().uri("/api/preview-ducks"), None).await;
+    assert!(preview(&previews, &second)["title"].is_null());
+}
+
+#[tokio::test]
+async fn nearby_unlocked_hidden_ducks() {
+    let app = app();
+    let first = located_duck(&app, "first", false, None).await;
+    located_duck(&app, "second", true, Some(&first)).await;
+    located_duck(&app, "secret", true, None).await;
+    let nearby = "/api/nearby-ducks?lat=22.3193&lng=114.1694&radius=100";
+
+    let mut browser = Browser::default();
+    browser.login(&app, "niaj").await;
+    let (_, ducks) = browser.send(&app, Method::GET, nearby, None).await;
+    assert_eq!(ducks.as_array().unwrap().len(), 1);
+    assert_eq!(ducks[0]["id"], first.as_str());
+
+    let uri = find_duck_uri(&app, &first).await;
+    browser.send(&app, Method::GET, &uri, None).await;
+    let (status, ducks) = browser.send(&app, Method::GET, nearby, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(ducks.as_array().unwrap().len(), 1);
+    assert_eq!(ducks[0]["title"]["en"], "second");
+}